@@ -0,0 +1,31 @@
+use image::{DynamicImage, RgbaImage};
+
+use crate::config::Adjust;
+
+// Rec. 601 luma, the same weights image's grayscale uses
+const LUMA: [f32; 3] = [0.299, 0.587, 0.114];
+
+// Brightness shifts every channel, contrast stretches them around the middle
+// gray, and saturation blends each pixel with its own gray; grayscale is
+// saturation at -100. Transparency is left alone.
+pub fn apply(img: DynamicImage, adjust: &Adjust) -> DynamicImage {
+    let had_alpha = img.color().has_alpha();
+    let mut rgba: RgbaImage = img.into_rgba8();
+
+    let brightness = adjust.brightness as f32 * 255.0 / 100.0;
+    let contrast = (100 + adjust.contrast) as f32 / 100.0;
+    let saturation = if adjust.grayscale { 0.0 } else { (100 + adjust.saturation) as f32 / 100.0 };
+    for pixel in rgba.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        let channels = [r, g, b].map(|v| (v as f32 - 127.5) * contrast + 127.5 + brightness);
+        let gray: f32 = channels.iter().zip(LUMA).map(|(v, weight)| v * weight).sum();
+        let [r, g, b] = channels.map(|v| (gray + (v - gray) * saturation).round().clamp(0.0, 255.0) as u8);
+        pixel.0 = [r, g, b, a];
+    }
+
+    if had_alpha {
+        DynamicImage::ImageRgba8(rgba)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(rgba).into_rgb8())
+    }
+}
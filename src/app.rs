@@ -0,0 +1,1362 @@
+use crossterm::{
+    cursor,
+    event::{self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent, KeyModifiers},
+    execute, queue,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor},
+    terminal::{self, ClearType},
+};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::browser::Browser;
+use crate::cache::Cache;
+use crate::config::{Config, ConfigWatcher, Resize};
+use crate::content::Content;
+use crate::finder::Finder;
+use crate::history::{History, SLOTS};
+use crate::i18n::{tr, trf};
+use crate::clipboard::copy_text;
+use crate::cloud;
+use crate::input::{InputLine, PasteBuffer};
+use crate::inspect::Inspector;
+use crate::multiplexer::Multiplexer;
+use crate::onboarding::Onboarding;
+use crate::optimize::{format_bytes, optimize, optimize_diff, optimize_favicons, optimize_grid, optimize_srcset, Delivery, OptimizeError, Outcome, Stage};
+use crate::palette::{css_variables, hex};
+use crate::qr::{self, UploadQr};
+use crate::pages;
+use crate::pasted::{self, Embedded, Pasted};
+use crate::paths::{is_image_path, parse_dropped_path, resolve};
+use crate::picker::PagePicker;
+use crate::recent::RecentList;
+use crate::reveal::{file_manager, reveal};
+use crate::session::Session;
+use crate::settings::Settings;
+use crate::theme::Theme;
+use crate::transfer;
+use crate::video;
+use crate::ui::{char_width, display_width, draw_box, error_title, print_centered, truncate, wrap, Action};
+use crate::{log, Result};
+
+// Keys closer together than this are assumed to come from a drop or paste
+// rather than a person typing
+const BURST_GAP: Duration = Duration::from_millis(15);
+// tmux and screen pass a drop along in chunks, with longer gaps in between
+const MULTIPLEXER_BURST_GAP: Duration = Duration::from_millis(60);
+const MIN_BURST: usize = 4;
+
+// Pastes this close together are pieces of one drop; some terminals split a
+// long path across several, and multiplexers do it more slowly
+const PASTE_DEBOUNCE: Duration = Duration::from_millis(50);
+const MULTIPLEXER_PASTE_DEBOUNCE: Duration = Duration::from_millis(150);
+
+// Hotkeys only fire once a lone key has been left alone this long, so a path
+// that starts with `/` or `o` and arrives as keystrokes isn't mistaken for one
+const HOTKEY_DELAY: Duration = Duration::from_millis(300);
+
+// How long a notice such as "Config reloaded" stays at the top of the screen
+const TOAST_DWELL: Duration = Duration::from_secs(3);
+
+// A grid starts by itself once it has GRID_MAX images, or on Enter from GRID_MIN
+const GRID_MIN: usize = 2;
+const GRID_MAX: usize = 4;
+
+// What Shift+1 to Shift+9 type on a US layout, which is all most terminals send
+const SHIFTED_DIGITS: [char; SLOTS] = ['!', '@', '#', '$', '%', '^', '&', '*', '('];
+
+pub enum Screen {
+    DropZone,
+    Browser(Browser),
+    Finder(Finder),
+    Recent(RecentList),
+    Inspect(Inspector),
+    // Which page of a multi-page TIFF or PDF to optimize
+    Pages(PagePicker),
+    Settings(Settings),
+    // First run only, until there is a config file
+    Onboarding(Onboarding),
+    Processing(ProcessingView),
+    // Boxed, since it holds a whole Outcome
+    Result(Box<ResultView>),
+}
+
+enum Progress {
+    Stage(Stage),
+    // Boxed, since an Outcome is much larger than a Stage
+    Done(Box<std::result::Result<Outcome, OptimizeError>>),
+}
+
+// What the next drop is for, chosen with a hotkey on the drop zone
+enum DropMode {
+    Optimize,
+    Inspect,
+    // Collecting the before image, then the after image
+    Diff(Option<PathBuf>),
+    // Collecting up to GRID_MAX images for one collage
+    Grid(Vec<PathBuf>),
+    // The next drop becomes a favicon set
+    Favicon,
+    // The next drop becomes responsive images and a srcset snippet
+    Srcset,
+}
+
+impl DropMode {
+    fn notice(&self) -> Option<String> {
+        match self {
+            DropMode::Optimize => None,
+            DropMode::Inspect => Some(tr("mode.inspect").to_string()),
+            DropMode::Diff(None) => Some(tr("mode.diff.before").to_string()),
+            DropMode::Diff(Some(before)) => Some(trf("mode.diff.after", &[("name", &file_name(before))])),
+            DropMode::Grid(paths) if paths.len() < GRID_MIN => {
+                Some(trf("mode.grid.start", &[("min", &(GRID_MIN - paths.len())), ("max", &(GRID_MAX - paths.len()))]))
+            }
+            DropMode::Grid(paths) => Some(trf("mode.grid.more", &[("count", &paths.len()), ("more", &(GRID_MAX - paths.len()))])),
+            DropMode::Favicon => Some(tr("mode.favicon").to_string()),
+            DropMode::Srcset => Some(tr("mode.srcset").to_string()),
+        }
+    }
+}
+
+// One unit of work for the worker thread
+enum Job {
+    Optimize(PathBuf),
+    // One page of a multi-page file, counted from 1
+    Page(PathBuf, usize),
+    Diff(PathBuf, PathBuf),
+    Grid(Vec<PathBuf>),
+    Favicon(PathBuf),
+    Srcset(PathBuf),
+}
+
+impl Job {
+    fn label(&self) -> String {
+        match self {
+            Job::Optimize(path) => file_name(path),
+            Job::Page(path, page) => trf("job.page", &[("name", &file_name(path)), ("page", page)]),
+            Job::Diff(before, after) => trf("job.diff", &[("before", &file_name(before)), ("after", &file_name(after))]),
+            Job::Grid(paths) => trf("job.grid", &[("names", &paths.iter().map(|p| file_name(p)).collect::<Vec<_>>().join(", "))]),
+            Job::Favicon(path) => trf("job.favicon", &[("name", &file_name(path))]),
+            Job::Srcset(path) => trf("job.srcset", &[("name", &file_name(path))]),
+        }
+    }
+
+    fn inputs(&self) -> Vec<PathBuf> {
+        match self {
+            Job::Optimize(path) | Job::Page(path, _) | Job::Favicon(path) | Job::Srcset(path) => vec![path.clone()],
+            Job::Diff(before, after) => vec![before.clone(), after.clone()],
+            Job::Grid(paths) => paths.clone(),
+        }
+    }
+
+    fn run(&self, config: &Config, cache: &Cache, progress: &dyn Fn(Stage) -> bool) -> std::result::Result<Outcome, OptimizeError> {
+        match self {
+            Job::Optimize(path) => optimize(path, config, cache, progress),
+            Job::Page(path, page) => {
+                let mut config = config.clone();
+                config.page = Some(*page);
+                optimize(path, &config, cache, progress)
+            }
+            Job::Diff(before, after) => optimize_diff(before, after, config, progress),
+            Job::Grid(paths) => optimize_grid(paths, config, progress),
+            Job::Favicon(path) => optimize_favicons(path, None, config, progress),
+            Job::Srcset(path) => optimize_srcset(path, None, config, progress),
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+// An image, or a link, alias or cloud placeholder standing in for one
+fn is_droppable(path: &Path) -> bool {
+    resolve(path).is_ok_and(|path| cloud::exists(&path) && (is_image_path(&path.to_string_lossy()) || video::is_video(&path)))
+}
+
+// Optimization runs on a worker thread and reports back over a channel
+pub struct ProcessingView {
+    label: String,
+    inputs: Vec<PathBuf>,
+    stage: Stage,
+    started: Instant,
+    progress: Receiver<Progress>,
+    cancel: Arc<AtomicBool>,
+}
+
+pub struct ResultView {
+    outcome: std::result::Result<Outcome, OptimizeError>,
+    shown_at: Instant,
+    // Set once `c` has tried to copy the palette as CSS
+    palette_status: Option<std::result::Result<(), String>>,
+    // The uploaded URL as a QR code, drawn in place of the palette
+    qr: Option<Vec<String>>,
+    // "URL" or "QR code": what an upload put on the clipboard
+    copied: &'static str,
+    // Estimated upload times before and after, see transfer.rs
+    transfer: Option<String>,
+}
+
+impl ResultView {
+    // A fallback path has to stay on screen until it has been read, and a QR
+    // code until it has been scanned
+    fn needs_key(&self) -> bool {
+        self.qr.is_some() || matches!(
+            &self.outcome,
+            Ok(Outcome {
+                delivery: Delivery::Fallback { .. } | Delivery::Uploaded { copied: Err(_), .. } | Delivery::Written { copied: Some(Err(_)), .. },
+                ..
+            })
+        )
+    }
+
+    // A file or folder on disk that `o` can show in the file manager
+    fn output(&self) -> Option<&Path> {
+        match &self.outcome.as_ref().ok()?.delivery {
+            Delivery::Saved(path) | Delivery::Fallback { saved: Ok(path), .. } => Some(path),
+            Delivery::Written { dir, .. } => Some(dir),
+            _ => None,
+        }
+    }
+
+    fn palette(&self) -> &[[u8; 3]] {
+        self.outcome.as_ref().map(|o| o.palette.as_slice()).unwrap_or_default()
+    }
+}
+
+// A short notice at the top of the screen that goes away by itself
+struct Toast {
+    text: String,
+    error: bool,
+    shown_at: Instant,
+}
+
+pub struct App {
+    config: Config,
+    watcher: ConfigWatcher,
+    toast: Option<Toast>,
+    cache: Arc<Cache>,
+    screen: Screen,
+    input: InputLine,
+    // Pasted pieces waiting to be put together into one drop
+    paste: PasteBuffer,
+    paste_enabled: bool,
+    multiplexer: Option<Multiplexer>,
+    last_char_at: Option<Instant>,
+    burst: usize,
+    drop_mode: DropMode,
+    // Recent clipboard images, for `[` and `]`
+    history: History,
+    // Every job so far, for the session report
+    session: Session,
+    // Drops that arrived while busy, in order
+    queue: VecDeque<PathBuf>,
+    // The input of the last image optimized, for the settings preview
+    last_input: Option<PathBuf>,
+    // An image from pasted HTML or RTF being downloaded, to be dropped once saved
+    fetching: Option<Receiver<std::result::Result<Pasted, String>>>,
+    // Saved pastes not yet processed, and the one the settings preview may
+    // still open; the rest are removed when their job ends
+    pastes: Vec<Pasted>,
+    dirty: bool,
+    quit: bool,
+}
+
+impl App {
+    pub fn new(config: Config) -> App {
+        App {
+            config,
+            watcher: ConfigWatcher::new(),
+            toast: None,
+            cache: Arc::default(),
+            screen: Screen::DropZone,
+            input: InputLine::default(),
+            paste: PasteBuffer::default(),
+            paste_enabled: false,
+            multiplexer: Multiplexer::detect(),
+            last_char_at: None,
+            burst: 0,
+            drop_mode: DropMode::Optimize,
+            history: History::default(),
+            session: Session::new(),
+            queue: VecDeque::new(),
+            last_input: None,
+            fetching: None,
+            pastes: Vec::new(),
+            dirty: true,
+            quit: false,
+        }
+    }
+
+    fn theme(&self) -> &Theme {
+        &self.config.theme
+    }
+
+    pub fn run(&mut self) -> Result<()> {
+        log("App starting");
+        terminal::enable_raw_mode()?;
+        log("Raw mode enabled");
+        let mut stdout = io::stdout();
+
+        // Not every terminal (or multiplexer) supports bracketed paste; without it
+        // a drop arrives as a burst of key events and goes through the input line
+        self.paste_enabled = execute!(stdout, EnableBracketedPaste).is_ok();
+        log(&format!("Bracketed paste enabled: {}", self.paste_enabled));
+        if let Some(multiplexer) = self.multiplexer {
+            log(&format!("Running inside {}", multiplexer.name()));
+        }
+
+        if Config::path().is_some_and(|path| !path.exists()) {
+            log("No config file, showing the setup");
+            self.set_screen(Screen::Onboarding(Onboarding::new(&self.config, self.paste_enabled, self.multiplexer)));
+        } else if self.config.show_recent_on_start {
+            self.show_recent();
+        }
+        log("Entering loop");
+
+        let result = self.event_loop(&mut stdout);
+
+        log("Exited main loop");
+        if self.paste_enabled {
+            execute!(stdout, DisableBracketedPaste)?;
+        }
+        execute!(stdout, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0), cursor::Show)?;
+        terminal::disable_raw_mode()?;
+        log("Disabled raw mode");
+        result?;
+        if let Some(dir) = self.config.report_dir().filter(|_| !self.session.is_empty()) {
+            match self.session.write(&dir, self.config.report_format) {
+                Ok(path) => println!("{}", trf("report.saved", &[("path", &path.display())])),
+                Err(e) => eprintln!("imgopt: could not save the session report: {}", e),
+            }
+        }
+        println!("{}{}", self.theme().pick("👋 ", ""), tr("app.goodbye"));
+        Ok(())
+    }
+
+    fn event_loop(&mut self, stdout: &mut io::Stdout) -> Result<()> {
+        loop {
+            self.poll_worker();
+            if self.dirty {
+                self.render(stdout)?;
+                self.dirty = false;
+            }
+            if self.quit {
+                return Ok(());
+            }
+
+            let debounce = self.paste_debounce();
+            if let Some((data, chunks)) = self.paste.settled(debounce) {
+                self.paste_done(&data, chunks);
+                continue;
+            }
+
+            let timeout = self.paste.remaining(debounce).unwrap_or(Duration::from_millis(100));
+            if !event::poll(timeout)? {
+                self.tick();
+                continue;
+            }
+
+            match event::read()? {
+                // The rest of a drop that started as a paste
+                Event::Key(KeyEvent { code: KeyCode::Char(c), modifiers, .. })
+                    if self.paste.is_open(debounce) && modifiers.difference(KeyModifiers::SHIFT).is_empty() =>
+                {
+                    log(&format!("Key event joins the paste: {:?}", c));
+                    self.paste.push(c.encode_utf8(&mut [0; 4]));
+                }
+                Event::Key(key) => {
+                    log(&format!("Key event: {:?}", key));
+                    // Whatever was pasted before this key comes first
+                    if let Some((data, chunks)) = self.paste.take() {
+                        self.paste_done(&data, chunks);
+                    }
+                    self.handle_key(stdout, key)?;
+                }
+                // Drag and drop triggers paste event!
+                Event::Paste(data) => {
+                    log(&format!("Paste event: {}", data));
+                    self.paste.push(&data);
+                }
+                Event::Resize(..) => self.dirty = true,
+                evt => log(&format!("Other event: {:?}", evt)),
+            }
+        }
+    }
+
+    // Time-based transitions: hotkeys, finished key bursts, expiring result
+    // screens and toasts, config file edits and the settings preview
+    fn tick(&mut self) {
+        self.reload_config();
+        if self.toast.as_ref().is_some_and(|toast| toast.shown_at.elapsed() >= TOAST_DWELL) {
+            self.toast = None;
+            self.dirty = true;
+        }
+        if let Screen::Settings(settings) = &mut self.screen {
+            self.dirty |= settings.tick(&self.config);
+        }
+        self.fetched();
+
+        let idle = self.last_char_at.is_some_and(|at| at.elapsed() >= HOTKEY_DELAY);
+        let dwell = self.config.result_dwell();
+
+        match &self.screen {
+            Screen::DropZone if idle && self.burst == 0 && self.input.text().chars().count() == 1 => {
+                let hotkey = self.input.text().chars().next();
+                match hotkey {
+                    Some('o') => {
+                        let start = std::env::current_dir().unwrap_or_else(|_| ".".into());
+                        self.set_screen(Screen::Browser(Browser::new(&start)));
+                    }
+                    Some('/') => {
+                        let mut roots: Vec<_> = std::env::current_dir().into_iter().collect();
+                        roots.extend(self.config.screenshots_dir().filter(|d| d.is_dir()));
+                        roots.dedup();
+                        if let Some(finder) = Finder::new(roots) {
+                            self.set_screen(Screen::Finder(finder));
+                        }
+                    }
+                    Some('r') => self.show_recent(),
+                    Some('i') => self.toggle_drop_mode(DropMode::Inspect),
+                    Some('d') => self.toggle_drop_mode(DropMode::Diff(None)),
+                    Some('g') => self.toggle_drop_mode(DropMode::Grid(Vec::new())),
+                    Some('f') => self.toggle_drop_mode(DropMode::Favicon),
+                    Some('w') => self.toggle_drop_mode(DropMode::Srcset),
+                    Some(key @ ('[' | ']')) => self.step_history(key == '['),
+                    Some(key @ '1'..='9') => self.recall_slot(key as usize - '0' as usize),
+                    Some(key) if SHIFTED_DIGITS.contains(&key) => {
+                        self.hold_slot(SHIFTED_DIGITS.iter().position(|c| *c == key).unwrap_or(0) + 1)
+                    }
+                    Some('e') => self.save_report(),
+                    Some('s') => {
+                        let settings = Settings::new(&self.config, self.last_input.clone());
+                        self.set_screen(Screen::Settings(settings));
+                    }
+                    _ => return,
+                }
+                log(&format!("Hotkey: {:?}", hotkey));
+                self.input.clear();
+            }
+            Screen::DropZone if self.burst >= MIN_BURST => {
+                // A burst just ended: if it spelled out an image path, treat it as a drop
+                self.burst = 0;
+                let path = parse_dropped_path(self.input.text());
+                if is_droppable(&path) {
+                    log(&format!("Auto-processing burst input: {}", path.display()));
+                    self.input.clear();
+                    self.dropped(path);
+                }
+            }
+            // The same for a drop that arrived as keystrokes while busy
+            Screen::Processing(_) if self.burst >= MIN_BURST => {
+                self.burst = 0;
+                let path = parse_dropped_path(self.input.text());
+                if is_droppable(&path) {
+                    self.input.clear();
+                    self.enqueue(path);
+                }
+            }
+            Screen::Result(view) if !view.needs_key() && dwell.is_some_and(|dwell| view.shown_at.elapsed() >= dwell) => {
+                self.leave_result();
+            }
+            _ => {}
+        }
+    }
+
+    // `[` copies the previous clip again, `]` the next one
+    fn step_history(&mut self, older: bool) {
+        let (text, error) = match self.history.step(older, &self.config) {
+            None => (tr("history.empty").to_string(), false),
+            Some(Ok(position)) => (trf("history.copied", &[("position", &position)]), false),
+            Some(Err(e)) => (trf("history.failed", &[("error", &e)]), true),
+        };
+        log(&text);
+        self.toast = Some(Toast { text, error, shown_at: Instant::now() });
+        self.dirty = true;
+    }
+
+    // Shift+N holds what's on the clipboard in slot N
+    fn hold_slot(&mut self, slot: usize) {
+        let text = match self.history.hold(slot) {
+            Some(label) => trf("slots.held", &[("label", &label), ("slot", &slot)]),
+            None => tr("slots.nothing").to_string(),
+        };
+        log(&text);
+        self.toast = Some(Toast { text, error: false, shown_at: Instant::now() });
+        self.dirty = true;
+    }
+
+    // N copies what slot N holds again
+    fn recall_slot(&mut self, slot: usize) {
+        let (text, error) = match self.history.recall(slot, &self.config) {
+            None => (trf("slots.empty", &[("slot", &slot)]), false),
+            Some(Ok(label)) => (trf("slots.copied", &[("label", &label), ("slot", &slot)]), false),
+            Some(Err(e)) => (trf("history.failed", &[("error", &e)]), true),
+        };
+        log(&text);
+        self.toast = Some(Toast { text, error, shown_at: Instant::now() });
+        self.dirty = true;
+    }
+
+    // `e` writes the session report so far, to `report_dir` or else the current directory
+    fn save_report(&mut self) {
+        let (text, error) = if self.session.is_empty() {
+            (tr("report.empty").to_string(), false)
+        } else {
+            let dir = self.config.report_dir().unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| ".".into()));
+            match self.session.write(&dir, self.config.report_format) {
+                Ok(path) => (trf("report.saved", &[("path", &path.display())]), false),
+                Err(e) => (trf("report.failed", &[("error", &e)]), true),
+            }
+        };
+        log(&text);
+        self.toast = Some(Toast { text, error, shown_at: Instant::now() });
+        self.dirty = true;
+    }
+
+    // Jobs already running finish with the config they started with
+    fn reload_config(&mut self) {
+        let (text, error) = match self.watcher.poll() {
+            None => return,
+            Some(Ok(config)) => {
+                self.config = config;
+                (tr("config.reloaded").to_string(), false)
+            }
+            Some(Err(e)) => (trf("config.failed", &[("error", &e)]), true),
+        };
+        log(&text);
+        self.toast = Some(Toast { text, error, shown_at: Instant::now() });
+        self.dirty = true;
+    }
+
+    // The setup wrote the first config; it applies right away, and the watcher
+    // starts from it so it isn't reported as an edit
+    fn setup_saved(&mut self, path: &Path) {
+        self.watcher = ConfigWatcher::new();
+        let (text, error) = match Config::load() {
+            Ok(config) => {
+                self.config = config;
+                (trf("setup.saved", &[("path", &path.display())]), false)
+            }
+            Err(e) => (trf("config.failed", &[("error", &e)]), true),
+        };
+        log(&text);
+        self.toast = Some(Toast { text, error, shown_at: Instant::now() });
+    }
+
+    fn set_screen(&mut self, screen: Screen) {
+        self.screen = screen;
+        self.dirty = true;
+    }
+
+    fn show_recent(&mut self) {
+        if let Some(dir) = self.config.screenshots_dir() {
+            self.set_screen(Screen::Recent(RecentList::new(&dir, self.config.recent_count)));
+        }
+    }
+
+    fn handle_key(&mut self, stdout: &mut io::Stdout, key: KeyEvent) -> Result<()> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        let action = match &mut self.screen {
+            Screen::DropZone => return self.handle_drop_zone_key(stdout, key),
+            // The worker notices at its next stage and discards what it has.
+            // Esc moves on to the next queued drop, Ctrl+C drops the queue too.
+            Screen::Processing(view) if key.code == KeyCode::Esc || (ctrl && key.code == KeyCode::Char('c')) => {
+                view.cancel.store(true, Ordering::Relaxed);
+                log("Cancel requested");
+                if ctrl {
+                    self.queue.clear();
+                }
+                self.leave_result();
+                return Ok(());
+            }
+            // Typed or dropped as keystrokes: kept for later, see tick()
+            Screen::Processing(_) if key.code == KeyCode::Enter && !self.input.is_empty() => {
+                let path = parse_dropped_path(self.input.text());
+                self.input.clear();
+                self.burst = 0;
+                self.enqueue(path);
+                return Ok(());
+            }
+            Screen::Processing(_) => {
+                if matches!(key.code, KeyCode::Char(_)) && key.modifiers.difference(KeyModifiers::SHIFT).is_empty() {
+                    self.count_burst();
+                }
+                self.input.handle_key(key);
+                return Ok(());
+            }
+            // Copying the palette replaces the image on the clipboard, so it's opt-in
+            Screen::Result(view) if key.code == KeyCode::Char('o') && view.output().is_some() => {
+                let path = view.output().map(Path::to_path_buf).unwrap_or_default();
+                view.shown_at = Instant::now();
+                let (text, error) = match reveal(&path) {
+                    Ok(()) => (trf("reveal.shown", &[("app", &file_manager())]), false),
+                    Err(e) => (trf("reveal.failed", &[("app", &file_manager()), ("error", &e)]), true),
+                };
+                self.toast = Some(Toast { text, error, shown_at: Instant::now() });
+                Action::None
+            }
+            Screen::Result(view) if !view.needs_key() && key.code == KeyCode::Char('c') && !view.palette().is_empty() => {
+                let copied = copy_text(&css_variables(view.palette()), self.config.clipboard);
+                if let Err(e) = &copied {
+                    log(&format!("Palette copy failed: {}", e));
+                }
+                view.palette_status = Some(copied.map_err(|e| e.to_string()));
+                view.shown_at = Instant::now();
+                Action::None
+            }
+            // Any other key goes back (or on to the next queued drop), and
+            // typing carries on there so a path or a hotkey doesn't have to wait
+            Screen::Result(_) => {
+                self.leave_result();
+                if matches!(key.code, KeyCode::Char(_)) && key.modifiers.difference(KeyModifiers::SHIFT).is_empty() {
+                    return self.handle_key(stdout, key);
+                }
+                return Ok(());
+            }
+            _ if ctrl && key.code == KeyCode::Char('c') => Action::Back,
+            Screen::Browser(browser) => browser.handle_key(key),
+            Screen::Finder(finder) => finder.handle_key(key),
+            Screen::Recent(recent) => recent.handle_key(key),
+            Screen::Inspect(inspector) => inspector.handle_key(key),
+            Screen::Pages(picker) => picker.handle_key(key),
+            Screen::Settings(settings) => {
+                let action = settings.handle_key(key);
+                settings.apply(&mut self.config);
+                action
+            }
+            Screen::Onboarding(setup) => {
+                let action = setup.handle_key(key);
+                if let Some(path) = setup.saved.take() {
+                    self.setup_saved(&path);
+                }
+                action
+            }
+        };
+
+        match action {
+            Action::None => self.dirty = true,
+            Action::Back => self.set_screen(Screen::DropZone),
+            Action::Open(path) => {
+                log(&format!("Picked: {}", path.display()));
+                self.open(path);
+            }
+            Action::OpenPage(path, page) => {
+                log(&format!("Picked page {} of {}", page, path.display()));
+                self.start(Job::Page(path, page));
+            }
+            Action::Inspect(path) => self.set_screen(Screen::Inspect(Inspector::new(path))),
+        }
+        Ok(())
+    }
+
+    fn handle_drop_zone_key(&mut self, stdout: &mut io::Stdout, key: KeyEvent) -> Result<()> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Esc if !matches!(self.drop_mode, DropMode::Optimize) => self.toggle_drop_mode(DropMode::Optimize),
+            KeyCode::Esc => {
+                log("Breaking on ESC");
+                self.quit = true;
+            }
+            KeyCode::Char('c') if ctrl => {
+                log("Breaking on Ctrl+C");
+                self.quit = true;
+            }
+            KeyCode::Char('d') if ctrl => {
+                log("Breaking on Ctrl+D");
+                self.quit = true;
+            }
+            KeyCode::Enter if self.input.is_empty() && matches!(&self.drop_mode, DropMode::Grid(paths) if paths.len() >= GRID_MIN) => {
+                if let DropMode::Grid(paths) = std::mem::replace(&mut self.drop_mode, DropMode::Optimize) {
+                    self.start(Job::Grid(paths));
+                }
+            }
+            KeyCode::Enter if !self.input.is_empty() => {
+                let path = parse_dropped_path(self.input.text());
+                self.input.clear();
+                self.burst = 0;
+                self.dropped(path);
+            }
+            // Terminals that report modifiers send Shift+N as the digit itself
+            KeyCode::Char(c @ '1'..='9') if key.modifiers == KeyModifiers::SHIFT && self.input.is_empty() => {
+                self.hold_slot(c as usize - '0' as usize);
+            }
+            KeyCode::Char(_) if key.modifiers.difference(KeyModifiers::SHIFT).is_empty() => {
+                self.count_burst();
+                self.input.handle_key(key);
+                self.draw_input_line(stdout)?;
+            }
+            _ => {
+                self.input.handle_key(key);
+                self.draw_input_line(stdout)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Keys closer together than the burst gap count toward one drop or paste
+    fn count_burst(&mut self) {
+        let now = Instant::now();
+        let gap = if self.multiplexer.is_some() { MULTIPLEXER_BURST_GAP } else { BURST_GAP };
+        if self.last_char_at.is_some_and(|at| now.duration_since(at) < gap) {
+            self.burst += 1;
+        } else {
+            self.burst = 0;
+        }
+        self.last_char_at = Some(now);
+    }
+
+    fn paste_debounce(&self) -> Duration {
+        if self.multiplexer.is_some() {
+            MULTIPLEXER_PASTE_DEBOUNCE
+        } else {
+            PASTE_DEBOUNCE
+        }
+    }
+
+    // A paste has settled; only now is it parsed as a path
+    fn paste_done(&mut self, data: &str, chunks: usize) {
+        if chunks > 1 {
+            log(&format!("Paste put together from {} pieces: {}", chunks, data));
+        }
+        if let Some(embedded) = pasted::find(data) {
+            self.fetch(embedded);
+        } else if pasted::is_markup(data) {
+            self.toast = Some(Toast { text: tr("paste.no_image").to_string(), error: true, shown_at: Instant::now() });
+            self.dirty = true;
+        } else if !data.trim().is_empty() {
+            self.pasted(parse_dropped_path(data));
+        }
+    }
+
+    // Saves the image in pasted HTML or RTF on a worker thread, since it may
+    // have to be downloaded; it's dropped from `tick` once it's there
+    fn fetch(&mut self, embedded: Embedded) {
+        let text = match embedded.describe() {
+            Some(host) => trf("paste.fetching", &[("host", &host)]),
+            None => tr("paste.embedded").to_string(),
+        };
+        log(&text);
+        self.toast = Some(Toast { text, error: false, shown_at: Instant::now() });
+        self.dirty = true;
+        let (sender, receiver) = mpsc::channel();
+        let max_bytes = self.config.limits.max_input_bytes;
+        thread::spawn(move || sender.send(embedded.save(max_bytes)));
+        self.fetching = Some(receiver);
+    }
+
+    fn fetched(&mut self) {
+        let result = match self.fetching.as_ref().map(Receiver::try_recv) {
+            None | Some(Err(TryRecvError::Empty)) => return,
+            Some(Ok(result)) => result,
+            Some(Err(TryRecvError::Disconnected)) => Err("the download stopped".to_string()),
+        };
+        self.fetching = None;
+        match result {
+            Ok(pasted) => {
+                log(&format!("Pasted image saved to {}", pasted.path.display()));
+                self.toast = None;
+                self.pasted(pasted.path.clone());
+                self.pastes.push(pasted);
+            }
+            Err(e) => {
+                let text = trf("paste.failed", &[("error", &e)]);
+                log(&text);
+                self.toast = Some(Toast { text, error: true, shown_at: Instant::now() });
+            }
+        }
+        self.dirty = true;
+    }
+
+    // A drop while an image is still processing, or its result still showing
+    // after others have queued up, waits its turn. A result alone doesn't
+    // hold the next drop back.
+    fn pasted(&mut self, path: PathBuf) {
+        match &mut self.screen {
+            Screen::Processing(_) => self.enqueue(path),
+            Screen::Result(_) if !self.queue.is_empty() => self.enqueue(path),
+            Screen::Result(_) => {
+                self.set_screen(Screen::DropZone);
+                self.dropped(path);
+            }
+            Screen::DropZone => self.dropped(path),
+            // Trying a drop out, not optimizing yet
+            Screen::Onboarding(setup) => {
+                setup.dropped(&path);
+                self.dirty = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn enqueue(&mut self, path: PathBuf) {
+        let (text, error) = if is_droppable(&path) {
+            self.queue.push_back(path.clone());
+            (trf("queue.added", &[("name", &file_name(&path)), ("count", &self.queue.len())]), false)
+        } else {
+            (trf("queue.rejected", &[("path", &path.display())]), true)
+        };
+        log(&text);
+        self.toast = Some(Toast { text, error, shown_at: Instant::now() });
+        self.dirty = true;
+    }
+
+    // Done with a result (or a cancelled job): the next queued drop, else the drop zone
+    fn leave_result(&mut self) {
+        match self.queue.pop_front() {
+            Some(path) => self.dropped(path),
+            None => {
+                self.set_screen(Screen::DropZone);
+                log("Back to drop zone");
+            }
+        }
+    }
+
+    // Pressing a mode's hotkey again (or Esc) goes back to plain optimizing
+    fn toggle_drop_mode(&mut self, mode: DropMode) {
+        let same = std::mem::discriminant(&self.drop_mode) == std::mem::discriminant(&mode);
+        self.drop_mode = if same { DropMode::Optimize } else { mode };
+        self.dirty = true;
+    }
+
+    // A path arrived in the drop zone; what happens depends on the drop mode
+    fn dropped(&mut self, path: PathBuf) {
+        match std::mem::replace(&mut self.drop_mode, DropMode::Optimize) {
+            DropMode::Optimize => self.open(path),
+            DropMode::Inspect => self.set_screen(Screen::Inspect(Inspector::new(path))),
+            DropMode::Diff(None) => {
+                self.drop_mode = DropMode::Diff(Some(path));
+                self.dirty = true;
+            }
+            DropMode::Diff(Some(before)) => self.start(Job::Diff(before, path)),
+            DropMode::Grid(mut paths) => {
+                paths.push(path);
+                if paths.len() == GRID_MAX {
+                    self.start(Job::Grid(paths));
+                } else {
+                    self.drop_mode = DropMode::Grid(paths);
+                    self.dirty = true;
+                }
+            }
+            DropMode::Favicon => self.start(Job::Favicon(path)),
+            DropMode::Srcset => self.start(Job::Srcset(path)),
+        }
+    }
+
+    // A file with several pages asks which one first
+    fn open(&mut self, path: PathBuf) {
+        let sizes = resolve(&path).map(|path| pages::sizes(&path)).unwrap_or_default();
+        if sizes.len() > 1 {
+            log(&format!("{} has {} pages", path.display(), sizes.len()));
+            self.set_screen(Screen::Pages(PagePicker::new(path, sizes)));
+        } else {
+            self.start(Job::Optimize(path));
+        }
+    }
+
+    fn start(&mut self, job: Job) {
+        log(&format!("Processing: {}", job.label()));
+        let (tx, rx) = mpsc::channel();
+
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let label = job.label();
+        let inputs = job.inputs();
+        let worker_cancel = Arc::clone(&cancel);
+        let config = self.config.clone();
+        let cache = Arc::clone(&self.cache);
+        thread::spawn(move || {
+            let stage_tx = tx.clone();
+            let outcome = job.run(&config, &cache, &|stage| {
+                let _ = stage_tx.send(Progress::Stage(stage));
+                !worker_cancel.load(Ordering::Relaxed)
+            });
+            if let Err(OptimizeError::Cancelled) = &outcome {
+                log(&format!("Cancelled: {}", job.label()));
+            }
+            let _ = tx.send(Progress::Done(Box::new(outcome)));
+        });
+
+        self.set_screen(Screen::Processing(ProcessingView {
+            label,
+            inputs,
+            stage: Stage::Decoding,
+            started: Instant::now(),
+            progress: rx,
+            cancel,
+        }));
+    }
+
+    fn poll_worker(&mut self) {
+        let Screen::Processing(view) = &mut self.screen else {
+            return;
+        };
+
+        // Redraw at least once per loop so the elapsed time keeps moving
+        self.dirty = true;
+        let mut finished = None;
+        loop {
+            match view.progress.try_recv() {
+                Ok(Progress::Stage(stage)) => view.stage = stage,
+                Ok(Progress::Done(outcome)) => finished = Some(*outcome),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    finished.get_or_insert(Err(OptimizeError::Unreadable("worker thread stopped".to_string())));
+                    break;
+                }
+            }
+        }
+
+        if let Some(mut outcome) = finished {
+            log(&format!("Finished in {:?}", view.started.elapsed()));
+            let previous = self.last_input.clone();
+            if outcome.is_ok() && view.inputs.len() == 1 {
+                self.last_input = view.inputs.first().cloned();
+            }
+            let inputs = std::mem::take(&mut view.inputs);
+            let (previous, last_input) = (previous.as_ref(), self.last_input.as_ref());
+            self.pastes.retain(|pasted| {
+                last_input == Some(&pasted.path) || !(inputs.contains(&pasted.path) || previous == Some(&pasted.path))
+            });
+            self.session.record(inputs, &outcome, &self.config);
+            if !matches!(outcome, Err(OptimizeError::Cancelled)) {
+                self.config.notify.done(self.config.notify_sound().as_deref());
+            }
+            if let Ok(outcome) = &mut outcome {
+                if let Some(clip) = outcome.clip.take() {
+                    let label = format!("{} ({}x{})", view.label, outcome.optimized.0, outcome.optimized.1);
+                    self.history.push(label, clip, self.config.history_size);
+                }
+            }
+            let qr = match &outcome {
+                Ok(Outcome { delivery: Delivery::Uploaded { url, .. }, .. }) if self.config.upload_qr != UploadQr::Off => {
+                    qr::lines(url, self.theme().ascii())
+                }
+                _ => None,
+            };
+            // A URL too long for a QR code is copied as it is
+            let copied = if qr.is_some() { self.config.upload_qr.copied() } else { tr("result.copied_url") };
+            let arrow = self.theme().pick("→", "->");
+            let transfer = outcome
+                .as_ref()
+                .ok()
+                .and_then(|outcome| outcome.bytes)
+                .map(|bytes| transfer::describe(bytes, self.config.upload_mbps, arrow));
+            self.set_screen(Screen::Result(Box::new(ResultView {
+                outcome,
+                shown_at: Instant::now(),
+                palette_status: None,
+                qr,
+                copied,
+                transfer,
+            })));
+        }
+    }
+
+    fn render(&self, stdout: &mut io::Stdout) -> Result<()> {
+        let theme = self.theme();
+        execute!(stdout, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+
+        match &self.screen {
+            Screen::DropZone => {
+                show_drop_zone(stdout, theme, &self.config.resize())?;
+                let (width, height) = terminal::size()?;
+                // Drops that never arrive look like a broken app, so say so and offer the pickers
+                let held = self.history.held();
+                let notice = match (self.drop_mode.notice(), self.multiplexer) {
+                    (Some(notice), _) => Some((notice, theme.accent)),
+                    (None, Some(multiplexer)) => Some((trf("drop.multiplexer", &[("multiplexer", &multiplexer.name())]), theme.muted)),
+                    (None, None) if !held.is_empty() => {
+                        let slots = held.iter().map(|slot| slot.to_string()).collect::<Vec<_>>().join(" ");
+                        Some((trf("slots.notice", &[("slots", &slots)]), theme.muted))
+                    }
+                    (None, None) => None,
+                };
+                if let Some((notice, color)) = notice {
+                    print_centered(stdout, height.saturating_sub(4), color, &truncate(&notice, width.saturating_sub(4) as usize))?;
+                }
+                self.draw_input_line(stdout)?;
+            }
+            Screen::Browser(browser) => browser.render(stdout, theme)?,
+            Screen::Finder(finder) => finder.render(stdout, theme)?,
+            Screen::Recent(recent) => recent.render(stdout, theme)?,
+            Screen::Inspect(inspector) => inspector.render(stdout, theme)?,
+            Screen::Pages(picker) => picker.render(stdout, theme)?,
+            Screen::Settings(settings) => settings.render(stdout, theme)?,
+            Screen::Onboarding(setup) => setup.render(stdout, theme)?,
+            Screen::Processing(view) => show_processing(stdout, theme, view, self.queue.len())?,
+            Screen::Result(view) => {
+                show_result(stdout, theme, view)?;
+                if !view.needs_key() && self.config.result_dwell().is_none() {
+                    let (_, height) = terminal::size()?;
+                    print_centered(stdout, height.saturating_sub(2), theme.muted, tr("result.any_key"))?;
+                }
+            }
+        }
+        if let Some(toast) = &self.toast {
+            let (width, _) = terminal::size()?;
+            let color = if toast.error { theme.error } else { theme.success };
+            print_centered(stdout, 0, color, &truncate(&toast.text, width.saturating_sub(4) as usize))?;
+        }
+
+        queue!(stdout, cursor::Hide)?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    // Shows what has been typed or dropped so far, just above the bottom edge
+    fn draw_input_line(&self, stdout: &mut io::Stdout) -> Result<()> {
+        let theme = self.theme();
+        let input = &self.input;
+        let (width, height) = terminal::size()?;
+        let y = height.saturating_sub(2);
+        let max_len = width.saturating_sub(6) as usize;
+
+        // Scroll horizontally so the cursor stays visible on long paths,
+        // counting columns since a wide character takes two
+        let chars: Vec<char> = input.text().chars().collect();
+        let at = chars.get(input.cursor()).copied().unwrap_or(' ');
+        let mut used = char_width(at).max(1);
+        let mut first = input.cursor();
+        while first > 0 && used + char_width(chars[first - 1]) <= max_len {
+            first -= 1;
+            used += char_width(chars[first]);
+        }
+        let before: String = chars[first..input.cursor()].iter().collect();
+        let after: String = chars
+            .iter()
+            .skip(input.cursor() + 1)
+            .take_while(|c| {
+                used += char_width(**c);
+                used <= max_len
+            })
+            .collect();
+
+        queue!(
+            stdout,
+            cursor::MoveTo(0, y),
+            terminal::Clear(ClearType::CurrentLine),
+            cursor::MoveTo(2, y),
+            SetForegroundColor(theme.accent),
+            Print("> "),
+            ResetColor,
+            Print(before),
+            SetAttribute(Attribute::Reverse),
+            Print(at),
+            SetAttribute(Attribute::NoReverse),
+            Print(after),
+        )?;
+
+        // Bracketed paste can be switched on inside a multiplexer without ever getting through
+        if (!self.paste_enabled || self.multiplexer.is_some()) && input.is_empty() {
+            queue!(
+                stdout,
+                SetForegroundColor(theme.muted),
+                Print(tr("drop.type_hint")),
+                ResetColor,
+            )?;
+        }
+
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+fn show_drop_zone(stdout: &mut io::Stdout, theme: &Theme, resize: &Resize) -> Result<()> {
+    let clamp = resize.describe();
+    let (width, height) = terminal::size()?;
+    let center_y = height / 2;
+
+    // Simple mode for narrow terminals
+    if width < 50 {
+        let lines = [
+            &format!("{}{}", theme.pick("📸 ", ""), tr("app.title")),
+            tr("drop.title"),
+            &clamp,
+            tr("drop.keys.narrow"),
+        ];
+
+        for (i, line) in lines.iter().enumerate() {
+            let color = if i == 0 { theme.title } else { theme.text };
+            print_centered(stdout, center_y.saturating_sub(2) + i as u16, color, line)?;
+        }
+    } else {
+        // Full box mode for wider terminals
+        let title = theme.pick("      📸  {}  📸", "        [ {} ]").replace("{}", tr("app.title"));
+        let line = |text: &str| format!("      {}", text);
+        let lines = [
+            ("".to_string(), theme.border),
+            (title, theme.title),
+            ("".to_string(), theme.border),
+            (line(tr("drop.title")), theme.text),
+            (line(&clamp), theme.text),
+            (line(tr("drop.keys.browse")), theme.muted),
+            (line(tr("drop.keys.inspect")), theme.muted),
+            (line(tr("drop.keys.grid")), theme.muted),
+            (line(tr("drop.keys.web")), theme.muted),
+            (line(tr("drop.keys.history")), theme.muted),
+            (line(tr("drop.keys.quit")), theme.muted),
+            ("".to_string(), theme.border),
+        ];
+        // Translations can run longer than the English the box was sized for
+        let inner_width = lines.iter().map(|(text, _)| display_width(text) + 1).max().unwrap_or(0).max(40);
+        let lines: Vec<(&str, Color)> = lines.iter().map(|(text, color)| (text.as_str(), *color)).collect();
+        draw_box(stdout, theme, inner_width, &lines)?;
+    }
+    Ok(())
+}
+
+fn show_processing(stdout: &mut io::Stdout, theme: &Theme, view: &ProcessingView, queued: usize) -> Result<()> {
+    let (width, height) = terminal::size()?;
+    let center_y = height / 2;
+
+    print_centered(
+        stdout,
+        center_y.saturating_sub(3),
+        theme.warning,
+        &format!("{}{}", theme.pick("⚡ ", ""), tr("processing.title")),
+    )?;
+    print_centered(stdout, center_y.saturating_sub(2), theme.muted, &truncate(&view.label, width.saturating_sub(4) as usize))?;
+
+    let bar_width = width.saturating_sub(20).min(40) as usize;
+    print_centered(stdout, center_y, theme.accent, &progress_bar(theme, view.stage.fraction(), bar_width))?;
+    print_centered(
+        stdout,
+        center_y + 1,
+        theme.text,
+        &format!("{}...  {:.1}s", view.stage.label(), view.started.elapsed().as_secs_f32()),
+    )?;
+    if queued > 0 {
+        print_centered(stdout, center_y + 3, theme.muted, &trf("processing.queued", &[("count", &queued)]))?;
+    } else {
+        print_centered(stdout, center_y + 3, theme.muted, tr("processing.cancel"))?;
+    }
+    Ok(())
+}
+
+fn progress_bar(theme: &Theme, fraction: f32, width: usize) -> String {
+    let filled = ((fraction.clamp(0.0, 1.0) * width as f32).round() as usize).min(width);
+    let (full, empty) = if theme.ascii() { ("#", "-") } else { ("█", "░") };
+    format!("[{}{}] {:>3}%", full.repeat(filled), empty.repeat(width - filled), (fraction * 100.0).round() as u32)
+}
+
+// A colored swatch and hex code per color, centered on one line
+fn draw_palette(stdout: &mut io::Stdout, theme: &Theme, y: u16, palette: &[[u8; 3]]) -> Result<()> {
+    let (width, _) = terminal::size()?;
+    let swatch = theme.pick("██", "##");
+    // Swatch, space, "#rrggbb", two spaces
+    let total = palette.len() * 12 - 2;
+    queue!(stdout, cursor::MoveTo(width.saturating_sub(total as u16) / 2, y))?;
+    for (i, &[r, g, b]) in palette.iter().enumerate() {
+        if i > 0 {
+            queue!(stdout, Print("  "))?;
+        }
+        queue!(
+            stdout,
+            SetForegroundColor(Color::Rgb { r, g, b }),
+            Print(swatch),
+            SetForegroundColor(theme.muted),
+            Print(format!(" {}", hex([r, g, b]))),
+        )?;
+    }
+    queue!(stdout, ResetColor)?;
+    Ok(())
+}
+
+fn show_result(stdout: &mut io::Stdout, theme: &Theme, view: &ResultView) -> Result<()> {
+    let (term_width, term_height) = terminal::size()?;
+    let center_y = term_height / 2;
+    let max_len = term_width.saturating_sub(4) as usize;
+
+    let outcome = match &view.outcome {
+        Ok(outcome) => outcome,
+        Err(OptimizeError::NotFound) => {
+            return print_centered(stdout, center_y.saturating_sub(1), theme.error, &error_title(theme, tr("result.not_found")));
+        }
+        Err(OptimizeError::Cancelled) => {
+            return print_centered(stdout, center_y.saturating_sub(1), theme.muted, tr("result.cancelled"));
+        }
+        Err(OptimizeError::TooLarge(reason)) => {
+            print_centered(
+                stdout,
+                center_y.saturating_sub(1),
+                theme.error,
+                &error_title(theme, tr("result.too_large")),
+            )?;
+            return print_centered(stdout, center_y + 1, theme.error_dim, &truncate(reason, max_len));
+        }
+        Err(OptimizeError::Transform(reason)) => {
+            print_centered(
+                stdout,
+                center_y.saturating_sub(1),
+                theme.error,
+                &error_title(theme, tr("result.transform_failed")),
+            )?;
+            return print_centered(stdout, center_y + 1, theme.error_dim, &truncate(reason, max_len));
+        }
+        Err(OptimizeError::Unsupported(reason)) => {
+            print_centered(
+                stdout,
+                center_y.saturating_sub(1),
+                theme.error,
+                &error_title(theme, tr("result.unsupported")),
+            )?;
+            return print_centered(stdout, center_y + 1, theme.error_dim, &truncate(reason, max_len));
+        }
+        Err(OptimizeError::Output(reason)) => {
+            print_centered(
+                stdout,
+                center_y.saturating_sub(1),
+                theme.error,
+                &error_title(theme, tr("result.output_failed")),
+            )?;
+            return print_centered(stdout, center_y + 1, theme.error_dim, &truncate(reason, max_len));
+        }
+        Err(OptimizeError::Unreadable(_)) => {
+            return print_centered(
+                stdout,
+                center_y.saturating_sub(1),
+                theme.error,
+                &error_title(theme, tr("result.unreadable")),
+            );
+        }
+    };
+
+    match &outcome.delivery {
+        Delivery::Clipboard | Delivery::Saved(_) | Delivery::Stdout | Delivery::Uploaded { .. } | Delivery::Written { .. } => {
+            if let Some(diff) = &outcome.diff {
+                let summary = trf("result.diff", &[("similarity", &format!("{:.1}", diff.similarity)), ("regions", &diff.regions)]);
+                print_centered(stdout, center_y.saturating_sub(3), theme.accent, &summary)?;
+            } else if let Some(count) = outcome.grid {
+                print_centered(stdout, center_y.saturating_sub(3), theme.accent, &trf("result.grid", &[("count", &count)]))?;
+            } else if outcome.cached {
+                print_centered(stdout, center_y.saturating_sub(3), theme.muted, tr("result.cached"))?;
+            } else if outcome.passed_through {
+                print_centered(stdout, center_y.saturating_sub(3), theme.muted, tr("result.passed_through"))?;
+            } else if let Some(content) = outcome.content {
+                let key = match content {
+                    Content::Text => "result.auto_text",
+                    Content::Photo => "result.auto_photo",
+                    Content::Graphic => "result.auto_graphic",
+                };
+                print_centered(stdout, center_y.saturating_sub(3), theme.muted, tr(key))?;
+            }
+            let size = |bytes: u64| format!(", {}", format_bytes(bytes));
+            let (original_bytes, optimized_bytes) = outcome.bytes.map(|(o, n)| (size(o), size(n))).unwrap_or_default();
+            let (width, height) = outcome.original;
+            let original = trf("result.original", &[("width", &width), ("height", &height), ("bytes", &original_bytes)]);
+            print_centered(stdout, center_y.saturating_sub(1), theme.info, &original)?;
+            let (width, height) = outcome.optimized;
+            let optimized = trf("result.optimized", &[("width", &width), ("height", &height), ("bytes", &optimized_bytes)]);
+            print_centered(stdout, center_y, theme.success, &optimized)?;
+            if !outcome.warnings.is_empty() {
+                print_centered(stdout, center_y + 1, theme.warning, &truncate(&outcome.warnings.join("; "), max_len))?;
+            }
+            match &outcome.delivery {
+                Delivery::Uploaded { url, copied: Ok(()) } => {
+                    let copied = trf("result.uploaded", &[("copied", &view.copied)]);
+                    print_centered(stdout, center_y + 2, theme.success, &format!("{}{}", theme.pick("🔗 ", ""), copied))?;
+                    print_centered(stdout, center_y + 3, theme.text, &truncate(url, max_len))?;
+                    print_centered(stdout, center_y + 4, theme.success_dim, tr("result.ready"))?;
+                }
+                Delivery::Uploaded { url, copied: Err(e) } => {
+                    let failed = trf("result.upload_copy_failed", &[("copied", &view.copied), ("error", &e)]);
+                    print_centered(stdout, center_y + 2, theme.error, &truncate(&failed, max_len))?;
+                    print_centered(stdout, center_y + 3, theme.text, &truncate(url, max_len))?;
+                    print_centered(stdout, center_y + 4, theme.muted, tr("result.any_key"))?;
+                }
+                Delivery::Saved(saved) => {
+                    print_centered(stdout, center_y + 2, theme.success, &format!("{}{}", theme.pick("💾 ", ""), tr("result.saved_to")))?;
+                    for (i, line) in wrap(&saved.display().to_string(), max_len, 2).iter().enumerate() {
+                        print_centered(stdout, center_y + 3 + i as u16, theme.text, line)?;
+                    }
+                }
+                Delivery::Stdout => {
+                    print_centered(stdout, center_y + 2, theme.success, tr("result.stdout"))?;
+                }
+                Delivery::Written { dir, files, copied } => {
+                    let saved = trf("result.saved_files", &[("count", &files.len())]);
+                    print_centered(stdout, center_y + 2, theme.success, &format!("{}{}", theme.pick("💾 ", ""), saved))?;
+                    print_centered(stdout, center_y + 3, theme.text, &truncate(&dir.display().to_string(), max_len))?;
+                    let (color, status) = match copied {
+                        None => (theme.muted, files.join("  ")),
+                        Some(Ok(())) => (theme.success_dim, tr("result.snippet_copied").to_string()),
+                        Some(Err(e)) => (theme.error, trf("result.snippet_failed", &[("error", &e)])),
+                    };
+                    print_centered(stdout, center_y + 4, color, &truncate(&status, max_len))?;
+                }
+                _ => {
+                    print_centered(stdout, center_y + 2, theme.success, &format!("{}{}", theme.pick("✅ ", ""), tr("result.copied")))?;
+                    print_centered(stdout, center_y + 4, theme.success_dim, tr("result.ready"))?;
+                }
+            }
+            if let Some(transfer) = &view.transfer {
+                print_centered(stdout, center_y + 5, theme.muted, &truncate(transfer, max_len))?;
+            }
+            if let Some(qr) = &view.qr {
+                let fits = center_y as usize + 6 + qr.len() <= term_height as usize
+                    && qr.first().is_some_and(|line| display_width(line) <= term_width as usize);
+                if fits {
+                    for (i, line) in qr.iter().enumerate() {
+                        print_centered(stdout, center_y + 6 + i as u16, Color::White, line)?;
+                    }
+                } else {
+                    print_centered(stdout, center_y + 6, theme.muted, tr("result.enlarge_qr"))?;
+                }
+            } else {
+                if !outcome.palette.is_empty() {
+                    draw_palette(stdout, theme, center_y + 6, &outcome.palette)?;
+                }
+                let reveal = view.output().map(|_| trf("result.reveal", &[("app", &file_manager())]));
+                let (color, status) = match &view.palette_status {
+                    _ if outcome.palette.is_empty() => (theme.muted, reveal.unwrap_or_default()),
+                    None => {
+                        let hints: Vec<String> = reveal.into_iter().chain([tr("result.copy_palette").to_string()]).collect();
+                        (theme.muted, hints.join("  "))
+                    }
+                    Some(Ok(())) => (theme.success_dim, tr("result.palette_copied").to_string()),
+                    Some(Err(e)) => (theme.error_dim, truncate(&trf("result.palette_failed", &[("error", &e)]), max_len)),
+                };
+                print_centered(stdout, center_y + 7, color, &status)?;
+            }
+        }
+        Delivery::Fallback { reason, saved } => {
+            print_centered(
+                stdout,
+                center_y.saturating_sub(3),
+                theme.error,
+                &error_title(theme, tr("result.clipboard_failed")),
+            )?;
+            print_centered(stdout, center_y.saturating_sub(1), theme.error_dim, &truncate(reason, max_len))?;
+            match saved {
+                Ok(fallback) => {
+                    print_centered(stdout, center_y + 1, theme.warning, tr("result.fallback_saved"))?;
+                    for (i, line) in wrap(&fallback.display().to_string(), max_len, 2).iter().enumerate() {
+                        print_centered(stdout, center_y + 2 + i as u16, theme.text, line)?;
+                    }
+                }
+                Err(_) => {
+                    print_centered(
+                        stdout,
+                        center_y + 1,
+                        theme.error,
+                        &error_title(theme, tr("result.fallback_failed")),
+                    )?;
+                }
+            }
+            let hint = match saved {
+                Ok(_) => trf("result.fallback_hint", &[("app", &file_manager())]),
+                Err(_) => tr("result.any_key").to_string(),
+            };
+            print_centered(stdout, center_y + 4, theme.muted, &hint)?;
+        }
+    }
+    Ok(())
+}
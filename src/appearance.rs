@@ -0,0 +1,101 @@
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+static SYSTEM_DARK: OnceLock<bool> = OnceLock::new();
+
+const LIGHT: [u8; 3] = [255, 255, 255];
+// Close to the page color of dark-mode editors, chats and issue trackers
+const DARK: [u8; 3] = [30, 30, 30];
+
+// Whether what imgopt adds around images (grid gaps, window frames, and the
+// color transparency is flattened onto for JPEG) is light or dark. "auto"
+// follows the system's dark mode, so pasting into a dark document doesn't
+// bring white borders along.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Appearance {
+    #[default]
+    Auto,
+    Light,
+    Dark,
+}
+
+impl Appearance {
+    pub fn parse(name: &str) -> Option<Appearance> {
+        match name {
+            "auto" => Some(Appearance::Auto),
+            "light" => Some(Appearance::Light),
+            "dark" => Some(Appearance::Dark),
+            _ => None,
+        }
+    }
+
+    // The system is asked once; a batch shouldn't change color halfway
+    pub fn is_dark(self) -> bool {
+        match self {
+            Appearance::Auto => *SYSTEM_DARK.get_or_init(|| detect().unwrap_or(false)),
+            Appearance::Light => false,
+            Appearance::Dark => true,
+        }
+    }
+
+    // The background behind transparent and padded areas
+    pub fn matte(self) -> [u8; 3] {
+        if self.is_dark() {
+            DARK
+        } else {
+            LIGHT
+        }
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux", windows))]
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).output().ok().filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// AppleInterfaceStyle is only set, to "Dark", in dark mode
+#[cfg(target_os = "macos")]
+fn detect() -> Option<bool> {
+    Some(output("defaults", &["read", "-g", "AppleInterfaceStyle"]).is_some_and(|style| style == "Dark"))
+}
+
+// GNOME's color scheme, which most GTK desktops follow, then KDE's, then
+// the convention of naming dark themes so
+#[cfg(target_os = "linux")]
+fn detect() -> Option<bool> {
+    let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    if var("WAYLAND_DISPLAY").is_none() && var("DISPLAY").is_none() {
+        return None;
+    }
+    if let Some(theme) = var("GTK_THEME") {
+        return Some(theme.to_lowercase().contains("dark"));
+    }
+    // 'prefer-dark', 'prefer-light', or 'default', which leaves it to the theme
+    let scheme = output("gsettings", &["get", "org.gnome.desktop.interface", "color-scheme"]);
+    match scheme.as_deref() {
+        Some(scheme) if scheme.contains("prefer-dark") => return Some(true),
+        Some(scheme) if scheme.contains("prefer-light") => return Some(false),
+        _ => {}
+    }
+    let kde = ["kreadconfig6", "kreadconfig5"]
+        .iter()
+        .find_map(|program| output(program, &["--group", "General", "--key", "ColorScheme"]).filter(|scheme| !scheme.is_empty()));
+    kde.or_else(|| output("gsettings", &["get", "org.gnome.desktop.interface", "gtk-theme"]))
+        .map(|theme| theme.to_lowercase().contains("dark"))
+}
+
+// Apps follow AppsUseLightTheme, which is 0 in dark mode
+#[cfg(windows)]
+fn detect() -> Option<bool> {
+    let key = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Themes\Personalize";
+    let text = output("reg", &["query", key, "/v", "AppsUseLightTheme"])?;
+    let value = text.lines().find(|line| line.contains("AppsUseLightTheme"))?.split_whitespace().last()?;
+    Some(value == "0x0")
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", windows)))]
+fn detect() -> Option<bool> {
+    None
+}
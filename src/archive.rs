@@ -0,0 +1,35 @@
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::log;
+use crate::stamp::civil_date;
+
+// With `archive_dir` set, every input is copied to <archive_dir>/<date>/
+// before imgopt does anything with it, so workflows that replace or delete
+// originals (`imgopt precommit`, watch folders that get cleaned up) can
+// always be undone. The date is today's, in UTC.
+pub fn keep(path: &Path, archive_dir: &Path) -> Result<PathBuf, String> {
+    let secs = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (year, month, day) = civil_date(secs / 86400);
+    let dir = archive_dir.join(format!("{:04}-{:02}-{:02}", year, month, day));
+    std::fs::create_dir_all(&dir).map_err(|e| format!("could not create {}: {}", dir.display(), e))?;
+
+    let name = path.file_name().ok_or("the input has no file name")?;
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let contents = std::fs::read(path).map_err(|e| e.to_string())?;
+
+    // The same file dropped twice is archived once; a different file with
+    // the same name gets a number
+    let mut target = dir.join(name);
+    for n in 2.. {
+        match std::fs::read(&target) {
+            Ok(existing) if existing == contents => return Ok(target),
+            Ok(_) => target = dir.join(format!("{}-{}{}", stem, n, extension)),
+            Err(_) => break,
+        }
+    }
+    std::fs::write(&target, &contents).map_err(|e| format!("could not write {}: {}", target.display(), e))?;
+    log(&format!("Archived {} to {}", path.display(), target.display()));
+    Ok(target)
+}
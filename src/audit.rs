@@ -0,0 +1,184 @@
+use image::ImageReader;
+use rayon::prelude::*;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::cache::Cache;
+use crate::config::Config;
+use crate::optimize::format_bytes;
+use crate::paths::collect_images;
+use crate::precommit::{budget_config, inspect};
+use crate::serve::process;
+use crate::Result;
+
+// Deep enough for any real project, shallow enough to escape symlink loops
+pub(crate) const MAX_DEPTH: usize = 32;
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Report {
+    Table,
+    Csv,
+    Json,
+}
+
+struct Finding {
+    path: PathBuf,
+    size: (u32, u32),
+    bytes: u64,
+    reasons: Vec<&'static str>,
+    // What `imgopt precommit` would make of it; None for formats it leaves alone
+    estimate: Option<((u32, u32), u64)>,
+}
+
+impl Finding {
+    fn savings(&self) -> u64 {
+        self.estimate.map(|(_, bytes)| self.bytes.saturating_sub(bytes)).unwrap_or(0)
+    }
+}
+
+// `imgopt audit <dir>`: lists images below `dir` that exceed the [budget] and
+// what optimizing them would save, without touching any file
+pub fn run_audit(dir: &Path, report: Report, config: &Config) -> Result<()> {
+    if !dir.is_dir() {
+        return Err(format!("not a directory: {}", dir.display()).into());
+    }
+    let budget = config.budget;
+    let images = collect_images(dir, MAX_DEPTH, usize::MAX);
+    let scanned = images.len();
+    let total_bytes: u64 = images.iter().filter_map(|path| std::fs::metadata(path).ok()).map(|m| m.len()).sum();
+
+    let mut findings: Vec<Finding> = images
+        .into_iter()
+        .filter_map(|path| {
+            let size = ImageReader::open(&path).ok()?.with_guessed_format().ok()?.into_dimensions().ok()?;
+            let bytes = std::fs::metadata(&path).ok()?.len();
+            let reasons = budget.exceeded(size, bytes);
+            (!reasons.is_empty()).then_some(Finding { path, size, bytes, reasons, estimate: None })
+        })
+        .collect();
+
+    // Estimates come from actually running the pipeline in memory, like precommit would
+    let config = budget_config(config);
+    let cache = Cache::default();
+    findings.par_iter_mut().for_each(|finding| {
+        finding.estimate = inspect(&finding.path).and_then(|(format, _, _)| {
+            let params = HashMap::from([("format".to_string(), format.extensions_str()[0].to_string())]);
+            let processed = process(&finding.path, &params, &config, &cache).ok()?;
+            let optimized = &processed.metadata["optimized"];
+            let size = (optimized["width"].as_u64()? as u32, optimized["height"].as_u64()? as u32);
+            // precommit keeps the original when a re-encode doesn't help
+            let bytes = processed.bytes.len() as u64;
+            let better = budget.improved((finding.size, finding.bytes), (size, bytes));
+            Some(if better { (size, bytes) } else { (finding.size, finding.bytes) })
+        });
+    });
+    findings.sort_by_key(|finding| std::cmp::Reverse(finding.bytes));
+
+    let relative = |path: &Path| path.strip_prefix(dir).unwrap_or(path).display().to_string();
+    let savings: u64 = findings.iter().map(Finding::savings).sum();
+    match report {
+        Report::Table => {
+            println!(
+                "imgopt audit: {} images, {} in {}",
+                scanned,
+                format_bytes(total_bytes),
+                dir.display()
+            );
+            println!("Budget: {} per image, {}px longest side", format_bytes(budget.max_bytes), budget.max_dimension);
+            println!();
+            if findings.is_empty() {
+                println!("Everything is within budget.");
+                return Ok(());
+            }
+            for finding in &findings {
+                let estimate = match finding.estimate {
+                    Some(((width, height), bytes)) => format!(
+                        "-> {}x{}, {} (saves {})",
+                        width,
+                        height,
+                        format_bytes(bytes),
+                        format_bytes(finding.savings())
+                    ),
+                    None => "-> no estimate for this format".to_string(),
+                };
+                println!(
+                    "{}  {}x{}, {}  [{}]  {}",
+                    relative(&finding.path),
+                    finding.size.0,
+                    finding.size.1,
+                    format_bytes(finding.bytes),
+                    finding.reasons.join(", "),
+                    estimate
+                );
+            }
+            println!();
+            println!(
+                "{} of {} images over budget; optimizing them would save about {}",
+                findings.len(),
+                scanned,
+                format_bytes(savings)
+            );
+        }
+        Report::Csv => {
+            println!("path,width,height,bytes,over,optimized_width,optimized_height,optimized_bytes,savings");
+            for finding in &findings {
+                let (optimized_width, optimized_height, optimized_bytes) = match finding.estimate {
+                    Some(((width, height), bytes)) => (width.to_string(), height.to_string(), bytes.to_string()),
+                    None => Default::default(),
+                };
+                println!(
+                    "{},{},{},{},{},{},{},{},{}",
+                    csv_field(&relative(&finding.path)),
+                    finding.size.0,
+                    finding.size.1,
+                    finding.bytes,
+                    finding.reasons.join(" "),
+                    optimized_width,
+                    optimized_height,
+                    optimized_bytes,
+                    finding.savings()
+                );
+            }
+        }
+        Report::Json => {
+            let images: Vec<_> = findings
+                .iter()
+                .map(|finding| {
+                    json!({
+                        "path": relative(&finding.path),
+                        "width": finding.size.0,
+                        "height": finding.size.1,
+                        "bytes": finding.bytes,
+                        "over": finding.reasons,
+                        "optimized": finding.estimate.map(|((width, height), bytes)| json!({
+                            "width": width,
+                            "height": height,
+                            "bytes": bytes,
+                        })),
+                        "savings": finding.savings(),
+                    })
+                })
+                .collect();
+            let report = json!({
+                "dir": dir,
+                "budget": { "max_bytes": budget.max_bytes, "max_dimension": budget.max_dimension },
+                "scanned": scanned,
+                "total_bytes": total_bytes,
+                "over_budget": images.len(),
+                "savings": savings,
+                "images": images,
+            });
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+    }
+    Ok(())
+}
+
+pub fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
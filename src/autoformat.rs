@@ -0,0 +1,51 @@
+use image::{DynamicImage, ImageFormat};
+use std::collections::HashSet;
+
+// Above this share of pixels repeating their left neighbor, an image is flat
+// (UI, diagrams, text) rather than a photo
+const FLAT_RUNS: f64 = 0.5;
+// Larger images are sampled on a grid for the color count
+const SAMPLE_PIXELS: u32 = 1_000_000;
+
+// What `format = "auto"` settled on: the formats worth encoding, smallest
+// output wins, and why they were picked
+pub struct Choice {
+    pub candidates: Vec<ImageFormat>,
+    pub reason: &'static str,
+}
+
+// Few colors always fit a palette PNG (see dpi.rs). Photos go to JPEG unless
+// they have transparency. Everything else is lossless either way, so PNG and
+// WebP both get tried.
+pub fn choose(img: &DynamicImage) -> Choice {
+    let rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let step = ((width as u64 * height as u64) as f64 / SAMPLE_PIXELS as f64).sqrt().ceil().max(1.0) as usize;
+
+    let mut colors = HashSet::new();
+    let mut transparent = false;
+    for y in (0..height).step_by(step) {
+        for x in (0..width).step_by(step) {
+            let pixel = rgba.get_pixel(x, y).0;
+            transparent |= pixel[3] < 255;
+            if colors.len() <= 256 {
+                colors.insert(pixel);
+            }
+        }
+    }
+
+    let mut runs = 0u64;
+    for row in rgba.rows() {
+        let pixels: Vec<_> = row.collect();
+        runs += pixels.windows(2).filter(|pair| pair[0] == pair[1]).count() as u64;
+    }
+    let flat = runs as f64 / (width as u64 * height as u64).max(1) as f64 > FLAT_RUNS;
+
+    let lossless = vec![ImageFormat::Png, ImageFormat::WebP];
+    match (colors.len() <= 256, transparent, flat) {
+        (true, _, _) => Choice { candidates: vec![ImageFormat::Png], reason: "256 colors or fewer" },
+        (_, true, _) => Choice { candidates: lossless, reason: "transparency" },
+        (_, _, true) => Choice { candidates: lossless, reason: "flat content" },
+        _ => Choice { candidates: vec![ImageFormat::Jpeg], reason: "photographic" },
+    }
+}
@@ -0,0 +1,140 @@
+use serde::Deserialize;
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use crate::{log, say};
+
+// How often a paused job looks at the power source again
+const BATTERY_POLL: Duration = Duration::from_secs(10);
+
+// How hard `imgopt watch` and `imgopt daemon` may work the machine, so they
+// stay out of the way of screen recordings and the battery
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct Background {
+    // Images optimized at the same time; 0 is one per core
+    pub jobs: usize,
+    // Threads each image is resized with; 0 uses every core
+    pub threads: usize,
+    // Added to the process's niceness, up to 19; higher gives way to other
+    // programs more, and 0 leaves it alone
+    pub nice: u32,
+    // Hold jobs while the machine runs on battery
+    pub pause_on_battery: bool,
+}
+
+impl Background {
+    pub fn slots(&self) -> Slots {
+        let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        Slots::new(if self.jobs == 0 { cores } else { self.jobs })
+    }
+
+    // Set once at startup; threads started later inherit the priority
+    pub fn apply(&self) {
+        if self.threads > 0 {
+            if let Err(e) = rayon::ThreadPoolBuilder::new().num_threads(self.threads).build_global() {
+                log(&format!("Could not limit threads: {}", e));
+            }
+        }
+        if self.nice > 0 {
+            if let Err(e) = renice(self.nice) {
+                log(&format!("Could not lower priority: {}", e));
+            }
+        }
+    }
+}
+
+// At most `jobs` holders at a time; the rest wait their turn
+pub struct Slots {
+    free: Mutex<usize>,
+    released: Condvar,
+}
+
+pub struct Slot<'a>(&'a Slots);
+
+impl Slots {
+    fn new(jobs: usize) -> Slots {
+        Slots { free: Mutex::new(jobs.max(1)), released: Condvar::new() }
+    }
+
+    pub fn take(&self) -> Slot<'_> {
+        let mut free = self.free.lock().unwrap();
+        while *free == 0 {
+            free = self.released.wait(free).unwrap();
+        }
+        *free -= 1;
+        Slot(self)
+    }
+}
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        *self.0.free.lock().unwrap() += 1;
+        self.0.released.notify_one();
+    }
+}
+
+// Returns once the machine is on mains power, right away unless
+// pause_on_battery is set
+pub fn wait_for_power(background: &Background) {
+    if !background.pause_on_battery || !on_battery() {
+        return;
+    }
+    say("On battery power, holding jobs until the charger is plugged in");
+    while on_battery() {
+        std::thread::sleep(BATTERY_POLL);
+    }
+    say("On mains power again, resuming");
+}
+
+#[cfg(unix)]
+fn renice(nice: u32) -> std::result::Result<(), String> {
+    // Lowers the calling thread on Linux and the whole process elsewhere; both
+    // are started from the main thread before any others
+    let nice = nice.min(19) as libc::c_int;
+    let current = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, (current + nice).min(19)) } != 0 {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn renice(_: u32) -> std::result::Result<(), String> {
+    Err("not supported on this system".to_string())
+}
+
+// pmset says "Now drawing from 'Battery Power'"
+#[cfg(target_os = "macos")]
+fn on_battery() -> bool {
+    std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"))
+}
+
+// A battery that is discharging, or one next to a mains supply that is offline
+#[cfg(target_os = "linux")]
+fn on_battery() -> bool {
+    let read = |path: std::path::PathBuf| std::fs::read_to_string(path).map(|text| text.trim().to_string()).unwrap_or_default();
+    let Ok(supplies) = std::fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    let (mut mains, mut battery, mut discharging) = (None, false, false);
+    for supply in supplies.filter_map(|e| e.ok()).map(|e| e.path()) {
+        match read(supply.join("type")).as_str() {
+            "Mains" => mains = Some(mains.unwrap_or(false) || read(supply.join("online")) == "1"),
+            "Battery" => {
+                battery = true;
+                discharging |= read(supply.join("status")) == "Discharging";
+            }
+            _ => {}
+        }
+    }
+    battery && mains.map_or(discharging, |online| !online)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn on_battery() -> bool {
+    false
+}
@@ -0,0 +1,184 @@
+use fast_image_resize::{self as fir, ResizeAlg, ResizeOptions, Resizer};
+use image::{imageops::FilterType, DynamicImage, ImageFormat, ImageReader};
+use std::io::Cursor;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+use crate::dpi::is_retina;
+use crate::large::decode_reduced;
+use crate::optimize::{format_bytes, output_dimensions, reduction_factor};
+use crate::Result;
+
+// Each measurement keeps the best of this many runs
+const RUNS: usize = 3;
+
+struct Row {
+    stage: &'static str,
+    variant: String,
+    threads: String,
+    time: Option<Duration>,
+    note: String,
+}
+
+// `imgopt bench <file>`: times every decode, resize and encode option on one
+// image so presets can be tuned for the machine they run on
+pub fn run_bench(path: &Path, config: &Config) -> Result<()> {
+    let (width, height) = ImageReader::open(path)?.with_guessed_format()?.into_dimensions()?;
+    let pixels = width as u64 * height as u64;
+    let resize = config.resize();
+    let (new_width, new_height) = output_dimensions(width, height, is_retina(path), &resize);
+    println!("imgopt bench: {}", path.display());
+    println!(
+        "{}x{} ({:.1} megapixels) -> {}x{}, best of {} runs",
+        width,
+        height,
+        pixels as f64 / 1e6,
+        new_width,
+        new_height,
+        RUNS
+    );
+    println!();
+
+    let mut rows = Vec::new();
+
+    // Decode
+    let full = if pixels <= config.limits.max_pixels {
+        let (time, img) = best_of(|| ImageReader::open(path)?.with_guessed_format()?.decode().map_err(Into::into))?;
+        rows.push(Row {
+            stage: "decode",
+            variant: "full".into(),
+            threads: "1".into(),
+            time: Some(time),
+            note: String::new(),
+        });
+        Some(img)
+    } else {
+        rows.push(Row {
+            stage: "decode",
+            variant: "full".into(),
+            threads: "-".into(),
+            time: None,
+            note: "skipped, over max_pixels".into(),
+        });
+        None
+    };
+
+    let factor = reduction_factor(width, height, (new_width, new_height));
+    let mut reduced = None;
+    if factor >= 2 && pixels <= config.limits.max_scaled_pixels {
+        let (time, img) = best_of(|| Ok(decode_reduced(path, width, height, factor).transpose()?))?;
+        if let Some(img) = img {
+            rows.push(Row {
+                stage: "decode",
+                variant: "reduced".into(),
+                threads: "1".into(),
+                time: Some(time),
+                note: format!("{}x{}", img.width(), img.height()),
+            });
+            reduced = Some(img);
+        }
+    }
+
+    // Resize whatever the real pipeline would have resized
+    let Some(source) = reduced.or(full) else {
+        print_table(&rows);
+        return Err("image is too large to decode with the current limits".into());
+    };
+
+    let mut thread_counts = vec![1, 2, 4];
+    thread_counts.push(std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1));
+    thread_counts.sort();
+    thread_counts.dedup();
+
+    let fast_filters = [
+        ("Lanczos3", fir::FilterType::Lanczos3),
+        ("CatmullRom", fir::FilterType::CatmullRom),
+        ("Bilinear", fir::FilterType::Bilinear),
+        ("Box", fir::FilterType::Box),
+    ];
+    for (name, filter) in fast_filters {
+        for &threads in &thread_counts {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build()?;
+            let options = ResizeOptions::new().resize_alg(ResizeAlg::Convolution(filter));
+            let (time, _) = best_of(|| {
+                let mut resized = DynamicImage::new(new_width, new_height, source.color());
+                pool.install(|| Resizer::new().resize(&source, &mut resized, &options))?;
+                Ok(resized)
+            })?;
+            rows.push(Row {
+                stage: "resize",
+                variant: format!("fast {}", name),
+                threads: threads.to_string(),
+                time: Some(time),
+                note: String::new(),
+            });
+        }
+    }
+
+    let image_filters = [
+        ("Lanczos3", FilterType::Lanczos3),
+        ("CatmullRom", FilterType::CatmullRom),
+        ("Triangle", FilterType::Triangle),
+        ("Nearest", FilterType::Nearest),
+    ];
+    for (name, filter) in image_filters {
+        let (time, _) = best_of(|| Ok(source.resize_exact(new_width, new_height, filter)))?;
+        rows.push(Row {
+            stage: "resize",
+            variant: format!("image {}", name),
+            threads: "1".into(),
+            time: Some(time),
+            note: String::new(),
+        });
+    }
+
+    // Encode the same output the clipboard gets
+    let resized = source.resize_exact(new_width, new_height, FilterType::Lanczos3);
+    let encoders = [
+        ("PNG", ImageFormat::Png, resized.clone()),
+        ("TIFF", ImageFormat::Tiff, resized.clone()),
+        ("JPEG", ImageFormat::Jpeg, DynamicImage::ImageRgb8(resized.to_rgb8())),
+    ];
+    for (name, format, img) in encoders {
+        let (time, bytes) = best_of(|| {
+            let mut buffer = Cursor::new(Vec::new());
+            img.write_to(&mut buffer, format)?;
+            Ok(buffer.into_inner().len() as u64)
+        })?;
+        rows.push(Row {
+            stage: "encode",
+            variant: name.into(),
+            threads: "1".into(),
+            time: Some(time),
+            note: format_bytes(bytes),
+        });
+    }
+
+    print_table(&rows);
+    Ok(())
+}
+
+fn best_of<T>(mut run: impl FnMut() -> Result<T>) -> Result<(Duration, T)> {
+    let mut best: Option<(Duration, T)> = None;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let value = run()?;
+        let elapsed = start.elapsed();
+        if best.as_ref().is_none_or(|(time, _)| elapsed < *time) {
+            best = Some((elapsed, value));
+        }
+    }
+    Ok(best.expect("RUNS is not zero"))
+}
+
+fn print_table(rows: &[Row]) {
+    println!("{:<8}{:<18}{:>8}{:>12}  note", "stage", "variant", "threads", "time");
+    for row in rows {
+        let time = match row.time {
+            Some(time) => format!("{:.1} ms", time.as_secs_f64() * 1000.0),
+            None => "-".to_string(),
+        };
+        println!("{:<8}{:<18}{:>8}{:>12}  {}", row.stage, row.variant, row.threads, time, row.note);
+    }
+}
@@ -0,0 +1,112 @@
+// Thin client for `imgopt daemon`: sends each path as a job over the daemon's
+// Unix socket and prints the replies. Only uses std and serde_json, so it
+// starts fast.
+use serde_json::{json, Map, Value};
+use std::io::{BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const USAGE: &str = "usage: imgopt-cli send <path>... [--output <file>] [--<parameter> <value>]...";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("imgopt-cli: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+// Ok(false) when any job failed
+fn run() -> Result<bool, String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) != Some("send") {
+        return Err(USAGE.to_string());
+    }
+
+    // Options apply to every path: --max-dimension 600 becomes "max_dimension": "600"
+    let mut paths = Vec::new();
+    let mut options = Map::new();
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.strip_prefix("--") {
+            Some(name) => {
+                let value = rest.next().ok_or(format!("--{} needs a value", name))?;
+                options.insert(name.replace('-', "_"), json!(value));
+            }
+            None => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    if paths.len() > 1 && options.contains_key("output") {
+        return Err("--output only works with a single path".to_string());
+    }
+
+    let (mut writer, replies) = connect(&socket_path())?;
+    let mut replies = replies.lines();
+
+    let mut all_ok = true;
+    for path in paths {
+        // The daemon runs elsewhere, so it needs absolute paths
+        let absolute = match std::fs::canonicalize(&path) {
+            Ok(absolute) => absolute,
+            Err(e) => {
+                println!("{}: {}", path.display(), e);
+                all_ok = false;
+                continue;
+            }
+        };
+        let mut job = options.clone();
+        job.insert("path".to_string(), json!(absolute));
+        writeln!(writer, "{}", Value::Object(job)).map_err(|e| format!("sending to the daemon: {}", e))?;
+
+        let reply = replies
+            .next()
+            .ok_or("the daemon closed the connection")?
+            .map_err(|e| format!("reading from the daemon: {}", e))?;
+        let reply: Value = serde_json::from_str(&reply).map_err(|e| format!("bad reply from the daemon: {}", e))?;
+        if reply["ok"].as_bool() == Some(true) {
+            println!(
+                "{}: {}, saved {}",
+                path.display(),
+                reply["summary"].as_str().unwrap_or_default(),
+                reply["output"].as_str().unwrap_or_default()
+            );
+        } else {
+            println!("{}: {}", path.display(), reply["error"].as_str().unwrap_or("failed"));
+            all_ok = false;
+        }
+    }
+    Ok(all_ok)
+}
+
+// Jobs go out through the first, replies come back through the second
+#[cfg(unix)]
+fn connect(socket: &Path) -> Result<(UnixStream, BufReader<UnixStream>), String> {
+    let stream = UnixStream::connect(socket)
+        .map_err(|e| format!("no daemon at {} ({}); start one with `imgopt daemon`", socket.display(), e))?;
+    let writer = stream.try_clone().map_err(|e| e.to_string())?;
+    Ok((writer, BufReader::new(stream)))
+}
+
+#[cfg(not(unix))]
+fn connect(_: &Path) -> Result<(std::io::Sink, BufReader<std::io::Empty>), String> {
+    Err("the daemon needs Unix sockets".to_string())
+}
+
+// Same lookup as socket_path() in src/daemon.rs
+fn socket_path() -> PathBuf {
+    if let Some(path) = std::env::var_os("IMGOPT_SOCKET").filter(|p| !p.is_empty()) {
+        return PathBuf::from(path);
+    }
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|d| !d.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join("imgopt.sock"),
+        None => std::env::temp_dir().join(format!("imgopt-{}.sock", std::env::var("USER").unwrap_or_default())),
+    }
+}
@@ -0,0 +1,146 @@
+use crossterm::{
+    cursor,
+    event::{KeyCode, KeyEvent},
+    queue,
+    style::{Print, ResetColor, SetForegroundColor},
+    terminal,
+};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::i18n::tr;
+use crate::paths::is_image_path;
+use crate::theme::Theme;
+use crate::ui::{draw_footer, draw_list, print_centered, truncate, Action, ListItem};
+use crate::video;
+use crate::{log, Result};
+
+struct Entry {
+    name: String,
+    path: PathBuf,
+    is_dir: bool,
+}
+
+// Directories first, then files, both alphabetical; dotfiles are hidden
+fn list_dir(dir: &Path) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = match std::fs::read_dir(dir) {
+        Ok(read) => read
+            .filter_map(|e| e.ok())
+            .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+            .map(|e| Entry {
+                name: e.file_name().to_string_lossy().into_owned(),
+                is_dir: e.path().is_dir(),
+                path: e.path(),
+            })
+            .collect(),
+        Err(e) => {
+            log(&format!("Could not read {}: {}", dir.display(), e));
+            Vec::new()
+        }
+    };
+
+    entries.sort_by(|a, b| {
+        b.is_dir
+            .cmp(&a.is_dir)
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    entries
+}
+
+// Directory listing for picking a file with the keyboard
+pub struct Browser {
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    selected: usize,
+}
+
+impl Browser {
+    pub fn new(start: &Path) -> Browser {
+        Browser {
+            dir: start.to_path_buf(),
+            entries: list_dir(start),
+            selected: 0,
+        }
+    }
+
+    fn enter(&mut self, dir: PathBuf) {
+        self.entries = list_dir(&dir);
+        self.dir = dir;
+        self.selected = 0;
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return Action::Back,
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') if self.selected + 1 < self.entries.len() => self.selected += 1,
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(10),
+            KeyCode::PageDown => self.selected = (self.selected + 10).min(self.entries.len().saturating_sub(1)),
+            KeyCode::Left | KeyCode::Backspace | KeyCode::Char('h') => {
+                if let Some(parent) = self.dir.parent().map(Path::to_path_buf) {
+                    let previous = self.dir.file_name().map(|n| n.to_string_lossy().into_owned());
+                    self.enter(parent);
+                    self.selected = previous
+                        .and_then(|name| self.entries.iter().position(|e| e.name == name))
+                        .unwrap_or(0);
+                }
+            }
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => {
+                let Some(entry) = self.entries.get(self.selected) else {
+                    return Action::None;
+                };
+                if entry.is_dir {
+                    self.enter(entry.path.clone());
+                } else if key.code == KeyCode::Enter && (is_image_path(&entry.name) || video::is_video(&entry.path)) {
+                    return Action::Open(entry.path.clone());
+                }
+            }
+            KeyCode::Char('i') => match self.entries.get(self.selected) {
+                Some(entry) if !entry.is_dir && is_image_path(&entry.name) => return Action::Inspect(entry.path.clone()),
+                _ => {}
+            },
+            _ => {}
+        }
+        Action::None
+    }
+
+    pub fn render(&self, stdout: &mut io::Stdout, theme: &Theme) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let max_len = width.saturating_sub(4) as usize;
+
+        queue!(
+            stdout,
+            cursor::MoveTo(1, 0),
+            SetForegroundColor(theme.title),
+            Print(truncate(&format!("{}{}", theme.pick("📂 ", ""), self.dir.display()), max_len)),
+            ResetColor,
+        )?;
+
+        if self.entries.is_empty() {
+            print_centered(stdout, height / 2, theme.muted, tr("browser.empty"))?;
+        }
+
+        let items: Vec<ListItem> = self
+            .entries
+            .iter()
+            .map(|entry| ListItem {
+                text: format!("{}{}", entry.name, if entry.is_dir { "/" } else { "" }),
+                color: if entry.is_dir {
+                    theme.accent
+                } else if is_image_path(&entry.name) || video::is_video(&entry.path) {
+                    theme.highlight
+                } else {
+                    theme.muted
+                },
+            })
+            .collect();
+
+        // Two header lines and one footer line
+        draw_list(stdout, theme, 1, 2, height.saturating_sub(3) as usize, &items, self.selected)?;
+        draw_footer(
+            stdout,
+            theme,
+            theme.pick(tr("browser.footer"), tr("browser.footer_ascii")),
+        )
+    }
+}
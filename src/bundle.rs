@@ -0,0 +1,282 @@
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde_json::{json, Value};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::config::Config;
+use crate::optimize::{Delivery, OptimizeError, Outcome};
+use crate::session::timestamp;
+use crate::stamp::civil_date;
+use crate::Result;
+
+// ustar keeps names in a 100-byte field
+const TAR_NAME: usize = 100;
+const MANIFEST: &str = "manifest.json";
+
+// The archive `imgopt optimize --bundle` writes, picked by its extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Zip,
+    TarZst,
+}
+
+impl Kind {
+    fn of(path: &Path) -> Option<Kind> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        if name.ends_with(".zip") {
+            Some(Kind::Zip)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(Kind::TarZst)
+        } else {
+            None
+        }
+    }
+}
+
+enum Archive {
+    Zip(ZipWriter<BufWriter<File>>, zip::DateTime),
+    TarZst(tar::Builder<zstd::Encoder<'static, BufWriter<File>>>, u64),
+}
+
+impl Archive {
+    fn create(path: &Path, kind: Kind, secs: u64) -> Result<Archive> {
+        let file = File::create(path).map_err(|e| format!("could not create {}: {}", path.display(), e))?;
+        let out = BufWriter::new(file);
+        Ok(match kind {
+            Kind::Zip => Archive::Zip(ZipWriter::new(out), dos_time(secs)),
+            // Level 0 is zstd's default
+            Kind::TarZst => Archive::TarZst(tar::Builder::new(zstd::Encoder::new(out, 0)?), secs),
+        })
+    }
+
+    // Zip entries are deflated when that makes them smaller, which PNGs and
+    // JPEGs hardly ever are, and stored otherwise
+    fn add(&mut self, name: &str, bytes: &[u8]) -> Result<()> {
+        match self {
+            Archive::Zip(zip, time) => {
+                let mut deflater = DeflateEncoder::new(Vec::new(), Compression::default());
+                deflater.write_all(bytes)?;
+                let method = if deflater.finish()?.len() < bytes.len() { CompressionMethod::Deflated } else { CompressionMethod::Stored };
+                let options = SimpleFileOptions::default()
+                    .compression_method(method)
+                    .last_modified_time(*time)
+                    .unix_permissions(0o644)
+                    .large_file(bytes.len() as u64 >= u32::MAX as u64);
+                zip.start_file(name, options)?;
+                zip.write_all(bytes)?;
+            }
+            Archive::TarZst(tar, secs) => {
+                let mut header = tar::Header::new_ustar();
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_size(bytes.len() as u64);
+                header.set_mode(0o644);
+                header.set_mtime(*secs);
+                tar.append_data(&mut header, name, bytes)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Archive::Zip(zip, _) => zip.finish()?.flush()?,
+            Archive::TarZst(tar, _) => tar.into_inner()?.finish()?.flush()?,
+        }
+        Ok(())
+    }
+}
+
+// Zip times are MS-DOS ones, local and from 1980; UTC is what we have
+fn dos_time(secs: u64) -> zip::DateTime {
+    let ((year, month, day), seconds) = (civil_date(secs / 86400), secs % 86400);
+    let (hour, minute, second) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+    zip::DateTime::from_date_and_time(year as u16, month as u8, day as u8, hour as u8, minute as u8, second as u8).unwrap_or_default()
+}
+
+// `imgopt optimize --bundle <archive>`: the files a batch saved, written into
+// one archive as they are done, and a manifest.json describing each input at
+// the end, for attaching to a ticket or pull request
+pub struct Bundle {
+    path: PathBuf,
+    archive: Archive,
+    names: Vec<String>,
+    files: Vec<Value>,
+    // The first write that failed; nothing more goes in after it
+    failed: Option<String>,
+}
+
+impl Bundle {
+    pub fn new(path: &Path) -> Result<Bundle> {
+        let kind = Kind::of(path).ok_or_else(|| format!("{}: bundles are .zip or .tar.zst files", path.display()))?;
+        let archive = Archive::create(path, kind, SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0))?;
+        Ok(Bundle { path: path.to_path_buf(), archive, names: Vec::new(), files: Vec::new(), failed: None })
+    }
+
+    // Failed inputs are listed in the manifest with their error
+    pub fn add(&mut self, input: &Path, outcome: &std::result::Result<Outcome, OptimizeError>) {
+        let outcome = match outcome {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                self.files.push(json!({ "input": input, "error": e.to_string() }));
+                return;
+            }
+        };
+        let Delivery::Saved(saved) = &outcome.delivery else {
+            return;
+        };
+        let bytes = match std::fs::read(saved) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                self.files.push(json!({ "input": input, "error": format!("could not read {}: {}", saved.display(), e) }));
+                return;
+            }
+        };
+        let name = unique_name(&saved.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(), &self.names);
+        if self.failed.is_none() {
+            if let Err(e) = self.archive.add(&name, &bytes) {
+                self.failed = Some(e.to_string());
+            }
+        }
+        self.files.push(json!({
+            "input": input,
+            "file": name,
+            "original": [outcome.original.0, outcome.original.1],
+            "optimized": [outcome.optimized.0, outcome.optimized.1],
+            "input_bytes": outcome.bytes.map(|(input, _)| input),
+            "bytes": bytes.len(),
+            "passed_through": outcome.passed_through,
+            "warnings": outcome.warnings,
+        }));
+        self.names.push(name);
+    }
+
+    // Adds the manifest and closes the archive; returns how many images went
+    // in. A bundle that couldn't be written whole is removed.
+    pub fn finish(mut self, config: &Config) -> Result<(PathBuf, usize)> {
+        let manifest = json!({
+            "imgopt": env!("CARGO_PKG_VERSION"),
+            "created": timestamp(SystemTime::now()),
+            "preset": config.preset,
+            "limits": config.resize().describe(),
+            "files": self.files,
+        });
+        let bytes = format!("{}\n", serde_json::to_string_pretty(&manifest)?).into_bytes();
+        let written = match self.failed.take() {
+            Some(e) => Err(e.into()),
+            None => self.archive.add(MANIFEST, &bytes).and_then(|_| self.archive.finish()),
+        };
+        if let Err(e) = written {
+            std::fs::remove_file(&self.path).ok();
+            return Err(format!("could not write {}: {}", self.path.display(), e).into());
+        }
+        Ok((self.path, self.names.len()))
+    }
+}
+
+// A name for `file` that no entry has yet and that fits a tar header: a
+// number goes before the extension, and long stems are shortened
+fn unique_name(file: &str, names: &[String]) -> String {
+    let (stem, extension) = match file.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (file, String::new()),
+    };
+    (1..)
+        .map(|n| {
+            let number = if n == 1 { String::new() } else { format!("-{}", n) };
+            let mut stem = stem.to_string();
+            while stem.len() + number.len() + extension.len() > TAR_NAME && stem.pop().is_some() {}
+            format!("{}{}{}", stem, number, extension)
+        })
+        .find(|name| names.iter().all(|taken| taken != name) && name != MANIFEST)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn entries() -> Vec<(&'static str, Vec<u8>)> {
+        let text = "{\"file\": \"shot.png\"}\n".repeat(200).into_bytes();
+        // Like an already-compressed PNG: nothing for deflate to find
+        let mut seed = 7u32;
+        let noise = (0..3000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 16) as u8
+            })
+            .collect();
+        vec![("shot.png", noise), ("empty.png", Vec::new()), ("notes.txt", b"x".to_vec()), (MANIFEST, text)]
+    }
+
+    // Every entry written into a new archive of `kind`, at 2023-11-14 22:13:20 UTC
+    fn write(kind: Kind, extension: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("imgopt-bundle-{}.{}", std::process::id(), extension));
+        let mut archive = Archive::create(&path, kind, 1_700_000_000).unwrap();
+        for (name, bytes) in entries() {
+            archive.add(name, &bytes).unwrap();
+        }
+        archive.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn zip_entries_read_back() {
+        let path = write(Kind::Zip, "zip");
+        let mut zip = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(zip.len(), 4);
+        for (index, (expected, bytes)) in entries().into_iter().enumerate() {
+            let mut entry = zip.by_index(index).unwrap();
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents).unwrap();
+            assert_eq!((entry.name(), contents), (expected, bytes));
+            // Only the manifest gets smaller by deflating
+            let method = if expected == MANIFEST { CompressionMethod::Deflated } else { CompressionMethod::Stored };
+            assert_eq!(entry.compression(), method, "{}", expected);
+            assert_eq!(entry.unix_mode(), Some(0o100644));
+            let time = entry.last_modified().unwrap();
+            assert_eq!((time.year(), time.month(), time.day(), time.hour(), time.minute(), time.second()), (2023, 11, 14, 22, 13, 20));
+        }
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn tar_zst_entries_read_back() {
+        let path = write(Kind::TarZst, "tar.zst");
+        let mut tar = tar::Archive::new(zstd::Decoder::new(File::open(&path).unwrap()).unwrap());
+        let read: Vec<_> = tar
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                assert_eq!((entry.header().mode().unwrap(), entry.header().mtime().unwrap()), (0o644, 1_700_000_000));
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents).unwrap();
+                (entry.path().unwrap().to_string_lossy().into_owned(), contents)
+            })
+            .collect();
+        let expected: Vec<_> = entries().into_iter().map(|(name, bytes)| (name.to_string(), bytes)).collect();
+        assert_eq!(read, expected);
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn names_are_unique_and_fit_tar() {
+        let mut names = Vec::new();
+        for file in ["shot.png", "shot.png", "shot.png", "manifest.json", "README"] {
+            names.push(unique_name(file, &names));
+        }
+        assert_eq!(names, ["shot.png", "shot-2.png", "shot-3.png", "manifest-2.json", "README"]);
+
+        let long = format!("{}.png", "a".repeat(150));
+        let first = unique_name(&long, &[]);
+        let second = unique_name(&long, std::slice::from_ref(&first));
+        assert_eq!((first.len(), second.len()), (TAR_NAME, TAR_NAME));
+        assert!(second.ends_with("a-2.png"), "{}", second);
+    }
+}
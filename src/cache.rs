@@ -0,0 +1,68 @@
+use image::DynamicImage;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Read;
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::content::Content;
+
+// Resized images are small, so this stays a few dozen MB
+const CAPACITY: usize = 8;
+
+struct Entry {
+    key: u64,
+    original: (u32, u32),
+    image: DynamicImage,
+    // What the auto preset found, which the steps after the cached ones depend on
+    content: Option<Content>,
+}
+
+// Recent results keyed by file content and settings, so dropping the same
+// screenshot again skips straight to the clipboard. Shared with worker threads.
+#[derive(Default)]
+pub struct Cache {
+    entries: Mutex<VecDeque<Entry>>,
+}
+
+impl Cache {
+    pub fn get(&self, key: u64) -> Option<((u32, u32), DynamicImage, Option<Content>)> {
+        let mut entries = self.entries.lock().ok()?;
+        let index = entries.iter().position(|e| e.key == key)?;
+        let entry = entries.remove(index)?;
+        let hit = (entry.original, entry.image.clone(), entry.content);
+        entries.push_front(entry);
+        Some(hit)
+    }
+
+    pub fn insert(&self, key: u64, original: (u32, u32), image: DynamicImage, content: Option<Content>) {
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        entries.retain(|e| e.key != key);
+        entries.push_front(Entry { key, original, image, content });
+        entries.truncate(CAPACITY);
+    }
+}
+
+// Hashes the file's bytes together with everything that affects the output
+// (`settings`). Files over `max_bytes` aren't worth reading twice and get no key.
+pub fn key(path: &Path, max_bytes: u64, settings: &impl Hash) -> Option<u64> {
+    let mut file = File::open(path).ok()?;
+    if file.metadata().ok()?.len() > max_bytes {
+        return None;
+    }
+
+    let mut hasher = DefaultHasher::new();
+    settings.hash(&mut hasher);
+
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        match file.read(&mut buffer).ok()? {
+            0 => break,
+            n => hasher.write(&buffer[..n]),
+        }
+    }
+    Some(hasher.finish())
+}
@@ -0,0 +1,120 @@
+use crate::clipboard::{self, ClipboardMode};
+use crate::display;
+use crate::i18n::{tr, trf};
+use crate::multiplexer::Multiplexer;
+use crate::paths::find_program;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Pass,
+    // Works, but not as well as it could
+    Warn,
+    Fail,
+}
+
+// One thing imgopt depends on, what was found and what to do about it
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    pub hint: Option<String>,
+}
+
+// Ways of drawing pixels in the terminal itself
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Graphics {
+    Kitty,
+    Iterm2,
+    Sixel,
+}
+
+impl Graphics {
+    // Only from the environment: asking the terminal means a round trip
+    // through raw mode that not every terminal answers
+    pub fn detect() -> Option<Graphics> {
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        let (term, program) = (var("TERM"), var("TERM_PROGRAM"));
+        if term.contains("kitty") || !var("KITTY_WINDOW_ID").is_empty() || program == "ghostty" {
+            Some(Graphics::Kitty)
+        } else if program == "iTerm.app" || program == "WezTerm" || var("LC_TERMINAL") == "iTerm2" {
+            Some(Graphics::Iterm2)
+        } else if ["foot", "mlterm", "yaft-256color"].contains(&term.as_str())
+            || !var("KONSOLE_VERSION").is_empty()
+            || !var("WT_SESSION").is_empty()
+        {
+            Some(Graphics::Sixel)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Graphics::Kitty => "kitty graphics protocol",
+            Graphics::Iterm2 => "iTerm2 inline images",
+            Graphics::Sixel => "sixel",
+        }
+    }
+}
+
+pub fn clipboard(mode: ClipboardMode) -> Check {
+    let name = tr("check.name.clipboard");
+    match clipboard::describe(mode) {
+        Ok((backend, Some((program, package)))) if find_program(program).is_none() => Check {
+            name,
+            status: Status::Fail,
+            detail: trf("check.clipboard.missing", &[("backend", &backend), ("program", &program)]),
+            hint: Some(trf("check.clipboard.install", &[("package", &package)])),
+        },
+        Ok((backend, _)) => Check { name, status: Status::Pass, detail: backend.to_string(), hint: None },
+        Err(e) => Check {
+            name,
+            status: Status::Fail,
+            detail: e.to_string(),
+            hint: Some(tr("check.clipboard.osc52").to_string()),
+        },
+    }
+}
+
+// Drops reach imgopt as bracketed pastes, unless something in between eats them
+pub fn drops(paste_enabled: bool, multiplexer: Option<Multiplexer>) -> Check {
+    let name = tr("check.name.drops");
+    match multiplexer {
+        Some(multiplexer) => Check {
+            name,
+            status: Status::Warn,
+            detail: trf("check.drops.multiplexer", &[("multiplexer", &multiplexer.name())]),
+            hint: Some(tr("check.drops.pickers").to_string()),
+        },
+        None if !paste_enabled => Check {
+            name,
+            status: Status::Warn,
+            detail: tr("check.drops.keys").to_string(),
+            hint: Some(tr("check.drops.pickers").to_string()),
+        },
+        None => Check { name, status: Status::Pass, detail: tr("check.drops.paste").to_string(), hint: None },
+    }
+}
+
+pub fn graphics() -> Check {
+    let name = tr("check.name.graphics");
+    match Graphics::detect() {
+        Some(graphics) => Check { name, status: Status::Pass, detail: graphics.name().to_string(), hint: None },
+        None => Check { name, status: Status::Warn, detail: tr("check.graphics.none").to_string(), hint: None },
+    }
+}
+
+// Screenshots on a HiDPI display come out at twice their logical size, which
+// the 480-720px defaults only account for with `retina` on
+pub fn display(scales: &[f64], retina: bool) -> Check {
+    let name = tr("check.name.display");
+    if scales.is_empty() {
+        return Check { name, status: Status::Warn, detail: tr("check.display.unknown").to_string(), hint: None };
+    }
+    let detail = display::describe(scales);
+    if display::is_hidpi(scales) && !retina {
+        Check { name, status: Status::Warn, detail, hint: Some(tr("check.display.retina").to_string()) }
+    } else {
+        Check { name, status: Status::Pass, detail, hint: None }
+    }
+}
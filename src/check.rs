@@ -0,0 +1,110 @@
+use image::ImageReader;
+use rayon::prelude::*;
+use serde_json::json;
+use std::path::PathBuf;
+
+use crate::audit::{csv_field, Report, MAX_DEPTH};
+use crate::config::Budget;
+use crate::exit::Exit;
+use crate::optimize::format_bytes;
+use crate::paths::collect_images;
+use crate::Result;
+
+struct Violation {
+    path: PathBuf,
+    // None when the header can't be read; the file size is still checked
+    size: Option<(u32, u32)>,
+    bytes: u64,
+    reasons: Vec<&'static str>,
+}
+
+// `imgopt check <path>...`: fails with Exit::OverBudget when any image in the
+// given folders (recursively) or files exceeds the budget, listing them. Only
+// headers and file sizes are read, so it is quick enough for every CI run.
+pub fn run_check(paths: &[PathBuf], budget: Budget, report: Report) -> Result<Exit> {
+    let mut images = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            images.extend(collect_images(path, MAX_DEPTH, usize::MAX));
+        } else if path.is_file() {
+            images.push(path.clone());
+        } else {
+            return Err(format!("no such file or directory: {}", path.display()).into());
+        }
+    }
+
+    let mut violations: Vec<Violation> = images
+        .par_iter()
+        .filter_map(|path| {
+            let bytes = std::fs::metadata(path).ok()?.len();
+            let size = ImageReader::open(path).ok().and_then(|r| r.with_guessed_format().ok()).and_then(|r| r.into_dimensions().ok());
+            let reasons = budget.exceeded(size.unwrap_or((0, 0)), bytes);
+            (!reasons.is_empty()).then(|| Violation { path: path.clone(), size, bytes, reasons })
+        })
+        .collect();
+    violations.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let limits = format!("{} per image, {}px longest side", format_bytes(budget.max_bytes), budget.max_dimension);
+    let size = |violation: &Violation| violation.size.map(|(width, height)| format!("{}x{}", width, height)).unwrap_or_default();
+    match report {
+        Report::Table => {
+            for violation in &violations {
+                let dimensions = violation.size.map(|_| format!("{}, ", size(violation))).unwrap_or_default();
+                let over = violation.reasons.join(", ");
+                println!("{}: {}{} [{}]", violation.path.display(), dimensions, format_bytes(violation.bytes), over);
+            }
+            if violations.is_empty() {
+                println!("imgopt check: {} images within budget ({})", images.len(), limits);
+            } else {
+                println!("imgopt check: {} of {} images over budget ({})", violations.len(), images.len(), limits);
+            }
+        }
+        Report::Csv => {
+            println!("path,width,height,bytes,over");
+            for violation in &violations {
+                let (width, height) = violation.size.map(|(w, h)| (w.to_string(), h.to_string())).unwrap_or_default();
+                let path = csv_field(&violation.path.display().to_string());
+                println!("{},{},{},{},{}", path, width, height, violation.bytes, violation.reasons.join(" "));
+            }
+        }
+        Report::Json => {
+            let images_over: Vec<_> = violations
+                .iter()
+                .map(|violation| {
+                    json!({
+                        "path": violation.path,
+                        "width": violation.size.map(|(width, _)| width),
+                        "height": violation.size.map(|(_, height)| height),
+                        "bytes": violation.bytes,
+                        "over": violation.reasons,
+                    })
+                })
+                .collect();
+            let report = json!({
+                "budget": { "max_bytes": budget.max_bytes, "max_dimension": budget.max_dimension },
+                "scanned": images.len(),
+                "over_budget": images_over.len(),
+                "images": images_over,
+            });
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+    }
+    Ok(if violations.is_empty() { Exit::Success } else { Exit::OverBudget })
+}
+
+// "300KB", "1.5 MB", "200k" or plain bytes; K and M are 1024 and 1024², as
+// imgopt prints sizes
+pub fn parse_bytes(text: &str) -> std::result::Result<u64, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().map_err(|_| format!("\"{}\" is not a size like 300KB", text))?;
+    let scale = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "k" | "kb" | "kib" => 1024.0,
+        "m" | "mb" | "mib" => 1024.0 * 1024.0,
+        "g" | "gb" | "gib" => 1024.0 * 1024.0 * 1024.0,
+        other => return Err(format!("unknown unit \"{}\" (B, KB, MB or GB)", other)),
+    };
+    Ok((number * scale).round() as u64)
+}
@@ -0,0 +1,141 @@
+use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+
+use crate::audit::Report;
+use crate::check;
+use crate::deliver::Destination;
+use crate::serve::DEFAULT_PORT;
+use crate::video;
+
+#[derive(Parser)]
+#[command(name = "imgopt", version, about = "Resize images so they fit in Claude's context, then copy them")]
+pub struct Cli {
+    #[arg(short, long, global = true, help = "Print errors only")]
+    pub quiet: bool,
+
+    #[arg(long, help = "Line-by-line output for screen readers and dumb terminals (TUI only)")]
+    pub plain: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    #[command(about = "Drop zone in the terminal (the default)")]
+    Tui,
+
+    #[command(about = "Optimize files, copy the result and exit")]
+    Optimize {
+        #[arg(required = true, help = "Images to optimize, in order; the clipboard ends up with the last")]
+        files: Vec<String>,
+        #[arg(long, value_enum, help = "Where results go [default: from the config]")]
+        to: Option<Destination>,
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..), help = "Page of multi-page TIFFs and PDFs, from 1 [default: 1]")]
+        page: Option<u32>,
+        #[arg(long, value_parser = video::Frame::parse, help = "Frame of .mov and .mp4 files: first, middle, last or a time like 1:05 [default: middle]")]
+        frame: Option<video::Frame>,
+        #[arg(long, help = "Skip the files an interrupted run of the same command finished")]
+        resume: bool,
+        #[arg(long, value_name = "ARCHIVE", help = "Also pack the saved files and a manifest.json into a .zip or .tar.zst (implies --to file)")]
+        bundle: Option<String>,
+    },
+
+    #[command(about = "Show what optimize would do with each file, as JSON lines, without writing anything")]
+    Plan {
+        #[arg(required = true)]
+        files: Vec<String>,
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..), help = "Page of multi-page TIFFs and PDFs, from 1 [default: 1]")]
+        page: Option<u32>,
+    },
+
+    #[command(about = "Optimize new images as they appear in a folder")]
+    Watch {
+        #[arg(help = "Folder to watch [default: the screenshots folder]")]
+        dir: Option<String>,
+        #[arg(long, value_enum, help = "Where results go [default: from the config]")]
+        to: Option<Destination>,
+    },
+
+    #[command(about = "HTTP server with a POST /optimize endpoint on localhost")]
+    Serve {
+        #[arg(long, default_value_t = DEFAULT_PORT)]
+        port: u16,
+    },
+
+    #[command(about = "Model Context Protocol server on stdin/stdout")]
+    Mcp,
+
+    #[command(about = "Resident process taking jobs from imgopt-cli over a Unix socket")]
+    Daemon,
+
+    #[cfg(feature = "jxl")]
+    #[command(about = "Optimize files to JPEG XL next to them (needs cjxl)")]
+    Jxl {
+        #[arg(required = true)]
+        files: Vec<String>,
+        #[arg(long, help = "Repack JPEGs losslessly instead of optimizing them; djxl restores the original")]
+        lossless_jpeg: bool,
+    },
+
+    #[command(about = "Write favicon.ico and apple-touch icons from one square image")]
+    Favicon {
+        file: String,
+        #[arg(long, help = "Folder to write to [default: <name>_favicons next to the image]")]
+        out: Option<String>,
+    },
+
+    #[command(about = "Write WebP and JPEG/PNG at several widths, then copy a srcset snippet")]
+    Srcset {
+        file: String,
+        #[arg(long, help = "Folder to write to [default: <name>_srcset next to the image]")]
+        out: Option<String>,
+    },
+
+    #[command(
+        about = "Copy JPEGs with their orientation tag corrected, leaving the pixels and originals alone",
+        group = clap::ArgGroup::new("change").args(["rotate", "flip"]).required(true).multiple(true)
+    )]
+    Orient {
+        #[arg(required = true)]
+        files: Vec<String>,
+        #[arg(long, value_parser = ["90", "180", "270"], help = "Degrees clockwise to turn")]
+        rotate: Option<String>,
+        #[arg(long, value_parser = ["horizontal", "vertical"], help = "Mirror, after turning")]
+        flip: Option<String>,
+    },
+
+    #[command(about = "Time every decode, resize and encode option on one image")]
+    Bench { file: String },
+
+    #[command(about = "List images in a directory that exceed the [budget]")]
+    Audit {
+        dir: String,
+        #[arg(long, value_enum, default_value = "table")]
+        format: Report,
+    },
+
+    #[command(about = "Fail when images in the given folders or files exceed the [budget], for CI")]
+    Check {
+        #[arg(required = true)]
+        paths: Vec<String>,
+        #[arg(long, value_parser = check::parse_bytes, help = "Largest file allowed, like 300KB or 1.5MB (defaults to the [budget])")]
+        budget: Option<u64>,
+        #[arg(long, help = "Longest side allowed in pixels (defaults to the [budget])")]
+        max_dimension: Option<u32>,
+        #[arg(long, value_enum, default_value = "table")]
+        format: Report,
+    },
+
+    #[command(about = "Shrink and re-stage staged images that exceed the [budget]")]
+    Precommit {
+        #[arg(long, help = "Install as the repository's git pre-commit hook")]
+        install: bool,
+    },
+
+    #[command(about = "Check codecs, the clipboard, the config and folders imgopt writes to")]
+    Doctor,
+
+    #[command(about = "Print a shell completion script")]
+    Completions { shell: Shell },
+}
@@ -0,0 +1,381 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use image::{DynamicImage, ImageFormat};
+use serde::Deserialize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::config::Config;
+use crate::deliver::TempFile;
+use crate::dpi;
+use crate::multiplexer::Multiplexer;
+use crate::optimize::format_bytes;
+use crate::{log, Result};
+
+// Each representation is written to its own temp file. On macOS they go to
+// osascript as one clipboard record, so the paste target can pick the flavor
+// it accepts; Windows gets CF_DIB and the registered PNG format; Linux
+// clipboards and OSC 52 get the PNG.
+const FLAVORS: &[(ImageFormat, &str)] =
+    &[(ImageFormat::Png, "png"), (ImageFormat::Tiff, "tiff"), (ImageFormat::Jpeg, "jpg"), (ImageFormat::Bmp, "bmp")];
+
+// Screen drops string sequences longer than this, so OSC 52 goes to it in pieces
+const SCREEN_CHUNK: usize = 512;
+// Base64 that terminals commonly take in one OSC 52 sequence; many drop
+// longer ones without a word, so nothing larger is sent
+const OSC52_MAX: usize = 100_000;
+
+// The `clipboard` setting: "auto" picks from the platform and the session
+// (OSC 52 over SSH), "osc52" always copies through the terminal, which works
+// wherever the terminal supports it, without any external command
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipboardMode {
+    #[default]
+    Auto,
+    Osc52,
+}
+
+// What puts things on the clipboard here, picked from the session
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Backend {
+    // osascript and pbcopy
+    MacOs,
+    // The clipboard API, through clipboard-win
+    Windows,
+    // wl-copy, from wl-clipboard
+    Wayland,
+    // xclip
+    X11,
+    // OSC 52 escape sequences, which the terminal at the other end of an SSH
+    // session turns into a write to its own clipboard
+    Osc52,
+}
+
+impl Backend {
+    fn formats(self) -> &'static [ImageFormat] {
+        match self {
+            Backend::MacOs => &[ImageFormat::Png, ImageFormat::Tiff, ImageFormat::Jpeg],
+            Backend::Windows => &[ImageFormat::Png, ImageFormat::Bmp],
+            Backend::Wayland | Backend::X11 | Backend::Osc52 => &[ImageFormat::Png],
+        }
+    }
+}
+
+// What copies go through here, e.g. "wl-copy (Wayland)", and the program and
+// package it needs, for the setup screen
+pub fn describe(mode: ClipboardMode) -> Result<(&'static str, Option<(&'static str, &'static str)>)> {
+    Ok(match backend(mode)? {
+        Backend::MacOs => ("osascript (macOS)", Some(("osascript", "macOS"))),
+        Backend::Windows => ("the Windows clipboard", None),
+        Backend::Wayland => ("wl-copy (Wayland)", Some(("wl-copy", "wl-clipboard"))),
+        Backend::X11 => ("xclip (X11)", Some(("xclip", "xclip"))),
+        Backend::Osc52 => ("OSC 52 through the terminal", None),
+    })
+}
+
+fn backend(mode: ClipboardMode) -> Result<Backend> {
+    if mode == ClipboardMode::Osc52 {
+        return Ok(Backend::Osc52);
+    }
+    if cfg!(windows) {
+        return Ok(Backend::Windows);
+    }
+    let set = |name: &str| std::env::var_os(name).is_some_and(|value| !value.is_empty());
+    // Over SSH the clipboard that matters is the one in front of the user.
+    // A forwarded X display reaches it directly; otherwise the terminal does.
+    if (set("SSH_CONNECTION") || set("SSH_TTY")) && !set("DISPLAY") && !set("WAYLAND_DISPLAY") {
+        return Ok(Backend::Osc52);
+    }
+    if cfg!(target_os = "macos") {
+        return Ok(Backend::MacOs);
+    }
+    // XWayland sets DISPLAY too, but wl-copy reaches native Wayland apps as well
+    if set("WAYLAND_DISPLAY") || std::env::var("XDG_SESSION_TYPE").is_ok_and(|session| session == "wayland") {
+        return Ok(Backend::Wayland);
+    }
+    if set("DISPLAY") {
+        return Ok(Backend::X11);
+    }
+    Err("no clipboard available: not running under Wayland or X11".into())
+}
+
+// Whether an input file in `format` can go on the clipboard as it is. Windows
+// apps expect a CF_DIB next to the PNG, which means decoding it.
+pub fn takes_original(format: ImageFormat, mode: ClipboardMode) -> bool {
+    backend(mode).is_ok_and(|backend| backend != Backend::Windows && backend.formats().contains(&format))
+}
+
+// Encoded files waiting to be put on the clipboard
+pub struct Flavors {
+    mode: ClipboardMode,
+    files: Vec<(PathBuf, ImageFormat)>,
+    // The ones encoded here, removed on drop; an original stays where it is
+    _temps: Vec<TempFile>,
+}
+
+impl Flavors {
+    // The PNG flavor is `png` as it is (it may have been post-processed); the
+    // others are encoded from `img`, for the clipboards that take them
+    pub fn encode(img: &DynamicImage, png: &Path, config: &Config) -> Result<Flavors> {
+        let (dpi, quality, icc) = (config.output_dpi, config.jpeg_quality, config.icc_profile());
+        let (mut files, mut temps) = (Vec::new(), Vec::new());
+        let formats = backend(config.clipboard).map(Backend::formats).unwrap_or(&[ImageFormat::Png]);
+
+        for (format, extension) in FLAVORS.iter().filter(|(format, _)| formats.contains(format)) {
+            let temp = TempFile::new(extension);
+            let path = temp.path.clone();
+            temps.push(temp);
+            let saved = match format {
+                ImageFormat::Png => std::fs::copy(png, &path).map(|_| ()).map_err(|e| e.into()),
+                // JPEG has no alpha channel, and most CF_DIB readers ignore it
+                ImageFormat::Jpeg | ImageFormat::Bmp => dpi::save(&flatten(img, config.appearance.matte()), &path, *format, dpi, quality, icc),
+                _ => dpi::save(img, &path, *format, dpi, quality, icc),
+            };
+
+            match saved {
+                Ok(_) => files.push((path, *format)),
+                Err(e) => log(&format!("Skipping {} flavor: {}", format.extensions_str()[0], e)),
+            }
+        }
+
+        if files.is_empty() {
+            return Err("failed to save temp file".into());
+        }
+        Ok(Flavors { mode: config.clipboard, files, _temps: temps })
+    }
+
+    // The file itself as its own flavor, without re-encoding
+    pub fn original(path: &Path, format: ImageFormat, config: &Config) -> Flavors {
+        Flavors { mode: config.clipboard, files: vec![(path.to_path_buf(), format)], _temps: Vec::new() }
+    }
+
+    pub fn copy(self) -> Result<()> {
+        match backend(self.mode)? {
+            Backend::MacOs => {
+                let entries: Vec<String> =
+                    self.files.iter().filter_map(|(path, format)| flavor(*format, path)).collect();
+                if entries.is_empty() {
+                    return Err(unsupported(&self.files));
+                }
+                let mut command = Command::new("osascript");
+                command.arg("-e").arg(format!("set the clipboard to {{{}}}", entries.join(", ")));
+                run(command, None)
+            }
+            Backend::Windows => copy_windows(&self.files),
+            Backend::Osc52 => {
+                let (path, _) = self.files.first().ok_or("nothing to copy")?;
+                osc52(&std::fs::read(path)?)
+            }
+            backend => {
+                let (path, format) = self.files.first().ok_or("nothing to copy")?;
+                let mime = format.to_mime_type();
+                let mut command = match backend {
+                    Backend::Wayland => Command::new("wl-copy"),
+                    _ => Command::new("xclip"),
+                };
+                match backend {
+                    Backend::Wayland => command.args(["--type", mime]),
+                    _ => command.args(["-selection", "clipboard", "-t", mime, "-i"]),
+                };
+                run(command, Some(File::open(path)?))
+            }
+        }
+    }
+}
+
+// Hands `data` to the terminal, which decides whether to accept it: some
+// ask first, some cap the size, and most store it as text, so an image may
+// only paste as an image in terminals that understand PNG data.
+fn osc52(data: &[u8]) -> Result<()> {
+    let sequence = osc52_sequence(data, Multiplexer::detect())?;
+    let mut tty = OpenOptions::new()
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|e| format!("no terminal to copy through: {}", e))?;
+    tty.write_all(sequence.as_bytes()).and_then(|_| tty.flush())?;
+    log(&format!("Sent {} bytes over OSC 52", data.len()));
+    Ok(())
+}
+
+// The escape sequence for `data`, or an error when it's more than terminals
+// take. Inside tmux and screen it's wrapped so it reaches the terminal outside.
+fn osc52_sequence(data: &[u8], multiplexer: Option<Multiplexer>) -> Result<String> {
+    let encoded = BASE64.encode(data);
+    if encoded.len() > OSC52_MAX {
+        return Err(format!(
+            "{} is too large to copy through the terminal, which takes up to {}",
+            format_bytes(data.len() as u64),
+            format_bytes((OSC52_MAX / 4 * 3) as u64)
+        )
+        .into());
+    }
+    let sequence = format!("\x1b]52;c;{}\x07", encoded);
+    Ok(match multiplexer {
+        // Passthrough, with the escape inside doubled
+        Some(Multiplexer::Tmux) => format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b")),
+        // One device control string per piece; screen joins them back up
+        Some(Multiplexer::Screen) => sequence
+            .as_bytes()
+            .chunks(SCREEN_CHUNK)
+            .map(|chunk| format!("\x1bP{}\x1b\\", String::from_utf8_lossy(chunk)))
+            .collect(),
+        None => sequence,
+    })
+}
+
+// CF_DIB for anything that pastes bitmaps, and the registered "PNG" format,
+// which Office, Teams and browsers prefer since it keeps transparency
+#[cfg(windows)]
+fn copy_windows(files: &[(PathBuf, ImageFormat)]) -> Result<()> {
+    use clipboard_win::{formats, raw, Clipboard};
+
+    let _open = Clipboard::new_attempts(10).map_err(|e| format!("could not open the clipboard: {}", e))?;
+    raw::empty().map_err(|e| format!("could not clear the clipboard: {}", e))?;
+    if !files.iter().any(|(_, format)| matches!(format, ImageFormat::Png | ImageFormat::Bmp)) {
+        return Err(unsupported(files));
+    }
+    for (path, format) in files {
+        let bytes = std::fs::read(path)?;
+        let (id, data) = match format {
+            ImageFormat::Png => {
+                let id = raw::register_format("PNG").ok_or("could not register the PNG clipboard format")?;
+                (id.get(), &bytes[..])
+            }
+            // A CF_DIB is a BMP file without its 14-byte file header
+            ImageFormat::Bmp => (formats::CF_DIB, &bytes[14.min(bytes.len())..]),
+            _ => continue,
+        };
+        raw::set_without_clear(id, data)
+            .map_err(|e| format!("could not copy the {} flavor: {}", format.extensions_str()[0], e))?;
+    }
+    Ok(())
+}
+
+// For a flavor list the clipboard has no use for, such as an animated WebP
+fn unsupported(files: &[(PathBuf, ImageFormat)]) -> Box<dyn std::error::Error> {
+    let format = files.first().map_or("image", |(_, format)| format.extensions_str()[0]);
+    format!("the clipboard doesn't take {} images", format).into()
+}
+
+#[cfg(not(windows))]
+fn copy_windows(_: &[(PathBuf, ImageFormat)]) -> Result<()> {
+    Err("the Windows clipboard is only available on Windows".into())
+}
+
+// Replaces the clipboard with plain text
+pub fn copy_text(text: &str, mode: ClipboardMode) -> Result<()> {
+    let command = match backend(mode)? {
+        Backend::MacOs => Command::new("pbcopy"),
+        Backend::Windows => return copy_text_windows(text),
+        Backend::Osc52 => return osc52(text.as_bytes()),
+        Backend::Wayland => Command::new("wl-copy"),
+        Backend::X11 => {
+            let mut command = Command::new("xclip");
+            command.args(["-selection", "clipboard"]);
+            command
+        }
+    };
+    // Named per call, since watch mode and the daemon copy from several threads
+    let input = TempFile::new("txt");
+    std::fs::write(&input.path, text)?;
+    let file = File::open(&input.path)?;
+    run(command, Some(file))
+}
+
+#[cfg(windows)]
+fn copy_text_windows(text: &str) -> Result<()> {
+    clipboard_win::set_clipboard_string(text).map_err(|e| format!("could not copy text: {}", e).into())
+}
+
+#[cfg(not(windows))]
+fn copy_text_windows(_: &str) -> Result<()> {
+    Err("the Windows clipboard is only available on Windows".into())
+}
+
+// Runs a clipboard command with `input` on stdin. wl-copy and xclip stay in
+// the background to serve the clipboard, holding on to what they inherited,
+// so errors are collected in a file: waiting for a pipe to close would mean
+// waiting for the next copy.
+fn run(mut command: Command, input: Option<File>) -> Result<()> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let errors = TempFile::new("err");
+    command.stdin(input.map(Stdio::from).unwrap_or_else(Stdio::null));
+    command.stdout(Stdio::null());
+    command.stderr(File::create(&errors.path)?);
+    let status = command.status().map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => format!("{} not found, is it installed?", program),
+        _ => format!("could not run {}: {}", program, e),
+    });
+    let stderr = std::fs::read_to_string(&errors.path).unwrap_or_default();
+
+    let status = status?;
+    if !status.success() {
+        let reason = match stderr.trim() {
+            "" => format!("{} exited with {}", program, status),
+            msg => msg.to_string(),
+        };
+        return Err(reason.into());
+    }
+    Ok(())
+}
+
+// Transparent areas (frame shadows, cut-outs) become `matte`, white or dark
+// by the appearance, instead of whatever color their invisible pixels happen
+// to have
+pub fn flatten(img: &DynamicImage, matte: [u8; 3]) -> DynamicImage {
+    if !img.color().has_alpha() {
+        return DynamicImage::ImageRgb8(img.to_rgb8());
+    }
+    let rgba = img.to_rgba8();
+    DynamicImage::ImageRgb8(image::RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let alpha = a as f32 / 255.0;
+        let blend = |c: u8, m: u8| (c as f32 * alpha + m as f32 * (1.0 - alpha)).round() as u8;
+        image::Rgb([blend(r, matte[0]), blend(g, matte[1]), blend(b, matte[2])])
+    }))
+}
+
+// One osascript clipboard record entry
+fn flavor(format: ImageFormat, path: &Path) -> Option<String> {
+    let class = match format {
+        ImageFormat::Png => "«class PNGf»",
+        ImageFormat::Tiff => "«class TIFF»",
+        ImageFormat::Jpeg => "«class JPEG»",
+        ImageFormat::Gif => "«class GIFf»",
+        _ => return None,
+    };
+    // AppleScript string literal
+    let quoted = path.to_string_lossy().replace('\\', "\\\\").replace('"', "\\\"");
+    Some(format!("{}:(read (POSIX file \"{}\") as {})", class, quoted, class))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc52_is_wrapped_for_multiplexers() {
+        assert_eq!(osc52_sequence(b"hi", None).unwrap(), "\x1b]52;c;aGk=\x07");
+        assert_eq!(osc52_sequence(b"hi", Some(Multiplexer::Tmux)).unwrap(), "\x1bPtmux;\x1b\x1b]52;c;aGk=\x07\x1b\\");
+
+        let data = vec![7; 5000];
+        let screen = osc52_sequence(&data, Some(Multiplexer::Screen)).unwrap();
+        let pieces: Vec<_> = screen.split_terminator("\x1b\\").collect();
+        assert!(pieces.iter().all(|piece| piece.starts_with("\x1bP") && piece.len() <= SCREEN_CHUNK + 2));
+        let joined: String = pieces.iter().map(|piece| &piece[2..]).collect();
+        assert_eq!(joined, osc52_sequence(&data, None).unwrap());
+    }
+
+    #[test]
+    fn osc52_refuses_what_terminals_would_drop() {
+        let largest = vec![0; OSC52_MAX / 4 * 3];
+        for multiplexer in [None, Some(Multiplexer::Tmux), Some(Multiplexer::Screen)] {
+            assert!(osc52_sequence(&largest, multiplexer).is_ok());
+            let error = osc52_sequence(&[0; OSC52_MAX], multiplexer).unwrap_err().to_string();
+            assert!(error.contains("too large to copy through the terminal"), "{}", error);
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::log;
+use crate::optimize::{checkpoint, OptimizeError, Stage};
+use crate::paths::resolve;
+
+// Files a sync client has offloaded to save space. iCloud Drive (and other
+// macOS File Provider clients such as OneDrive and Dropbox) leave a dataless
+// file in place, or on older macOS a hidden `.<name>.icloud` stub next to
+// where the file was. OneDrive on Windows leaves a file that is fetched the
+// first time it's read.
+enum Placeholder {
+    Dataless,
+    Stub(PathBuf),
+}
+
+// Sync clients can take a while for large photos over slow links
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+const POLL: Duration = Duration::from_millis(250);
+
+// Whether there is a file here or a placeholder for one
+pub fn exists(path: &Path) -> bool {
+    path.is_file() || stub(path).is_some_and(|stub| stub.is_file())
+}
+
+// Makes sure the file is on disk, following links and aliases to it and
+// downloading it first if it's only a placeholder. Returns the real path,
+// which differs when a link or stub was dropped.
+pub fn fetch(path: &Path, progress: &dyn Fn(Stage) -> bool) -> std::result::Result<PathBuf, OptimizeError> {
+    let path = &resolve(path).map_err(OptimizeError::Unreadable)?;
+    let (path, placeholder) = match unstubbed(path) {
+        // A stub left over next to a file that has been downloaded since
+        Some(real) if real.is_file() && !is_dataless(&real) => return Ok(real),
+        Some(real) => (real, Placeholder::Stub(path.to_path_buf())),
+        None if path.is_file() && is_dataless(path) => (path.to_path_buf(), Placeholder::Dataless),
+        None if path.exists() => return Ok(path.to_path_buf()),
+        None => match stub(path).filter(|stub| stub.is_file()) {
+            Some(stub) => (path.to_path_buf(), Placeholder::Stub(stub)),
+            None => return Err(OptimizeError::NotFound),
+        },
+    };
+
+    checkpoint(progress, Stage::Downloading)?;
+    log(&format!("Downloading placeholder: {}", path.display()));
+    start_download(&path, &placeholder).map_err(OptimizeError::Unreadable)?;
+
+    let started = Instant::now();
+    while !path.is_file() || is_dataless(&path) {
+        if started.elapsed() >= DOWNLOAD_TIMEOUT {
+            return Err(OptimizeError::Unreadable(format!(
+                "{} is still downloading after {} seconds",
+                path.display(),
+                DOWNLOAD_TIMEOUT.as_secs()
+            )));
+        }
+        std::thread::sleep(POLL);
+        checkpoint(progress, Stage::Downloading)?;
+    }
+    log(&format!("Downloaded in {:.1}s: {}", started.elapsed().as_secs_f64(), path.display()));
+    Ok(path)
+}
+
+// `.shot.png.icloud` stands for `shot.png`
+fn unstubbed(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let real = name.strip_prefix('.')?.strip_suffix(".icloud").filter(|real| !real.is_empty())?;
+    Some(path.with_file_name(real))
+}
+
+fn stub(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    Some(path.with_file_name(format!(".{}.icloud", name)))
+}
+
+#[cfg(target_os = "macos")]
+fn is_dataless(path: &Path) -> bool {
+    use std::os::macos::fs::MetadataExt;
+    const SF_DATALESS: u32 = 0x4000_0000;
+    std::fs::metadata(path).is_ok_and(|m| m.st_flags() & SF_DATALESS != 0)
+}
+
+#[cfg(windows)]
+fn is_dataless(path: &Path) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const RECALL_ON_OPEN: u32 = 0x0004_0000;
+    const RECALL_ON_DATA_ACCESS: u32 = 0x0040_0000;
+    std::fs::metadata(path).is_ok_and(|m| m.file_attributes() & (RECALL_ON_OPEN | RECALL_ON_DATA_ACCESS) != 0)
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn is_dataless(_: &Path) -> bool {
+    false
+}
+
+// `brctl download` asks iCloud for the file and returns right away
+#[cfg(target_os = "macos")]
+fn start_download(path: &Path, placeholder: &Placeholder) -> std::result::Result<(), String> {
+    use std::process::{Command, Stdio};
+    let target = match placeholder {
+        Placeholder::Stub(stub) => stub.as_path(),
+        Placeholder::Dataless => path,
+    };
+    let status = Command::new("brctl")
+        .arg("download")
+        .arg(target)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| format!("could not run brctl to download {}: {}", path.display(), e))?;
+    match placeholder {
+        _ if status.success() => Ok(()),
+        // Not iCloud, so some other File Provider; reading the file makes it fetch
+        Placeholder::Dataless => read_through(path),
+        Placeholder::Stub(_) => Err(format!("iCloud would not download {}", path.display())),
+    }
+}
+
+// Reading a cloud file is what makes the sync client fetch it. iCloud stubs
+// only turn back into files on a Mac.
+#[cfg(not(target_os = "macos"))]
+fn start_download(path: &Path, placeholder: &Placeholder) -> std::result::Result<(), String> {
+    match placeholder {
+        Placeholder::Dataless => read_through(path),
+        Placeholder::Stub(stub) => {
+            Err(format!("{} only stands in for {} in iCloud, download it on a Mac first", stub.display(), path.display()))
+        }
+    }
+}
+
+fn read_through(path: &Path) -> std::result::Result<(), String> {
+    let mut file = File::open(path).map_err(|e| format!("could not download {}: {}", path.display(), e))?;
+    std::io::copy(&mut file, &mut std::io::sink()).map_err(|e| format!("could not download {}: {}", path.display(), e))?;
+    Ok(())
+}
@@ -0,0 +1,1128 @@
+use image::ImageFormat;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::appearance::Appearance;
+use crate::background::Background;
+use crate::clipboard::ClipboardMode;
+use crate::deliver::Destination;
+use crate::frame::Frame;
+use crate::icc::{self, ColorProfile};
+use crate::legibility::Legibility;
+use crate::notify::Notify;
+use crate::session::ReportFormat;
+use crate::stamp::Stamp;
+use crate::qr::UploadQr;
+use crate::paths::{expand_home, home_dir, matches_pattern};
+use crate::theme::Theme;
+use crate::transform::pipeline;
+use crate::upload::Upload;
+use crate::video;
+use crate::{log, Result};
+
+// A preset name that needs no [presets] entry: settings are picked per image
+// from what it shows (see content.rs), starting from [presets.auto] or [resize]
+pub const AUTO_PRESET: &str = "auto";
+
+// Another built-in preset: screen recordings become a short animated GIF or
+// WebP (see [gif]) instead of one frame. [presets.gif] sets its sizes.
+pub const GIF_PRESET: &str = "gif";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub screenshots_dir: Option<PathBuf>,
+    // Every input is copied into a dated folder here first; see archive.rs
+    pub archive_dir: Option<PathBuf>,
+    // Session reports from the TUI and plain mode go here on exit; see session.rs
+    pub report_dir: Option<PathBuf>,
+    pub report_format: ReportFormat,
+    pub recent_count: usize,
+    pub show_recent_on_start: bool,
+    // Clipboard images kept for `[` and `]` in the drop zone; 0 keeps none
+    pub history_size: usize,
+    // Seconds a result stays up before the drop zone comes back; 0 waits for a key
+    pub result_dwell: f64,
+    // A cue when an image is done: "off", "bell" or "sound"; see notify.rs
+    pub notify: Notify,
+    pub notify_sound: Option<PathBuf>,
+    // TUI language such as "de"; unset follows the system locale. See i18n.rs.
+    pub language: Option<String>,
+    pub theme: Theme,
+    pub limits: Limits,
+    pub passthrough: Passthrough,
+    pub resize: Resize,
+    // Name of an entry in `presets` to use instead of `resize`, or "auto"
+    pub preset: Option<String>,
+    pub presets: HashMap<String, Preset>,
+    // Transform specs applied after resizing; see transform.rs
+    pub transforms: Vec<String>,
+    // Color adjustments made after resizing
+    pub adjust: Adjust,
+    // Capture date and original size burned into a corner
+    pub stamp: Stamp,
+    // Noise reduction before resizing, 1-100; 0 turns it off. See denoise.rs.
+    pub denoise: u32,
+    // Pixel density written into PNG and JPEG output
+    pub output_dpi: Option<u16>,
+    // ICC profile embedded in PNG, JPEG and WebP output; see icc.rs
+    pub color_profile: ColorProfile,
+    // Dominant colors shown on the result screen; 0 turns the palette off
+    pub palette_size: usize,
+    pub grid: Grid,
+    pub favicon: Favicon,
+    pub srcset: Srcset,
+    // Window chrome around optimized images: "none", "window" or "browser"
+    pub frame: Frame,
+    // Light or dark grid gaps, frames and flattened transparency: "auto"
+    // follows the system; see appearance.rs
+    pub appearance: Appearance,
+    // Shell command run on the encoded PNG before it is copied; see hook.rs
+    pub post_process: Option<String>,
+    pub upload: Option<Upload>,
+    // Show uploaded URLs as a QR code: "off", "terminal" or "clipboard"
+    pub upload_qr: UploadQr,
+    // Upload speed the result screen estimates transfer times for; unset shows
+    // a few typical connections
+    pub upload_mbps: Option<f64>,
+    // Where results go. The default is the clipboard, or the uploader when
+    // [upload] is set.
+    pub destination: Option<Destination>,
+    // How copies reach the clipboard: "auto" or "osc52"
+    pub clipboard: ClipboardMode,
+    pub budget: Budget,
+    // OCR check of results, made again larger while text reads poorly; see legibility.rs
+    pub legibility: Legibility,
+    // Concurrency, priority and battery use of watch and daemon; see background.rs
+    pub background: Background,
+    // Encoding of the images `imgopt serve`, `imgopt mcp` and `imgopt daemon` return
+    pub format: OutputFormat,
+    // 1-100, for every JPEG imgopt writes
+    pub jpeg_quality: u8,
+    // Lossy output scoring below this SSIM (0-1) against the image before
+    // encoding comes with a warning; 0 turns the warning off. See ssim.rs.
+    pub min_ssim: f64,
+    // Which frame of a screen recording is optimized: "first", "middle",
+    // "last" or a time such as "1:05"; --frame overrides it. See video.rs.
+    pub video_frame: video::Frame,
+    pub gif: Gif,
+    // Per-file settings for batch and watch modes, applied in order
+    pub rules: Vec<Rule>,
+    // Which page of a TIFF or PDF to use, counted from 1; set per job by --page
+    // or the TUI's page picker, never in the file
+    #[serde(skip)]
+    pub page: Option<usize>,
+    // The input's ICC profile, read per job when the color profile is
+    // "source"; never in the file
+    #[serde(skip)]
+    pub source_profile: Option<Vec<u8>>,
+}
+
+// Guards against huge files and decompression bombs
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    pub max_input_bytes: u64,
+    pub max_pixels: u64,
+    // JPEG and PNG inputs that can be reduced while decoding never need the
+    // full-size buffer, so they get a much higher ceiling
+    pub max_scaled_pixels: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_input_bytes: 100 * 1024 * 1024,
+            max_pixels: 100_000_000,
+            max_scaled_pixels: 1_000_000_000,
+        }
+    }
+}
+
+// Images already within bounds and under `max_bytes` are copied unchanged
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Passthrough {
+    pub enabled: bool,
+    pub max_bytes: u64,
+    // Repack JPEGs with jpegtran on the way through; see lossless.rs
+    pub lossless_jpeg: bool,
+}
+
+impl Default for Passthrough {
+    fn default() -> Passthrough {
+        Passthrough {
+            enabled: true,
+            max_bytes: 1024 * 1024,
+            lossless_jpeg: true,
+        }
+    }
+}
+
+// How output dimensions are chosen. The defaults clamp the longest side to
+// 480-720px, enlarging small images and shrinking large ones.
+#[derive(Debug, Clone, Copy, PartialEq, Hash, Deserialize)]
+#[serde(default)]
+pub struct Resize {
+    pub min_dimension: u32,
+    pub max_dimension: u32,
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    pub never_enlarge: bool,
+    // When false, the shortest side is also brought up to min_dimension
+    pub longest_side_only: bool,
+    // Halve @2x / 144 DPI captures to logical pixels before clamping
+    pub retina: bool,
+    // Exact output size, [width, height]: the image is fitted inside instead
+    // of clamped, and the rest filled with canvas_background (see letterbox.rs)
+    pub canvas: Option<(u32, u32)>,
+    // Unset: a blurred copy of the image
+    pub canvas_background: Option<[u8; 3]>,
+}
+
+// A named set of settings that `preset` picks: the [resize] keys, and
+// replacements for the top-level transforms, adjust, stamp, color_profile and
+// destination while it is selected
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Preset {
+    #[serde(flatten)]
+    pub resize: Resize,
+    pub transforms: Option<Vec<String>>,
+    pub adjust: Option<Adjust>,
+    pub stamp: Option<Stamp>,
+    pub color_profile: Option<ColorProfile>,
+    pub destination: Option<Destination>,
+}
+
+impl Default for Resize {
+    fn default() -> Resize {
+        Resize {
+            min_dimension: 480,
+            max_dimension: 720,
+            max_width: None,
+            max_height: None,
+            never_enlarge: false,
+            longest_side_only: true,
+            retina: false,
+            canvas: None,
+            canvas_background: None,
+        }
+    }
+}
+
+impl Resize {
+    // Short summary for headers, e.g. "480-720px" or "up to 720px, max height 600px"
+    pub fn describe(&self) -> String {
+        let mut text = if let Some((width, height)) = self.canvas {
+            format!("{}x{}px canvas", width, height)
+        } else if self.never_enlarge || self.min_dimension >= self.max_dimension {
+            format!("up to {}px", self.max_dimension)
+        } else {
+            format!("{}-{}px", self.min_dimension, self.max_dimension)
+        };
+        // A canvas takes the place of the limits
+        let limited = self.canvas.is_none();
+        if let Some(width) = self.max_width.filter(|_| limited) {
+            text.push_str(&format!(", max width {}px", width));
+        }
+        if let Some(height) = self.max_height.filter(|_| limited) {
+            text.push_str(&format!(", max height {}px", height));
+        }
+        if self.retina {
+            text.push_str(", @2x halved");
+        }
+        text
+    }
+
+    // "1200x630", as canvas sizes are given in environment variables and query parameters
+    pub fn parse_canvas(text: &str) -> Option<(u32, u32)> {
+        let (width, height) = text.split_once(['x', 'X'])?;
+        let side = |side: &str| side.trim().parse::<u32>().ok().filter(|side| *side > 0);
+        Some((side(width)?, side(height)?))
+    }
+
+    // The same limits with room left for `extra` pixels (width, height) that get
+    // added around the image afterwards, like frames and grid gaps
+    pub fn shrunk(&self, extra: (u32, u32)) -> Resize {
+        let shrink = |limit: u32, by: u32| limit.saturating_sub(by).max(1);
+        let longest = extra.0.max(extra.1);
+        Resize {
+            min_dimension: shrink(self.min_dimension, longest),
+            max_dimension: shrink(self.max_dimension, longest),
+            max_width: self.max_width.map(|width| shrink(width, extra.0)),
+            max_height: self.max_height.map(|height| shrink(height, extra.1)),
+            canvas: self.canvas.map(|(width, height)| (shrink(width, extra.0), shrink(height, extra.1))),
+            ..*self
+        }
+    }
+}
+
+// Layout of the collage made from several drops in grid mode
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Grid {
+    pub columns: u32,
+    // Pixels between cells and around the edge, in the final image
+    pub gap: u32,
+    // Unset: white, or dark with a dark `appearance`
+    pub background: Option<[u8; 3]>,
+    // File names under each image
+    pub labels: bool,
+}
+
+impl Default for Grid {
+    fn default() -> Grid {
+        Grid {
+            columns: 2,
+            gap: 16,
+            background: None,
+            labels: true,
+        }
+    }
+}
+
+// Color adjustments applied before transforms and encoding (see adjust.rs).
+// The amounts run from -100 to 100, and 0 leaves the image alone.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Adjust {
+    pub grayscale: bool,
+    pub brightness: i32,
+    pub contrast: i32,
+    pub saturation: i32,
+}
+
+impl Adjust {
+    pub fn is_neutral(&self) -> bool {
+        *self == Adjust::default()
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.grayscale {
+            parts.push("grayscale".to_string());
+        }
+        for (name, amount) in [("brightness", self.brightness), ("contrast", self.contrast), ("saturation", self.saturation)] {
+            if amount != 0 {
+                parts.push(format!("{} {:+}", name, amount));
+            }
+        }
+        parts.join(", ")
+    }
+}
+
+// What `imgopt favicon` and the `f` drop mode write from one image
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Favicon {
+    // Sizes inside favicon.ico, 1-256
+    pub ico_sizes: Vec<u32>,
+    // apple-touch-icon.png at the first size, apple-touch-icon-<n>x<n>.png at the others
+    pub apple_touch_sizes: Vec<u32>,
+    // iOS shows transparency as black, so touch icons are flattened onto this
+    pub background: [u8; 3],
+}
+
+impl Default for Favicon {
+    fn default() -> Favicon {
+        Favicon {
+            ico_sizes: vec![16, 32, 48, 256],
+            apple_touch_sizes: vec![180],
+            background: [255, 255, 255],
+        }
+    }
+}
+
+impl Favicon {
+    // The largest icon, which the source should be at least as big as
+    pub fn largest(&self) -> u32 {
+        self.ico_sizes.iter().chain(&self.apple_touch_sizes).copied().max().unwrap_or(256)
+    }
+}
+
+// What the gif preset makes of a screen recording
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Gif {
+    pub fps: u32,
+    // Longer recordings are cut off here
+    pub max_seconds: f64,
+    pub format: AnimationFormat,
+}
+
+impl Default for Gif {
+    fn default() -> Gif {
+        Gif { fps: 10, max_seconds: 15.0, format: AnimationFormat::default() }
+    }
+}
+
+// GIF pastes everywhere; animated WebP is a fraction of the size where it's taken
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimationFormat {
+    #[default]
+    Gif,
+    Webp,
+}
+
+impl AnimationFormat {
+    pub fn image_format(self) -> ImageFormat {
+        match self {
+            AnimationFormat::Gif => ImageFormat::Gif,
+            AnimationFormat::Webp => ImageFormat::WebP,
+        }
+    }
+}
+
+// What `imgopt srcset` and the `w` drop mode write for responsive images
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Srcset {
+    // Widths above the image's own are skipped
+    pub widths: Vec<u32>,
+    // The `sizes` attribute of the copied snippet
+    pub sizes: String,
+}
+
+impl Default for Srcset {
+    fn default() -> Srcset {
+        Srcset { widths: vec![480, 768, 1200, 1600], sizes: "100vw".to_string() }
+    }
+}
+
+// What images kept in a repository may weigh; enforced by `imgopt precommit`
+// and reported by `imgopt audit`
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Budget {
+    pub max_bytes: u64,
+    // Longest side in pixels
+    pub max_dimension: u32,
+}
+
+impl Default for Budget {
+    fn default() -> Budget {
+        Budget {
+            max_bytes: 512 * 1024,
+            max_dimension: 2048,
+        }
+    }
+}
+
+impl Budget {
+    // What an image of this size and file size exceeds, if anything
+    pub fn exceeded(&self, (width, height): (u32, u32), bytes: u64) -> Vec<&'static str> {
+        let mut reasons = Vec::new();
+        if width.max(height) > self.max_dimension {
+            reasons.push("dimensions");
+        }
+        if bytes > self.max_bytes {
+            reasons.push("bytes");
+        }
+        reasons
+    }
+
+    // Whether a re-encode is worth keeping over the original: over budget on
+    // fewer counts, or on the same ones with a smaller file
+    pub fn improved(&self, (size, bytes): ((u32, u32), u64), (new_size, new_bytes): ((u32, u32), u64)) -> bool {
+        let (before, after) = (self.exceeded(size, bytes).len(), self.exceeded(new_size, new_bytes).len());
+        after < before || (after == before && new_bytes < bytes)
+    }
+
+    // Brings an image within max_dimension, never enlarging it
+    pub fn resize(&self) -> Resize {
+        Resize {
+            max_dimension: self.max_dimension,
+            never_enlarge: true,
+            ..Resize::default()
+        }
+    }
+}
+
+// Settings for inputs whose file name matches `pattern` (`*` and `?`
+// wildcards, any case). Unset fields keep the config's values; resize fields
+// adjust the preset in effect, as environment variables do.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub pattern: String,
+    pub preset: Option<String>,
+    pub min_dimension: Option<u32>,
+    pub max_dimension: Option<u32>,
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    pub never_enlarge: Option<bool>,
+    pub retina: Option<bool>,
+    pub canvas: Option<(u32, u32)>,
+    pub canvas_background: Option<[u8; 3]>,
+    pub frame: Option<Frame>,
+    pub format: Option<OutputFormat>,
+    pub jpeg_quality: Option<u8>,
+    pub transforms: Option<Vec<String>>,
+    pub destination: Option<Destination>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    #[serde(alias = "jpg")]
+    Jpeg,
+    // Picked per image; see autoformat.rs
+    Auto,
+}
+
+impl OutputFormat {
+    pub fn parse(name: &str) -> Option<OutputFormat> {
+        match name {
+            "png" => Some(OutputFormat::Png),
+            "jpeg" | "jpg" => Some(OutputFormat::Jpeg),
+            "auto" => Some(OutputFormat::Auto),
+            _ => None,
+        }
+    }
+
+    // None for auto, which depends on the image
+    pub fn image_format(self) -> Option<ImageFormat> {
+        match self {
+            OutputFormat::Png => Some(ImageFormat::Png),
+            OutputFormat::Jpeg => Some(ImageFormat::Jpeg),
+            OutputFormat::Auto => None,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            screenshots_dir: None,
+            archive_dir: None,
+            report_dir: None,
+            report_format: ReportFormat::default(),
+            recent_count: 5,
+            show_recent_on_start: false,
+            history_size: 10,
+            result_dwell: 2.0,
+            notify: Notify::default(),
+            notify_sound: None,
+            language: None,
+            theme: Theme::default(),
+            limits: Limits::default(),
+            passthrough: Passthrough::default(),
+            resize: Resize::default(),
+            preset: None,
+            presets: HashMap::new(),
+            transforms: Vec::new(),
+            adjust: Adjust::default(),
+            stamp: Stamp::default(),
+            denoise: 0,
+            output_dpi: None,
+            color_profile: ColorProfile::default(),
+            palette_size: 5,
+            grid: Grid::default(),
+            favicon: Favicon::default(),
+            srcset: Srcset::default(),
+            frame: Frame::default(),
+            appearance: Appearance::default(),
+            post_process: None,
+            upload: None,
+            upload_qr: UploadQr::default(),
+            upload_mbps: None,
+            destination: None,
+            clipboard: ClipboardMode::default(),
+            budget: Budget::default(),
+            legibility: Legibility::default(),
+            background: Background::default(),
+            format: OutputFormat::default(),
+            jpeg_quality: 75,
+            min_ssim: 0.9,
+            video_frame: video::Frame::default(),
+            gif: Gif::default(),
+            rules: Vec::new(),
+            page: None,
+            source_profile: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => home_dir()?.join(".config"),
+        };
+        Some(base.join("imgopt").join("config.toml"))
+    }
+
+    // The config file with IMGOPT_* environment variables applied on top
+    pub fn load() -> Result<Config> {
+        let mut config = Config::load_file()?;
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    // A missing file is not an error; a malformed one is
+    fn load_file() -> Result<Config> {
+        let Some(path) = Config::path() else {
+            return Ok(Config::default());
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(format!("could not read {}: {}", path.display(), e).into()),
+        };
+        let config: Config = toml::from_str(&text).map_err(|e| format!("invalid {}: {}", path.display(), e))?;
+        if let Some(name) = &config.preset {
+            if !config.has_preset(name) {
+                return Err(format!("invalid {}: no preset named \"{}\"", path.display(), name).into());
+            }
+        }
+        let rule_transforms = config.rules.iter().filter_map(|rule| rule.transforms.as_ref());
+        let preset_transforms = config.presets.values().filter_map(|preset| preset.transforms.as_ref());
+        for specs in std::iter::once(&config.transforms).chain(preset_transforms).chain(rule_transforms) {
+            pipeline(specs).map_err(|e| format!("invalid {}: {}", path.display(), e))?;
+        }
+        for rule in &config.rules {
+            if let Some(name) = rule.preset.as_ref().filter(|name| !config.has_preset(name)) {
+                let error = format!("rule \"{}\" uses no preset named \"{}\"", rule.pattern, name);
+                return Err(format!("invalid {}: {}", path.display(), error).into());
+            }
+        }
+        let rule_canvases = config.rules.iter().filter_map(|rule| rule.canvas);
+        let preset_sizes = config.presets.values().map(|preset| &preset.resize);
+        let mut canvases = std::iter::once(&config.resize).chain(preset_sizes).filter_map(|resize| resize.canvas).chain(rule_canvases);
+        if canvases.any(|(width, height)| width == 0 || height == 0) {
+            return Err(format!("invalid {}: canvas sizes must be positive", path.display()).into());
+        }
+        let rule_destinations = config.rules.iter().filter_map(|rule| rule.destination);
+        let preset_destinations = config.presets.values().filter_map(|preset| preset.destination);
+        let mut destinations = config.destination.into_iter().chain(preset_destinations).chain(rule_destinations);
+        if config.upload.is_none() && destinations.any(|destination| destination == Destination::Upload) {
+            return Err(format!("invalid {}: the upload destination needs an [upload] table", path.display()).into());
+        }
+        let qualities = config.rules.iter().filter_map(|rule| rule.jpeg_quality);
+        if std::iter::once(config.jpeg_quality).chain(qualities).any(|quality| !(1..=100).contains(&quality)) {
+            return Err(format!("invalid {}: jpeg_quality must be from 1 to 100", path.display()).into());
+        }
+        if !(0.0..=1.0).contains(&config.min_ssim) {
+            return Err(format!("invalid {}: min_ssim must be from 0 to 1", path.display()).into());
+        }
+        if !(config.result_dwell >= 0.0 && config.result_dwell.is_finite()) {
+            return Err(format!("invalid {}: result_dwell must be a number of seconds, or 0", path.display()).into());
+        }
+        if config.upload_mbps.is_some_and(|mbps| !(mbps > 0.0 && mbps.is_finite())) {
+            return Err(format!("invalid {}: upload_mbps must be a positive number", path.display()).into());
+        }
+        let favicon = &config.favicon;
+        if favicon.ico_sizes.is_empty() || favicon.ico_sizes.iter().any(|size| !(1..=256).contains(size)) {
+            return Err(format!("invalid {}: favicon ico_sizes must be from 1 to 256", path.display()).into());
+        }
+        if favicon.apple_touch_sizes.contains(&0) {
+            return Err(format!("invalid {}: favicon apple_touch_sizes must be positive", path.display()).into());
+        }
+        let adjustments = std::iter::once(&config.adjust).chain(config.presets.values().filter_map(|preset| preset.adjust.as_ref()));
+        if adjustments.flat_map(|a| [a.brightness, a.contrast, a.saturation]).any(|amount| !(-100..=100).contains(&amount)) {
+            return Err(format!("invalid {}: brightness, contrast and saturation must be from -100 to 100", path.display()).into());
+        }
+        if config.legibility.min_confidence > 100 {
+            return Err(format!("invalid {}: legibility.min_confidence must be from 0 to 100", path.display()).into());
+        }
+        if config.denoise > 100 {
+            return Err(format!("invalid {}: denoise must be from 0 to 100", path.display()).into());
+        }
+        if config.srcset.widths.is_empty() || config.srcset.widths.contains(&0) {
+            return Err(format!("invalid {}: srcset widths must be positive", path.display()).into());
+        }
+        if config.gif.fps == 0 || !(config.gif.max_seconds > 0.0 && config.gif.max_seconds.is_finite()) {
+            return Err(format!("invalid {}: gif fps and max_seconds must be positive", path.display()).into());
+        }
+        log(&format!("Loaded config from {}", path.display()));
+        Ok(config)
+    }
+
+    // Lets CI and wrapper scripts steer imgopt without writing a config file.
+    // Resize variables adjust the selected preset, or [resize] when there is none.
+    fn apply_env(&mut self) -> Result<()> {
+        self.apply_vars(|name| std::env::var(name).ok())
+    }
+
+    // apply_env with the variables looked up in `vars`; empty ones count as unset
+    fn apply_vars(&mut self, vars: impl Fn(&str) -> Option<String>) -> Result<()> {
+        let env = |name: &str| vars(name).filter(|value| !value.is_empty());
+        if let Some(name) = env("IMGOPT_PRESET") {
+            if !self.has_preset(&name) {
+                return Err(format!("invalid IMGOPT_PRESET: no preset named \"{}\"", name).into());
+            }
+            self.preset = Some(name);
+        }
+        if let Some(format) = env("IMGOPT_FORMAT") {
+            self.format = OutputFormat::parse(&format).ok_or("invalid IMGOPT_FORMAT: use png, jpeg or auto")?;
+        }
+        if let Some(frame) = env("IMGOPT_FRAME") {
+            self.frame = match frame.as_str() {
+                "none" => Frame::None,
+                "window" => Frame::Window,
+                "browser" => Frame::Browser,
+                _ => return Err("invalid IMGOPT_FRAME: use none, window or browser".into()),
+            };
+        }
+        if let Some(appearance) = env("IMGOPT_APPEARANCE") {
+            self.appearance = Appearance::parse(&appearance).ok_or("invalid IMGOPT_APPEARANCE: use auto, light or dark")?;
+        }
+        if let Some(clipboard) = env("IMGOPT_CLIPBOARD") {
+            self.clipboard = match clipboard.as_str() {
+                "auto" => ClipboardMode::Auto,
+                "osc52" => ClipboardMode::Osc52,
+                _ => return Err("invalid IMGOPT_CLIPBOARD: use auto or osc52".into()),
+            };
+        }
+        if let Some(dir) = env("IMGOPT_SCREENSHOTS_DIR") {
+            self.screenshots_dir = Some(PathBuf::from(dir));
+        }
+        if let Some(dir) = env("IMGOPT_ARCHIVE_DIR") {
+            self.archive_dir = Some(PathBuf::from(dir));
+        }
+        if let Some(dir) = env("IMGOPT_REPORT_DIR") {
+            self.report_dir = Some(PathBuf::from(dir));
+        }
+        if let Some(language) = env("IMGOPT_LANGUAGE") {
+            self.language = Some(language);
+        }
+        if let Some(dpi) = env("IMGOPT_OUTPUT_DPI") {
+            let dpi = dpi.parse().ok().filter(|dpi| *dpi > 0);
+            self.output_dpi = Some(dpi.ok_or("invalid IMGOPT_OUTPUT_DPI: must be a positive number")?);
+        }
+        if let Some(ssim) = env("IMGOPT_MIN_SSIM") {
+            let ssim = ssim.parse().ok().filter(|ssim| (0.0..=1.0).contains(ssim));
+            self.min_ssim = ssim.ok_or("invalid IMGOPT_MIN_SSIM: must be from 0 to 1")?;
+        }
+        if let Some(profile) = env("IMGOPT_COLOR_PROFILE") {
+            self.color_profile = ColorProfile::parse(&profile).ok_or("invalid IMGOPT_COLOR_PROFILE: use none, srgb or source")?;
+            for preset in self.presets.values_mut() {
+                preset.color_profile = None;
+            }
+        }
+        if let Some(frame) = env("IMGOPT_VIDEO_FRAME") {
+            self.video_frame = video::Frame::parse(&frame).map_err(|e| format!("invalid IMGOPT_VIDEO_FRAME: {}", e))?;
+        }
+        if let Some(format) = env("IMGOPT_GIF_FORMAT") {
+            self.gif.format = match format.as_str() {
+                "gif" => AnimationFormat::Gif,
+                "webp" => AnimationFormat::Webp,
+                _ => return Err("invalid IMGOPT_GIF_FORMAT: use gif or webp".into()),
+            };
+        }
+
+        let resize = self.resize_mut();
+        let number = |var: &str| -> Result<Option<u32>> {
+            let Some(value) = env(var) else {
+                return Ok(None);
+            };
+            match value.parse::<u32>() {
+                Ok(n) if n > 0 => Ok(Some(n)),
+                _ => Err(format!("invalid {}: must be a positive number", var).into()),
+            }
+        };
+        let flag = |var: &str| -> Result<Option<bool>> {
+            match env(var).as_deref() {
+                None => Ok(None),
+                Some("true" | "1") => Ok(Some(true)),
+                Some("false" | "0") => Ok(Some(false)),
+                Some(_) => Err(format!("invalid {}: must be true or false", var).into()),
+            }
+        };
+        if let Some(n) = number("IMGOPT_MIN_DIM")? {
+            resize.min_dimension = n;
+        }
+        if let Some(n) = number("IMGOPT_MAX_DIM")? {
+            resize.max_dimension = n;
+        }
+        if let Some(n) = number("IMGOPT_MAX_WIDTH")? {
+            resize.max_width = Some(n);
+        }
+        if let Some(n) = number("IMGOPT_MAX_HEIGHT")? {
+            resize.max_height = Some(n);
+        }
+        if let Some(on) = flag("IMGOPT_NEVER_ENLARGE")? {
+            resize.never_enlarge = on;
+        }
+        if let Some(on) = flag("IMGOPT_RETINA")? {
+            resize.retina = on;
+        }
+        if let Some(canvas) = env("IMGOPT_CANVAS") {
+            resize.canvas = Some(Resize::parse_canvas(&canvas).ok_or("invalid IMGOPT_CANVAS: use WIDTHxHEIGHT, like 1200x630")?);
+        }
+        if let Some(on) = flag("IMGOPT_LEGIBILITY")? {
+            self.legibility.enabled = on;
+        }
+        if let Some(n) = number("IMGOPT_JOBS")? {
+            self.background.jobs = n as usize;
+        }
+        if let Some(n) = number("IMGOPT_NICE")? {
+            self.background.nice = n;
+        }
+        if let Some(on) = flag("IMGOPT_PAUSE_ON_BATTERY")? {
+            self.background.pause_on_battery = on;
+        }
+        Ok(())
+    }
+
+    // The config for one input in batch and watch modes: every rule whose
+    // pattern matches the file name applied on top, in order
+    pub fn for_file(&self, path: &Path) -> Config {
+        let mut config = self.clone();
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        for rule in self.rules.iter().filter(|rule| matches_pattern(&rule.pattern, &name)) {
+            log(&format!("Rule {} applies to {}", rule.pattern, name));
+            if rule.preset.is_some() {
+                config.preset = rule.preset.clone();
+            }
+            config.frame = rule.frame.unwrap_or(config.frame);
+            config.format = rule.format.unwrap_or(config.format);
+            config.jpeg_quality = rule.jpeg_quality.unwrap_or(config.jpeg_quality);
+            if let Some(destination) = rule.destination {
+                config.set_destination(destination);
+            }
+            if let Some(transforms) = &rule.transforms {
+                config.set_transforms(transforms.clone());
+            }
+
+            let resize = config.resize_mut();
+            resize.min_dimension = rule.min_dimension.unwrap_or(resize.min_dimension);
+            resize.max_dimension = rule.max_dimension.unwrap_or(resize.max_dimension);
+            resize.max_width = rule.max_width.or(resize.max_width);
+            resize.max_height = rule.max_height.or(resize.max_height);
+            resize.never_enlarge = rule.never_enlarge.unwrap_or(resize.never_enlarge);
+            resize.retina = rule.retina.unwrap_or(resize.retina);
+            resize.canvas = rule.canvas.or(resize.canvas);
+            resize.canvas_background = rule.canvas_background.or(resize.canvas_background);
+        }
+        config
+    }
+
+    // The selected preset's table, if it has one; built-in presets may not
+    fn selected(&self) -> Option<&Preset> {
+        self.preset.as_ref().and_then(|name| self.presets.get(name))
+    }
+
+    fn selected_mut(&mut self) -> Option<&mut Preset> {
+        self.preset.as_ref().and_then(|name| self.presets.get_mut(name))
+    }
+
+    // What the resize settings in effect are read from: the selected preset, or [resize]
+    pub fn resize_mut(&mut self) -> &mut Resize {
+        match self.preset.as_ref().and_then(|name| self.presets.get_mut(name)) {
+            Some(preset) => &mut preset.resize,
+            None => &mut self.resize,
+        }
+    }
+
+    pub fn has_preset(&self, name: &str) -> bool {
+        name == AUTO_PRESET || name == GIF_PRESET || self.presets.contains_key(name)
+    }
+
+    // Whether screen recordings are turned into animations
+    pub fn is_gif(&self) -> bool {
+        self.preset.as_deref() == Some(GIF_PRESET)
+    }
+
+    // Whether settings are still to be picked per image
+    pub fn is_auto(&self) -> bool {
+        self.preset.as_deref() == Some(AUTO_PRESET)
+    }
+
+    // The selected preset, or the [resize] table when none is selected
+    pub fn resize(&self) -> Resize {
+        self.selected().map_or(self.resize, |preset| preset.resize)
+    }
+
+    // Where results go: the selected preset's destination, else `destination`,
+    // else the uploader if one is set up, else the clipboard
+    pub fn destination(&self) -> Destination {
+        let preset = self.selected().and_then(|preset| preset.destination);
+        let fallback = if self.upload.is_some() { Destination::Upload } else { Destination::Clipboard };
+        preset.or(self.destination).unwrap_or(fallback)
+    }
+
+    pub fn color_profile(&self) -> ColorProfile {
+        self.selected().and_then(|preset| preset.color_profile).unwrap_or(self.color_profile)
+    }
+
+    // The profile to embed in what imgopt encodes, if any
+    pub fn icc_profile(&self) -> Option<&[u8]> {
+        match self.color_profile() {
+            ColorProfile::None => None,
+            ColorProfile::Srgb => Some(icc::srgb()),
+            ColorProfile::Source => self.source_profile.as_deref(),
+        }
+    }
+
+    // A copy that knows the profile of `input`, when outputs take it over
+    pub fn with_source_profile(&self, input: &Path) -> Config {
+        let mut config = self.clone();
+        if self.color_profile() == ColorProfile::Source {
+            config.source_profile = icc::read(input);
+        }
+        config
+    }
+
+    // For `--to` and [[rules]]: wins over per-preset destinations and later rules
+    pub fn set_destination(&mut self, destination: Destination) {
+        self.destination = Some(destination);
+        for preset in self.presets.values_mut() {
+            preset.destination = None;
+        }
+        for rule in &mut self.rules {
+            rule.destination = None;
+        }
+    }
+
+    // The selected preset's pipeline, or the top-level transforms
+    pub fn transforms(&self) -> &[String] {
+        self.selected().and_then(|preset| preset.transforms.as_deref()).unwrap_or(&self.transforms)
+    }
+
+    // Replaces whichever pipeline would have run
+    pub fn set_transforms(&mut self, transforms: Vec<String>) {
+        match self.selected_mut().and_then(|preset| preset.transforms.as_mut()) {
+            Some(preset) => *preset = transforms,
+            None => self.transforms = transforms,
+        }
+    }
+
+    pub fn adjust(&self) -> Adjust {
+        self.selected().and_then(|preset| preset.adjust).unwrap_or(self.adjust)
+    }
+
+    pub fn stamp(&self) -> Stamp {
+        self.selected().and_then(|preset| preset.stamp).unwrap_or(self.stamp)
+    }
+
+    // From the settings screen: changes whichever adjustments are in effect
+    pub fn set_adjust(&mut self, adjust: Adjust) {
+        match self.selected_mut().and_then(|preset| preset.adjust.as_mut()) {
+            Some(preset) => *preset = adjust,
+            None => self.adjust = adjust,
+        }
+    }
+
+    pub fn result_dwell(&self) -> Option<Duration> {
+        (self.result_dwell > 0.0).then(|| Duration::from_secs_f64(self.result_dwell))
+    }
+
+    pub fn notify_sound(&self) -> Option<PathBuf> {
+        self.notify_sound.as_deref().map(expand_home)
+    }
+
+    pub fn archive_dir(&self) -> Option<PathBuf> {
+        self.archive_dir.as_deref().map(expand_home)
+    }
+
+    pub fn report_dir(&self) -> Option<PathBuf> {
+        self.report_dir.as_deref().map(expand_home)
+    }
+
+    pub fn screenshots_dir(&self) -> Option<PathBuf> {
+        if let Some(dir) = &self.screenshots_dir {
+            return Some(expand_home(dir));
+        }
+        system_screenshots_dir().or_else(|| home_dir().map(|h| h.join("Desktop")))
+    }
+}
+
+// How often long-running modes look at the config file's modification time
+const RELOAD_CHECK: Duration = Duration::from_secs(1);
+
+// Notices edits to the config file so the TUI, watch mode and the daemon can
+// pick them up without a restart
+pub struct ConfigWatcher {
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    checked: Instant,
+}
+
+impl ConfigWatcher {
+    pub fn new() -> ConfigWatcher {
+        let path = Config::path();
+        let modified = path.as_deref().and_then(modified);
+        ConfigWatcher {
+            path,
+            modified,
+            checked: Instant::now(),
+        }
+    }
+
+    // The reloaded config when the file changed since the last call, checking
+    // at most once a second. An invalid file is reported once per edit, and the
+    // caller keeps what it had.
+    pub fn poll(&mut self) -> Option<Result<Config>> {
+        if self.checked.elapsed() < RELOAD_CHECK {
+            return None;
+        }
+        self.checked = Instant::now();
+        let modified = self.path.as_deref().and_then(modified);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        log("Config file changed, reloading");
+        Some(Config::load())
+    }
+}
+
+// Unset and empty variables are the same
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).ok()?.modified().ok()
+}
+
+// macOS lets users move screenshots away from the Desktop
+fn system_screenshots_dir() -> Option<PathBuf> {
+    let output = Command::new("defaults")
+        .args(["read", "com.apple.screencapture", "location"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let location = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!location.is_empty()).then(|| expand_home(Path::new(&location)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_vars(config: &mut Config, vars: &[(&str, &str)]) -> Result<()> {
+        config.apply_vars(|name| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string()))
+    }
+
+    #[test]
+    fn variables_override_the_config() {
+        let mut config = Config::default();
+        let vars = [
+            ("IMGOPT_FORMAT", "jpg"),
+            ("IMGOPT_MAX_DIM", "500"),
+            ("IMGOPT_MAX_WIDTH", "400"),
+            ("IMGOPT_RETINA", "1"),
+            ("IMGOPT_NEVER_ENLARGE", "false"),
+            ("IMGOPT_CANVAS", "1200x630"),
+            ("IMGOPT_MIN_SSIM", "0.9"),
+            ("IMGOPT_OUTPUT_DPI", "144"),
+        ];
+        with_vars(&mut config, &vars).unwrap();
+        assert_eq!((config.format, config.min_ssim, config.output_dpi), (OutputFormat::Jpeg, 0.9, Some(144)));
+        let resize = config.resize();
+        assert_eq!((resize.max_dimension, resize.max_width, resize.canvas), (500, Some(400), Some((1200, 630))));
+        assert!(resize.retina && !resize.never_enlarge);
+    }
+
+    #[test]
+    fn empty_variables_are_unset() {
+        let mut config = Config::default();
+        let vars = [("IMGOPT_MAX_DIM", ""), ("IMGOPT_FORMAT", ""), ("IMGOPT_PRESET", ""), ("IMGOPT_RETINA", "")];
+        with_vars(&mut config, &vars).unwrap();
+        assert_eq!((config.resize().max_dimension, config.format, config.preset), (720, OutputFormat::Png, None));
+    }
+
+    #[test]
+    fn sizes_go_to_the_selected_preset() {
+        let mut config = Config::default();
+        config.presets.insert("docs".to_string(), Preset { color_profile: Some(ColorProfile::Srgb), ..Preset::default() });
+        with_vars(&mut config, &[("IMGOPT_PRESET", "docs"), ("IMGOPT_MIN_DIM", "300"), ("IMGOPT_COLOR_PROFILE", "none")]).unwrap();
+        assert_eq!((config.resize().min_dimension, config.resize.min_dimension), (300, 480));
+        // The variable wins over the preset's own profile
+        assert_eq!(config.presets["docs"].color_profile, None);
+    }
+
+    #[test]
+    fn bad_values_are_reported() {
+        for (var, value, error) in [
+            ("IMGOPT_PRESET", "missing", "invalid IMGOPT_PRESET: no preset named \"missing\""),
+            ("IMGOPT_FORMAT", "webp", "invalid IMGOPT_FORMAT: use png, jpeg or auto"),
+            ("IMGOPT_FRAME", "Window", "invalid IMGOPT_FRAME: use none, window or browser"),
+            ("IMGOPT_CLIPBOARD", "x11", "invalid IMGOPT_CLIPBOARD: use auto or osc52"),
+            ("IMGOPT_MAX_DIM", "0", "invalid IMGOPT_MAX_DIM: must be a positive number"),
+            ("IMGOPT_MIN_DIM", "-1", "invalid IMGOPT_MIN_DIM: must be a positive number"),
+            ("IMGOPT_MAX_WIDTH", "12px", "invalid IMGOPT_MAX_WIDTH: must be a positive number"),
+            ("IMGOPT_OUTPUT_DPI", "0", "invalid IMGOPT_OUTPUT_DPI: must be a positive number"),
+            ("IMGOPT_MIN_SSIM", "1.5", "invalid IMGOPT_MIN_SSIM: must be from 0 to 1"),
+            ("IMGOPT_MIN_SSIM", "NaN", "invalid IMGOPT_MIN_SSIM: must be from 0 to 1"),
+            ("IMGOPT_RETINA", "yes", "invalid IMGOPT_RETINA: must be true or false"),
+            ("IMGOPT_CANVAS", "1200", "invalid IMGOPT_CANVAS: use WIDTHxHEIGHT, like 1200x630"),
+            ("IMGOPT_GIF_FORMAT", "apng", "invalid IMGOPT_GIF_FORMAT: use gif or webp"),
+        ] {
+            let error_text = with_vars(&mut Config::default(), &[(var, value)]).map_err(|e| e.to_string()).err();
+            assert_eq!(error_text.as_deref(), Some(error), "{}={}", var, value);
+        }
+    }
+
+    fn with_rules() -> Config {
+        let text = r#"
+            preset = "base"
+            [presets.base]
+            max_dimension = 1000
+            [presets.docs]
+            max_dimension = 600
+
+            [[rules]]
+            pattern = "docs-*"
+            preset = "docs"
+            [[rules]]
+            pattern = "*.PNG"
+            max_width = 800
+            [[rules]]
+            pattern = "docs-wide*"
+            max_dimension = 900
+            format = "png"
+            [[rules]]
+            pattern = "*wide*"
+            format = "jpeg"
+        "#;
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn every_matching_rule_applies_in_order() {
+        let config = with_rules();
+        let wide = config.for_file(Path::new("/shots/Docs-Wide.png"));
+        assert_eq!(wide.preset.as_deref(), Some("docs"));
+        // The preset a rule picks is what later rules adjust; the last format wins
+        let resize = wide.resize();
+        assert_eq!((resize.max_dimension, resize.max_width, wide.format), (900, Some(800), OutputFormat::Jpeg));
+        assert_eq!(wide.presets["base"].resize.max_width, None);
+    }
+
+    #[test]
+    fn rules_adjust_the_preset_in_effect() {
+        let config = with_rules();
+        let shot = config.for_file(Path::new("shot.png"));
+        assert_eq!(shot.preset.as_deref(), Some("base"));
+        assert_eq!((shot.resize().max_dimension, shot.resize().max_width, shot.format), (1000, Some(800), OutputFormat::Png));
+        // Untouched: [resize], and the config the rules were applied to
+        assert_eq!((shot.resize.max_width, config.resize().max_width), (None, None));
+    }
+
+    #[test]
+    fn unmatched_files_keep_the_config() {
+        let config = with_rules();
+        let other = config.for_file(Path::new("photo.jpg"));
+        assert_eq!((other.preset.as_deref(), other.resize().max_dimension, other.format), (Some("base"), 1000, OutputFormat::Png));
+        // The pattern is matched against the name, not the folder
+        assert_eq!(config.for_file(Path::new("/docs-1/photo.jpg")).preset.as_deref(), Some("base"));
+    }
+
+    #[test]
+    fn rules_go_on_top_of_variables() {
+        let mut config = with_rules();
+        with_vars(&mut config, &[("IMGOPT_MAX_DIM", "500"), ("IMGOPT_FORMAT", "auto")]).unwrap();
+        assert_eq!(config.for_file(Path::new("photo.jpg")).resize().max_dimension, 500);
+        let wide = config.for_file(Path::new("docs-wide.png"));
+        assert_eq!((wide.resize().max_dimension, wide.format), (900, OutputFormat::Jpeg));
+        assert_eq!(config.for_file(Path::new("docs-1.png")).format, OutputFormat::Auto);
+    }
+}
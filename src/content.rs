@@ -0,0 +1,109 @@
+use image::DynamicImage;
+use std::collections::HashSet;
+
+use crate::config::{Config, OutputFormat, AUTO_PRESET};
+
+// Larger images are sampled on a grid, one full-resolution pixel pair per
+// cell, so glyph edges aren't blurred away as they would be by downscaling
+const SAMPLE_PIXELS: u32 = 1_000_000;
+// Neighbors further apart than this in luma are a hard edge, like the stroke
+// of a glyph on its background; photos rarely change this fast
+const EDGE_STEP: i32 = 64;
+// Share of sampled pixels on a hard edge above which an image reads as text
+const TEXT_EDGES: f64 = 0.03;
+// Share of pixels repeating their left neighbor above which an image is flat
+// (UI, diagrams, text) rather than a photo, as in autoformat.rs
+const FLAT_RUNS: f64 = 0.5;
+// Distinct sampled colors from which an image that isn't flat is a photo
+const PHOTO_COLORS: usize = 4096;
+
+// Text stays legible at no less than this, and may go up to TEXT_MAX
+const TEXT_MIN: u32 = 960;
+const TEXT_MAX: u32 = 1280;
+const TEXT_TRANSFORMS: [&str; 2] = ["sharpen:0.6", "quantize:256"];
+
+// What `preset = "auto"` found an image to show
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Content {
+    // Code, documents, chats and other UI that is mostly words
+    Text,
+    Photo,
+    // Diagrams, charts and UI that isn't mostly text
+    Graphic,
+}
+
+impl Content {
+    pub fn name(self) -> &'static str {
+        match self {
+            Content::Text => "text",
+            Content::Photo => "photo",
+            Content::Graphic => "graphic",
+        }
+    }
+
+    // Lossless palette PNG keeps glyph edges exact; photos are far smaller as
+    // JPEG. Graphics are left to `format`. Only serve, mcp and the daemon pick
+    // a format; the clipboard and saved files are always PNG.
+    pub fn format(self) -> Option<OutputFormat> {
+        match self {
+            Content::Text => Some(OutputFormat::Png),
+            Content::Photo => Some(OutputFormat::Jpeg),
+            Content::Graphic => None,
+        }
+    }
+
+    // The config an image of this kind is optimized with: the sizes of
+    // [presets.auto], else [resize], and everything else as configured, tuned
+    // for legibility (text) or size (photos). It fills in the "auto" preset,
+    // so the rest of a [presets.auto] table still applies.
+    pub fn tune(self, config: &Config) -> Config {
+        let mut tuned = config.clone();
+        let mut resize = config.resize();
+        let mut transforms = config.transforms().to_vec();
+        if self == Content::Text {
+            resize.min_dimension = resize.min_dimension.max(TEXT_MIN);
+            resize.max_dimension = resize.max_dimension.max(TEXT_MAX);
+            transforms.extend(TEXT_TRANSFORMS.iter().map(|spec| spec.to_string()));
+        }
+        let preset = tuned.presets.entry(AUTO_PRESET.to_string()).or_default();
+        preset.resize = resize;
+        preset.transforms = Some(transforms);
+        tuned
+    }
+}
+
+// Text is flat with many hard edges; photos have many colors and few runs of
+// the same one. Measured on the decoded image, before it's resized.
+pub fn classify(img: &DynamicImage) -> Content {
+    let rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    if width < 2 || height == 0 {
+        return Content::Graphic;
+    }
+    let step = ((width as u64 * height as u64) as f64 / SAMPLE_PIXELS as f64).sqrt().ceil().max(1.0) as usize;
+    let luma = |[r, g, b, _]: [u8; 4]| (r as i32 * 299 + g as i32 * 587 + b as i32 * 114) / 1000;
+
+    let (mut samples, mut edges, mut runs) = (0u64, 0u64, 0u64);
+    let mut colors = HashSet::new();
+    for y in (0..height).step_by(step) {
+        for x in (0..width - 1).step_by(step) {
+            let (left, right) = (rgba.get_pixel(x, y).0, rgba.get_pixel(x + 1, y).0);
+            samples += 1;
+            runs += (left == right) as u64;
+            edges += ((luma(left) - luma(right)).abs() > EDGE_STEP) as u64;
+            if colors.len() < PHOTO_COLORS {
+                colors.insert(left);
+            }
+        }
+    }
+
+    let share = |count: u64| count as f64 / samples.max(1) as f64;
+    let flat = share(runs) > FLAT_RUNS;
+    if flat && share(edges) > TEXT_EDGES {
+        Content::Text
+    } else if !flat && colors.len() >= PHOTO_COLORS {
+        Content::Photo
+    } else {
+        Content::Graphic
+    }
+}
@@ -0,0 +1,154 @@
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::background::{wait_for_power, Slots};
+use crate::cache::Cache;
+use crate::config::{Config, ConfigWatcher};
+use crate::optimize::{fallback_path, format_bytes};
+use crate::serve::{params_from_json, process};
+use crate::{log, say, Result};
+
+// $IMGOPT_SOCKET, else imgopt.sock in $XDG_RUNTIME_DIR or the temp dir.
+// src/bin/imgopt-cli.rs has a copy of this.
+pub fn socket_path() -> PathBuf {
+    if let Some(path) = std::env::var_os("IMGOPT_SOCKET").filter(|p| !p.is_empty()) {
+        return PathBuf::from(path);
+    }
+    match std::env::var_os("XDG_RUNTIME_DIR").filter(|d| !d.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join("imgopt.sock"),
+        None => std::env::temp_dir().join(format!("imgopt-{}.sock", std::env::var("USER").unwrap_or_default())),
+    }
+}
+
+// `imgopt daemon`: stays resident with the config loaded and the result cache
+// warm, taking jobs over a Unix socket so scripts skip process startup per
+// file. Each request is a JSON line with an absolute `path`, an optional
+// `output` and any `imgopt serve` parameters; the optimized image is written
+// to `output` (default: <name>_optimized.<format> next to the input) and the
+// reply is one JSON line with its metadata.
+pub fn run_daemon(config: &Config) -> Result<()> {
+    let path = socket_path();
+    if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+        if UnixStream::connect(&path).is_ok() {
+            return Err(format!("a daemon is already listening on {}", path.display()).into());
+        }
+        // Left behind by a daemon that didn't shut down cleanly. Anything else
+        // there is someone's file, likely a mistyped IMGOPT_SOCKET.
+        if !metadata.file_type().is_socket() {
+            return Err(format!("{} exists and isn't a socket; point IMGOPT_SOCKET somewhere else", path.display()).into());
+        }
+        std::fs::remove_file(&path)?;
+    }
+    let listener = UnixListener::bind(&path).map_err(|e| format!("could not listen on {}: {}", path.display(), e))?;
+    // Jobs read and write any file the daemon can, so only its owner may
+    // connect, even in a shared temp folder
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+        .map_err(|e| format!("could not restrict {}: {}", path.display(), e))?;
+    say(&format!("imgopt daemon: listening on {}", path.display()));
+    say("Send jobs with `imgopt-cli send <path>`, Ctrl-C to stop");
+    log(&format!("Daemon listening on {}", path.display()));
+
+    // Before any threads are started, so they all get it
+    config.background.apply();
+    let slots = Arc::new(config.background.slots());
+
+    // Swapped whole when the config file changes; each job takes the current one
+    let config = Arc::new(RwLock::new(Arc::new(config.clone())));
+    let shared = Arc::clone(&config);
+    std::thread::spawn(move || reload_config(&shared));
+
+    let cache = Arc::new(Cache::default());
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let (config, cache, slots) = (Arc::clone(&config), Arc::clone(&cache), Arc::clone(&slots));
+        std::thread::spawn(move || {
+            if let Err(e) = serve_client(stream, &config, &cache, &slots) {
+                log(&format!("Daemon client error: {}", e));
+            }
+        });
+    }
+    Ok(())
+}
+
+// Polls the config file and swaps in each edit that parses; jobs already
+// running keep the config they started with
+fn reload_config(config: &RwLock<Arc<Config>>) {
+    let mut watcher = ConfigWatcher::new();
+    loop {
+        std::thread::sleep(Duration::from_secs(1));
+        match watcher.poll() {
+            Some(Ok(reloaded)) => {
+                *config.write().unwrap() = Arc::new(reloaded);
+                say("Config reloaded");
+            }
+            Some(Err(e)) => eprintln!("imgopt: config not reloaded: {}", e),
+            None => {}
+        }
+    }
+}
+
+// One client may send any number of jobs; each gets a reply in order
+fn serve_client(stream: UnixStream, config: &RwLock<Arc<Config>>, cache: &Cache, slots: &Slots) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Value>(&line) {
+            Ok(job) => {
+                let config = Arc::clone(&config.read().unwrap());
+                wait_for_power(&config.background);
+                let _slot = slots.take();
+                run_job(&job, &config, cache)
+            }
+            Err(e) => Err(format!("not a JSON job: {}", e)),
+        };
+        let reply = match reply {
+            Ok(reply) => reply,
+            Err(e) => json!({ "ok": false, "error": e }),
+        };
+        writeln!(writer, "{}", reply)?;
+    }
+    Ok(())
+}
+
+fn run_job(job: &Value, config: &Config, cache: &Cache) -> std::result::Result<Value, String> {
+    let input = job["path"].as_str().map(Path::new).ok_or("job has no path")?;
+    if !input.is_absolute() {
+        return Err(format!("path must be absolute: {}", input.display()));
+    }
+
+    // Job parameters win over [[rules]]
+    let params = params_from_json(job, &["path", "output"]);
+    let processed = process(input, &params, &config.for_file(input), cache).map_err(|(_, e)| e)?;
+    let output = match job["output"].as_str() {
+        Some(output) => PathBuf::from(output),
+        None => fallback_path(input, "optimized", processed.format.extensions_str()[0]),
+    };
+    std::fs::write(&output, &processed.bytes).map_err(|e| format!("could not write {}: {}", output.display(), e))?;
+    log(&format!("Daemon wrote {}", output.display()));
+
+    let mut reply = processed.metadata;
+    let score = reply["ssim"].as_f64().map(|ssim| format!(", SSIM {:.3}", ssim)).unwrap_or_default();
+    reply["summary"] = json!(format!(
+        "{}x{} -> {}x{}, {} -> {}{}",
+        reply["original"]["width"],
+        reply["original"]["height"],
+        reply["optimized"]["width"],
+        reply["optimized"]["height"],
+        format_bytes(reply["input_bytes"].as_u64().unwrap_or(0)),
+        format_bytes(processed.bytes.len() as u64),
+        score
+    ));
+    reply["ok"] = json!(true);
+    reply["output"] = json!(output);
+    Ok(reply)
+}
@@ -0,0 +1,215 @@
+use image::{DynamicImage, ImageFormat};
+use serde::Deserialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::clipboard::{copy_text, Flavors};
+use crate::config::Config;
+use crate::dpi;
+use crate::optimize::{fallback_path, Delivery, Stage};
+use crate::qr::{self, UploadQr};
+use crate::upload::{self, Upload};
+use crate::{log, Result};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+// Where finished images go: `destination` in the config or in a preset, per
+// file in [[rules]], or `--to` on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Destination {
+    Clipboard,
+    // <name>_optimized.png next to the input
+    File,
+    // The encoded bytes, for `imgopt optimize --to stdout shot.png > small.png`
+    Stdout,
+    // The [upload] service; the URL is copied
+    Upload,
+}
+
+impl Destination {
+    pub fn deliverer(self, config: &Config) -> Box<dyn Deliver> {
+        match (self, &config.upload) {
+            (Destination::File, _) => Box::new(ToFile),
+            (Destination::Stdout, _) => Box::new(ToStdout),
+            (Destination::Upload, Some(upload)) => Box::new(ToUpload(upload.clone())),
+            // Checked when the config was loaded
+            (Destination::Clipboard | Destination::Upload, _) => Box::new(ToClipboard),
+        }
+    }
+}
+
+// The PNG every destination starts from, in a temp file of its own so the
+// post-process hook can rewrite it; removed on drop
+pub struct EncodedPng {
+    pub path: PathBuf,
+}
+
+impl EncodedPng {
+    pub fn new(img: &DynamicImage, config: &Config) -> Result<EncodedPng> {
+        let path = temp_path("png");
+        dpi::save(img, &path, ImageFormat::Png, config.output_dpi, config.jpeg_quality, config.icc_profile())?;
+        Ok(EncodedPng { path })
+    }
+}
+
+impl Drop for EncodedPng {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+// A temp file with a name of its own, for output made by another program
+// (an animation from ffmpeg) or input handed to one; removed on drop
+pub struct TempFile {
+    pub path: PathBuf,
+}
+
+impl TempFile {
+    pub fn new(extension: &str) -> TempFile {
+        TempFile { path: temp_path(extension) }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+fn temp_path(extension: &str) -> PathBuf {
+    let name = format!("imgopt-{}-{}.{}", std::process::id(), NEXT_ID.fetch_add(1, Ordering::Relaxed), extension);
+    std::env::temp_dir().join(name)
+}
+
+// A finished image on its way out
+pub enum Output<'a> {
+    // Encoded by us: the image and its PNG, already post-processed
+    Encoded { image: &'a DynamicImage, png: &'a Path },
+    // An input handed over untouched (passthrough), in its own format
+    Original { path: &'a Path, format: ImageFormat },
+}
+
+// Names files after the input they came from
+pub struct Naming<'a> {
+    pub source: &'a Path,
+    // "optimized", "diff" or "grid"
+    pub suffix: &'a str,
+}
+
+impl Naming<'_> {
+    fn path(&self, output: &Output) -> PathBuf {
+        fallback_path(self.source, self.suffix, format(output).extensions_str()[0])
+    }
+}
+
+// One destination. A new one implements this and gets a Destination variant;
+// the processing code doesn't change. An error is reported as a warning and
+// the image is copied to the clipboard instead.
+pub trait Deliver {
+    // Progress reported before delivering
+    fn stage(&self) -> Stage;
+
+    fn deliver(&self, output: &Output, naming: &Naming, config: &Config) -> Result<Delivery>;
+}
+
+pub struct ToClipboard;
+
+impl Deliver for ToClipboard {
+    fn stage(&self) -> Stage {
+        Stage::Clipboard
+    }
+
+    // Failing to copy isn't an error here: the image is saved to a file instead
+    fn deliver(&self, output: &Output, naming: &Naming, config: &Config) -> Result<Delivery> {
+        let copied = match output {
+            Output::Encoded { image, png } => {
+                Flavors::encode(image, png, config).and_then(|flavors| flavors.copy())
+            }
+            Output::Original { path, format } => Flavors::original(path, *format, config).copy(),
+        };
+        let Err(e) = copied else {
+            return Ok(Delivery::Clipboard);
+        };
+        log(&format!("Clipboard copy failed: {}", e));
+        let saved = save(output, naming);
+        match &saved {
+            Ok(fallback) => log(&format!("Saved fallback file: {}", fallback.display())),
+            Err(e) => log(&format!("Fallback save failed: {}", e)),
+        }
+        Ok(Delivery::Fallback { reason: e.to_string(), saved })
+    }
+}
+
+pub struct ToFile;
+
+impl Deliver for ToFile {
+    fn stage(&self) -> Stage {
+        Stage::Saving
+    }
+
+    fn deliver(&self, output: &Output, naming: &Naming, _: &Config) -> Result<Delivery> {
+        let saved = save(output, naming).map_err(|e| format!("Saving failed: {}", e))?;
+        log(&format!("Saved {}", saved.display()));
+        Ok(Delivery::Saved(saved))
+    }
+}
+
+pub struct ToStdout;
+
+impl Deliver for ToStdout {
+    fn stage(&self) -> Stage {
+        Stage::Saving
+    }
+
+    fn deliver(&self, output: &Output, _: &Naming, _: &Config) -> Result<Delivery> {
+        let bytes = std::fs::read(source(output))?;
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(&bytes).and_then(|_| stdout.flush()).map_err(|e| format!("Writing to stdout failed: {}", e))?;
+        Ok(Delivery::Stdout)
+    }
+}
+
+pub struct ToUpload(pub Upload);
+
+impl Deliver for ToUpload {
+    fn stage(&self) -> Stage {
+        Stage::Uploading
+    }
+
+    fn deliver(&self, output: &Output, _: &Naming, config: &Config) -> Result<Delivery> {
+        let url = upload::upload(&self.0, &std::fs::read(source(output))?, format(output))
+            .map_err(|e| format!("Upload failed: {}", e))?;
+        let copied = match qr::image(&url).filter(|_| config.upload_qr == UploadQr::Clipboard) {
+            Some(code) => EncodedPng::new(&code, config).and_then(|png| Flavors::encode(&code, &png.path, config)?.copy()),
+            None => copy_text(&url, config.clipboard),
+        }
+        .map_err(|e| e.to_string());
+        if let Err(e) = &copied {
+            log(&format!("{} copy failed: {}", config.upload_qr.copied(), e));
+        }
+        Ok(Delivery::Uploaded { url, copied })
+    }
+}
+
+// The file holding the output's bytes
+fn source<'a>(output: &Output<'a>) -> &'a Path {
+    match output {
+        Output::Encoded { png, .. } => png,
+        Output::Original { path, .. } => path,
+    }
+}
+
+fn format(output: &Output) -> ImageFormat {
+    match output {
+        Output::Encoded { .. } => ImageFormat::Png,
+        Output::Original { format, .. } => *format,
+    }
+}
+
+fn save(output: &Output, naming: &Naming) -> std::result::Result<PathBuf, String> {
+    let target = naming.path(output);
+    std::fs::copy(source(output), &target).map_err(|e| format!("could not write {}: {}", target.display(), e))?;
+    Ok(target)
+}
@@ -0,0 +1,56 @@
+use image::{DynamicImage, RgbaImage};
+use rayon::prelude::*;
+
+// Pixels on each side of the one being smoothed
+const RADIUS: i64 = 2;
+const SIGMA_SPACE: f32 = 1.5;
+
+// A bilateral filter: each pixel becomes a weighted average of its
+// neighbors, where neighbors of a very different color count for little.
+// Sensor noise is smoothed away while edges and text stay sharp, and the
+// smoother image compresses much better. `strength` (1-100) is how different,
+// in 0-255 steps, a color may be and still count as noise. Rows run in
+// parallel; transparency is kept as it was.
+pub fn bilateral(img: &DynamicImage, strength: u32) -> DynamicImage {
+    let had_alpha = img.color().has_alpha();
+    let rgba = img.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let sigma_color = strength.max(1) as f32;
+    let color_weight = |difference: f32| (-difference / (2.0 * sigma_color * sigma_color)).exp();
+    let space: Vec<f32> = (-RADIUS..=RADIUS)
+        .flat_map(|dy| (-RADIUS..=RADIUS).map(move |dx| (-((dx * dx + dy * dy) as f32) / (2.0 * SIGMA_SPACE * SIGMA_SPACE)).exp()))
+        .collect();
+
+    let mut out = vec![0u8; rgba.as_raw().len()];
+    out.par_chunks_mut(width as usize * 4).enumerate().for_each(|(y, row)| {
+        let y = y as i64;
+        for x in 0..width as i64 {
+            let center = rgba.get_pixel(x as u32, y as u32).0;
+            let (mut sums, mut total) = ([0f32; 3], 0f32);
+            for dy in -RADIUS..=RADIUS {
+                for dx in -RADIUS..=RADIUS {
+                    let (nx, ny) = ((x + dx).clamp(0, width as i64 - 1), (y + dy).clamp(0, height as i64 - 1));
+                    let neighbor = rgba.get_pixel(nx as u32, ny as u32).0;
+                    let difference: f32 = (0..3).map(|c| (neighbor[c] as f32 - center[c] as f32).powi(2)).sum();
+                    let weight = space[((dy + RADIUS) * (2 * RADIUS + 1) + dx + RADIUS) as usize] * color_weight(difference);
+                    for c in 0..3 {
+                        sums[c] += neighbor[c] as f32 * weight;
+                    }
+                    total += weight;
+                }
+            }
+            let pixel = &mut row[x as usize * 4..x as usize * 4 + 4];
+            for c in 0..3 {
+                pixel[c] = (sums[c] / total).round() as u8;
+            }
+            pixel[3] = center[3];
+        }
+    });
+
+    let rgba = RgbaImage::from_raw(width, height, out).expect("buffer sized from the image");
+    if had_alpha {
+        DynamicImage::ImageRgba8(rgba)
+    } else {
+        DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(rgba).into_rgb8())
+    }
+}
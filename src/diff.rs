@@ -0,0 +1,110 @@
+use image::{Rgba, RgbaImage};
+
+// Per-channel difference below this is treated as compression noise
+const THRESHOLD: u8 = 32;
+// Changed pixels are grouped into regions on a grid of this many pixels
+const CELL: u32 = 8;
+
+const HIGHLIGHT: [u8; 3] = [230, 40, 40];
+
+pub struct DiffSummary {
+    // Share of pixels that did not change, 0-100
+    pub similarity: f64,
+    pub regions: usize,
+}
+
+// Marks what changed between two same-sized images: unchanged areas of `after`
+// are faded, changed pixels tinted, and each changed region boxed
+pub fn diff(before: &RgbaImage, after: &RgbaImage) -> (RgbaImage, DiffSummary) {
+    let (width, height) = after.dimensions();
+    let (cols, rows) = (width.div_ceil(CELL), height.div_ceil(CELL));
+    let mut cells = vec![false; (cols * rows) as usize];
+    let mut changed = 0u64;
+
+    let mut out = RgbaImage::new(width, height);
+    for (x, y, pixel) in after.enumerate_pixels() {
+        let old = before.get_pixel(x, y);
+        let delta = pixel.0.iter().zip(old.0).map(|(a, b)| a.abs_diff(b)).max().unwrap_or(0);
+        let [r, g, b, _] = pixel.0;
+
+        let shown = if delta > THRESHOLD {
+            changed += 1;
+            cells[((y / CELL) * cols + x / CELL) as usize] = true;
+            blend([r, g, b], HIGHLIGHT, 0.5)
+        } else {
+            blend([r, g, b], [255, 255, 255], 0.6)
+        };
+        out.put_pixel(x, y, Rgba([shown[0], shown[1], shown[2], 255]));
+    }
+
+    let boxes = regions(&cells, cols, rows);
+    for &(left, top, right, bottom) in &boxes {
+        draw_rect(&mut out, left * CELL, top * CELL, ((right + 1) * CELL).min(width) - 1, ((bottom + 1) * CELL).min(height) - 1);
+    }
+
+    let total = (width as u64 * height as u64).max(1);
+    let summary = DiffSummary {
+        similarity: 100.0 * (total - changed) as f64 / total as f64,
+        regions: boxes.len(),
+    };
+    (out, summary)
+}
+
+fn blend(color: [u8; 3], toward: [u8; 3], amount: f32) -> [u8; 3] {
+    let mut result = [0; 3];
+    for i in 0..3 {
+        result[i] = (color[i] as f32 * (1.0 - amount) + toward[i] as f32 * amount) as u8;
+    }
+    result
+}
+
+// Bounding boxes (in cells) of 8-connected groups of changed cells
+fn regions(cells: &[bool], cols: u32, rows: u32) -> Vec<(u32, u32, u32, u32)> {
+    let mut seen = vec![false; cells.len()];
+    let mut boxes = Vec::new();
+
+    for start in 0..cells.len() {
+        if !cells[start] || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let mut stack = vec![start];
+        let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+
+        while let Some(index) = stack.pop() {
+            let (x, y) = (index as u32 % cols, index as u32 / cols);
+            (left, top, right, bottom) = (left.min(x), top.min(y), right.max(x), bottom.max(y));
+
+            for (dx, dy) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                if nx < 0 || ny < 0 || nx >= cols as i64 || ny >= rows as i64 {
+                    continue;
+                }
+                let next = (ny as u32 * cols + nx as u32) as usize;
+                if cells[next] && !seen[next] {
+                    seen[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        boxes.push((left, top, right, bottom));
+    }
+    boxes
+}
+
+// Two pixel wide outline
+fn draw_rect(img: &mut RgbaImage, left: u32, top: u32, right: u32, bottom: u32) {
+    let color = Rgba([HIGHLIGHT[0], HIGHLIGHT[1], HIGHLIGHT[2], 255]);
+    for inset in 0..2 {
+        let (l, t) = (left + inset, top + inset);
+        let (r, b) = (right.saturating_sub(inset).max(l), bottom.saturating_sub(inset).max(t));
+        for x in l..=r {
+            img.put_pixel(x, t, color);
+            img.put_pixel(x, b, color);
+        }
+        for y in t..=b {
+            img.put_pixel(l, y, color);
+            img.put_pixel(r, y, color);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+// X11's base density; Xft.dpi is set to a multiple of it on scaled screens
+const BASE_DPI: f64 = 96.0;
+
+// How many device pixels each connected display draws per logical pixel, one
+// entry per display where the platform tells them apart: 2 on a Retina or
+// other HiDPI screen. Empty when there is no display or it can't be told.
+pub fn scales() -> Vec<f64> {
+    detect().into_iter().filter(|scale| scale.is_finite() && *scale > 0.0).collect()
+}
+
+// True when a display draws at 2x or more, where screenshots come out at twice
+// the size they look and `retina` halving maps them back
+pub fn is_hidpi(scales: &[f64]) -> bool {
+    scales.iter().any(|scale| *scale >= 2.0)
+}
+
+// "2x" or "1x, 2x", the way display settings name them
+pub fn describe(scales: &[f64]) -> String {
+    let names: Vec<String> = scales.iter().map(|scale| format!("{}x", (scale * 100.0).round() / 100.0)).collect();
+    names.join(", ")
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = std::process::Command::new(program).args(args).output().ok().filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Every screen's backingScaleFactor, through AppKit from JavaScript for Automation
+#[cfg(target_os = "macos")]
+fn detect() -> Vec<f64> {
+    let script = "ObjC.import('AppKit'); $.NSScreen.screens.js.map(s => s.backingScaleFactor).join(' ')";
+    output("osascript", &["-l", "JavaScript", "-e", script])
+        .map(|text| text.split_whitespace().filter_map(|scale| scale.parse().ok()).collect())
+        .unwrap_or_default()
+}
+
+// Per output from sway or Hyprland, then the one scale GNOME, the toolkit
+// variables or X resources give for every screen
+#[cfg(target_os = "linux")]
+fn detect() -> Vec<f64> {
+    let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    if var("WAYLAND_DISPLAY").is_none() && var("DISPLAY").is_none() {
+        return Vec::new();
+    }
+    let outputs = |program: &str, args: &[&str]| -> Option<Vec<f64>> {
+        let outputs: Vec<serde_json::Value> = serde_json::from_str(&output(program, args)?).ok()?;
+        let scales: Vec<f64> = outputs
+            .iter()
+            .filter(|output| output["active"].as_bool() != Some(false))
+            .filter_map(|output| output["scale"].as_f64())
+            .collect();
+        (!scales.is_empty()).then_some(scales)
+    };
+    if var("SWAYSOCK").is_some() {
+        if let Some(scales) = outputs("swaymsg", &["-t", "get_outputs", "-r"]) {
+            return scales;
+        }
+    }
+    if var("HYPRLAND_INSTANCE_SIGNATURE").is_some() {
+        if let Some(scales) = outputs("hyprctl", &["monitors", "-j"]) {
+            return scales;
+        }
+    }
+    if let Some(scale) = var("GDK_SCALE").or_else(|| var("QT_SCALE_FACTOR")).and_then(|value| value.parse().ok()) {
+        return vec![scale];
+    }
+    // "uint32 2"; 0 leaves it to GNOME, which then goes by the screen's density
+    let gnome = output("gsettings", &["get", "org.gnome.desktop.interface", "scaling-factor"])
+        .and_then(|text| text.rsplit(' ').next().and_then(|scale| scale.parse::<f64>().ok()))
+        .filter(|scale| *scale > 0.0);
+    if let Some(scale) = gnome {
+        return vec![scale];
+    }
+    output("xrdb", &["-query"])
+        .and_then(|text| {
+            let line = text.lines().find(|line| line.starts_with("Xft.dpi:"))?;
+            line["Xft.dpi:".len()..].trim().parse::<f64>().ok()
+        })
+        .map(|dpi| vec![dpi / BASE_DPI])
+        .unwrap_or_default()
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn detect() -> Vec<f64> {
+    Vec::new()
+}
@@ -0,0 +1,170 @@
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use std::io::Cursor;
+use std::path::Path;
+
+use crate::capabilities::{self, Check, Status};
+use crate::config::Config;
+use crate::display;
+use crate::exit::Exit;
+use crate::paths::find_program;
+
+// Every format imgopt reads or writes through the image crate: inputs, the
+// clipboard flavors, favicons and srcsets
+const CODECS: [(&str, ImageFormat); 7] = [
+    ("PNG", ImageFormat::Png),
+    ("JPEG", ImageFormat::Jpeg),
+    ("GIF", ImageFormat::Gif),
+    ("WebP", ImageFormat::WebP),
+    ("TIFF", ImageFormat::Tiff),
+    ("BMP", ImageFormat::Bmp),
+    ("ICO", ImageFormat::Ico),
+];
+
+// `imgopt doctor`: checks what imgopt needs on this machine and prints one
+// line per item, with what to do about anything that's missing. Meant to be
+// pasted into bug reports, so it starts with the version and platform.
+pub fn run_doctor() -> Exit {
+    let features: Vec<&str> = [("wasm", cfg!(feature = "wasm")), ("jxl", cfg!(feature = "jxl"))]
+        .into_iter()
+        .filter_map(|(name, on)| on.then_some(name))
+        .collect();
+    println!(
+        "imgopt {} on {} ({}), features: {}",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        if features.is_empty() { "none".to_string() } else { features.join(", ") }
+    );
+
+    let (config_check, config) = config();
+    let mut checks = vec![config_check];
+    checks.extend(CODECS.iter().map(|(name, format)| codec(name, *format)));
+    checks.push(tool("jpegtran", "JPEGs that need no resize are re-encoded instead of repacked", "Install libjpeg-turbo"));
+    checks.push(tool("pdfinfo", "PDFs can't be opened", "Install poppler"));
+    checks.push(tool("pdftoppm", "PDFs can't be opened", "Install poppler"));
+    checks.push(tool("ffmpeg", "screen recordings can't be opened", "Install ffmpeg"));
+    checks.push(tool("ffprobe", "screen recordings can't be opened", "Install ffmpeg"));
+    checks.push(tool("tesseract", "[legibility] can't check results", "Install tesseract"));
+    if cfg!(feature = "jxl") {
+        checks.push(tool("cjxl", "imgopt jxl can't run", "Install libjxl"));
+    }
+    checks.push(capabilities::clipboard(config.clipboard));
+    checks.push(capabilities::display(&display::scales(), config.resize().retina));
+    checks.push(writable("Temp folder", Some(&std::env::temp_dir()), "Point TMPDIR at a folder you can write to"));
+    checks.push(writable("Archive", config.archive_dir().as_deref(), "Fix archive_dir or its permissions"));
+    checks.push(writable("Reports", config.report_dir().as_deref(), "Fix report_dir or its permissions"));
+
+    for check in &checks {
+        let status = match check.status {
+            Status::Pass => "ok",
+            Status::Warn => "warn",
+            Status::Fail => "FAIL",
+        };
+        println!("{:<6}{:<14}{}", status, check.name, check.detail);
+        if let Some(hint) = check.hint.as_ref().filter(|_| check.status != Status::Pass) {
+            println!("{:<20}-> {}", "", hint);
+        }
+    }
+
+    let count = |status| checks.iter().filter(|check| check.status == status).count();
+    let (failed, warned) = (count(Status::Fail), count(Status::Warn));
+    println!();
+    if failed == 0 && warned == 0 {
+        println!("Everything looks fine");
+    } else {
+        println!("{} problem{}, {} warning{}", failed, plural(failed), warned, plural(warned));
+    }
+    if failed > 0 {
+        Exit::Failure
+    } else {
+        Exit::Success
+    }
+}
+
+// An invalid config is reported here instead of stopping imgopt; the other
+// checks go on with the defaults
+fn config() -> (Check, Config) {
+    let path = Config::path();
+    let detail = match path.as_deref().filter(|path| path.exists()) {
+        Some(path) => path.display().to_string(),
+        None => "no config file, using the defaults".to_string(),
+    };
+    match Config::load() {
+        Ok(config) => (Check { name: "Config", status: Status::Pass, detail, hint: None }, config),
+        Err(e) => {
+            let hint = Some("Fix the setting it names, or move the file away to start over".to_string());
+            (Check { name: "Config", status: Status::Fail, detail: e.to_string(), hint }, Config::default())
+        }
+    }
+}
+
+// Encodes a small image and decodes it again. JPEG has no alpha channel,
+// and icons are always written with one.
+fn codec(name: &'static str, format: ImageFormat) -> Check {
+    let image = RgbaImage::from_fn(16, 16, |x, y| image::Rgba([x as u8 * 16, y as u8 * 16, 128, 255]));
+    let image = match format {
+        ImageFormat::Jpeg => DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(image).to_rgb8()),
+        _ => DynamicImage::ImageRgba8(image),
+    };
+    let mut encoded = Cursor::new(Vec::new());
+    let result = image
+        .write_to(&mut encoded, format)
+        .map_err(|e| format!("encoding failed: {}", e))
+        .and_then(|_| {
+            image::load_from_memory_with_format(encoded.get_ref(), format).map_err(|e| format!("decoding failed: {}", e))
+        })
+        .and_then(|decoded| {
+            let size = (decoded.width(), decoded.height());
+            if size == (16, 16) {
+                Ok(())
+            } else {
+                Err(format!("decoded as {}x{} instead of 16x16", size.0, size.1))
+            }
+        });
+    match result {
+        Ok(()) => Check { name, status: Status::Pass, detail: "encodes and decodes".to_string(), hint: None },
+        Err(e) => {
+            let hint = Some("Rebuild imgopt with the image crate's default features".to_string());
+            Check { name, status: Status::Fail, detail: e, hint }
+        }
+    }
+}
+
+// An optional external program: missing it only costs a feature
+fn tool(program: &'static str, without: &str, hint: &str) -> Check {
+    match find_program(program) {
+        Some(path) => Check { name: program, status: Status::Pass, detail: path.display().to_string(), hint: None },
+        None => {
+            let detail = format!("not found, so {}", without);
+            Check { name: program, status: Status::Warn, detail, hint: Some(hint.to_string()) }
+        }
+    }
+}
+
+// Creates the folder if needed and writes a file into it; an unset folder passes
+fn writable(name: &'static str, dir: Option<&Path>, hint: &str) -> Check {
+    let Some(dir) = dir else {
+        return Check { name, status: Status::Pass, detail: "not set".to_string(), hint: None };
+    };
+    let probe = dir.join(format!(".imgopt-doctor-{}", std::process::id()));
+    let result = std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(&probe, b"imgopt"))
+        .and_then(|_| std::fs::remove_file(&probe));
+    match result {
+        Ok(()) => Check { name, status: Status::Pass, detail: dir.display().to_string(), hint: None },
+        Err(e) => Check {
+            name,
+            status: Status::Fail,
+            detail: format!("{} is not writable: {}", dir.display(), e),
+            hint: Some(hint.to_string()),
+        },
+    }
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
@@ -0,0 +1,129 @@
+use image::codecs::jpeg::{JpegEncoder, PixelDensity};
+use image::codecs::webp::WebPEncoder;
+use image::{DynamicImage, ImageEncoder, ImageFormat};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read};
+use std::path::Path;
+
+use crate::Result;
+
+const INCHES_PER_METER: f64 = 39.3701;
+
+// macOS saves Retina screenshots at 144 DPI; pHYs rounding lands just under it
+const RETINA_DPI: f64 = 143.5;
+
+// A Retina capture: `name@2x.png`, or anything tagged with 144 DPI or more
+pub fn is_retina(path: &Path) -> bool {
+    let named = path.file_stem().and_then(|s| s.to_str()).is_some_and(|stem| stem.ends_with("@2x"));
+    named || read_dpi(path).is_some_and(|dpi| dpi >= RETINA_DPI)
+}
+
+// Horizontal density from a PNG pHYs chunk or a JPEG JFIF header
+pub fn read_dpi(path: &Path) -> Option<f64> {
+    let mut header = [0u8; 18];
+    File::open(path).ok()?.read_exact(&mut header).ok()?;
+
+    if header.starts_with(b"\x89PNG") {
+        let decoder = png::Decoder::new(BufReader::new(File::open(path).ok()?));
+        let reader = decoder.read_info().ok()?;
+        let dims = reader.info().pixel_dims?;
+        return (dims.unit == png::Unit::Meter).then(|| dims.xppu as f64 / INCHES_PER_METER);
+    }
+
+    // SOI, APP0 length, "JFIF\0", version, units, X density, Y density
+    if header.starts_with(&[0xFF, 0xD8, 0xFF, 0xE0]) && &header[6..11] == b"JFIF\0" {
+        let density = u16::from_be_bytes([header[14], header[15]]) as f64;
+        return match header[13] {
+            1 => Some(density),
+            2 => Some(density * 2.54),
+            _ => None,
+        };
+    }
+    None
+}
+
+// Saves like `DynamicImage::save_with_format`, tagging PNG and JPEG output
+// with `dpi` when one is given and embedding the `icc` color profile in PNG,
+// JPEG and WebP output. Other formats are written without them. JPEGs are
+// written at `quality` (1-100).
+pub fn save(img: &DynamicImage, path: &Path, format: ImageFormat, dpi: Option<u16>, quality: u8, icc: Option<&[u8]>) -> Result<()> {
+    match format {
+        ImageFormat::Png => save_png(img, path, dpi, icc),
+        ImageFormat::Jpeg => {
+            let mut encoder = JpegEncoder::new_with_quality(BufWriter::new(File::create(path)?), quality);
+            if let Some(dpi) = dpi {
+                encoder.set_pixel_density(PixelDensity::dpi(dpi));
+            }
+            if let Some(icc) = icc {
+                encoder.set_icc_profile(icc.to_vec())?;
+            }
+            Ok(encoder.encode_image(img)?)
+        }
+        ImageFormat::WebP => match icc {
+            Some(icc) => save_webp(img, path, icc),
+            None => Ok(img.save_with_format(path, format)?),
+        },
+        _ => Ok(img.save_with_format(path, format)?),
+    }
+}
+
+// Lossless, like `save_with_format`, which has no way to add a profile
+fn save_webp(img: &DynamicImage, path: &Path, icc: &[u8]) -> Result<()> {
+    let mut encoder = WebPEncoder::new_lossless(BufWriter::new(File::create(path)?));
+    encoder.set_icc_profile(icc.to_vec())?;
+    // It takes 8-bit RGB or RGBA only
+    let img = if img.color().has_alpha() { DynamicImage::ImageRgba8(img.to_rgba8()) } else { DynamicImage::ImageRgb8(img.to_rgb8()) };
+    Ok(encoder.write_image(img.as_bytes(), img.width(), img.height(), img.color().into())?)
+}
+
+// Images with 256 colors or fewer (flat UI, or after the quantize transform)
+// are written with a palette, which is lossless and much smaller
+fn save_png(img: &DynamicImage, path: &Path, dpi: Option<u16>, icc: Option<&[u8]>) -> Result<()> {
+    let mut info = png::Info::with_size(img.width(), img.height());
+    info.icc_profile = icc.map(Into::into);
+    let mut encoder = png::Encoder::with_info(BufWriter::new(File::create(path)?), info)?;
+    encoder.set_depth(png::BitDepth::Eight);
+    if let Some(dpi) = dpi {
+        let ppm = (dpi as f64 * INCHES_PER_METER).round() as u32;
+        encoder.set_pixel_dims(Some(png::PixelDimensions { xppu: ppm, yppu: ppm, unit: png::Unit::Meter }));
+    }
+
+    let rgba = img.to_rgba8();
+    if let Some((palette, indices)) = indexed(&rgba) {
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_palette(palette.iter().flat_map(|[r, g, b, _]| [*r, *g, *b]).collect::<Vec<_>>());
+        if palette.iter().any(|color| color[3] < 255) {
+            encoder.set_trns(palette.iter().map(|color| color[3]).collect::<Vec<_>>());
+        }
+        encoder.write_header()?.write_image_data(&indices)?;
+    } else if img.color().has_alpha() {
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.write_header()?.write_image_data(&rgba)?;
+    } else {
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.write_header()?.write_image_data(&img.to_rgb8())?;
+    }
+    Ok(())
+}
+
+// The palette and one index per pixel, unless there are more than 256 colors
+fn indexed(img: &image::RgbaImage) -> Option<(Vec<[u8; 4]>, Vec<u8>)> {
+    let mut palette = Vec::new();
+    let mut lookup: HashMap<[u8; 4], u8> = HashMap::new();
+    let mut indices = Vec::with_capacity((img.width() * img.height()) as usize);
+    for pixel in img.pixels() {
+        let index = match lookup.get(&pixel.0) {
+            Some(index) => *index,
+            None if palette.len() < 256 => {
+                let index = palette.len() as u8;
+                palette.push(pixel.0);
+                lookup.insert(pixel.0, index);
+                index
+            }
+            None => return None,
+        };
+        indices.push(index);
+    }
+    Some((palette, indices))
+}
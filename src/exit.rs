@@ -0,0 +1,53 @@
+use std::process::ExitCode;
+
+use crate::optimize::{Delivery, OptimizeError, Outcome};
+
+// Process exit statuses scripts can branch on. These are part of the CLI:
+// never renumber them, only add new ones at the end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Exit {
+    Success = 0,
+    // Bad arguments, an invalid config and anything else not listed here
+    Failure = 1,
+    UnsupportedFormat = 2,
+    DecodeFailed = 3,
+    // The image (or its URL) didn't reach the clipboard; the image was saved to a file if possible
+    ClipboardFailed = 4,
+    NotFound = 5,
+    TooLarge = 6,
+    TransformFailed = 7,
+    // `imgopt precommit` left images over the [budget], or `imgopt check` found some
+    OverBudget = 8,
+}
+
+impl Exit {
+    pub fn of(outcome: &Result<Outcome, OptimizeError>) -> Exit {
+        match outcome {
+            Ok(outcome) => match &outcome.delivery {
+                Delivery::Clipboard | Delivery::Saved(_) | Delivery::Stdout => Exit::Success,
+                Delivery::Uploaded { copied: Ok(()), .. } | Delivery::Written { copied: None | Some(Ok(())), .. } => Exit::Success,
+                Delivery::Uploaded { copied: Err(_), .. } | Delivery::Written { copied: Some(Err(_)), .. } | Delivery::Fallback { .. } => {
+                    Exit::ClipboardFailed
+                }
+            },
+            Err(e) => Exit::error(e),
+        }
+    }
+
+    pub fn error(e: &OptimizeError) -> Exit {
+        match e {
+            OptimizeError::NotFound => Exit::NotFound,
+            OptimizeError::Unsupported(_) => Exit::UnsupportedFormat,
+            OptimizeError::Unreadable(_) => Exit::DecodeFailed,
+            OptimizeError::TooLarge(_) => Exit::TooLarge,
+            OptimizeError::Transform(_) => Exit::TransformFailed,
+            OptimizeError::Output(_) | OptimizeError::Cancelled => Exit::Failure,
+        }
+    }
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> ExitCode {
+        ExitCode::from(exit as u8)
+    }
+}
@@ -0,0 +1,66 @@
+use image::codecs::ico::{IcoEncoder, IcoFrame};
+use image::{DynamicImage, ExtendedColorType, ImageFormat, RgbImage, RgbaImage};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::config::Favicon;
+use crate::dpi;
+use crate::optimize::resample;
+use crate::Result;
+
+// Icons are square: anything else is centered on a transparent square
+pub fn square(img: &DynamicImage) -> RgbaImage {
+    let rgba = img.to_rgba8();
+    let side = rgba.width().max(rgba.height());
+    if rgba.width() == rgba.height() {
+        return rgba;
+    }
+    let mut canvas = RgbaImage::new(side, side);
+    let x = (side - rgba.width()) / 2;
+    let y = (side - rgba.height()) / 2;
+    image::imageops::overlay(&mut canvas, &rgba, x as i64, y as i64);
+    canvas
+}
+
+// Writes favicon.ico (one PNG-encoded entry per size) and the apple-touch
+// icons into `dir`, returning the file names in the order written
+pub fn write_set(icon: &RgbaImage, dir: &Path, settings: &Favicon, quality: u8) -> Result<Vec<String>> {
+    std::fs::create_dir_all(dir)?;
+    let source = DynamicImage::ImageRgba8(icon.clone());
+    let mut written = Vec::new();
+
+    let mut sizes = settings.ico_sizes.clone();
+    sizes.sort_unstable();
+    sizes.dedup();
+    let frames = sizes
+        .iter()
+        .map(|size| {
+            let scaled = resample(&source, *size, *size).to_rgba8();
+            IcoFrame::as_png(scaled.as_raw(), *size, *size, ExtendedColorType::Rgba8)
+        })
+        .collect::<image::ImageResult<Vec<_>>>()?;
+    IcoEncoder::new(BufWriter::new(File::create(dir.join("favicon.ico"))?)).encode_images(&frames)?;
+    written.push("favicon.ico".to_string());
+
+    for (i, size) in settings.apple_touch_sizes.iter().enumerate() {
+        let name = match i {
+            0 => "apple-touch-icon.png".to_string(),
+            _ => format!("apple-touch-icon-{}x{}.png", size, size),
+        };
+        let scaled = resample(&source, *size, *size).to_rgba8();
+        let opaque = DynamicImage::ImageRgb8(on_background(&scaled, settings.background));
+        dpi::save(&opaque, &dir.join(&name), ImageFormat::Png, None, quality, None)?;
+        written.push(name);
+    }
+    Ok(written)
+}
+
+fn on_background(img: &RgbaImage, background: [u8; 3]) -> RgbImage {
+    RgbImage::from_fn(img.width(), img.height(), |x, y| {
+        let [r, g, b, a] = img.get_pixel(x, y).0;
+        let alpha = a as f32 / 255.0;
+        let blend = |c: u8, bg: u8| (c as f32 * alpha + bg as f32 * (1.0 - alpha)).round() as u8;
+        image::Rgb([blend(r, background[0]), blend(g, background[1]), blend(b, background[2])])
+    })
+}
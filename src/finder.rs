@@ -0,0 +1,164 @@
+use crossterm::{
+    cursor,
+    event::{KeyCode, KeyEvent},
+    queue,
+    style::{Print, ResetColor, SetForegroundColor},
+    terminal,
+};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::i18n::{tr, trf};
+use crate::paths::collect_images;
+use crate::theme::Theme;
+use crate::ui::{draw_footer, draw_list, print_centered, truncate, Action, ListItem};
+use crate::Result;
+
+const MAX_DEPTH: usize = 8;
+const MAX_FILES: usize = 20_000;
+
+struct Candidates {
+    root: PathBuf,
+    // Paths relative to `root`, which is what the query is matched against
+    files: Vec<String>,
+}
+
+impl Candidates {
+    fn scan(root: &Path) -> Candidates {
+        let mut files: Vec<String> = collect_images(root, MAX_DEPTH, MAX_FILES)
+            .iter()
+            .map(|p| p.strip_prefix(root).unwrap_or(p).to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        Candidates { root: root.to_path_buf(), files }
+    }
+
+    fn matches(&self, query: &str) -> Vec<&str> {
+        let mut scored: Vec<(i64, &str)> = self
+            .files
+            .iter()
+            .filter_map(|f| fuzzy_score(query, f).map(|score| (score, f.as_str())))
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.len().cmp(&b.1.len())));
+        scored.into_iter().map(|(_, f)| f).collect()
+    }
+}
+
+// Case-insensitive subsequence match. Consecutive characters and matches at the
+// start of a path component or word score higher; long paths score lower.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let chars: Vec<char> = candidate.chars().collect();
+    let mut score = 0i64;
+    let mut pos = 0usize;
+    let mut last_match: Option<usize> = None;
+
+    for q in query.chars().flat_map(char::to_lowercase) {
+        let found = (pos..chars.len()).find(|&i| chars[i].to_lowercase().eq(std::iter::once(q)))?;
+
+        score += 10;
+        if last_match.is_some_and(|last| last + 1 == found) {
+            score += 15;
+        }
+        if found == 0 || matches!(chars[found - 1], '/' | '_' | '-' | '.' | ' ') {
+            score += 20;
+        }
+        score -= (found - pos) as i64;
+
+        last_match = Some(found);
+        pos = found + 1;
+    }
+
+    Some(score - chars.len() as i64 / 4)
+}
+
+// Fuzzy search over the images below one of several roots
+pub struct Finder {
+    roots: Vec<PathBuf>,
+    root_index: usize,
+    candidates: Candidates,
+    query: String,
+    selected: usize,
+}
+
+impl Finder {
+    pub fn new(roots: Vec<PathBuf>) -> Option<Finder> {
+        let candidates = Candidates::scan(roots.first()?);
+        Some(Finder {
+            roots,
+            root_index: 0,
+            candidates,
+            query: String::new(),
+            selected: 0,
+        })
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc => return Action::Back,
+            KeyCode::Enter => {
+                if let Some(file) = self.candidates.matches(&self.query).get(self.selected) {
+                    return Action::Open(self.candidates.root.join(file));
+                }
+            }
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => {
+                let count = self.candidates.matches(&self.query).len();
+                self.selected = (self.selected + 1).min(count.saturating_sub(1));
+            }
+            KeyCode::Tab if self.roots.len() > 1 => {
+                self.root_index = (self.root_index + 1) % self.roots.len();
+                self.candidates = Candidates::scan(&self.roots[self.root_index]);
+                self.selected = 0;
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.selected = 0;
+            }
+            KeyCode::Char(c) => {
+                self.query.push(c);
+                self.selected = 0;
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    pub fn render(&self, stdout: &mut io::Stdout, theme: &Theme) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let max_len = width.saturating_sub(4) as usize;
+        let matches = self.candidates.matches(&self.query);
+
+        queue!(
+            stdout,
+            cursor::MoveTo(1, 0),
+            SetForegroundColor(theme.muted),
+            Print(truncate(&trf("finder.root", &[("dir", &self.candidates.root.display())]), max_len)),
+            cursor::MoveTo(1, 1),
+            SetForegroundColor(theme.accent),
+            Print(theme.pick("🔍 ", "/ ")),
+            ResetColor,
+            Print(&self.query),
+        )?;
+
+        if matches.is_empty() {
+            print_centered(stdout, height / 2, theme.muted, tr("finder.empty"))?;
+        }
+
+        let items: Vec<ListItem> = matches
+            .iter()
+            .map(|file| ListItem { text: file.to_string(), color: theme.text })
+            .collect();
+
+        // Two header lines, a spacer and one footer line
+        draw_list(stdout, theme, 1, 3, height.saturating_sub(4) as usize, &items, self.selected)?;
+        draw_footer(
+            stdout,
+            theme,
+            theme.pick(tr("finder.footer"), tr("finder.footer_ascii")),
+        )
+    }
+}
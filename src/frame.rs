@@ -0,0 +1,143 @@
+use image::{imageops, GrayImage, Luma, Rgba, RgbaImage};
+use serde::Deserialize;
+
+// Transparent room around the window for its shadow
+const MARGIN: u32 = 28;
+const RADIUS: f32 = 10.0;
+const SHADOW_OFFSET: u32 = 6;
+const SHADOW_BLUR: f32 = 8.0;
+const SHADOW_OPACITY: f32 = 0.35;
+
+// Title bar, its bottom edge and the address field, light and dark
+const BAR: [[u8; 3]; 2] = [[236, 236, 236], [54, 54, 56]];
+const BAR_EDGE: [[u8; 3]; 2] = [[210, 210, 210], [24, 24, 26]];
+const ADDRESS: [[u8; 3]; 2] = [[255, 255, 255], [82, 82, 86]];
+// Close, minimize, zoom
+const LIGHTS: [[u8; 3]; 3] = [[255, 95, 87], [254, 188, 46], [40, 200, 64]];
+const LIGHT_RADIUS: f32 = 6.0;
+
+// Window chrome drawn around the optimized image, for documentation shots
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Frame {
+    #[default]
+    None,
+    // macOS title bar with traffic lights
+    Window,
+    // Title bar with an address field
+    Browser,
+}
+
+impl Frame {
+    fn bar_height(self) -> u32 {
+        match self {
+            Frame::None => 0,
+            Frame::Window => 28,
+            Frame::Browser => 40,
+        }
+    }
+
+    // Pixels the frame adds to the width and height of the image
+    pub fn extra(self) -> (u32, u32) {
+        match self {
+            Frame::None => (0, 0),
+            _ => (2 * MARGIN, 2 * MARGIN + self.bar_height()),
+        }
+    }
+}
+
+// Puts the image in a window with rounded corners and a soft drop shadow on
+// a transparent canvas. `dark` draws the title bar the way dark mode does.
+pub fn wrap(img: &RgbaImage, frame: Frame, dark: bool) -> RgbaImage {
+    let bar = frame.bar_height();
+    let (width, height) = (img.width(), img.height() + bar);
+    let (extra_width, extra_height) = frame.extra();
+    let (canvas_width, canvas_height) = (img.width() + extra_width, img.height() + extra_height);
+
+    // The window's outline, shifted down and blurred
+    let mut mask = GrayImage::new(canvas_width, canvas_height);
+    for y in 0..height {
+        for x in 0..width {
+            let coverage = rounded_rect(x, y, width, height, RADIUS);
+            let shadow_y = (y + MARGIN + SHADOW_OFFSET).min(canvas_height - 1);
+            mask.put_pixel(x + MARGIN, shadow_y, Luma([(coverage * 255.0) as u8]));
+        }
+    }
+    let mask = imageops::fast_blur(&mask, SHADOW_BLUR);
+    let mut canvas = RgbaImage::from_fn(canvas_width, canvas_height, |x, y| {
+        Rgba([0, 0, 0, (mask.get_pixel(x, y).0[0] as f32 * SHADOW_OPACITY) as u8])
+    });
+
+    for y in 0..height {
+        for x in 0..width {
+            let coverage = rounded_rect(x, y, width, height, RADIUS);
+            if coverage == 0.0 {
+                continue;
+            }
+            let pixel = if y < bar {
+                let [r, g, b] = bar_pixel(frame, x, y, width, dark as usize);
+                Rgba([r, g, b, 255])
+            } else {
+                *img.get_pixel(x, y - bar)
+            };
+            over(canvas.get_pixel_mut(x + MARGIN, y + MARGIN), pixel, coverage);
+        }
+    }
+    canvas
+}
+
+fn bar_pixel(frame: Frame, x: u32, y: u32, width: u32, shade: usize) -> [u8; 3] {
+    let bar = frame.bar_height();
+    if y == bar - 1 {
+        return BAR_EDGE[shade];
+    }
+
+    let mut color = BAR[shade];
+    let center_y = bar as f32 / 2.0;
+    for (i, light) in LIGHTS.iter().enumerate() {
+        let center_x = 18.0 + i as f32 * 20.0;
+        let distance = ((x as f32 + 0.5 - center_x).powi(2) + (y as f32 + 0.5 - center_y).powi(2)).sqrt();
+        color = mix(color, *light, (LIGHT_RADIUS - distance + 0.5).clamp(0.0, 1.0));
+    }
+
+    if frame == Frame::Browser {
+        let (left, right, top) = (84, width.saturating_sub(16), (bar - 22) / 2);
+        if x >= left && x < right && y >= top && y < top + 22 {
+            color = mix(color, ADDRESS[shade], rounded_rect(x - left, y - top, right - left, 22, 6.0));
+        }
+    }
+    color
+}
+
+// How much of pixel (x, y) lies inside a width x height rectangle with
+// rounded corners, for anti-aliased edges
+fn rounded_rect(x: u32, y: u32, width: u32, height: u32, radius: f32) -> f32 {
+    let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+    let nearest_x = px.clamp(radius, (width as f32 - radius).max(radius));
+    let nearest_y = py.clamp(radius, (height as f32 - radius).max(radius));
+    let distance = ((px - nearest_x).powi(2) + (py - nearest_y).powi(2)).sqrt();
+    (radius - distance + 0.5).clamp(0.0, 1.0)
+}
+
+fn mix(base: [u8; 3], top: [u8; 3], amount: f32) -> [u8; 3] {
+    let mut result = [0; 3];
+    for i in 0..3 {
+        result[i] = (base[i] as f32 * (1.0 - amount) + top[i] as f32 * amount).round() as u8;
+    }
+    result
+}
+
+// Source-over compositing of `pixel`, weighted by `coverage`
+fn over(dst: &mut Rgba<u8>, pixel: Rgba<u8>, coverage: f32) {
+    let alpha = pixel.0[3] as f32 / 255.0 * coverage;
+    let below = dst.0[3] as f32 / 255.0;
+    let out = alpha + below * (1.0 - alpha);
+    if out == 0.0 {
+        return;
+    }
+    for i in 0..3 {
+        let value = (pixel.0[i] as f32 * alpha + dst.0[i] as f32 * below * (1.0 - alpha)) / out;
+        dst.0[i] = value.round() as u8;
+    }
+    dst.0[3] = (out * 255.0).round() as u8;
+}
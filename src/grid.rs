@@ -0,0 +1,119 @@
+use image::{imageops, Rgba, RgbaImage};
+
+use crate::config::{Grid, Resize};
+use crate::font::{self, ADVANCE, GLYPH_HEIGHT};
+use crate::optimize::target_dimensions;
+
+// Labels are drawn at this many image pixels per font pixel
+const LABEL_SCALE: u32 = 2;
+const LABEL_PADDING: u32 = 6;
+
+// Where everything goes on the canvas, in output pixels
+#[derive(Clone, Copy)]
+pub struct Layout {
+    columns: u32,
+    rows: u32,
+    cell: (u32, u32),
+    gap: u32,
+    label_height: u32,
+    pub size: (u32, u32),
+}
+
+impl Layout {
+    // Every image at full size: cells are as large as the largest image
+    pub fn new(sizes: &[(u32, u32)], grid: &Grid) -> Layout {
+        let count = (sizes.len() as u32).max(1);
+        let columns = grid.columns.clamp(1, count);
+        let cell_width = sizes.iter().map(|s| s.0).max().unwrap_or(1).max(1);
+        let cell_height = sizes.iter().map(|s| s.1).max().unwrap_or(1).max(1);
+
+        let mut layout = Layout {
+            columns,
+            rows: count.div_ceil(columns),
+            cell: (cell_width, cell_height),
+            gap: grid.gap,
+            label_height: if grid.labels { GLYPH_HEIGHT * LABEL_SCALE + 2 * LABEL_PADDING } else { 0 },
+            size: (0, 0),
+        };
+        layout.size = layout.size_with(layout.cell);
+        layout
+    }
+
+    // Scales the cells so the whole canvas fits the resize settings. Gaps and
+    // labels keep their size, so they come off the limits first.
+    pub fn scaled(&self, resize: &Resize) -> Layout {
+        let extra = self.size_with((0, 0));
+        let content = (self.columns * self.cell.0, self.rows * self.cell.1);
+        let (width, height) = target_dimensions(content.0, content.1, &resize.shrunk(extra));
+        let scale = (width as f64 / content.0 as f64).min(height as f64 / content.1 as f64);
+        let side = |length: u32| ((length as f64 * scale).floor() as u32).max(1);
+        let cell = (side(self.cell.0), side(self.cell.1));
+        Layout { cell, size: self.size_with(cell), ..*self }
+    }
+
+    fn size_with(&self, (cell_width, cell_height): (u32, u32)) -> (u32, u32) {
+        let width = self.columns * cell_width + (self.columns + 1) * self.gap;
+        let height = self.rows * (cell_height + self.label_height) + (self.rows + 1) * self.gap;
+        (width, height)
+    }
+
+    // Size of an image scaled to fit its cell
+    pub fn fit(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        let (cell_width, cell_height) = self.cell;
+        let scale = (cell_width as f64 / width.max(1) as f64).min(cell_height as f64 / height.max(1) as f64);
+        let side = |length: u32, cell: u32| ((length as f64 * scale).round() as u32).clamp(1, cell);
+        (side(width, cell_width), side(height, cell_height))
+    }
+}
+
+// Places tiles (already sized with `Layout::fit`) in reading order, centered
+// in their cells, with a short last row centered as well
+pub fn compose(layout: &Layout, tiles: &[(String, RgbaImage)], background: [u8; 3]) -> RgbaImage {
+    let [r, g, b] = background;
+    let (width, height) = layout.size;
+    let mut canvas = RgbaImage::from_pixel(width, height, Rgba([r, g, b, 255]));
+    let color = label_color(background);
+
+    let (cell_width, cell_height) = layout.cell;
+    let count = tiles.len() as u32;
+    let last_row = count.saturating_sub(1) / layout.columns;
+
+    for (i, (label, tile)) in tiles.iter().enumerate() {
+        let (column, row) = (i as u32 % layout.columns, i as u32 / layout.columns);
+        let in_row = if row == last_row { count - row * layout.columns } else { layout.columns };
+        let indent = (layout.columns - in_row) * (cell_width + layout.gap) / 2;
+        let left = layout.gap + indent + column * (cell_width + layout.gap);
+        let top = layout.gap + row * (cell_height + layout.label_height + layout.gap);
+
+        let x = left + cell_width.saturating_sub(tile.width()) / 2;
+        let y = top + cell_height.saturating_sub(tile.height()) / 2;
+        imageops::overlay(&mut canvas, tile, x as i64, y as i64);
+
+        if layout.label_height > 0 {
+            let label = fit_label(label, cell_width);
+            let x = left + cell_width.saturating_sub(font::text_width(&label, LABEL_SCALE)) / 2;
+            font::draw_text(&mut canvas, x, top + cell_height + LABEL_PADDING, &label, LABEL_SCALE, color);
+        }
+    }
+    canvas
+}
+
+// Shortened with ".." when it is wider than the cell
+fn fit_label(label: &str, width: u32) -> String {
+    let max = ((width + LABEL_SCALE) / (ADVANCE * LABEL_SCALE)) as usize;
+    if label.chars().count() <= max {
+        return label.to_string();
+    }
+    let kept: String = label.chars().take(max.saturating_sub(2)).collect();
+    format!("{}..", kept)
+}
+
+// Dark text on light backgrounds, light text on dark ones
+fn label_color([r, g, b]: [u8; 3]) -> Rgba<u8> {
+    let luma = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+    if luma > 128.0 {
+        Rgba([60, 60, 60, 255])
+    } else {
+        Rgba([220, 220, 220, 255])
+    }
+}
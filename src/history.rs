@@ -0,0 +1,94 @@
+use image::{DynamicImage, ImageFormat};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use crate::clipboard::Flavors;
+use crate::config::Config;
+use crate::deliver::EncodedPng;
+use crate::Result;
+
+// What one job put on the clipboard, kept in memory so it can go back on
+pub enum Clip {
+    Image(DynamicImage),
+    // Passed through unchanged, in its own format
+    Original { bytes: Vec<u8>, format: ImageFormat },
+}
+
+impl Clip {
+    pub fn copy(&self, config: &Config) -> Result<()> {
+        match self {
+            Clip::Image(img) => {
+                let png = EncodedPng::new(img, config)?;
+                Flavors::encode(img, &png.path, config)?.copy()
+            }
+            Clip::Original { bytes, format } => {
+                let path = std::env::temp_dir().join(format!("imgopt-{}-history.{}", std::process::id(), format.extensions_str()[0]));
+                std::fs::write(&path, bytes)?;
+                let copied = Flavors::original(&path, *format, config).copy();
+                std::fs::remove_file(&path).ok();
+                copied
+            }
+        }
+    }
+}
+
+struct Entry {
+    label: String,
+    clip: Clip,
+}
+
+// Numbered hold slots, 1 to SLOTS
+pub const SLOTS: usize = 9;
+
+// The last `history_size` clips of the session, newest first: a paste
+// buffer ring that `[` and `]` in the drop zone walk through, copying the
+// selected clip again. Any clip can also be held in a numbered slot, where
+// it stays when the ring moves on, to be copied again by number.
+#[derive(Default)]
+pub struct History {
+    entries: VecDeque<Rc<Entry>>,
+    selected: usize,
+    held: [Option<Rc<Entry>>; SLOTS],
+}
+
+impl History {
+    // A new clip is what's on the clipboard, so the selection starts over
+    pub fn push(&mut self, label: String, clip: Clip, size: usize) {
+        self.entries.push_front(Rc::new(Entry { label, clip }));
+        self.entries.truncate(size);
+        self.selected = 0;
+    }
+
+    // One clip older (or newer), wrapping around, and copies it. None when
+    // there is nothing else to go to.
+    pub fn step(&mut self, older: bool, config: &Config) -> Option<Result<String>> {
+        let count = self.entries.len();
+        if count < 2 {
+            return None;
+        }
+        self.selected = if older { (self.selected + 1) % count } else { (self.selected + count - 1) % count };
+        let entry = &self.entries[self.selected];
+        let position = format!("{}/{}: {}", self.selected + 1, count, entry.label);
+        Some(entry.clip.copy(config).map(|_| position))
+    }
+
+    // Holds what's on the clipboard (the selected clip) in `slot`, counted
+    // from 1, replacing what was there. None when nothing was copied yet.
+    pub fn hold(&mut self, slot: usize) -> Option<String> {
+        let entry = self.entries.get(self.selected)?.clone();
+        let label = entry.label.clone();
+        self.held[slot - 1] = Some(entry);
+        Some(label)
+    }
+
+    // Copies the clip held in `slot` again. None when the slot is empty.
+    pub fn recall(&self, slot: usize, config: &Config) -> Option<Result<String>> {
+        let entry = self.held[slot - 1].as_ref()?;
+        Some(entry.clip.copy(config).map(|_| entry.label.clone()))
+    }
+
+    // The slots in use, for the drop zone
+    pub fn held(&self) -> Vec<usize> {
+        (1..=SLOTS).filter(|slot| self.held[slot - 1].is_some()).collect()
+    }
+}
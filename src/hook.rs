@@ -0,0 +1,39 @@
+use std::path::Path;
+use std::process::Command;
+
+use crate::{log, Result};
+
+// Runs the configured post-process command through `sh -c`. `{path}` becomes
+// the (quoted) file about to be copied, `{width}` and `{height}` its size.
+// The command may rewrite the file in place.
+pub fn run(command: &str, path: &Path, (width, height): (u32, u32)) -> Result<()> {
+    let command = command
+        .replace("{path}", &shell_quote(&path.to_string_lossy()))
+        .replace("{width}", &width.to_string())
+        .replace("{height}", &height.to_string());
+    log(&format!("Running hook: {}", command));
+
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .output()
+        .map_err(|e| format!("could not run sh: {}", e))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !stdout.trim().is_empty() {
+        log(&format!("Hook output: {}", stdout.trim()));
+    }
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.trim().lines().last() {
+            Some(line) => format!("{} ({})", line, output.status),
+            None => format!("exited with {}", output.status),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
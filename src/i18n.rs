@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::config::Config;
+use crate::log;
+
+// Translations that ship with imgopt, by language code
+const BUNDLED: &[(&str, &str)] = &[("de", include_str!("../locales/de.toml"))];
+
+// Every string the TUI shows, by key. Translations are TOML files with the
+// same keys, as dotted keys or tables; `{name}` placeholders are filled in by
+// trf. A key a translation leaves out shows in English.
+const ENGLISH: &[(&str, &str)] = &[
+    ("app.title", "IMAGE OPTIMIZER"),
+    ("app.goodbye", "Goodbye!"),
+    ("error", "Error: {message}"),
+    ("drop.title", "Drop images here"),
+    ("drop.keys.browse", "o: browse  /: find  r: recent"),
+    ("drop.keys.inspect", "i: inspect first  d: diff two"),
+    ("drop.keys.grid", "g: grid of 2-4  f: favicons"),
+    ("drop.keys.web", "w: web srcset  s: settings"),
+    ("drop.keys.history", "[ ]: history  1-9: slots  e: report"),
+    ("drop.keys.quit", "(ESC or Ctrl+C to quit)"),
+    (
+        "drop.keys.narrow",
+        "(o browse, / find, r recent, i info, d diff, g grid, f icons, w web, s settings, [ ] history, 1-9 slots, e report)",
+    ),
+    ("drop.multiplexer", "Inside {multiplexer} drops may not arrive: o to browse, / to find, or type a path"),
+    ("drop.type_hint", "type or drop a path, then press Enter"),
+    ("mode.inspect", "Next drop opens the inspector (i to cancel)"),
+    ("mode.diff.before", "Diff: drop the before image (d to cancel)"),
+    ("mode.diff.after", "Diff: now drop the image to compare with {name} (d to cancel)"),
+    ("mode.grid.start", "Grid: drop {min} to {max} images (g to cancel)"),
+    ("mode.grid.more", "Grid: {count} images, drop up to {more} more or press Enter (g to cancel)"),
+    ("mode.favicon", "Favicons: drop a square logo (f to cancel)"),
+    ("mode.srcset", "Srcset: drop an image for the web (w to cancel)"),
+    ("job.diff", "{before} vs {after}"),
+    ("job.grid", "grid of {names}"),
+    ("job.favicon", "favicons from {name}"),
+    ("job.srcset", "srcset from {name}"),
+    ("job.page", "{name}, page {page}"),
+    ("history.empty", "Nothing else in the clipboard history yet"),
+    ("history.copied", "Copied {position}"),
+    ("history.failed", "Copy failed: {error}"),
+    ("slots.held", "Holding {label} in slot {slot}"),
+    ("slots.nothing", "Nothing copied yet to hold"),
+    ("slots.copied", "Copied slot {slot}: {label}"),
+    ("slots.empty", "Slot {slot} is empty, Shift+{slot} holds what's on the clipboard"),
+    ("slots.notice", "Held in slots {slots}, press a number to copy it again"),
+    ("report.empty", "Nothing to report yet"),
+    ("report.saved", "Session report saved to {path}"),
+    ("report.failed", "Session report failed: {error}"),
+    ("config.reloaded", "Config reloaded"),
+    ("config.failed", "Config not reloaded: {error}"),
+    ("reveal.shown", "Shown in {app}"),
+    ("reveal.failed", "Could not show it in the {app}: {error}"),
+    ("reveal.file_manager", "file manager"),
+    ("paste.fetching", "Fetching the pasted image from {host}..."),
+    ("paste.embedded", "Reading the image in the paste..."),
+    ("paste.failed", "Could not get the pasted image: {error}"),
+    ("paste.no_image", "The paste is HTML or RTF with no image in it"),
+    ("queue.added", "Queued {name} ({count} waiting)"),
+    ("queue.rejected", "Not queued, not an image: {path}"),
+    ("processing.title", "Processing image..."),
+    ("processing.queued", "{count} more queued (Esc skips to the next, Ctrl+C cancels all)"),
+    ("processing.cancel", "(Esc to cancel)"),
+    ("stage.downloading", "Downloading from the cloud"),
+    ("stage.decoding", "Decoding"),
+    ("stage.reading", "Checking legibility"),
+    ("stage.denoising", "Reducing noise"),
+    ("stage.resizing", "Resizing"),
+    ("stage.encoding", "Encoding"),
+    ("stage.post_process", "Running post-process hook"),
+    ("stage.uploading", "Uploading"),
+    ("stage.saving", "Saving"),
+    ("stage.clipboard", "Copying to clipboard"),
+    ("result.not_found", "File not found"),
+    ("result.cancelled", "Cancelled"),
+    ("result.too_large", "Image too large"),
+    ("result.transform_failed", "Transform failed"),
+    ("result.unsupported", "Unsupported format"),
+    ("result.output_failed", "Could not write output"),
+    ("result.unreadable", "Could not open image"),
+    ("result.diff", "Diff: {similarity}% similar, {regions} changed regions"),
+    ("result.grid", "Grid of {count} images"),
+    ("result.cached", "Already optimized (cache hit)"),
+    ("result.passed_through", "Already within bounds, copied unchanged"),
+    ("result.auto_text", "Looks like text, kept large and sharp"),
+    ("result.auto_photo", "Looks like a photo"),
+    ("result.auto_graphic", "Looks like a graphic"),
+    ("result.original", "Original: {width}x{height}px{bytes}"),
+    ("result.optimized", "Optimized: {width}x{height}px{bytes}"),
+    ("result.uploaded", "Uploaded, {copied} copied!"),
+    ("result.upload_copy_failed", "Uploaded, but copying the {copied} failed: {error}"),
+    ("result.copied_url", "URL"),
+    ("result.copied_qr", "QR code"),
+    ("result.ready", "Ready to paste into Claude..."),
+    ("result.any_key", "Press any key to continue"),
+    ("result.saved_to", "Saved to:"),
+    ("result.stdout", "Written to standard output"),
+    ("result.saved_files", "Saved {count} files to:"),
+    ("result.snippet_copied", "HTML snippet copied, ready to paste"),
+    ("result.snippet_failed", "Copying the HTML snippet failed: {error}"),
+    ("result.copied", "Copied to clipboard!"),
+    ("result.enlarge_qr", "Enlarge the terminal to see the QR code"),
+    ("result.reveal", "o: show in {app}"),
+    ("result.copy_palette", "c: copy palette as CSS variables"),
+    ("result.palette_copied", "Palette copied as CSS variables"),
+    ("result.palette_failed", "Palette copy failed: {error}"),
+    ("result.clipboard_failed", "Clipboard copy failed"),
+    ("result.fallback_saved", "Saved optimized image to:"),
+    ("result.fallback_failed", "Could not save a fallback file either"),
+    ("result.fallback_hint", "o: show in {app}, any other key to continue"),
+    ("transfer.typical", "Upload: {times}"),
+    ("transfer.at", "Upload at {mbps} Mbit/s: {times}"),
+    ("browser.empty", "(empty directory)"),
+    ("browser.footer", "↑↓ move  ←/Backspace up  Enter open  i info  Esc cancel"),
+    ("browser.footer_ascii", "Up/Down move  Left/Backspace up  Enter open  i info  Esc cancel"),
+    ("finder.root", "in {dir}"),
+    ("finder.empty", "(no matching images)"),
+    ("finder.footer", "type to search  ↑↓ select  Enter optimize  Tab switch folder  Esc cancel"),
+    ("finder.footer_ascii", "type to search  Up/Down select  Enter optimize  Tab switch folder  Esc cancel"),
+    ("recent.title", "Recent screenshots"),
+    ("recent.empty", "(no images found)"),
+    ("recent.footer", "1-9 optimize  ↑↓ + Enter select  i info  Esc back"),
+    ("recent.footer_ascii", "1-9 optimize  Up/Down + Enter select  i info  Esc back"),
+    ("pages.title", "Pick a page"),
+    ("pages.count", "{name} has {count} pages"),
+    ("pages.item", "Page {page}  {width}x{height}"),
+    ("pages.footer", "↑↓ or number select  Enter optimize  Esc back"),
+    ("pages.footer_ascii", "Up/Down or number select  Enter optimize  Esc back"),
+    ("age.now", "just now"),
+    ("age.minutes", "{count}m ago"),
+    ("age.hours", "{count}h ago"),
+    ("age.days", "{count}d ago"),
+    ("inspect.unreadable", "Could not read image"),
+    ("inspect.footer", "Enter optimize  Esc back"),
+    ("inspect.file", "File"),
+    ("inspect.size", "Size"),
+    ("inspect.format", "Format"),
+    ("inspect.dimensions", "Dimensions"),
+    ("inspect.color", "Color"),
+    ("inspect.decoded", "Decoded"),
+    ("inspect.profile", "Profile"),
+    ("inspect.density", "Density"),
+    ("inspect.camera", "Camera"),
+    ("inspect.lens", "Lens"),
+    ("inspect.taken", "Taken"),
+    ("inspect.exposure", "Exposure"),
+    ("inspect.orientation", "Orientation"),
+    ("inspect.software", "Software"),
+    ("inspect.location", "Location"),
+    ("inspect.raw", "camera RAW, using its embedded JPEG"),
+    ("inspect.psd", "Photoshop document, using its flattened copy"),
+    ("inspect.unknown", "unknown"),
+    ("inspect.megapixels", "{width}x{height} ({megapixels} megapixels)"),
+    ("inspect.channels", "{color}, {channels} channels at {bits} bits"),
+    ("inspect.in_memory", "{size} in memory"),
+    ("inspect.icc", "embedded ICC profile ({size})"),
+    ("inspect.no_profile", "none (assumed sRGB)"),
+    ("inspect.gps", "GPS coordinates embedded"),
+    ("check.name.clipboard", "Clipboard"),
+    ("check.name.drops", "Drops"),
+    ("check.name.graphics", "Graphics"),
+    ("check.name.display", "Display"),
+    ("check.clipboard.missing", "{backend}, but {program} is not installed"),
+    ("check.clipboard.install", "Install {package}, or copy through the terminal with OSC 52 if it supports that"),
+    ("check.clipboard.osc52", "Copies can go through the terminal instead, with clipboard = \"osc52\""),
+    ("check.drops.paste", "bracketed paste"),
+    ("check.drops.keys", "no bracketed paste, so drops arrive as typed keys"),
+    ("check.drops.multiplexer", "inside {multiplexer}, drops may arrive as typed keys or not at all"),
+    ("check.drops.pickers", "If they don't, type a path, or press o to browse and / to find images"),
+    ("check.graphics.none", "none detected (imgopt works without it)"),
+    ("check.display.unknown", "scale not detected"),
+    ("check.display.retina", "HiDPI screenshots stay at twice their size; set retina = true under [resize] to halve them"),
+    ("setup.title", "Welcome to imgopt"),
+    ("setup.subtitle", "A quick look at this terminal, then a few settings for your first config"),
+    ("setup.try_drop", "Try it: drop an image on this window"),
+    ("setup.dropped", "Drop received ({name}), drops work here"),
+    ("setup.clipboard", "Clipboard"),
+    ("setup.auto", "automatic"),
+    ("setup.osc52", "OSC 52, through the terminal"),
+    ("setup.drawing", "Drawing"),
+    ("setup.emoji", "emoji and box lines"),
+    ("setup.ascii", "ASCII only"),
+    ("setup.notify", "When done"),
+    ("setup.notify_off", "nothing"),
+    ("setup.notify_bell", "terminal bell"),
+    ("setup.notify_sound", "sound"),
+    ("setup.start", "On start"),
+    ("setup.start_drop", "drop zone"),
+    ("setup.start_recent", "recent screenshots"),
+    ("setup.hidpi", "Screenshots"),
+    ("setup.hidpi_halve", "halve HiDPI captures to logical pixels"),
+    ("setup.hidpi_keep", "keep every pixel"),
+    ("setup.saved", "Settings saved to {path}"),
+    ("setup.footer", "↑↓ select  ←→ change  Enter save  Esc skip for now"),
+    ("setup.footer_ascii", "Up/Down select  Left/Right change  Enter save  Esc skip for now"),
+    ("settings.title", "Adjustments"),
+    ("settings.scope", "until the config is reloaded"),
+    ("settings.scope_preset", "for preset {name}, until the config is reloaded"),
+    ("settings.grayscale", "Grayscale"),
+    ("settings.brightness", "Brightness"),
+    ("settings.contrast", "Contrast"),
+    ("settings.saturation", "Saturation"),
+    ("settings.quality", "Quality"),
+    ("settings.max_dimension", "Max size"),
+    ("settings.on", "on"),
+    ("settings.off", "off"),
+    ("settings.reset", "Reset all"),
+    ("settings.preview", "{name}: {width}x{height}, {size} (from {original})"),
+    ("settings.preview_ssim", ", SSIM {ssim}"),
+    ("settings.preview_rendering", "{name}: rendering…"),
+    ("settings.preview_failed", "{name}: {error}"),
+    ("settings.preview_none", "Optimize an image first to see the result of changes here"),
+    ("settings.footer", "↑↓ select  ←→ change  0 reset  Esc back"),
+    ("settings.footer_ascii", "Up/Down select  Left/Right change  0 reset  Esc back"),
+];
+
+// Set once at startup; until then, and outside the TUI, everything is English
+static CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+// Loads the translation for `language`, or for the system locale when it is
+// unset: the bundled one if there is one, with a user file from
+// ~/.config/imgopt/locales/<language>.toml on top
+pub fn init(language: Option<&str>) {
+    let language = language.map(str::to_lowercase).unwrap_or_else(detect);
+    let mut catalog = HashMap::new();
+    if let Some((_, text)) = BUNDLED.iter().find(|(code, _)| *code == language) {
+        // Bundled files are checked by hand; a broken one shouldn't stop the TUI
+        match parse(text) {
+            Ok(entries) => catalog.extend(entries),
+            Err(e) => log(&format!("Bundled {} translation is invalid: {}", language, e)),
+        }
+    }
+    if let Some(path) = user_file(&language) {
+        match std::fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|text| parse(&text)) {
+            Ok(entries) => {
+                log(&format!("Loaded {} translations from {}", entries.len(), path.display()));
+                catalog.extend(entries);
+            }
+            Err(e) => log(&format!("Ignoring {}: {}", path.display(), e)),
+        }
+    }
+    let unknown: Vec<&String> = catalog.keys().filter(|key| english(key).is_none()).collect();
+    if !unknown.is_empty() {
+        log(&format!("Unknown translation keys for {}: {:?}", language, unknown));
+    }
+    if catalog.is_empty() && language != "en" {
+        log(&format!("No translation for {}, using English", language));
+    }
+    let _ = CATALOG.set(catalog);
+}
+
+// The translated text for `key`, falling back to English
+pub fn tr(key: &'static str) -> &'static str {
+    CATALOG
+        .get()
+        .and_then(|catalog| catalog.get(key))
+        .map(String::as_str)
+        .or_else(|| english(key))
+        .unwrap_or(key)
+}
+
+// tr with each `{name}` replaced by its value
+pub fn trf(key: &'static str, args: &[(&str, &dyn Display)]) -> String {
+    args.iter().fold(tr(key).to_string(), |text, (name, value)| text.replace(&format!("{{{}}}", name), &value.to_string()))
+}
+
+fn english(key: &str) -> Option<&'static str> {
+    ENGLISH.iter().find(|(k, _)| *k == key).map(|(_, text)| *text)
+}
+
+// "de_DE.UTF-8" -> "de"; the C and POSIX locales mean English
+fn detect() -> String {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    let language = locale.split(['_', '.', '@', '-']).next().unwrap_or_default().to_lowercase();
+    if language.is_empty() || language == "c" || language == "posix" {
+        "en".to_string()
+    } else {
+        language
+    }
+}
+
+fn user_file(language: &str) -> Option<PathBuf> {
+    let path = Config::path()?.parent()?.join("locales").join(format!("{}.toml", language));
+    path.is_file().then_some(path)
+}
+
+// Nested tables become dotted keys, so `[result]` plus `saved_to = "..."` is "result.saved_to"
+fn parse(text: &str) -> Result<Vec<(String, String)>, String> {
+    fn flatten(prefix: &str, table: &toml::Table, entries: &mut Vec<(String, String)>) -> Result<(), String> {
+        for (name, value) in table {
+            let key = if prefix.is_empty() { name.clone() } else { format!("{}.{}", prefix, name) };
+            match value {
+                toml::Value::String(text) => entries.push((key, text.clone())),
+                toml::Value::Table(table) => flatten(&key, table, entries)?,
+                _ => return Err(format!("{} must be a string", key)),
+            }
+        }
+        Ok(())
+    }
+    let table: toml::Table = toml::from_str(text).map_err(|e| e.to_string())?;
+    let mut entries = Vec::new();
+    flatten("", &table, &mut entries)?;
+    Ok(entries)
+}
@@ -0,0 +1,108 @@
+use image::{ImageDecoder, ImageReader};
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::OnceLock;
+
+static SRGB: OnceLock<Vec<u8>> = OnceLock::new();
+
+// Points on the sRGB tone curve; the profile stays under 1 KB
+const CURVE_POINTS: usize = 256;
+// D50, the profile connection space's white, and the sRGB primaries adapted to it (Bradford)
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+const RED: [f64; 3] = [0.4361, 0.2225, 0.0139];
+const GREEN: [f64; 3] = [0.3851, 0.7169, 0.0971];
+const BLUE: [f64; 3] = [0.1431, 0.0606, 0.7141];
+
+// The color profile embedded in PNG, JPEG and WebP output. Untagged images
+// are meant to be sRGB, but some viewers and print services guess otherwise.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorProfile {
+    #[default]
+    None,
+    // Tags every output as sRGB, which is what the pixels are taken for anyway
+    Srgb,
+    // Carries the input's own profile over, so Display P3 screenshots and the
+    // like keep their colors; untagged inputs stay untagged
+    Source,
+}
+
+impl ColorProfile {
+    pub fn parse(name: &str) -> Option<ColorProfile> {
+        match name {
+            "none" => Some(ColorProfile::None),
+            "srgb" => Some(ColorProfile::Srgb),
+            "source" => Some(ColorProfile::Source),
+            _ => None,
+        }
+    }
+}
+
+// The profile embedded in `path`, if its format has one and it can be read
+pub fn read(path: &Path) -> Option<Vec<u8>> {
+    let mut decoder = ImageReader::open(path).ok()?.with_guessed_format().ok()?.into_decoder().ok()?;
+    decoder.icc_profile().ok().flatten().filter(|profile| !profile.is_empty())
+}
+
+// A compact ICC v2 display profile for sRGB: its primaries, D50 white and
+// the sRGB tone curve sampled at CURVE_POINTS points
+pub fn srgb() -> &'static [u8] {
+    SRGB.get_or_init(|| {
+        let curve: Vec<u8> = (0..CURVE_POINTS)
+            .flat_map(|i| {
+                let v = i as f64 / (CURVE_POINTS - 1) as f64;
+                let linear = if v <= 0.04045 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) };
+                ((linear * 65535.0).round() as u16).to_be_bytes()
+            })
+            .collect();
+        let curve = [b"curv".as_slice(), &[0; 4], &(CURVE_POINTS as u32).to_be_bytes(), &curve].concat();
+        let copyright = [b"text".as_slice(), &[0; 4], b"No copyright, use freely\0"].concat();
+
+        let bodies = [text_description("sRGB"), copyright, xyz(D50), xyz(RED), xyz(GREEN), xyz(BLUE), curve];
+        // The three tone curves share one copy
+        let tags: [(&[u8; 4], usize); 9] =
+            [(b"desc", 0), (b"cprt", 1), (b"wtpt", 2), (b"rXYZ", 3), (b"gXYZ", 4), (b"bXYZ", 5), (b"rTRC", 6), (b"gTRC", 6), (b"bTRC", 6)];
+        let start = 128 + 4 + 12 * tags.len();
+        let mut data = Vec::new();
+        let mut offsets = Vec::new();
+        for body in &bodies {
+            data.resize(data.len().next_multiple_of(4), 0);
+            offsets.push(start + data.len());
+            data.extend_from_slice(body);
+        }
+        let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+        for (signature, body) in tags {
+            table.extend_from_slice(signature);
+            table.extend_from_slice(&(offsets[body] as u32).to_be_bytes());
+            table.extend_from_slice(&(bodies[body].len() as u32).to_be_bytes());
+        }
+
+        let size = (start + data.len()) as u32;
+        let mut header = Vec::with_capacity(128);
+        header.extend_from_slice(&size.to_be_bytes());
+        header.extend_from_slice(&[0; 4]);
+        header.extend_from_slice(&[2, 0x10, 0, 0]);
+        header.extend_from_slice(b"mntrRGB XYZ ");
+        // 2024-01-01 00:00:00
+        for field in [2024u16, 1, 1, 0, 0, 0] {
+            header.extend_from_slice(&field.to_be_bytes());
+        }
+        header.extend_from_slice(b"acsp");
+        header.extend_from_slice(&[0; 28]);
+        header.extend_from_slice(&xyz(D50)[8..]);
+        header.resize(128, 0);
+        [header, table, data].concat()
+    })
+}
+
+// An XYZType tag: signature, reserved, then X, Y and Z as s15Fixed16
+fn xyz(values: [f64; 3]) -> Vec<u8> {
+    let numbers = values.iter().flat_map(|v| ((v * 65536.0).round() as i32).to_be_bytes());
+    [b"XYZ ".as_slice(), &[0; 4]].concat().into_iter().chain(numbers).collect()
+}
+
+// A v2 textDescriptionType with the ASCII name only
+fn text_description(name: &str) -> Vec<u8> {
+    let ascii = [name.as_bytes(), &[0]].concat();
+    [b"desc".as_slice(), &[0; 4], &(ascii.len() as u32).to_be_bytes(), &ascii, &[0; 4 + 4 + 2 + 1 + 67]].concat()
+}
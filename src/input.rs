@@ -0,0 +1,218 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::paths::parse_dropped_path;
+
+// Collects a drop that the terminal hands over in pieces: several pastes in a
+// row, sometimes with the tail end as keystrokes. Nothing comes out until it
+// has been quiet for the debounce window.
+#[derive(Default)]
+pub struct PasteBuffer {
+    text: String,
+    chunks: usize,
+    last_at: Option<Instant>,
+}
+
+impl PasteBuffer {
+    pub fn push(&mut self, chunk: &str) {
+        self.text.push_str(chunk);
+        self.chunks += 1;
+        self.last_at = Some(Instant::now());
+    }
+
+    // Whether a piece arriving now still belongs to the same drop
+    pub fn is_open(&self, window: Duration) -> bool {
+        self.last_at.is_some_and(|at| at.elapsed() < window)
+    }
+
+    // How long until the buffered text counts as complete
+    pub fn remaining(&self, window: Duration) -> Option<Duration> {
+        self.last_at.map(|at| window.saturating_sub(at.elapsed()))
+    }
+
+    // The whole drop and how many pieces it came in, once it has settled
+    pub fn settled(&mut self, window: Duration) -> Option<(String, usize)> {
+        if self.last_at.is_some_and(|at| at.elapsed() >= window) {
+            self.take()
+        } else {
+            None
+        }
+    }
+
+    pub fn take(&mut self) -> Option<(String, usize)> {
+        self.last_at.take()?;
+        Some((std::mem::take(&mut self.text), std::mem::replace(&mut self.chunks, 0)))
+    }
+}
+
+// Single-line editor for typed or dropped paths. The cursor is a char index.
+#[derive(Default)]
+pub struct InputLine {
+    text: String,
+    cursor: usize,
+}
+
+fn is_separator(c: char) -> bool {
+    c == '/' || c.is_whitespace()
+}
+
+impl InputLine {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+    }
+
+    fn byte_index(&self, char_index: usize) -> usize {
+        self.text.char_indices().nth(char_index).map(|(i, _)| i).unwrap_or(self.text.len())
+    }
+
+    fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    pub fn insert(&mut self, c: char) {
+        let at = self.byte_index(self.cursor);
+        self.text.insert(at, c);
+        self.cursor += 1;
+    }
+
+    fn delete_range(&mut self, from: usize, to: usize) {
+        let (start, end) = (self.byte_index(from), self.byte_index(to));
+        self.text.replace_range(start..end, "");
+        self.cursor = from;
+    }
+
+    fn word_start(&self) -> usize {
+        let chars: Vec<char> = self.text.chars().collect();
+        let mut i = self.cursor;
+        while i > 0 && is_separator(chars[i - 1]) {
+            i -= 1;
+        }
+        while i > 0 && !is_separator(chars[i - 1]) {
+            i -= 1;
+        }
+        i
+    }
+
+    fn word_end(&self) -> usize {
+        let chars: Vec<char> = self.text.chars().collect();
+        let mut i = self.cursor;
+        while i < chars.len() && is_separator(chars[i]) {
+            i += 1;
+        }
+        while i < chars.len() && !is_separator(chars[i]) {
+            i += 1;
+        }
+        i
+    }
+
+    // Applies an editing key; returns false for keys the line doesn't handle
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+
+        match key.code {
+            KeyCode::Left if ctrl || alt => self.cursor = self.word_start(),
+            KeyCode::Right if ctrl || alt => self.cursor = self.word_end(),
+            KeyCode::Char('b') if alt => self.cursor = self.word_start(),
+            KeyCode::Char('f') if alt => self.cursor = self.word_end(),
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.len(),
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::Char('e') if ctrl => self.cursor = self.len(),
+            KeyCode::Char('w') if ctrl => self.delete_range(self.word_start(), self.cursor),
+            KeyCode::Backspace if alt || ctrl => self.delete_range(self.word_start(), self.cursor),
+            KeyCode::Char('u') if ctrl => self.delete_range(0, self.cursor),
+            KeyCode::Char('k') if ctrl => {
+                let cursor = self.cursor;
+                self.delete_range(cursor, self.len());
+                self.cursor = cursor;
+            }
+            KeyCode::Backspace if self.cursor > 0 => self.delete_range(self.cursor - 1, self.cursor),
+            KeyCode::Backspace => {}
+            KeyCode::Delete if self.cursor < self.len() => {
+                let cursor = self.cursor;
+                self.delete_range(cursor, cursor + 1);
+            }
+            KeyCode::Delete => {}
+            KeyCode::Tab => self.complete(),
+            KeyCode::Char(c) if !ctrl && !alt => self.insert(c),
+            _ => return false,
+        }
+        true
+    }
+
+    // Completes the file name under the cursor to the longest unambiguous prefix
+    fn complete(&mut self) {
+        if self.cursor != self.len() || self.text.trim().is_empty() {
+            return;
+        }
+
+        // Nothing sensible to do once a quoted path has been closed
+        let trimmed = self.text.trim();
+        if trimmed.len() > 1 && trimmed.starts_with(['\'', '"']) && trimmed.ends_with(['\'', '"']) {
+            return;
+        }
+
+        let typed = parse_dropped_path(&self.text);
+        let typed_str = typed.to_string_lossy();
+        let (dir, prefix) = if typed_str.ends_with('/') {
+            (typed.clone(), String::new())
+        } else {
+            let dir = typed.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            let prefix = typed.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            (dir.to_path_buf(), prefix)
+        };
+
+        let Ok(read) = std::fs::read_dir(&dir) else {
+            return;
+        };
+        let candidates: Vec<(String, bool)> = read
+            .filter_map(|e| e.ok())
+            .map(|e| (e.file_name().to_string_lossy().into_owned(), e.path().is_dir()))
+            .filter(|(name, _)| name.starts_with(&prefix) && (prefix.starts_with('.') || !name.starts_with('.')))
+            .collect();
+
+        let completion = match candidates.as_slice() {
+            [] => return,
+            [(name, is_dir)] => {
+                let mut rest = name[prefix.len()..].to_string();
+                if *is_dir {
+                    rest.push('/');
+                }
+                rest
+            }
+            [(first, _), others @ ..] => {
+                let common = others.iter().fold(first.chars().count(), |len, (name, _)| {
+                    first.chars().zip(name.chars()).take(len).take_while(|(a, b)| a == b).count()
+                });
+                let end = first.char_indices().nth(common).map(|(i, _)| i).unwrap_or(first.len());
+                first[prefix.len().min(end)..end].to_string()
+            }
+        };
+
+        // Quoted input takes the completion verbatim; bare input needs escaping
+        let quoted = self.text.trim_start().starts_with(['\'', '"']);
+        for c in completion.chars() {
+            if !quoted && (c.is_whitespace() || "\\'\"()&;$`".contains(c)) {
+                self.insert('\\');
+            }
+            self.insert(c);
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use crossterm::{
+    cursor,
+    event::{KeyCode, KeyEvent},
+    queue,
+    style::{Print, ResetColor, SetForegroundColor},
+    terminal,
+};
+use exif::{In, Tag};
+use image::{ImageDecoder, ImageReader};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::dpi::read_dpi;
+use crate::i18n::{tr, trf};
+use crate::optimize::format_bytes;
+use crate::psd;
+use crate::raw::{self, is_raw};
+use crate::theme::Theme;
+use crate::ui::{draw_footer, error_title, pad, print_centered, truncate, Action};
+use crate::Result;
+
+const LABEL_WIDTH: usize = 14;
+
+// What the file is and why it is as big as it is, shown before optimizing
+pub struct Inspector {
+    path: PathBuf,
+    details: std::result::Result<Vec<(String, String)>, String>,
+}
+
+impl Inspector {
+    pub fn new(path: PathBuf) -> Inspector {
+        let details = inspect(&path).map_err(|e| e.to_string());
+        Inspector { path, details }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Backspace => Action::Back,
+            KeyCode::Enter if self.details.is_ok() => Action::Open(self.path.clone()),
+            _ => Action::None,
+        }
+    }
+
+    pub fn render(&self, stdout: &mut io::Stdout, theme: &Theme) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let max_len = width.saturating_sub(4) as usize;
+
+        let name = self.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        queue!(
+            stdout,
+            cursor::MoveTo(1, 0),
+            SetForegroundColor(theme.title),
+            Print(truncate(&format!("{}{}", theme.pick("🔎 ", ""), name), max_len)),
+            ResetColor,
+        )?;
+
+        match &self.details {
+            Ok(details) => {
+                let value_len = max_len.saturating_sub(LABEL_WIDTH);
+                for (i, (label, value)) in details.iter().enumerate().take(height.saturating_sub(4) as usize) {
+                    queue!(
+                        stdout,
+                        cursor::MoveTo(2, 2 + i as u16),
+                        SetForegroundColor(theme.muted),
+                        Print(pad(label, LABEL_WIDTH)),
+                        SetForegroundColor(theme.text),
+                        Print(truncate(value, value_len)),
+                        ResetColor,
+                    )?;
+                }
+            }
+            Err(e) => {
+                print_centered(stdout, height / 2, theme.error, &error_title(theme, tr("inspect.unreadable")))?;
+                print_centered(stdout, height / 2 + 2, theme.error_dim, &truncate(e, max_len))?;
+            }
+        }
+
+        draw_footer(stdout, theme, tr("inspect.footer"))
+    }
+}
+
+// Reads headers and metadata only; the pixels are never decoded
+fn inspect(path: &Path) -> Result<Vec<(String, String)>> {
+    let mut details = Vec::new();
+    let mut add = |label: &str, value: String| details.push((label.to_string(), value));
+
+    add(tr("inspect.file"), path.display().to_string());
+    add(tr("inspect.size"), format_bytes(std::fs::metadata(path)?.len()));
+
+    // TIFF-based RAW files read as a tiny TIFF thumbnail; what counts is the preview
+    if is_raw(path) {
+        let preview = raw::preview(path)?;
+        add(tr("inspect.format"), tr("inspect.raw").to_string());
+        add(tr("inspect.dimensions"), dimensions(preview.width, preview.height));
+        for (label, value) in exif_highlights(std::fs::read(path)?) {
+            add(label, value);
+        }
+        return Ok(details);
+    }
+    if psd::is_psd(path) {
+        let (width, height) = psd::dimensions(path)?;
+        add(tr("inspect.format"), tr("inspect.psd").to_string());
+        add(tr("inspect.dimensions"), dimensions(width, height));
+        return Ok(details);
+    }
+
+    let reader = ImageReader::open(path)?.with_guessed_format()?;
+    let format = reader.format().map(|f| format!("{:?}", f).to_uppercase()).unwrap_or_else(|| tr("inspect.unknown").into());
+    add(tr("inspect.format"), format);
+
+    let mut decoder = reader.into_decoder()?;
+    let (width, height) = decoder.dimensions();
+    let pixels = width as u64 * height as u64;
+    add(tr("inspect.dimensions"), dimensions(width, height));
+
+    let original = decoder.original_color_type();
+    let bits = original.bits_per_pixel() / original.channel_count().max(1) as u16;
+    let color = format!("{:?}", original);
+    add(tr("inspect.color"), trf("inspect.channels", &[("color", &color), ("channels", &original.channel_count()), ("bits", &bits)]));
+    let decoded = pixels * decoder.color_type().bytes_per_pixel() as u64;
+    add(tr("inspect.decoded"), trf("inspect.in_memory", &[("size", &format_bytes(decoded))]));
+
+    let profile = match decoder.icc_profile().ok().flatten() {
+        Some(icc) => icc_description(&icc).unwrap_or_else(|| trf("inspect.icc", &[("size", &format_bytes(icc.len() as u64))])),
+        None => tr("inspect.no_profile").to_string(),
+    };
+    add(tr("inspect.profile"), profile);
+
+    if let Some(dpi) = read_dpi(path) {
+        add(tr("inspect.density"), format!("{:.0} DPI", dpi));
+    }
+
+    if let Some(raw) = decoder.exif_metadata().ok().flatten() {
+        for (label, value) in exif_highlights(raw) {
+            add(label, value);
+        }
+    }
+
+    Ok(details)
+}
+
+// e.g. "4032x3024 (12.2 megapixels)"
+fn dimensions(width: u32, height: u32) -> String {
+    let megapixels = format!("{:.1}", (width as u64 * height as u64) as f64 / 1e6);
+    trf("inspect.megapixels", &[("width", &width), ("height", &height), ("megapixels", &megapixels)])
+}
+
+// The handful of EXIF fields that explain where a photo came from
+fn exif_highlights(raw: Vec<u8>) -> Vec<(&'static str, String)> {
+    let Ok(exif) = exif::Reader::new().read_raw(raw) else {
+        return Vec::new();
+    };
+    let field = |tag| exif.get_field(tag, In::PRIMARY).map(|f| f.display_value().with_unit(&exif).to_string());
+
+    let mut highlights = Vec::new();
+    let camera: Vec<String> = [Tag::Make, Tag::Model].into_iter().filter_map(field).map(|v| v.replace('"', "")).collect();
+    if !camera.is_empty() {
+        highlights.push((tr("inspect.camera"), camera.join(" ")));
+    }
+    if let Some(lens) = field(Tag::LensModel) {
+        highlights.push((tr("inspect.lens"), lens.replace('"', "")));
+    }
+    if let Some(taken) = field(Tag::DateTimeOriginal) {
+        highlights.push((tr("inspect.taken"), taken));
+    }
+    let exposure: Vec<String> = [Tag::ExposureTime, Tag::FNumber, Tag::PhotographicSensitivity, Tag::FocalLength]
+        .into_iter()
+        .filter_map(field)
+        .collect();
+    if !exposure.is_empty() {
+        highlights.push((tr("inspect.exposure"), exposure.join("  ")));
+    }
+    if let Some(orientation) = field(Tag::Orientation) {
+        highlights.push((tr("inspect.orientation"), orientation));
+    }
+    if let Some(software) = field(Tag::Software) {
+        highlights.push((tr("inspect.software"), software.replace('"', "")));
+    }
+    if exif.get_field(Tag::GPSLatitude, In::PRIMARY).is_some() {
+        highlights.push((tr("inspect.location"), tr("inspect.gps").to_string()));
+    }
+    highlights
+}
+
+// The profile's 'desc' tag, either an ICC v2 textDescriptionType or a v4
+// multiLocalizedUnicodeType (first record)
+fn icc_description(icc: &[u8]) -> Option<String> {
+    let be32 = |at: usize| icc.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize);
+
+    let count = be32(128)?;
+    let (offset, size) = (0..count).find_map(|i| {
+        let entry = 132 + i * 12;
+        (icc.get(entry..entry + 4)? == b"desc").then(|| Some((be32(entry + 4)?, be32(entry + 8)?)))?
+    })?;
+    let tag = icc.get(offset..offset + size)?;
+
+    let text = match tag.get(0..4)? {
+        b"desc" => {
+            let len = u32::from_be_bytes(tag.get(8..12)?.try_into().ok()?) as usize;
+            String::from_utf8_lossy(tag.get(12..12 + len)?).into_owned()
+        }
+        b"mluc" => {
+            let len = u32::from_be_bytes(tag.get(20..24)?.try_into().ok()?) as usize;
+            let start = u32::from_be_bytes(tag.get(24..28)?.try_into().ok()?) as usize;
+            let units: Vec<u16> = tag.get(start..start + len)?.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => return None,
+    };
+    let text = text.trim_end_matches('\0').trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
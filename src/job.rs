@@ -0,0 +1,170 @@
+use image::{ImageFormat, ImageReader};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, Passthrough, Resize};
+use crate::deliver::Destination;
+use crate::dpi::is_retina;
+use crate::exit::Exit;
+use crate::frame::Frame;
+use crate::icc::ColorProfile;
+use crate::optimize::{output_dimensions, reduction_factor, OptimizeError};
+use crate::orient;
+use crate::pages;
+use crate::paths::resolve;
+use crate::psd;
+use crate::raw;
+use crate::video;
+
+// What imgopt knows about an input before decoding it, read from the file
+// once. Everything decided from here on is a pure function of this and the
+// config, so it can be checked without pixels, a terminal or a clipboard.
+pub struct ImageJob {
+    pub path: PathBuf,
+    // None for camera RAW, Photoshop and PDF files, which have decoders of their own
+    pub format: Option<ImageFormat>,
+    pub bytes: u64,
+    // As stored, before the EXIF orientation is applied
+    pub stored: (u32, u32),
+    pub sideways: bool,
+    pub retina: bool,
+    // More than 1 for multi-page TIFFs and PDFs; `stored` is the chosen page's
+    pub pages: usize,
+}
+
+// What optimize will do with an ImageJob
+#[derive(Debug, PartialEq)]
+pub struct Plan {
+    // As shown, after the EXIF orientation
+    pub original: (u32, u32),
+    pub target: (u32, u32),
+    // 2 or more when the decoder can skip pixels (see large.rs)
+    pub reduction: u32,
+    // Set when the file can go out unchanged, in this format
+    pub passthrough: Option<ImageFormat>,
+}
+
+impl ImageJob {
+    // Links and aliases are followed, so the job is about the file they lead to.
+    // `page` is as for optimize.
+    pub fn probe(path: &Path, page: Option<usize>) -> std::result::Result<ImageJob, OptimizeError> {
+        let path = &resolve(path).map_err(OptimizeError::Unreadable)?;
+        let unreadable = |e: &dyn std::fmt::Display| OptimizeError::Unreadable(e.to_string());
+        let bytes = std::fs::metadata(path).map_err(|_| OptimizeError::NotFound)?.len();
+        let sizes = pages::read_sizes(path).map_err(OptimizeError::Unreadable)?;
+        let (format, stored) = if pages::is_pdf(path) {
+            (None, pages::size_of(&sizes, page.unwrap_or(1)).map_err(OptimizeError::Unreadable)?)
+        } else if video::is_video(path) {
+            (None, video::probe(path).map_err(OptimizeError::Unreadable)?.0)
+        } else if let Some(page) = page.filter(|&page| page != 1) {
+            // A later page of a TIFF; anything else has only the first
+            (Some(ImageFormat::Tiff), pages::size_of(&sizes, page).map_err(OptimizeError::Unreadable)?)
+        } else if raw::is_raw(path) {
+            let preview = raw::preview(path).map_err(OptimizeError::Unreadable)?;
+            (None, (preview.width, preview.height))
+        } else if psd::is_psd(path) {
+            (None, psd::dimensions(path).map_err(OptimizeError::Unreadable)?)
+        } else {
+            let reader = ImageReader::open(path).and_then(|r| r.with_guessed_format()).map_err(|e| unreadable(&e))?;
+            let format = reader.format().ok_or_else(|| OptimizeError::Unsupported("not an image format imgopt knows".to_string()))?;
+            (Some(format), reader.into_dimensions().map_err(|e| unreadable(&e))?)
+        };
+        Ok(ImageJob {
+            path: path.to_path_buf(),
+            format,
+            bytes,
+            stored,
+            sideways: orient::swaps_sides(orient::read(path)),
+            retina: is_retina(path),
+            pages: sizes.len().max(1),
+        })
+    }
+
+    pub fn plan(&self, config: &Config) -> Plan {
+        // A frame goes around the resized image, so the image itself gets less room
+        let resize = config.resize().shrunk(config.frame.extra());
+        let (width, height) = self.stored;
+        let original = if self.sideways { (height, width) } else { (width, height) };
+        let fitted = output_dimensions(original.0, original.1, self.retina, &resize);
+        let passthrough = self
+            .format
+            .filter(|format| is_plain(config) && passes_through(*format, self.stored, self.bytes, self.retina, &config.passthrough, &resize));
+        // Letterboxed onto the canvas, when there is one
+        let target = resize.canvas.unwrap_or(fitted);
+        Plan { original, target, reduction: reduction_factor(width, height, fitted), passthrough }
+    }
+}
+
+// Nothing asked for beyond resizing, so an image that needs no resize either
+// can be handed over as it is. Transforms, frames, hooks and uploads need a
+// file of our own to work on, the auto preset needs to see the pixels, and an
+// sRGB profile has to be written into the output.
+pub fn is_plain(config: &Config) -> bool {
+    !config.is_auto()
+        && config.frame == Frame::None
+        && config.post_process.is_none()
+        && config.transforms().is_empty()
+        && config.adjust().is_neutral()
+        && config.stamp().is_off()
+        && config.denoise == 0
+        && config.color_profile() != ColorProfile::Srgb
+        && config.destination() != Destination::Upload
+}
+
+// A PNG or JPEG that is already inside the dimension bounds (or exactly the
+// canvas) and the byte budget gains nothing from a re-encode
+pub fn passes_through(
+    format: ImageFormat,
+    (width, height): (u32, u32),
+    bytes: u64,
+    retina: bool,
+    passthrough: &Passthrough,
+    resize: &Resize,
+) -> bool {
+    passthrough.enabled
+        && bytes <= passthrough.max_bytes
+        && matches!(format, ImageFormat::Png | ImageFormat::Jpeg)
+        && output_dimensions(width, height, retina, resize) == (width, height)
+        && resize.canvas.is_none_or(|canvas| canvas == (width, height))
+}
+
+// `imgopt plan <file>...`: what `imgopt optimize` would do with each file, as
+// one JSON object per line, without decoding or writing anything. [[rules]]
+// apply as they would there.
+pub fn run_plan(paths: &[PathBuf], config: &Config) -> Exit {
+    let mut exit = Exit::Success;
+    for path in paths {
+        let line = match ImageJob::probe(path, config.page) {
+            Ok(job) => describe(&job, &job.plan(&config.for_file(path))),
+            Err(e) => {
+                if exit == Exit::Success {
+                    exit = Exit::error(&e);
+                }
+                json!({ "path": path, "error": e.to_string() })
+            }
+        };
+        println!("{}", line);
+    }
+    exit
+}
+
+fn describe(job: &ImageJob, plan: &Plan) -> Value {
+    let format = match job.format {
+        Some(format) => format.extensions_str().first().copied().unwrap_or("unknown"),
+        None if raw::is_raw(&job.path) => "raw",
+        None if pages::is_pdf(&job.path) => "pdf",
+        None if video::is_video(&job.path) => "video",
+        None => "psd",
+    };
+    json!({
+        "path": job.path,
+        "format": format,
+        "bytes": job.bytes,
+        "original": [plan.original.0, plan.original.1],
+        "target": [plan.target.0, plan.target.1],
+        "retina": job.retina,
+        "reduction": plan.reduction,
+        "pass_through": plan.passthrough.is_some(),
+        "pages": job.pages,
+    })
+}
@@ -0,0 +1,55 @@
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::log;
+
+// The files a batch of `imgopt optimize` has finished, one path per line, so
+// `--resume` can pick up where an interrupted run stopped. Kept in the temp
+// folder under a name made from the working directory and the file list,
+// which the same command run again finds. A run that goes through without
+// errors removes it.
+pub struct Journal {
+    path: PathBuf,
+    done: HashSet<PathBuf>,
+    file: Option<File>,
+}
+
+impl Journal {
+    // Starts a fresh journal, or with `resume` carries on with the last one
+    pub fn open(paths: &[PathBuf], resume: bool) -> Journal {
+        let mut hasher = DefaultHasher::new();
+        std::env::current_dir().ok().hash(&mut hasher);
+        paths.hash(&mut hasher);
+        let path = std::env::temp_dir().join(format!("imgopt-journal-{:016x}", hasher.finish()));
+
+        let done = match std::fs::read_to_string(&path) {
+            Ok(text) if resume => text.lines().map(PathBuf::from).collect(),
+            _ => HashSet::new(),
+        };
+        let file = OpenOptions::new().create(true).append(resume).write(true).truncate(!resume).open(&path);
+        let file = file.map_err(|e| log(&format!("Could not write the journal {}: {}", path.display(), e))).ok();
+        Journal { path, done, file }
+    }
+
+    pub fn is_done(&self, path: &Path) -> bool {
+        self.done.contains(path)
+    }
+
+    pub fn finished(&mut self, path: &Path) {
+        if let Some(file) = &mut self.file {
+            if let Err(e) = writeln!(file, "{}", path.display()) {
+                log(&format!("Could not write the journal {}: {}", self.path.display(), e));
+            }
+        }
+        self.done.insert(path.to_path_buf());
+    }
+
+    // Nothing left to resume
+    pub fn remove(self) {
+        drop(self.file);
+        std::fs::remove_file(&self.path).ok();
+    }
+}
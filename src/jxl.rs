@@ -0,0 +1,88 @@
+use image::{ImageFormat, ImageReader};
+use std::path::Path;
+use std::process::Command;
+
+use crate::cache::Cache;
+use crate::config::Config;
+use crate::deliver::EncodedPng;
+use crate::optimize::{archive, checkpoint, fallback_path, file_size, render, Delivery, OptimizeError, Outcome, Stage};
+use crate::log;
+
+// JPEG XL output, built with the `jxl` cargo feature. There is no mature Rust
+// encoder yet, so this hands the work to libjxl's `cjxl`, which has to be on
+// the PATH.
+
+// Optimizes like `imgopt optimize` but writes <name>_optimized.jxl next to
+// the input. With `lossless_jpeg`, a JPEG input is instead repacked as-is
+// into <name>_lossless.jxl: no decode, no resize, and `djxl` gives back the
+// original file bit for bit.
+pub fn optimize_jxl(
+    path: &Path,
+    lossless_jpeg: bool,
+    config: &Config,
+    cache: &Cache,
+    progress: &dyn Fn(Stage) -> bool,
+) -> Result<Outcome, OptimizeError> {
+    if !path.exists() {
+        return Err(OptimizeError::NotFound);
+    }
+    archive(&[path], config)?;
+
+    if lossless_jpeg {
+        let reader = ImageReader::open(path)
+            .and_then(|reader| reader.with_guessed_format())
+            .map_err(|e| OptimizeError::Unreadable(e.to_string()))?;
+        if reader.format() != Some(ImageFormat::Jpeg) {
+            return Err(OptimizeError::Unsupported("lossless recompression only works on JPEGs".into()));
+        }
+        let size = reader.into_dimensions().map_err(|e| OptimizeError::Unreadable(e.to_string()))?;
+        checkpoint(progress, Stage::Encoding)?;
+        let output = fallback_path(path, "lossless", "jxl");
+        cjxl(path, &output, &["--lossless_jpeg=1"]).map_err(OptimizeError::Output)?;
+        return Ok(written(path, size, size, output, true));
+    }
+
+    let rendered = render(path, config, cache, progress)?;
+    checkpoint(progress, Stage::Encoding)?;
+    let png = EncodedPng::new(&rendered.image, config).map_err(|e| OptimizeError::Output(e.to_string()))?;
+    let output = fallback_path(path, "optimized", "jxl");
+    // cjxl's quality scale is meant to line up with JPEG's
+    let quality = config.jpeg_quality.to_string();
+    cjxl(&png.path, &output, &["--quality", &quality]).map_err(OptimizeError::Output)?;
+    let optimized = (rendered.image.width(), rendered.image.height());
+    Ok(written(path, rendered.original, optimized, output, false))
+}
+
+fn written(path: &Path, original: (u32, u32), optimized: (u32, u32), output: std::path::PathBuf, passed_through: bool) -> Outcome {
+    log(&format!("Wrote {}", output.display()));
+    let bytes = (file_size(&[path]), file_size(&[&output]));
+    Outcome {
+        original,
+        optimized,
+        delivery: Delivery::Saved(output),
+        cached: false,
+        passed_through,
+        palette: Vec::new(),
+        diff: None,
+        grid: None,
+        content: None,
+        warnings: Vec::new(),
+        clip: None,
+        bytes: Some(bytes),
+    }
+}
+
+fn cjxl(input: &Path, output: &Path, options: &[&str]) -> Result<(), String> {
+    let result = Command::new("cjxl").arg(input).arg(output).args(options).output().map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => "cjxl not found, is libjxl installed?".to_string(),
+        _ => format!("could not run cjxl: {}", e),
+    })?;
+    if !result.status.success() {
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(match stderr.trim().lines().last() {
+            Some(line) => line.to_string(),
+            None => format!("cjxl exited with {}", result.status),
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use image::{DynamicImage, GrayAlphaImage, GrayImage, ImageBuffer, Luma, RgbImage, RgbaImage};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use crate::log;
+
+// Bounded-memory decoding for inputs far larger than the output. JPEGs are
+// scaled inside the DCT (1/2, 1/4 or 1/8) and non-interlaced PNGs are
+// box-filtered row by row, so the full-size image is never held in memory.
+// Returns None when the format can't be reduced this way.
+pub fn decode_reduced(path: &Path, width: u32, height: u32, factor: u32) -> Option<Result<DynamicImage, String>> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    let result = match ext.as_str() {
+        "jpg" | "jpeg" => File::open(path)
+            .map_err(|e| e.to_string())
+            .and_then(|file| decode_jpeg(BufReader::new(file), width, height, factor)),
+        "png" => decode_png(path, factor)?,
+        _ => return None,
+    };
+    if let Ok(img) = &result {
+        log(&format!(
+            "Reduced decode: {}x{} -> {}x{}",
+            width,
+            height,
+            img.width(),
+            img.height()
+        ));
+    }
+    Some(result)
+}
+
+// Also used for the JPEG previews inside camera RAW files
+pub fn decode_jpeg(reader: impl Read, width: u32, height: u32, factor: u32) -> Result<DynamicImage, String> {
+    let mut decoder = jpeg_decoder::Decoder::new(reader);
+
+    // The decoder picks the smallest DCT scale that is still at least this big
+    let requested = |side: u32| (side / factor).clamp(1, u16::MAX as u32) as u16;
+    decoder.scale(requested(width), requested(height)).map_err(|e| e.to_string())?;
+    let pixels = decoder.decode().map_err(|e| e.to_string())?;
+    let info = decoder.info().ok_or("missing JPEG header")?;
+    let (w, h) = (info.width as u32, info.height as u32);
+
+    let img = match info.pixel_format {
+        jpeg_decoder::PixelFormat::L8 => GrayImage::from_raw(w, h, pixels).map(DynamicImage::ImageLuma8),
+        jpeg_decoder::PixelFormat::L16 => {
+            let samples = pixels.chunks_exact(2).map(|b| u16::from_be_bytes([b[0], b[1]])).collect();
+            ImageBuffer::<Luma<u16>, Vec<u16>>::from_raw(w, h, samples).map(DynamicImage::ImageLuma16)
+        }
+        jpeg_decoder::PixelFormat::RGB24 => RgbImage::from_raw(w, h, pixels).map(DynamicImage::ImageRgb8),
+        jpeg_decoder::PixelFormat::CMYK32 => {
+            let rgb = pixels
+                .chunks_exact(4)
+                .flat_map(|p| {
+                    let k = p[3] as u32;
+                    [p[0], p[1], p[2]].map(|c| (c as u32 * k / 255) as u8)
+                })
+                .collect();
+            RgbImage::from_raw(w, h, rgb).map(DynamicImage::ImageRgb8)
+        }
+    };
+    img.ok_or_else(|| "JPEG decoder returned a short buffer".to_string())
+}
+
+fn decode_png(path: &Path, factor: u32) -> Option<Result<DynamicImage, String>> {
+    let file = File::open(path).ok()?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().ok()?;
+
+    // Interlaced rows arrive out of order; leave those to the regular decoder
+    if reader.info().interlaced {
+        return None;
+    }
+    Some(box_filter_png(&mut reader, factor))
+}
+
+fn box_filter_png<R: std::io::BufRead + std::io::Seek>(
+    reader: &mut png::Reader<R>,
+    factor: u32,
+) -> Result<DynamicImage, String> {
+    let (width, height) = (reader.info().width, reader.info().height);
+    let (color, _) = reader.output_color_type();
+    let channels = color.samples();
+    let factor = factor as usize;
+
+    let out_width = (width as usize).div_ceil(factor);
+    let out_height = (height as usize).div_ceil(factor);
+    let mut sums = vec![0u32; out_width * channels];
+    let mut out = Vec::with_capacity(out_width * out_height * channels);
+    let mut rows_in_block = 0;
+
+    for y in 0..height as usize {
+        let row = reader.next_row().map_err(|e| e.to_string())?.ok_or("PNG ended early")?;
+        for (x, pixel) in row.data().chunks_exact(channels).enumerate() {
+            let base = (x / factor) * channels;
+            for (c, value) in pixel.iter().enumerate() {
+                sums[base + c] += *value as u32;
+            }
+        }
+        rows_in_block += 1;
+
+        if rows_in_block == factor || y + 1 == height as usize {
+            for (i, sum) in sums.iter_mut().enumerate() {
+                // The last column block may be narrower than `factor`
+                let block_x = i / channels;
+                let cols = factor.min(width as usize - block_x * factor);
+                out.push((*sum / (cols * rows_in_block) as u32) as u8);
+                *sum = 0;
+            }
+            rows_in_block = 0;
+        }
+    }
+
+    let (w, h) = (out_width as u32, out_height as u32);
+    let img = match color {
+        png::ColorType::Grayscale => GrayImage::from_raw(w, h, out).map(DynamicImage::ImageLuma8),
+        png::ColorType::GrayscaleAlpha => GrayAlphaImage::from_raw(w, h, out).map(DynamicImage::ImageLumaA8),
+        png::ColorType::Rgb => RgbImage::from_raw(w, h, out).map(DynamicImage::ImageRgb8),
+        png::ColorType::Rgba => RgbaImage::from_raw(w, h, out).map(DynamicImage::ImageRgba8),
+        png::ColorType::Indexed => None,
+    };
+    img.ok_or_else(|| "unexpected PNG color type".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgb};
+    use std::io::Cursor;
+
+    fn reduce(img: &DynamicImage, factor: u32) -> DynamicImage {
+        let mut png = Cursor::new(Vec::new());
+        img.write_to(&mut png, ImageFormat::Png).unwrap();
+        png.set_position(0);
+        let mut decoder = png::Decoder::new(png);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        box_filter_png(&mut decoder.read_info().unwrap(), factor).unwrap()
+    }
+
+    #[test]
+    fn edge_blocks_average_what_they_cover() {
+        // 5x3 in blocks of 2: the last column and row of blocks are one pixel wide
+        let img = RgbImage::from_fn(5, 3, |x, y| Rgb([(x * 10 + y * 100) as u8, 200, (x * y) as u8]));
+        let reduced = reduce(&DynamicImage::ImageRgb8(img), 2).to_rgb8();
+        assert_eq!(reduced.dimensions(), (3, 2));
+        let expected = [
+            [[55, 200, 0], [75, 200, 1], [90, 200, 2]],
+            [[205, 200, 1], [225, 200, 5], [240, 200, 8]],
+        ];
+        for (y, row) in expected.iter().enumerate() {
+            for (x, pixel) in row.iter().enumerate() {
+                assert_eq!(reduced.get_pixel(x as u32, y as u32).0, *pixel, "block {},{}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn color_types_are_kept() {
+        let gray = reduce(&DynamicImage::ImageLuma8(GrayImage::from_fn(7, 7, |x, _| Luma([x as u8 * 30]))), 4);
+        assert_eq!((gray.width(), gray.height()), (2, 2));
+        assert_eq!(gray.to_luma8().get_pixel(1, 1).0, [150]);
+
+        let rgba = reduce(&DynamicImage::ImageRgba8(RgbaImage::from_pixel(3, 1, image::Rgba([1, 2, 3, 40]))), 8);
+        assert_eq!(rgba.as_rgba8().map(|img| img.get_pixel(0, 0).0), Some([1, 2, 3, 40]));
+    }
+}
@@ -0,0 +1,171 @@
+use image::{DynamicImage, ImageFormat};
+use serde::Deserialize;
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::config::Config;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+// Fewer words than this and the image is taken to hold no text to judge
+const MIN_WORDS: usize = 5;
+const SHARPEN: &str = "sharpen:0.8";
+// Each pass after the sharpened one makes the image this much larger
+const GROWTH: f64 = 1.25;
+
+// A second look at results that hold text: tesseract reads the optimized
+// image, and while it is unsure of the words the image is made again,
+// sharpened first and then larger, until it reads well or the passes run out
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Legibility {
+    pub enabled: bool,
+    // Mean OCR confidence per word, 0-100, that counts as readable
+    pub min_confidence: u32,
+    // Tries after the first result
+    pub passes: u32,
+    // Longest side the image may grow to
+    pub max_dimension: u32,
+}
+
+impl Default for Legibility {
+    fn default() -> Legibility {
+        Legibility {
+            enabled: false,
+            min_confidence: 75,
+            passes: 3,
+            max_dimension: 1920,
+        }
+    }
+}
+
+impl Legibility {
+    // The config for pass `pass` (from 1) after a result of size `last`, or
+    // None when there is nothing left to try. A canvas has an exact size, so
+    // those images are only sharpened.
+    pub fn retry(&self, config: &Config, pass: u32, last: (u32, u32)) -> Option<Config> {
+        if pass > self.passes {
+            return None;
+        }
+        let mut retried = config.clone();
+        if pass == 1 {
+            let mut transforms = config.transforms().to_vec();
+            transforms.push(SHARPEN.to_string());
+            retried.set_transforms(transforms);
+            return Some(retried);
+        }
+        // Larger than the last result, which may well have been within the limits already
+        let longest = last.0.max(last.1);
+        let resize = retried.resize_mut();
+        if resize.canvas.is_some() || longest >= self.max_dimension {
+            return None;
+        }
+        let ceiling = self.max_dimension;
+        resize.min_dimension = grow(longest, ceiling);
+        resize.max_dimension = resize.max_dimension.max(resize.min_dimension);
+        resize.max_width = resize.max_width.map(|width| grow(width, ceiling));
+        resize.max_height = resize.max_height.map(|height| grow(height, ceiling));
+        resize.never_enlarge = false;
+        Some(retried)
+    }
+}
+
+fn grow(side: u32, ceiling: u32) -> u32 {
+    ((side as f64 * GROWTH).round() as u32).min(ceiling)
+}
+
+// Mean confidence of the words tesseract finds, 0-100, or None when it finds
+// too few to go by
+pub fn read(img: &DynamicImage) -> Result<Option<f64>, String> {
+    let input = std::env::temp_dir().join(format!("imgopt-ocr-{}-{}.png", std::process::id(), NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+    img.save_with_format(&input, ImageFormat::Png).map_err(|e| format!("could not write the image for tesseract: {}", e))?;
+    let output = Command::new("tesseract").arg(&input).args(["stdout", "--psm", "3", "tsv"]).output();
+    std::fs::remove_file(&input).ok();
+    let output = output.map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => "tesseract not found, is it installed?".to_string(),
+        _ => format!("could not run tesseract: {}", e),
+    })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("tesseract: {}", stderr.trim().lines().last().unwrap_or("failed")));
+    }
+
+    // level, page, block, paragraph, line, word, left, top, width, height, conf, text;
+    // words are level 5, and their conf is -1 when tesseract gave up on them
+    let confidences: Vec<f64> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let (level, confidence, text) = (fields.first()?, fields.get(10)?, fields.get(11)?);
+            let confidence = confidence.parse::<f64>().ok().filter(|_| *level == "5" && !text.trim().is_empty())?;
+            (confidence >= 0.0).then_some(confidence)
+        })
+        .collect();
+    if confidences.len() < MIN_WORDS {
+        return Ok(None);
+    }
+    Ok(Some(confidences.iter().sum::<f64>() / confidences.len() as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Preset, Resize};
+
+    fn config() -> Config {
+        let resize = Resize { max_width: Some(900), never_enlarge: true, ..Resize::default() };
+        Config { resize, transforms: vec!["trim".to_string()], ..Config::default() }
+    }
+
+    #[test]
+    fn first_pass_sharpens_without_resizing() {
+        let legibility = Legibility::default();
+        let retried = legibility.retry(&config(), 1, (720, 400)).unwrap();
+        assert_eq!(retried.transforms(), ["trim", SHARPEN]);
+        assert_eq!(retried.resize().max_dimension, 720);
+        assert!(retried.resize().never_enlarge);
+    }
+
+    #[test]
+    fn later_passes_grow_past_the_last_result() {
+        let legibility = Legibility::default();
+        let retried = legibility.retry(&config(), 2, (720, 400)).unwrap();
+        let resize = retried.resize();
+        assert_eq!((resize.min_dimension, resize.max_dimension, resize.max_width), (900, 900, Some(1125)));
+        assert!(!resize.never_enlarge);
+        assert_eq!(retried.transforms(), ["trim"]);
+
+        // Up to max_dimension, and no further once it's reached
+        let resize = legibility.retry(&config(), 3, (1700, 900)).unwrap().resize();
+        assert_eq!((resize.min_dimension, resize.max_width), (1920, Some(1125)));
+        assert!(legibility.retry(&config(), 3, (900, 1920)).is_none());
+        assert!(legibility.retry(&config(), 4, (720, 400)).is_none());
+    }
+
+    #[test]
+    fn canvases_are_only_sharpened() {
+        let mut config = config();
+        config.resize.canvas = Some((1200, 630));
+        let legibility = Legibility { passes: 5, ..Legibility::default() };
+        assert!(legibility.retry(&config, 1, (1200, 630)).is_some());
+        assert!(legibility.retry(&config, 2, (1200, 630)).is_none());
+    }
+
+    #[test]
+    fn a_selected_preset_is_what_changes() {
+        let mut config = config();
+        let resize = Resize { max_dimension: 400, ..Resize::default() };
+        let preset = Preset { resize, transforms: Some(Vec::new()), ..Preset::default() };
+        config.presets.insert("small".to_string(), preset);
+        config.preset = Some("small".to_string());
+        let legibility = Legibility::default();
+
+        let sharpened = legibility.retry(&config, 1, (400, 300)).unwrap();
+        assert_eq!(sharpened.transforms(), [SHARPEN]);
+        assert_eq!(sharpened.transforms, ["trim"]);
+        let grown = legibility.retry(&config, 2, (400, 300)).unwrap();
+        assert_eq!((grown.resize().min_dimension, grown.resize().max_dimension), (500, 500));
+        assert_eq!(grown.resize.max_dimension, 720);
+    }
+}
@@ -0,0 +1,41 @@
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+
+use crate::clipboard::flatten;
+use crate::optimize::resample;
+
+// The blurred background is made at this fraction of the canvas size, which
+// blurs it for next to nothing before it is scaled back up
+const BLUR_SCALE: u32 = 16;
+const BLUR_SIGMA: f32 = 2.0;
+// Darkened a little so the image stands out from its own copy
+const BLUR_DIM: f32 = 0.8;
+
+// The resized image centered on a canvas of exactly `size`, for destinations
+// that take one size only, like 1200x630 link previews. The bars left over
+// are `background`, or the image itself scaled to cover the canvas and blurred,
+// with any transparency in it flattened onto `matte`.
+pub fn letterbox(img: &DynamicImage, (width, height): (u32, u32), background: Option<[u8; 3]>, matte: [u8; 3]) -> DynamicImage {
+    let mut canvas = match background {
+        Some([r, g, b]) => RgbaImage::from_pixel(width, height, Rgba([r, g, b, 255])),
+        None => blurred(img, (width, height), matte),
+    };
+    let x = width.saturating_sub(img.width()) / 2;
+    let y = height.saturating_sub(img.height()) / 2;
+    imageops::overlay(&mut canvas, &img.to_rgba8(), x as i64, y as i64);
+    DynamicImage::ImageRgb8(DynamicImage::ImageRgba8(canvas).to_rgb8())
+}
+
+fn blurred(img: &DynamicImage, (width, height): (u32, u32), matte: [u8; 3]) -> RgbaImage {
+    let small = ((width / BLUR_SCALE).max(1), (height / BLUR_SCALE).max(1));
+    let scale = (small.0 as f64 / img.width().max(1) as f64).max(small.1 as f64 / img.height().max(1) as f64);
+    let side = |length: u32, least: u32| ((length as f64 * scale).ceil() as u32).max(least);
+    let cover = resample(&flatten(img, matte), side(img.width(), small.0), side(img.height(), small.1)).to_rgba8();
+    let (left, top) = ((cover.width() - small.0) / 2, (cover.height() - small.1) / 2);
+    let mut background = imageops::fast_blur(&imageops::crop_imm(&cover, left, top, small.0, small.1).to_image(), BLUR_SIGMA);
+    for pixel in background.pixels_mut() {
+        for channel in &mut pixel.0[..3] {
+            *channel = (*channel as f32 * BLUR_DIM).round() as u8;
+        }
+    }
+    resample(&DynamicImage::ImageRgba8(background), width, height).to_rgba8()
+}
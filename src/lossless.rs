@@ -0,0 +1,77 @@
+use image::metadata::Orientation;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::orient;
+use crate::{log, Result};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+// A JPEG that passes through unchanged can still get lighter without losing
+// anything: jpegtran (libjpeg-turbo) rewrites the same DCT coefficients with
+// optimized Huffman tables, drops the metadata, and turns photos that are
+// only rotated by their EXIF orientation upright by moving blocks rather than
+// decoding pixels. The repacked copy lives in a temp file, removed on drop.
+pub struct Repacked {
+    pub path: PathBuf,
+    // Swapped from the original when the orientation turned it on its side
+    pub size: (u32, u32),
+}
+
+impl Drop for Repacked {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+// Fails when jpegtran is missing or the rotation can't be done without
+// touching pixels (sizes that aren't a multiple of the block size); the
+// original is passed through then, as before
+pub fn repack(path: &Path, (width, height): (u32, u32)) -> Result<Repacked> {
+    let orientation = orient::read(path);
+    let name = format!("imgopt-{}-{}.jpg", std::process::id(), NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    let repacked = std::env::temp_dir().join(name);
+
+    let mut command = Command::new("jpegtran");
+    command.args(["-copy", "none", "-optimize"]);
+    if orientation != Orientation::NoTransforms {
+        command.arg("-perfect").args(transform(orientation));
+    }
+    let output = command.arg("-outfile").arg(&repacked).arg(path).output().map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => "jpegtran not found, is libjpeg-turbo installed?".to_string(),
+        _ => format!("could not run jpegtran: {}", e),
+    })?;
+    let size = if orient::swaps_sides(orientation) { (height, width) } else { (width, height) };
+    let repacked = Repacked { path: repacked, size };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.trim().lines().last() {
+            Some(line) => format!("jpegtran: {}", line),
+            None => format!("jpegtran exited with {}", output.status),
+        }
+        .into());
+    }
+
+    // Upright already and nothing gained: the original is as good
+    let bytes = |path: &Path| std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    if orientation == Orientation::NoTransforms && bytes(&repacked.path) >= bytes(path) {
+        return Err("no smaller than the original".into());
+    }
+    log(&format!("Repacked {} losslessly (orientation {})", path.display(), orientation.to_exif()));
+    Ok(repacked)
+}
+
+// The jpegtran options that undo each orientation
+fn transform(orientation: Orientation) -> &'static [&'static str] {
+    match orientation {
+        Orientation::FlipHorizontal => &["-flip", "horizontal"],
+        Orientation::Rotate180 => &["-rotate", "180"],
+        Orientation::FlipVertical => &["-flip", "vertical"],
+        Orientation::Rotate90FlipH => &["-transpose"],
+        Orientation::Rotate90 => &["-rotate", "90"],
+        Orientation::Rotate270FlipH => &["-transverse"],
+        Orientation::Rotate270 => &["-rotate", "270"],
+        Orientation::NoTransforms => &[],
+    }
+}
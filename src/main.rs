@@ -1,340 +1,239 @@
-use crossterm::{
-    cursor,
-    event::{self, Event, KeyCode, KeyModifiers},
-    execute, queue,
-    style::{Color, Print, ResetColor, SetForegroundColor},
-    terminal::{self, ClearType},
-};
-use image::{imageops::FilterType, GenericImageView};
-use std::io::{self, Write};
-use std::path::Path;
-use std::process::Command;
-use std::thread;
-use std::time::Duration;
+use std::io::Write;
 use std::fs::OpenOptions;
-
-const MIN_DIMENSION: u32 = 480;
-const MAX_DIMENSION: u32 = 720;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+mod adjust;
+mod app;
+mod appearance;
+mod archive;
+mod audit;
+mod autoformat;
+mod background;
+mod bench;
+mod browser;
+mod bundle;
+mod cache;
+mod capabilities;
+mod check;
+mod cli;
+mod clipboard;
+mod cloud;
+mod config;
+mod content;
+#[cfg(unix)]
+mod daemon;
+mod deliver;
+mod denoise;
+mod diff;
+mod doctor;
+mod display;
+mod dpi;
+mod exit;
+mod favicon;
+mod finder;
+mod font;
+mod frame;
+mod input;
+mod job;
+mod journal;
+mod grid;
+mod history;
+mod hook;
+mod i18n;
+mod icc;
+mod inspect;
+#[cfg(feature = "jxl")]
+mod jxl;
+mod large;
+mod legibility;
+mod letterbox;
+mod lossless;
+mod mcp;
+mod multiplexer;
+mod notify;
+mod onboarding;
+mod oneshot;
+mod optimize;
+mod orient;
+mod palette;
+mod pages;
+mod pasted;
+mod paths;
+mod picker;
+mod pipeline;
+mod plain;
+mod precommit;
+mod psd;
+mod qr;
+mod quantize;
+mod raw;
+mod recent;
+mod reveal;
+#[cfg(feature = "wasm")]
+mod script;
+mod serve;
+mod session;
+mod settings;
+mod srcset;
+mod ssim;
+mod stamp;
+mod theme;
+mod transfer;
+mod transform;
+mod ui;
+mod upload;
+mod video;
+mod watch;
+
+use app::App;
+use bundle::Bundle;
+use clap::{CommandFactory, Parser};
+use cli::{Cli, Command};
+use config::Config;
+use deliver::Destination;
+use exit::Exit;
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
-fn main() -> Result<()> {
+// Set by --quiet
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+fn main() -> ExitCode {
     std::panic::set_hook(Box::new(|panic_info| {
         log(&format!("PANIC: {:?}", panic_info));
     }));
 
-    match run_app() {
-        Ok(_) => {
-            log("App exited normally");
-            Ok(())
-        }
+    match run() {
+        Ok(exit) => exit.into(),
         Err(e) => {
-            log(&format!("App error: {}", e));
-            Err(e)
+            eprintln!("imgopt: {}", e);
+            Exit::Failure.into()
         }
     }
 }
 
-fn log(msg: &str) {
-    let mut file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open("/tmp/imgopt.log")
-        .unwrap();
-    writeln!(file, "{}", msg).ok();
-}
-
-fn run_app() -> Result<()> {
-    log("App starting");
-    terminal::enable_raw_mode()?;
-    log("Raw mode enabled");
-    let mut stdout = io::stdout();
-
-    execute!(
-        stdout,
-        terminal::Clear(ClearType::All),
-        cursor::MoveTo(0, 0)
-    )?;
-
-    show_drop_zone(&mut stdout)?;
-    log("Drop zone shown, entering loop");
+fn run() -> Result<Exit> {
+    // clap's own usage errors exit with 2, which means "unsupported format" here
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            e.print()?;
+            return Ok(if e.use_stderr() { Exit::Failure } else { Exit::Success });
+        }
+    };
+    QUIET.store(cli.quiet, Ordering::Relaxed);
 
-    let mut input_buffer = String::new();
+    let command = cli.command.unwrap_or(Command::Tui);
+    // Completion scripts shouldn't depend on a valid config, and doctor reports on it
+    let mut config = match command {
+        Command::Completions { .. } | Command::Doctor => Config::default(),
+        _ => Config::load()?,
+    };
 
-    loop {
-        if !event::poll(Duration::from_millis(100))? {
-            continue;
+    if let Command::Optimize { to: Some(Destination::Upload), .. } | Command::Watch { to: Some(Destination::Upload), .. } = command {
+        if config.upload.is_none() {
+            return Err("--to upload needs an [upload] table in the config".into());
         }
+    }
 
-        match event::read()? {
-            Event::Key(key_event) => {
-                log(&format!("Key event: {:?}", key_event));
-                match key_event.code {
-                    KeyCode::Esc => {
-                        log("Breaking on ESC");
-                        break;
-                    }
-                    KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
-                        log("Breaking on Ctrl+C");
-                        break;
-                    }
-                    KeyCode::Char('d') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
-                        log("Breaking on Ctrl+D");
-                        break;
-                    }
-                    KeyCode::Char(c) => {
-                        input_buffer.push(c);
-
-                        // Auto-process when closing quote is detected (drag complete)
-                        if (c == '\'' || c == '"') && input_buffer.len() > 2 {
-                            let path = input_buffer.trim().trim_matches('\'').trim_matches('"');
-                            if path.ends_with(".png") || path.ends_with(".jpg") || path.ends_with(".jpeg") ||
-                               path.ends_with(".PNG") || path.ends_with(".JPG") || path.ends_with(".JPEG") ||
-                               path.ends_with(".gif") || path.ends_with(".GIF") || path.ends_with(".webp") {
-                                log(&format!("Auto-processing: {}", path));
-                                process_image(&mut stdout, path)?;
-                                input_buffer.clear();
-                                thread::sleep(Duration::from_secs(2));
-                                execute!(stdout, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
-                                show_drop_zone(&mut stdout)?;
-                            }
-                        }
-                    }
-                    KeyCode::Enter => {
-                        if !input_buffer.is_empty() {
-                            let path = input_buffer.trim().trim_matches('\'').trim_matches('"');
-                            process_image(&mut stdout, path)?;
-                            input_buffer.clear();
-                            thread::sleep(Duration::from_secs(2));
-                            execute!(stdout, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
-                            show_drop_zone(&mut stdout)?;
-                        }
-                    }
-                    KeyCode::Backspace => {
-                        input_buffer.pop();
-                    }
-                    _ => {}
-                }
+    match command {
+        Command::Tui => {
+            if config.destination() == Destination::Stdout {
+                return Err("the stdout destination only works with `imgopt optimize`".into());
+            }
+            // Dumb terminals can't do raw mode, colors or cursor movement
+            if cli.plain || std::env::var("TERM").is_ok_and(|term| term == "dumb") {
+                plain::run_plain(&config)?;
+                return Ok(Exit::Success);
             }
-            Event::Paste(data) => {
-                log(&format!("Paste event: {}", data));
-                // Drag and drop triggers paste event!
-                let path = data.trim().trim_matches('\'').trim_matches('"');
-                if !path.is_empty() {
-                    log(&format!("Processing: {}", path));
-                    process_image(&mut stdout, path)?;
-                    thread::sleep(Duration::from_secs(2));
-                    execute!(stdout, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
-                    show_drop_zone(&mut stdout)?;
-                    log("Back to drop zone");
+            i18n::init(config.language.as_deref());
+            match App::new(config).run() {
+                Ok(_) => log("App exited normally"),
+                Err(e) => {
+                    log(&format!("App error: {}", e));
+                    return Err(e);
                 }
             }
-            evt => {
-                log(&format!("Other event: {:?}", evt));
+        }
+        Command::Optimize { files, to, page, frame, resume, bundle } => {
+            let bundle = bundle.map(|bundle| Bundle::new(&paths::parse_argument(&bundle))).transpose()?;
+            if bundle.is_some() && to.is_some_and(|to| to != Destination::File) {
+                return Err("--bundle packs saved files, so it only goes with --to file".into());
+            }
+            if let Some(to) = to.or(bundle.as_ref().map(|_| Destination::File)) {
+                config.set_destination(to);
             }
+            config.page = page.map(|page| page as usize);
+            config.video_frame = frame.unwrap_or(config.video_frame);
+            let paths: Vec<_> = files.iter().map(|file| paths::parse_argument(file)).collect();
+            return Ok(oneshot::run_optimize(&paths, &config, resume, bundle));
         }
-    }
-
-    log("Exited main loop");
-    execute!(stdout, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
-    terminal::disable_raw_mode()?;
-    log("Disabled raw mode");
-    println!("👋 Goodbye!");
-    Ok(())
-}
-
-fn show_drop_zone(stdout: &mut io::Stdout) -> Result<()> {
-    let (width, height) = terminal::size()?;
-    let center_y = height / 2;
-
-    // Simple mode for narrow terminals
-    if width < 50 {
-        let lines = vec![
-            "📸 IMAGE OPTIMIZER",
-            "Drop images here",
-            "480-720px",
-            "(ESC to quit)",
-        ];
-
-        for (i, line) in lines.iter().enumerate() {
-            let x = (width.saturating_sub(line.len() as u16)) / 2;
-            queue!(
-                stdout,
-                cursor::MoveTo(x, center_y.saturating_sub(2) + i as u16),
-                SetForegroundColor(if i == 0 { Color::Blue } else { Color::White }),
-                Print(line),
-                ResetColor,
-            )?;
+        Command::Plan { files, page } => {
+            config.page = page.map(|page| page as usize);
+            let paths: Vec<_> = files.iter().map(|file| paths::parse_argument(file)).collect();
+            return Ok(job::run_plan(&paths, &config));
         }
-    } else {
-        // Full box mode for wider terminals
-        let box_width = 44;
-        let box_height = 10;
-        let start_x = (width.saturating_sub(box_width)) / 2;
-        let start_y = (height.saturating_sub(box_height)) / 2;
-
-        queue!(
-            stdout,
-            cursor::MoveTo(start_x, start_y),
-            SetForegroundColor(Color::Blue),
-            Print("╔════════════════════════════════════════╗"),
-            cursor::MoveTo(start_x, start_y + 1),
-            Print("║                                        ║"),
-            cursor::MoveTo(start_x, start_y + 2),
-            Print("║      📸  IMAGE OPTIMIZER  📸          ║"),
-            cursor::MoveTo(start_x, start_y + 3),
-            Print("║                                        ║"),
-            cursor::MoveTo(start_x, start_y + 4),
-            Print("║      Drop images here                  ║"),
-            cursor::MoveTo(start_x, start_y + 5),
-            Print("║      480-720px clamping                ║"),
-            cursor::MoveTo(start_x, start_y + 6),
-            Print("║                                        ║"),
-            cursor::MoveTo(start_x, start_y + 7),
-            SetForegroundColor(Color::DarkGrey),
-            Print("║      (ESC or Ctrl+C to quit)           ║"),
-            SetForegroundColor(Color::Blue),
-            cursor::MoveTo(start_x, start_y + 8),
-            Print("║                                        ║"),
-            cursor::MoveTo(start_x, start_y + 9),
-            Print("╚════════════════════════════════════════╝"),
-            ResetColor,
-        )?;
-    }
-
-    queue!(stdout, cursor::Hide)?;
-    stdout.flush()?;
-    Ok(())
-}
-
-fn process_image(stdout: &mut io::Stdout, path: &str) -> Result<()> {
-    execute!(stdout, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
-
-    let (term_width, term_height) = terminal::size()?;
-    let center_x = term_width / 2;
-    let center_y = term_height / 2;
-
-    queue!(
-        stdout,
-        cursor::MoveTo(center_x.saturating_sub(10), center_y.saturating_sub(3)),
-        SetForegroundColor(Color::Yellow),
-        Print("⚡ Processing image..."),
-        ResetColor,
-    )?;
-    stdout.flush()?;
-
-    let path_obj = Path::new(path);
-
-    if !path_obj.exists() {
-        queue!(
-            stdout,
-            cursor::MoveTo(center_x.saturating_sub(7), center_y.saturating_sub(1)),
-            SetForegroundColor(Color::Red),
-            Print("❌ File not found"),
-            ResetColor,
-        )?;
-        stdout.flush()?;
-        return Ok(());
-    }
-
-    // Load image
-    let img = match image::open(path_obj) {
-        Ok(img) => img,
-        Err(_) => {
-            queue!(
-                stdout,
-                cursor::MoveTo(center_x.saturating_sub(10), center_y.saturating_sub(1)),
-                SetForegroundColor(Color::Red),
-                Print("❌ Could not open image"),
-                ResetColor,
-            )?;
-            stdout.flush()?;
-            return Ok(());
+        #[cfg(feature = "jxl")]
+        Command::Jxl { files, lossless_jpeg } => {
+            let paths: Vec<_> = files.iter().map(|file| paths::parse_argument(file)).collect();
+            return Ok(oneshot::run_jxl(&paths, lossless_jpeg, &config));
         }
-    };
-
-    let (width, height) = img.dimensions();
-    let orig_text = format!("Original: {}x{}px", width, height);
-
-    queue!(
-        stdout,
-        cursor::MoveTo(center_x.saturating_sub((orig_text.len() / 2) as u16), center_y.saturating_sub(1)),
-        SetForegroundColor(Color::DarkYellow),
-        Print(&orig_text),
-        ResetColor,
-    )?;
-    stdout.flush()?;
-
-    // Calculate new dimensions
-    let max_dim = width.max(height);
-    let target_dim = if max_dim > MAX_DIMENSION {
-        MAX_DIMENSION
-    } else if max_dim < MIN_DIMENSION {
-        MIN_DIMENSION
-    } else {
-        max_dim
-    };
-
-    let (new_width, new_height) = if target_dim != max_dim {
-        if width > height {
-            (target_dim, (height * target_dim) / width)
-        } else {
-            ((width * target_dim) / height, target_dim)
+        Command::Favicon { file, out } => {
+            let out = out.map(|out| paths::parse_argument(&out));
+            return Ok(oneshot::run_favicon(&paths::parse_argument(&file), out.as_deref(), &config));
+        }
+        Command::Srcset { file, out } => {
+            let out = out.map(|out| paths::parse_argument(&out));
+            return Ok(oneshot::run_srcset(&paths::parse_argument(&file), out.as_deref(), &config));
+        }
+        Command::Orient { files, rotate, flip } => {
+            let paths: Vec<_> = files.iter().map(|file| paths::parse_argument(file)).collect();
+            return Ok(oneshot::run_orient(&paths, rotate.as_deref(), flip.as_deref()));
+        }
+        Command::Watch { dir, to } => {
+            let dir = match dir {
+                Some(dir) => paths::parse_argument(&dir),
+                None => config.screenshots_dir().ok_or("no screenshots folder found; pass the folder to watch")?,
+            };
+            watch::run_watch(&dir, &config, to)?;
+        }
+        Command::Serve { port } => serve::run_server(port, &config)?,
+        Command::Mcp => mcp::run_mcp(&config)?,
+        #[cfg(unix)]
+        Command::Daemon => daemon::run_daemon(&config)?,
+        #[cfg(not(unix))]
+        Command::Daemon => return Err("the daemon needs Unix sockets".into()),
+        Command::Bench { file } => bench::run_bench(&paths::parse_argument(&file), &config)?,
+        Command::Audit { dir, format } => audit::run_audit(&paths::parse_argument(&dir), format, &config)?,
+        Command::Check { paths, budget, max_dimension, format } => {
+            let paths: Vec<_> = paths.iter().map(|path| paths::parse_argument(path)).collect();
+            let mut limits = config.budget;
+            limits.max_bytes = budget.unwrap_or(limits.max_bytes);
+            limits.max_dimension = max_dimension.unwrap_or(limits.max_dimension);
+            return check::run_check(&paths, limits, format);
+        }
+        Command::Precommit { install } => return precommit::run_precommit(&config, install),
+        Command::Doctor => return Ok(doctor::run_doctor()),
+        Command::Completions { shell } => {
+            clap_complete::generate(shell, &mut Cli::command(), "imgopt", &mut std::io::stdout());
         }
-    } else {
-        (width, height)
-    };
-
-    let opt_text = format!("Optimized: {}x{}px", new_width, new_height);
-    queue!(
-        stdout,
-        cursor::MoveTo(center_x.saturating_sub((opt_text.len() / 2) as u16), center_y),
-        SetForegroundColor(Color::Green),
-        Print(&opt_text),
-        ResetColor,
-    )?;
-    stdout.flush()?;
-
-    // Resize image
-    let resized = img.resize_exact(new_width, new_height, FilterType::Lanczos3);
-
-    // Save to temp file
-    let temp_path = "/tmp/imgopt_temp.png";
-    if resized.save(temp_path).is_err() {
-        queue!(
-            stdout,
-            cursor::MoveTo(center_x.saturating_sub(13), center_y + 2),
-            SetForegroundColor(Color::Red),
-            Print("❌ Failed to save temp file"),
-            ResetColor,
-        )?;
-        stdout.flush()?;
-        return Ok(());
     }
+    Ok(Exit::Success)
+}
 
-    // Copy to clipboard using osascript
-    let _ = Command::new("osascript")
-        .arg("-e")
-        .arg(format!(
-            "set the clipboard to (read (POSIX file \"{}\") as «class PNGf»)",
-            temp_path
-        ))
-        .output();
-
-    // Cleanup
-    let _ = std::fs::remove_file(temp_path);
-
-    queue!(
-        stdout,
-        cursor::MoveTo(center_x.saturating_sub(12), center_y + 2),
-        SetForegroundColor(Color::Green),
-        Print("✅ Copied to clipboard!"),
-        cursor::MoveTo(center_x.saturating_sub(14), center_y + 4),
-        SetForegroundColor(Color::DarkGreen),
-        Print("Ready to paste into Claude..."),
-        ResetColor,
-    )?;
-    stdout.flush()?;
+// Status output for the terminal, silenced by --quiet. Errors go to stderr regardless.
+fn say(msg: &str) {
+    if !QUIET.load(Ordering::Relaxed) {
+        println!("{}", msg);
+    }
+}
 
-    Ok(())
+fn log(msg: &str) {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(std::env::temp_dir().join("imgopt.log"))
+        .unwrap();
+    writeln!(file, "{}", msg).ok();
 }
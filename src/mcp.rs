@@ -0,0 +1,121 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::{json, Value};
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use crate::cache::Cache;
+use crate::config::Config;
+use crate::paths::expand_home;
+use crate::serve::{params_from_json, process, temp_input, Processed};
+use crate::{log, Result};
+
+// Offered when the client doesn't say which version it speaks
+const PROTOCOL_VERSION: &str = "2025-06-18";
+
+// `imgopt mcp`: a Model Context Protocol server on stdin/stdout, so Claude
+// Desktop and Claude Code can call the optimizer as a tool. Messages are
+// JSON-RPC, one per line; stdout carries nothing else.
+pub fn run_mcp(config: &Config) -> Result<()> {
+    log("MCP server starting");
+    let cache = Cache::default();
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => handle(&message, config, &cache),
+            Err(e) => Some(error(Value::Null, -32700, &format!("parse error: {}", e))),
+        };
+        if let Some(reply) = reply {
+            writeln!(stdout, "{}", reply)?;
+            stdout.flush()?;
+        }
+    }
+    log("MCP server exited");
+    Ok(())
+}
+
+// The reply to one message, or None for notifications
+fn handle(message: &Value, config: &Config, cache: &Cache) -> Option<Value> {
+    let method = message["method"].as_str().unwrap_or_default();
+    let Some(id) = message.get("id").cloned() else {
+        log(&format!("MCP notification: {}", method));
+        return None;
+    };
+    let params = &message["params"];
+    let result = match method {
+        "initialize" => json!({
+            "protocolVersion": params["protocolVersion"].as_str().unwrap_or(PROTOCOL_VERSION),
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "imgopt", "version": env!("CARGO_PKG_VERSION") },
+        }),
+        "ping" => json!({}),
+        "tools/list" => json!({ "tools": [tool()] }),
+        "tools/call" => match params["name"].as_str() {
+            Some("optimize_image") => call(&params["arguments"], config, cache),
+            name => return Some(error(id, -32602, &format!("unknown tool: {}", name.unwrap_or_default()))),
+        },
+        _ => return Some(error(id, -32601, &format!("method not found: {}", method))),
+    };
+    Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+}
+
+fn error(id: Value, code: i32, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn tool() -> Value {
+    json!({
+        "name": "optimize_image",
+        "description": "Resize and re-encode an image so it is small enough to look at without filling the context \
+                        window. Give either a file path or base64 image data. Returns the optimized image and JSON \
+                        with its original and new dimensions and sizes.",
+        "inputSchema": {
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Image file to optimize" },
+                "data": { "type": "string", "description": "Base64-encoded image, instead of a path" },
+                "max_dimension": { "type": "integer", "description": "Longest side in pixels (default from the imgopt config)" },
+                "max_width": { "type": "integer" },
+                "max_height": { "type": "integer" },
+                "canvas": { "type": "string", "description": "Exact output size like 1200x630, with the image letterboxed inside" },
+                "preset": { "type": "string", "description": "Named preset from the imgopt config" },
+                "format": { "type": "string", "enum": ["png", "jpeg", "auto"] },
+            },
+        },
+    })
+}
+
+// Tool failures are results with isError set, so the model sees the message
+fn call(arguments: &Value, config: &Config, cache: &Cache) -> Value {
+    match optimize_image(arguments, config, cache) {
+        Ok(processed) => json!({
+            "content": [
+                { "type": "image", "data": BASE64.encode(&processed.bytes), "mimeType": processed.format.to_mime_type() },
+                { "type": "text", "text": processed.metadata.to_string() },
+            ],
+        }),
+        Err(e) => json!({ "content": [{ "type": "text", "text": e }], "isError": true }),
+    }
+}
+
+fn optimize_image(arguments: &Value, config: &Config, cache: &Cache) -> std::result::Result<Processed, String> {
+    // Everything besides the image itself maps onto the `imgopt serve` query parameters
+    let params = params_from_json(arguments, &["path", "data"]);
+
+    match (arguments["path"].as_str(), arguments["data"].as_str()) {
+        (Some(path), None) => process(&expand_home(Path::new(path)), &params, config, cache).map_err(|(_, e)| e),
+        (None, Some(data)) => {
+            let bytes = BASE64.decode(data.trim()).map_err(|e| format!("data is not valid base64: {}", e))?;
+            let input = temp_input(&bytes).map_err(|e| format!("could not store the image: {}", e))?;
+            let processed = process(&input, &params, config, cache).map_err(|(_, e)| e);
+            std::fs::remove_file(&input).ok();
+            processed
+        }
+        _ => Err("give either path or data".to_string()),
+    }
+}
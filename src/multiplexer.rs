@@ -0,0 +1,35 @@
+use std::sync::OnceLock;
+
+// tmux and GNU screen sit between the terminal and imgopt. Drops and pastes
+// often don't make it through as bracketed pastes (or arrive in chunks), and
+// their idea of how wide an emoji is rarely matches the terminal's.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Multiplexer {
+    Tmux,
+    Screen,
+}
+
+impl Multiplexer {
+    // Looked up once; the environment doesn't change under a running session
+    pub fn detect() -> Option<Multiplexer> {
+        static DETECTED: OnceLock<Option<Multiplexer>> = OnceLock::new();
+        *DETECTED.get_or_init(|| {
+            let set = |name: &str| std::env::var_os(name).is_some_and(|value| !value.is_empty());
+            let term = std::env::var("TERM").unwrap_or_default();
+            if set("TMUX") || term.starts_with("tmux") {
+                Some(Multiplexer::Tmux)
+            } else if set("STY") || term.starts_with("screen") {
+                Some(Multiplexer::Screen)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Multiplexer::Tmux => "tmux",
+            Multiplexer::Screen => "screen",
+        }
+    }
+}
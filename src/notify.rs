@@ -0,0 +1,68 @@
+use serde::Deserialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::log;
+
+const MACOS_SOUND: &str = "/System/Library/Sounds/Glass.aiff";
+const FREEDESKTOP_SOUND: &str = "/usr/share/sounds/freedesktop/stereo/complete.oga";
+
+// The `notify` setting: a cue when an image is done, for when the terminal
+// is behind another window. "bell" rings the terminal bell, which most
+// terminals turn into a sound, a flash or a badge; "sound" plays
+// `notify_sound` (or a system sound) with afplay or paplay.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Notify {
+    #[default]
+    Off,
+    Bell,
+    Sound,
+}
+
+impl Notify {
+    // Never waits for the sound to finish. Falls back to the bell when there
+    // is no player or nothing to play.
+    pub fn done(self, sound: Option<&Path>) {
+        match self {
+            Notify::Off => {}
+            Notify::Bell => bell(),
+            Notify::Sound => {
+                if let Err(e) = play(sound) {
+                    log(&format!("Not playing a sound: {}", e));
+                    bell();
+                }
+            }
+        }
+    }
+}
+
+fn bell() {
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(b"\x07").and_then(|_| stdout.flush());
+}
+
+fn play(sound: Option<&Path>) -> Result<(), String> {
+    let (player, default) = if cfg!(target_os = "macos") {
+        ("afplay", MACOS_SOUND)
+    } else if cfg!(windows) {
+        return Err("no sound player on Windows".into());
+    } else {
+        ("paplay", FREEDESKTOP_SOUND)
+    };
+    let sound = sound.map_or_else(|| PathBuf::from(default), Path::to_path_buf);
+    if !sound.is_file() {
+        return Err(format!("{} not found", sound.display()));
+    }
+    let mut child = Command::new(player)
+        .arg(&sound)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("could not run {}: {}", player, e))?;
+    // Reaped in the background so a long session doesn't collect zombies
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use crossterm::{
+    cursor,
+    event::{KeyCode, KeyEvent},
+    queue,
+    style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal,
+};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::capabilities::{self, Check, Status};
+use crate::clipboard::{self, ClipboardMode};
+use crate::config::Config;
+use crate::display;
+use crate::i18n::{tr, trf};
+use crate::multiplexer::Multiplexer;
+use crate::notify::Notify;
+use crate::theme::Theme;
+use crate::ui::{draw_footer, draw_list, pad, print_centered, truncate, Action, ListItem};
+use crate::{log, Result};
+
+const ROWS: usize = 5;
+const NOTIFY: [Notify; 3] = [Notify::Off, Notify::Bell, Notify::Sound];
+
+// Shown instead of the drop zone while there is no config file: what works in
+// this terminal, a place to try a drop, and the handful of settings that
+// depend on the terminal, written out as the first config
+pub struct Onboarding {
+    checks: Vec<Check>,
+    // The last drop onto this screen, proof that drops get through
+    dropped: Option<String>,
+    clipboard: ClipboardMode,
+    ascii: bool,
+    notify: Notify,
+    show_recent: bool,
+    // Preselected when a display scales its screenshots up
+    retina: bool,
+    selected: usize,
+    error: Option<String>,
+    // Set once the config is written, for the app to load it
+    pub saved: Option<PathBuf>,
+}
+
+impl Onboarding {
+    pub fn new(config: &Config, paste_enabled: bool, multiplexer: Option<Multiplexer>) -> Onboarding {
+        let scales = display::scales();
+        let retina = config.resize().retina || display::is_hidpi(&scales);
+        let checks = vec![
+            capabilities::clipboard(config.clipboard),
+            capabilities::drops(paste_enabled, multiplexer),
+            capabilities::graphics(),
+            capabilities::display(&scales, retina),
+        ];
+        for check in &checks {
+            log(&format!("Setup check: {} {:?} {}", check.name, check.status, check.detail));
+        }
+        // Without a display there is nothing to copy to but the terminal
+        let clipboard = if clipboard::describe(ClipboardMode::Auto).is_err() { ClipboardMode::Osc52 } else { config.clipboard };
+        Onboarding {
+            checks,
+            dropped: None,
+            clipboard,
+            ascii: config.theme.ascii(),
+            notify: config.notify,
+            show_recent: config.show_recent_on_start,
+            retina,
+            selected: 0,
+            error: None,
+            saved: None,
+        }
+    }
+
+    pub fn dropped(&mut self, path: &Path) {
+        self.dropped = Some(path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default());
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc => {
+                log("Setup skipped");
+                return Action::Back;
+            }
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Tab => self.selected = (self.selected + 1).min(ROWS - 1),
+            KeyCode::Left => self.change(false),
+            KeyCode::Right | KeyCode::Char(' ') => self.change(true),
+            KeyCode::Enter => match self.save() {
+                Ok(path) => {
+                    self.saved = Some(path);
+                    return Action::Back;
+                }
+                Err(e) => self.error = Some(e.to_string()),
+            },
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn change(&mut self, forward: bool) {
+        match self.selected {
+            0 => {
+                self.clipboard = if self.clipboard == ClipboardMode::Auto { ClipboardMode::Osc52 } else { ClipboardMode::Auto }
+            }
+            1 => self.ascii = !self.ascii,
+            2 => {
+                let i = NOTIFY.iter().position(|n| *n == self.notify).unwrap_or(0);
+                self.notify = NOTIFY[if forward { (i + 1) % NOTIFY.len() } else { (i + NOTIFY.len() - 1) % NOTIFY.len() }];
+            }
+            3 => self.show_recent = !self.show_recent,
+            _ => self.retina = !self.retina,
+        }
+    }
+
+    // Never overwrites: the screen only shows while there is no config, but
+    // another imgopt could have written one since
+    fn save(&self) -> Result<PathBuf> {
+        let path = Config::path().ok_or("no config directory: HOME is not set")?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let clipboard = if self.clipboard == ClipboardMode::Osc52 { "osc52" } else { "auto" };
+        let notify = match self.notify {
+            Notify::Off => "off",
+            Notify::Bell => "bell",
+            Notify::Sound => "sound",
+        };
+        let text = format!(
+            "# Written by imgopt's first-run setup. Every setting is described in the README.\n\
+             clipboard = \"{}\"\nnotify = \"{}\"\nshow_recent_on_start = {}\n\n[theme]\nascii = {}\n\n[resize]\nretina = {}\n",
+            clipboard, notify, self.show_recent, self.ascii, self.retina
+        );
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| format!("could not create {}: {}", path.display(), e))?;
+        file.write_all(text.as_bytes())?;
+        log(&format!("Setup wrote {}", path.display()));
+        Ok(path)
+    }
+
+    pub fn render(&self, stdout: &mut io::Stdout, theme: &Theme) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let max_len = width.saturating_sub(4) as usize;
+        // Title, blank, four checks with hints, drop line, blank, settings
+        let top = height.saturating_sub(ROWS as u16 + 16) / 2;
+        print_centered(stdout, top, theme.title, &format!("{}{}", theme.pick("👋 ", ""), tr("setup.title")))?;
+        print_centered(stdout, top + 1, theme.muted, &truncate(tr("setup.subtitle"), max_len))?;
+
+        let left = width.saturating_sub(max_len.min(70) as u16) / 2;
+        let mut y = top + 3;
+        for check in &self.checks {
+            let (mark, color) = match check.status {
+                Status::Pass => (theme.pick("✔", "ok"), theme.success),
+                Status::Warn => (theme.pick("!", "!!"), theme.warning),
+                Status::Fail => (theme.pick("✘", "--"), theme.error),
+            };
+            print_at(stdout, left, y, color, &truncate(&format!("{} {}{}", mark, pad(check.name, 12), check.detail), max_len))?;
+            y += 1;
+            if let Some(hint) = &check.hint {
+                print_at(stdout, left + 3, y, theme.muted, &truncate(hint, max_len.saturating_sub(3)))?;
+                y += 1;
+            }
+        }
+        let (drop, color) = match &self.dropped {
+            Some(name) => (trf("setup.dropped", &[("name", name)]), theme.success),
+            None => (tr("setup.try_drop").to_string(), theme.accent),
+        };
+        print_at(stdout, left, y + 1, color, &truncate(&drop, max_len))?;
+
+        let choice = |name: &str, value: &str| format!("{}{}", pad(name, 16), value);
+        let items = [
+            choice(tr("setup.clipboard"), tr(if self.clipboard == ClipboardMode::Osc52 { "setup.osc52" } else { "setup.auto" })),
+            choice(tr("setup.drawing"), tr(if self.ascii { "setup.ascii" } else { "setup.emoji" })),
+            choice(
+                tr("setup.notify"),
+                tr(match self.notify {
+                    Notify::Off => "setup.notify_off",
+                    Notify::Bell => "setup.notify_bell",
+                    Notify::Sound => "setup.notify_sound",
+                }),
+            ),
+            choice(tr("setup.start"), tr(if self.show_recent { "setup.start_recent" } else { "setup.start_drop" })),
+            choice(tr("setup.hidpi"), tr(if self.retina { "setup.hidpi_halve" } else { "setup.hidpi_keep" })),
+        ]
+        .map(|text| ListItem { text, color: theme.text });
+        draw_list(stdout, theme, left, y + 3, items.len(), &items, self.selected)?;
+
+        if let Some(error) = &self.error {
+            print_centered(stdout, y + 4 + ROWS as u16, theme.error, &truncate(error, max_len))?;
+        }
+        draw_footer(stdout, theme, theme.pick(tr("setup.footer"), tr("setup.footer_ascii")))
+    }
+}
+
+fn print_at(stdout: &mut io::Stdout, x: u16, y: u16, color: Color, text: &str) -> Result<()> {
+    queue!(stdout, cursor::MoveTo(x, y), SetForegroundColor(color), Print(text), ResetColor)?;
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use image::metadata::Orientation;
+use std::path::{Path, PathBuf};
+
+use crate::bundle::Bundle;
+use crate::cache::Cache;
+use crate::config::Config;
+use crate::exit::Exit;
+use crate::journal::Journal;
+use crate::orient;
+use crate::qr::{self, UploadQr};
+use crate::optimize::{optimize, optimize_favicons, optimize_srcset, Delivery, OptimizeError, Outcome};
+use crate::say;
+
+// `imgopt optimize <file>...`: optimizes each file and copies it, like a drop
+// in the TUI, then exits. One status line per file on stdout and errors on
+// stderr; the exit status is that of the first file that failed. [[rules]]
+// matching a file's name apply to it. Batches keep a journal, and with
+// `resume` the files it lists as done are skipped. With a `bundle`, the
+// saved files are packed into it as they are done.
+pub fn run_optimize(paths: &[PathBuf], config: &Config, resume: bool, mut bundle: Option<Bundle>) -> Exit {
+    let cache = Cache::default();
+    let mut journal = (paths.len() > 1 || resume).then(|| Journal::open(paths, resume));
+    if resume {
+        let done = paths.iter().filter(|path| journal.as_ref().is_some_and(|journal| journal.is_done(path))).count();
+        match done {
+            0 => say("Nothing to resume, starting with the first file"),
+            done => say(&format!("Resuming: {} of {} files already done", done, paths.len())),
+        }
+    }
+    let mut exit = Exit::Success;
+    for path in paths {
+        if journal.as_ref().is_some_and(|journal| journal.is_done(path)) {
+            continue;
+        }
+        let outcome = optimize(path, &config.for_file(path), &cache, &|_| true);
+        let status = Exit::of(&outcome);
+        if exit == Exit::Success {
+            exit = status;
+        }
+        if let (Some(journal), Ok(_)) = (&mut journal, &outcome) {
+            journal.finished(path);
+        }
+        if let Some(bundle) = &mut bundle {
+            bundle.add(path, &outcome);
+        }
+
+        let outcome = match outcome {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                eprintln!("imgopt: {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        for warning in &outcome.warnings {
+            eprintln!("imgopt: {}: {}", path.display(), warning);
+        }
+        let size = format!(
+            "{}x{} -> {}x{}",
+            outcome.original.0, outcome.original.1, outcome.optimized.0, outcome.optimized.1
+        );
+        match outcome.delivery {
+            Delivery::Clipboard => say(&format!("{}: {}, copied to clipboard", path.display(), size)),
+            Delivery::Saved(saved) => say(&format!("{}: {}, saved to {}", path.display(), size, saved.display())),
+            // The image is the output
+            Delivery::Stdout => {}
+            Delivery::Written { dir, files, .. } => say(&format!("{}: wrote {} to {}", path.display(), files.join(", "), dir.display())),
+            Delivery::Uploaded { url, copied } => {
+                let code = qr::lines(&url, false).filter(|_| config.upload_qr != UploadQr::Off);
+                let copied_what = if code.is_some() { config.upload_qr.copied() } else { "URL" };
+                match copied {
+                    Ok(()) => say(&format!("{}: {}, uploaded to {} ({} copied)", path.display(), size, url, copied_what)),
+                    Err(e) => eprintln!("imgopt: {}: uploaded to {}, but could not copy the {}: {}", path.display(), url, copied_what, e),
+                }
+                for line in code.unwrap_or_default() {
+                    say(&line);
+                }
+            }
+            Delivery::Fallback { reason, saved } => match saved {
+                Ok(saved) => eprintln!("imgopt: {}: clipboard copy failed ({}), saved to {}", path.display(), reason, saved.display()),
+                Err(e) => eprintln!("imgopt: {}: clipboard copy failed ({}) and saving failed too: {}", path.display(), reason, e),
+            },
+        }
+    }
+    // Failed files are tried again on --resume
+    if let Some(journal) = journal.filter(|_| exit == Exit::Success) {
+        journal.remove();
+    }
+    if let Some(bundle) = bundle {
+        match bundle.finish(config) {
+            Ok((archive, count)) => {
+                let files = if count == 1 { "file" } else { "files" };
+                say(&format!("Bundled {} {} and a manifest into {}", count, files, archive.display()))
+            }
+            Err(e) => {
+                eprintln!("imgopt: bundle: {}", e);
+                if exit == Exit::Success {
+                    exit = Exit::Failure;
+                }
+            }
+        }
+    }
+    exit
+}
+
+// `imgopt jxl <file>...`: like `imgopt optimize --to file`, but JPEG XL. Sizes
+// are in bytes here, since saving bytes is the point.
+#[cfg(feature = "jxl")]
+pub fn run_jxl(paths: &[PathBuf], lossless_jpeg: bool, config: &Config) -> Exit {
+    let cache = Cache::default();
+    let mut exit = Exit::Success;
+    for path in paths {
+        let outcome = crate::jxl::optimize_jxl(path, lossless_jpeg, &config.for_file(path), &cache, &|_| true);
+        let status = Exit::of(&outcome);
+        if exit == Exit::Success {
+            exit = status;
+        }
+        match outcome {
+            Ok(Outcome { delivery: Delivery::Saved(saved), .. }) => {
+                let bytes = |path: &Path| std::fs::metadata(path).map(|m| crate::optimize::format_bytes(m.len())).unwrap_or_default();
+                say(&format!("{}: {} -> {}, saved to {}", path.display(), bytes(path), bytes(&saved), saved.display()))
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("imgopt: {}: {}", path.display(), e),
+        }
+    }
+    exit
+}
+
+// `imgopt orient <file>...`: writes <name>_oriented.jpg copies turned by
+// --rotate, then mirrored by --flip, through the orientation tag alone
+pub fn run_orient(paths: &[PathBuf], rotate: Option<&str>, flip: Option<&str>) -> Exit {
+    let none = Orientation::NoTransforms;
+    let turn = rotate.and_then(orient::rotation).unwrap_or(none);
+    let change = orient::combine(turn, flip.and_then(orient::flip).unwrap_or(none));
+    let mut exit = Exit::Success;
+    for path in paths {
+        match orient::write_copy(path, change) {
+            Ok(copy) => say(&format!("{}: saved to {}", path.display(), copy.display())),
+            Err(e) => {
+                if exit == Exit::Success {
+                    exit = Exit::error(&e);
+                }
+                eprintln!("imgopt: {}: {}", path.display(), e);
+            }
+        }
+    }
+    exit
+}
+
+// `imgopt favicon <file>`: writes the [favicon] set from one image and lists
+// what was written
+pub fn run_favicon(path: &Path, out: Option<&Path>, config: &Config) -> Exit {
+    report_written(path, optimize_favicons(path, out, config, &|_| true))
+}
+
+// `imgopt srcset <file>`: the same for the [srcset] widths, copying the HTML
+pub fn run_srcset(path: &Path, out: Option<&Path>, config: &Config) -> Exit {
+    report_written(path, optimize_srcset(path, out, config, &|_| true))
+}
+
+fn report_written(path: &Path, outcome: Result<Outcome, OptimizeError>) -> Exit {
+    let exit = Exit::of(&outcome);
+    match outcome {
+        Ok(outcome) => {
+            for warning in &outcome.warnings {
+                eprintln!("imgopt: {}: {}", path.display(), warning);
+            }
+            if let Delivery::Written { dir, files, copied } = outcome.delivery {
+                say(&format!("Wrote to {}:", dir.display()));
+                for file in files {
+                    say(&format!("  {}", file));
+                }
+                match copied {
+                    Some(Ok(())) => say("HTML snippet copied to clipboard"),
+                    Some(Err(e)) => eprintln!("imgopt: {}: could not copy the HTML snippet: {}", path.display(), e),
+                    None => {}
+                }
+            }
+        }
+        Err(e) => eprintln!("imgopt: {}: {}", path.display(), e),
+    }
+    exit
+}
@@ -0,0 +1,964 @@
+use fast_image_resize::{self as fir, ResizeAlg, ResizeOptions, Resizer};
+use image::{imageops::FilterType, DynamicImage, ImageError, ImageFormat, ImageReader, RgbaImage};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::archive;
+use crate::clipboard::{self, copy_text};
+use crate::cloud;
+use crate::cache::Cache;
+use crate::config::{Config, Limits, Resize};
+use crate::content::Content;
+use crate::deliver::{Deliver, Destination, EncodedPng, Naming, Output, TempFile, ToClipboard};
+use crate::diff::{diff, DiffSummary};
+use crate::favicon;
+use crate::history::Clip;
+use crate::job::{is_plain, ImageJob};
+use crate::i18n::tr;
+use crate::orient;
+use crate::pages;
+use crate::pasted;
+use crate::srcset;
+use crate::dpi::is_retina;
+use crate::grid::{self, Layout};
+use crate::hook;
+use crate::large::{decode_jpeg, decode_reduced};
+use crate::legibility;
+use crate::log;
+use crate::lossless;
+use crate::palette::dominant_colors;
+use crate::pipeline::Pipeline;
+use crate::psd;
+use crate::raw;
+use crate::video;
+
+#[derive(Debug)]
+pub enum OptimizeError {
+    NotFound,
+    // Not an image format we can decode
+    Unsupported(String),
+    Unreadable(String),
+    TooLarge(String),
+    Transform(String),
+    // Writing a set of files (favicons) failed
+    Output(String),
+    Cancelled,
+}
+
+impl fmt::Display for OptimizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptimizeError::NotFound => write!(f, "file not found"),
+            OptimizeError::Unsupported(reason) => write!(f, "unsupported format: {}", reason),
+            OptimizeError::Unreadable(reason) => write!(f, "could not open image: {}", reason),
+            OptimizeError::TooLarge(reason) => write!(f, "image too large: {}", reason),
+            OptimizeError::Transform(reason) => write!(f, "transform failed: {}", reason),
+            OptimizeError::Output(reason) => write!(f, "could not write output: {}", reason),
+            OptimizeError::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for OptimizeError {}
+
+// Coarse steps of one optimization, reported as they start
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    // Fetching a file a sync client had offloaded (see cloud.rs)
+    Downloading,
+    Decoding,
+    // OCR of the result, when [legibility] is on
+    Reading,
+    Denoising,
+    Resizing,
+    Encoding,
+    PostProcess,
+    Uploading,
+    Saving,
+    Clipboard,
+}
+
+impl Stage {
+    pub fn label(self) -> &'static str {
+        match self {
+            Stage::Downloading => tr("stage.downloading"),
+            Stage::Decoding => tr("stage.decoding"),
+            Stage::Reading => tr("stage.reading"),
+            Stage::Denoising => tr("stage.denoising"),
+            Stage::Resizing => tr("stage.resizing"),
+            Stage::Encoding => tr("stage.encoding"),
+            Stage::PostProcess => tr("stage.post_process"),
+            Stage::Uploading => tr("stage.uploading"),
+            Stage::Saving => tr("stage.saving"),
+            Stage::Clipboard => tr("stage.clipboard"),
+        }
+    }
+
+    // Rough share of the total work done before this stage starts
+    pub fn fraction(self) -> f32 {
+        match self {
+            Stage::Downloading | Stage::Decoding => 0.0,
+            Stage::Denoising => 0.2,
+            Stage::Resizing => 0.4,
+            Stage::Reading => 0.6,
+            Stage::Encoding => 0.7,
+            Stage::PostProcess => 0.8,
+            Stage::Uploading => 0.85,
+            Stage::Saving => 0.9,
+            Stage::Clipboard => 0.9,
+        }
+    }
+}
+
+pub enum Delivery {
+    Clipboard,
+    // Uploaded; `copied` says whether the URL made it onto the clipboard
+    Uploaded {
+        url: String,
+        copied: std::result::Result<(), String>,
+    },
+    // Written next to the input
+    Saved(PathBuf),
+    Stdout,
+    // Several files written into one folder, by name. `copied` is set when
+    // something describing them (an HTML snippet) was copied too.
+    Written {
+        dir: PathBuf,
+        files: Vec<String>,
+        copied: Option<std::result::Result<(), String>>,
+    },
+    // The clipboard copy failed; `saved` is where the image went instead
+    Fallback {
+        reason: String,
+        saved: std::result::Result<PathBuf, String>,
+    },
+}
+
+pub struct Outcome {
+    pub original: (u32, u32),
+    pub optimized: (u32, u32),
+    pub delivery: Delivery,
+    // Served from the cache instead of decoding and resizing again
+    pub cached: bool,
+    // Copied as-is because it was already within bounds
+    pub passed_through: bool,
+    // Dominant colors, most common first
+    pub palette: Vec<[u8; 3]>,
+    // Set when the output is a diff of two images
+    pub diff: Option<DiffSummary>,
+    // Number of images when the output is a grid of them
+    pub grid: Option<usize>,
+    // What the auto preset took the image for
+    pub content: Option<Content>,
+    // Steps that failed without stopping delivery (post-process hook, upload)
+    pub warnings: Vec<String>,
+    // What went onto the clipboard, for the clipboard history
+    pub clip: Option<Clip>,
+    // Input and output file sizes; None when the output is a set of files
+    pub bytes: Option<(u64, u64)>,
+}
+
+// Loads, resizes and delivers one image. Everything the UI needs to report
+// comes back in the Outcome, so the TUI and plain mode share this path.
+// `progress` is called as each stage starts; returning false cancels the job
+// before anything reaches the clipboard or disk.
+pub fn optimize(
+    path: &Path,
+    config: &Config,
+    cache: &Cache,
+    progress: &dyn Fn(Stage) -> bool,
+) -> std::result::Result<Outcome, OptimizeError> {
+    let path: &Path = &cloud::fetch(path, progress)?;
+    archive(&[path], config)?;
+
+    if config.is_gif() && video::is_video(path) {
+        return animate(path, config, progress);
+    }
+
+    // Already small enough: hand over the original file untouched (see job.rs),
+    // if the clipboard takes it as it is. Sizing up a RAW file means reading all
+    // of it, and it never passes through anyway.
+    let probed = (is_plain(config) && !raw::is_raw(path)).then(|| ImageJob::probe(path, config.page).ok()).flatten();
+    let untouched = probed.and_then(|job| {
+        let format = job.plan(config).passthrough?;
+        let takes = config.destination() != Destination::Clipboard || clipboard::takes_original(format, config.clipboard);
+        takes.then_some((format, job.stored))
+    });
+    if let Some((format, original)) = untouched {
+        log(&format!("Passing through: {}", path.display()));
+        let mut warnings = Vec::new();
+        let naming = Naming { source: path, suffix: "optimized" };
+        let repacked = match format {
+            ImageFormat::Jpeg if config.passthrough.lossless_jpeg => {
+                lossless::repack(path, original).map_err(|e| log(&format!("Not repacking {}: {}", path.display(), e))).ok()
+            }
+            _ => None,
+        };
+        let handed = repacked.as_ref().map_or(path, |repacked| repacked.path.as_path());
+        let delivery = deliver(&Output::Original { path: handed, format }, &naming, config, progress, &mut warnings)?;
+        // Small by definition, so decoding it just for the palette is cheap
+        let palette = image::open(path)
+            .map(|img| dominant_colors(&img, config.palette_size))
+            .unwrap_or_default();
+        let clip = match delivery {
+            Delivery::Clipboard => std::fs::read(handed).ok().map(|bytes| Clip::Original { bytes, format }),
+            _ => None,
+        };
+        let bytes = (file_size(&[path]), file_size(&[handed]));
+        return Ok(Outcome {
+            original,
+            optimized: repacked.as_ref().map_or(original, |repacked| repacked.size),
+            delivery,
+            cached: false,
+            passed_through: true,
+            palette,
+            diff: None,
+            grid: None,
+            content: None,
+            warnings,
+            clip,
+            bytes: Some(bytes),
+        });
+    }
+
+    let config = &config.with_source_profile(path);
+    let rendered = render(path, config, cache, progress)?;
+    let output = rendered.image;
+    let (delivery, mut warnings, encoded) = encode_and_deliver(&output, Naming { source: path, suffix: "optimized" }, config, progress)?;
+    warnings.extend(rendered.warnings);
+    // Not picked in the TUI or with --page, so say which one it was
+    let pages = if config.page.is_none() { pages::sizes(path).len() } else { 0 };
+    if pages > 1 {
+        warnings.push(format!("has {} pages, only the first was used", pages));
+    }
+    let optimized = (output.width(), output.height());
+    let clip = clip(&delivery, output);
+    Ok(Outcome {
+        original: rendered.original,
+        optimized,
+        delivery,
+        cached: rendered.cached,
+        passed_through: false,
+        palette: rendered.palette,
+        diff: None,
+        grid: None,
+        content: rendered.content,
+        warnings,
+        clip,
+        bytes: Some((file_size(&[path]), encoded)),
+    })
+}
+
+// The gif preset's take on a screen recording (see [gif]): its start as an
+// animation no larger than the preset allows, delivered as it came from ffmpeg
+fn animate(path: &Path, config: &Config, progress: &dyn Fn(Stage) -> bool) -> std::result::Result<Outcome, OptimizeError> {
+    checkpoint(progress, Stage::Decoding)?;
+    let (original, duration) = video::probe(path).map_err(OptimizeError::Unreadable)?;
+    // Every extra pixel is paid for in each frame
+    let resize = Resize { never_enlarge: true, ..config.resize() };
+    let optimized = output_dimensions(original.0, original.1, is_retina(path), &resize);
+
+    checkpoint(progress, Stage::Encoding)?;
+    let format = config.gif.format.image_format();
+    let animation = TempFile::new(format.extensions_str()[0]);
+    video::animate(path, optimized, &config.gif, &animation.path).map_err(OptimizeError::Output)?;
+    log(&format!("Animated {} at {}x{}", path.display(), optimized.0, optimized.1));
+
+    let mut warnings = Vec::new();
+    if duration as f64 > config.gif.max_seconds * 1000.0 {
+        warnings.push(format!("only the first {}s of {:.1}s were used", config.gif.max_seconds, duration as f64 / 1000.0));
+    }
+    let naming = Naming { source: path, suffix: "optimized" };
+    let delivery = deliver(&Output::Original { path: &animation.path, format }, &naming, config, progress, &mut warnings)?;
+    let clip = match delivery {
+        Delivery::Clipboard => std::fs::read(&animation.path).ok().map(|bytes| Clip::Original { bytes, format }),
+        _ => None,
+    };
+    Ok(Outcome {
+        original,
+        optimized,
+        delivery,
+        cached: false,
+        passed_through: false,
+        palette: Vec::new(),
+        diff: None,
+        grid: None,
+        content: None,
+        warnings,
+        clip,
+        bytes: Some((file_size(&[path]), file_size(&[&animation.path]))),
+    })
+}
+
+// An optimized image before it is encoded or delivered anywhere
+pub struct Rendered {
+    pub image: DynamicImage,
+    pub original: (u32, u32),
+    pub cached: bool,
+    pub palette: Vec<[u8; 3]>,
+    // What the auto preset took the image for
+    pub content: Option<Content>,
+    pub warnings: Vec<String>,
+}
+
+// Decodes, resizes, transforms and frames one image (see pipeline.rs). Shared
+// by `optimize` and the HTTP server, which sends the result back instead of
+// copying it.
+pub fn render(
+    path: &Path,
+    config: &Config,
+    cache: &Cache,
+    progress: &dyn Fn(Stage) -> bool,
+) -> std::result::Result<Rendered, OptimizeError> {
+    let mut rendered = Pipeline::new(config).run(path, cache, progress)?;
+    let legibility = &config.legibility;
+    if !legibility.enabled {
+        return Ok(rendered);
+    }
+    let (mut retried, mut pass) = (config.clone(), 0);
+    loop {
+        checkpoint(progress, Stage::Reading)?;
+        let confidence = match legibility::read(&rendered.image) {
+            Ok(Some(confidence)) => confidence,
+            Ok(None) => return Ok(rendered),
+            Err(e) => {
+                rendered.warnings.push(format!("legibility not checked: {}", e));
+                return Ok(rendered);
+            }
+        };
+        let (width, height) = (rendered.image.width(), rendered.image.height());
+        log(&format!("Legibility of {} at {}x{}: {:.0}%", path.display(), width, height, confidence));
+        if confidence >= legibility.min_confidence as f64 {
+            return Ok(rendered);
+        }
+        pass += 1;
+        let Some(next) = legibility.retry(&retried, pass, (width, height)) else {
+            rendered.warnings.push(format!("text may be hard to read, OCR confidence is {:.0}%", confidence));
+            return Ok(rendered);
+        };
+        retried = next;
+        rendered = Pipeline::new(&retried).run(path, cache, progress)?;
+    }
+}
+
+// Compares two images at the output size of `after` and delivers a picture of
+// what changed instead of either image
+pub fn optimize_diff(
+    before: &Path,
+    after: &Path,
+    config: &Config,
+    progress: &dyn Fn(Stage) -> bool,
+) -> std::result::Result<Outcome, OptimizeError> {
+    let (before, after) = (&cloud::fetch(before, progress)?, &cloud::fetch(after, progress)?);
+    archive(&[before, after], config)?;
+
+    let resize = config.resize();
+    checkpoint(progress, Stage::Decoding)?;
+    let (after_img, original) = decode(after, &config.limits, &resize, None, config.video_frame)?;
+    let (before_img, _) = decode(before, &config.limits, &resize, None, config.video_frame)?;
+
+    // Both sides at the same size, even if the before image was cropped differently
+    checkpoint(progress, Stage::Resizing)?;
+    let (width, height) = output_dimensions(original.0, original.1, is_retina(after), &resize);
+    let (marked, summary) = diff(
+        &resample(&before_img, width, height).to_rgba8(),
+        &resample(&after_img, width, height).to_rgba8(),
+    );
+    log(&format!("Diff: {:.1}% similar, {} changed regions", summary.similarity, summary.regions));
+
+    let marked = DynamicImage::ImageRgba8(marked);
+    let (delivery, warnings, encoded) = encode_and_deliver(&marked, Naming { source: after, suffix: "diff" }, config, progress)?;
+    let clip = clip(&delivery, marked);
+    Ok(Outcome {
+        original,
+        optimized: (width, height),
+        delivery,
+        cached: false,
+        passed_through: false,
+        palette: Vec::new(),
+        diff: Some(summary),
+        grid: None,
+        content: None,
+        warnings,
+        clip,
+        bytes: Some((file_size(&[after]), encoded)),
+    })
+}
+
+// Lays several images out on one canvas, labeled with their file names, and
+// delivers that. The whole grid is sized like a single image would be.
+pub fn optimize_grid(paths: &[PathBuf], config: &Config, progress: &dyn Fn(Stage) -> bool) -> std::result::Result<Outcome, OptimizeError> {
+    let paths = paths.iter().map(|path| cloud::fetch(path, progress)).collect::<std::result::Result<Vec<_>, _>>()?;
+    archive(&paths.iter().map(PathBuf::as_path).collect::<Vec<_>>(), config)?;
+
+    let resize = config.resize();
+    checkpoint(progress, Stage::Decoding)?;
+    let mut images = Vec::new();
+    for path in &paths {
+        let (img, original) = decode(path, &config.limits, &resize, None, config.video_frame)?;
+        let label = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        images.push((label, img, original));
+    }
+
+    checkpoint(progress, Stage::Resizing)?;
+    let sizes: Vec<(u32, u32)> = images.iter().map(|(_, _, original)| *original).collect();
+    let full = Layout::new(&sizes, &config.grid);
+    let layout = full.scaled(&resize);
+    let tiles: Vec<(String, RgbaImage)> = images
+        .into_iter()
+        .map(|(label, img, original)| {
+            let (width, height) = layout.fit(original);
+            (label, resample(&img, width, height).to_rgba8())
+        })
+        .collect();
+    let background = config.grid.background.unwrap_or_else(|| config.appearance.matte());
+    let composed = DynamicImage::ImageRgba8(grid::compose(&layout, &tiles, background));
+    log(&format!("Grid: {} images at {}x{}", tiles.len(), layout.size.0, layout.size.1));
+
+    let (delivery, warnings, encoded) = encode_and_deliver(&composed, Naming { source: &paths[0], suffix: "grid" }, config, progress)?;
+    let clip = clip(&delivery, composed);
+    Ok(Outcome {
+        original: full.size,
+        optimized: layout.size,
+        delivery,
+        cached: false,
+        passed_through: false,
+        palette: Vec::new(),
+        diff: None,
+        grid: Some(tiles.len()),
+        content: None,
+        warnings,
+        clip,
+        bytes: Some((file_size(&paths.iter().map(PathBuf::as_path).collect::<Vec<_>>()), encoded)),
+    })
+}
+
+// Turns one square logo into favicon.ico and apple-touch icons (see
+// favicon.rs), written to <name>_favicons next to the input or to `out`.
+// Nothing goes to the clipboard.
+pub fn optimize_favicons(
+    path: &Path,
+    out: Option<&Path>,
+    config: &Config,
+    progress: &dyn Fn(Stage) -> bool,
+) -> std::result::Result<Outcome, OptimizeError> {
+    let path: &Path = &cloud::fetch(path, progress)?;
+    archive(&[path], config)?;
+
+    let largest = config.favicon.largest();
+    let resize = Resize { min_dimension: largest, max_dimension: largest, ..Resize::default() };
+    checkpoint(progress, Stage::Decoding)?;
+    let (img, original) = decode(path, &config.limits, &resize, config.page, config.video_frame)?;
+
+    let mut warnings = Vec::new();
+    if original.0 != original.1 {
+        warnings.push(format!("{}x{} isn't square, padded with transparency", original.0, original.1));
+    }
+    if original.0.max(original.1) < largest {
+        warnings.push(format!("smaller than {}px, the larger icons are upscaled", largest));
+    }
+
+    checkpoint(progress, Stage::Resizing)?;
+    let icon = favicon::square(&img);
+
+    checkpoint(progress, Stage::Saving)?;
+    let dir = out.map(Path::to_path_buf).unwrap_or_else(|| output_dir(path, "favicons"));
+    let files = favicon::write_set(&icon, &dir, &config.favicon, config.jpeg_quality)
+        .map_err(|e| OptimizeError::Output(format!("{}: {}", dir.display(), e)))?;
+    log(&format!("Favicons: {} files in {}", files.len(), dir.display()));
+    Ok(Outcome {
+        original,
+        optimized: (largest, largest),
+        delivery: Delivery::Written { dir, files, copied: None },
+        cached: false,
+        passed_through: false,
+        palette: Vec::new(),
+        diff: None,
+        grid: None,
+        content: None,
+        warnings,
+        clip: None,
+        bytes: None,
+    })
+}
+
+// Writes the image at each [srcset] width as WebP plus a JPEG or PNG fallback
+// into <name>_srcset next to the input (or `out`), and copies a <picture>
+// snippet that uses them
+pub fn optimize_srcset(
+    path: &Path,
+    out: Option<&Path>,
+    config: &Config,
+    progress: &dyn Fn(Stage) -> bool,
+) -> std::result::Result<Outcome, OptimizeError> {
+    let path: &Path = &cloud::fetch(path, progress)?;
+    archive(&[path], config)?;
+
+    // Reduced while decoding only as far as the widest width allows
+    let widest = config.srcset.widths.iter().copied().max().unwrap_or(u32::MAX);
+    let resize = Resize { min_dimension: 1, max_dimension: u32::MAX, max_width: Some(widest), never_enlarge: true, ..Resize::default() };
+    checkpoint(progress, Stage::Decoding)?;
+    let (img, original) = decode(path, &config.limits, &resize, config.page, config.video_frame)?;
+
+    let mut warnings = Vec::new();
+    if original.0 < config.srcset.widths.iter().copied().min().unwrap_or(0) {
+        warnings.push(format!("narrower than every srcset width, written at {}px only", original.0));
+    }
+
+    checkpoint(progress, Stage::Encoding)?;
+    let dir = out.map(Path::to_path_buf).unwrap_or_else(|| output_dir(path, "srcset"));
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("image");
+    let config = &config.with_source_profile(path);
+    let (files, snippet) = srcset::write_set(&img, &dir, stem, &config.srcset, config.jpeg_quality, config.icc_profile())
+        .map_err(|e| OptimizeError::Output(format!("{}: {}", dir.display(), e)))?;
+    log(&format!("Srcset: {} files in {}", files.len(), dir.display()));
+
+    checkpoint(progress, Stage::Clipboard)?;
+    let copied = copy_text(&snippet, config.clipboard).map_err(|e| e.to_string());
+    if let Err(e) = &copied {
+        log(&format!("Snippet copy failed: {}", e));
+    }
+    let widest = srcset::widths(img.width(), &config.srcset).last().copied().unwrap_or(img.width());
+    let height = (img.height() as f64 * widest as f64 / img.width() as f64).round() as u32;
+    Ok(Outcome {
+        original,
+        optimized: (widest, height.max(1)),
+        delivery: Delivery::Written { dir, files, copied: Some(copied) },
+        cached: false,
+        passed_through: false,
+        palette: Vec::new(),
+        diff: None,
+        grid: None,
+        content: None,
+        warnings,
+        clip: None,
+        bytes: None,
+    })
+}
+
+// Copies the inputs to `archive_dir` first, when it's set. Nothing happens
+// to an input that couldn't be archived.
+pub fn archive(paths: &[&Path], config: &Config) -> std::result::Result<(), OptimizeError> {
+    let Some(dir) = config.archive_dir() else {
+        return Ok(());
+    };
+    for path in paths {
+        archive::keep(path, &dir).map_err(|e| OptimizeError::Output(format!("could not archive the original: {}", e)))?;
+    }
+    Ok(())
+}
+
+// Together, for inputs like a grid's; 0 for what can't be read
+pub fn file_size(paths: &[&Path]) -> u64 {
+    paths.iter().filter_map(|path| std::fs::metadata(path).ok()).map(|m| m.len()).sum()
+}
+
+// Kept for the clipboard history when that's where the image went
+fn clip(delivery: &Delivery, image: DynamicImage) -> Option<Clip> {
+    matches!(delivery, Delivery::Clipboard).then_some(Clip::Image(image))
+}
+
+pub fn checkpoint(progress: &dyn Fn(Stage) -> bool, stage: Stage) -> std::result::Result<(), OptimizeError> {
+    if progress(stage) {
+        Ok(())
+    } else {
+        Err(OptimizeError::Cancelled)
+    }
+}
+
+// Encodes a finished image as PNG, runs the post-process hook on it and hands
+// it to the configured destination (see deliver.rs). When that fails, the image
+// is copied to the clipboard instead and the failure returned as a warning, as
+// is a failed hook. Also returns the size of the encoded file.
+fn encode_and_deliver(
+    img: &DynamicImage,
+    naming: Naming,
+    config: &Config,
+    progress: &dyn Fn(Stage) -> bool,
+) -> std::result::Result<(Delivery, Vec<String>, u64), OptimizeError> {
+    checkpoint(progress, Stage::Encoding)?;
+    let mut warnings = Vec::new();
+    let png = match EncodedPng::new(img, config) {
+        Ok(png) => png,
+        // Nothing to deliver; this is what the clipboard fallback reports
+        Err(e) => {
+            log(&format!("Encoding failed: {}", e));
+            let reason = format!("could not encode: {}", e);
+            return Ok((Delivery::Fallback { reason: reason.clone(), saved: Err(reason) }, warnings, 0));
+        }
+    };
+
+    if let Some(command) = &config.post_process {
+        checkpoint(progress, Stage::PostProcess)?;
+        if let Err(e) = hook::run(command, &png.path, (img.width(), img.height())) {
+            log(&format!("Post-process hook failed: {}", e));
+            warnings.push(format!("Post-process hook failed: {}", e));
+        }
+    }
+
+    // Measured after the hook, which may have recompressed it
+    let bytes = file_size(&[&png.path]);
+    let output = Output::Encoded { image: img, png: &png.path };
+    let delivery = deliver(&output, &naming, config, progress, &mut warnings)?;
+    Ok((delivery, warnings, bytes))
+}
+
+// Sends `output` to the configured destination, falling back to the clipboard
+fn deliver(
+    output: &Output,
+    naming: &Naming,
+    config: &Config,
+    progress: &dyn Fn(Stage) -> bool,
+    warnings: &mut Vec<String>,
+) -> std::result::Result<Delivery, OptimizeError> {
+    let destination = config.destination().deliverer(config);
+    checkpoint(progress, destination.stage())?;
+    match destination.deliver(output, naming, config) {
+        Ok(delivery) => return Ok(delivery),
+        Err(e) => {
+            log(&format!("Delivery failed: {}", e));
+            warnings.push(e.to_string());
+        }
+    }
+    checkpoint(progress, Stage::Clipboard)?;
+    // Saves a file itself when the copy fails, so this never errors
+    Ok(ToClipboard.deliver(output, naming, config).unwrap_or_else(|e| Delivery::Fallback {
+        reason: e.to_string(),
+        saved: Err(e.to_string()),
+    }))
+}
+
+// Checks file size and header dimensions before decoding anything, and caps
+// decoder allocations in case the header lies. Returns the image along with its
+// original dimensions, since very large inputs come back already reduced.
+// Upright, as the EXIF orientation says it should be shown. The original size
+// is as shown too. `page` picks one page of a TIFF or PDF (see pages.rs); the
+// first is used without one. `frame` picks the frame of a video.
+pub fn decode(
+    path: &Path,
+    limits: &Limits,
+    resize: &Resize,
+    page: Option<usize>,
+    frame: video::Frame,
+) -> std::result::Result<(DynamicImage, (u32, u32)), OptimizeError> {
+    let orientation = orient::read(path);
+    let sideways = orient::swaps_sides(orientation);
+    let (mut img, (width, height)) = decode_stored(path, limits, resize, sideways, page, frame)?;
+    img.apply_orientation(orientation);
+    Ok((img, if sideways { (height, width) } else { (width, height) }))
+}
+
+// The output size is worked out upright, so a sideways photo is reduced by
+// how its sides will end up
+fn upright_target(path: &Path, (width, height): (u32, u32), resize: &Resize, sideways: bool) -> (u32, u32) {
+    if sideways {
+        output_dimensions(height, width, is_retina(path), resize)
+    } else {
+        output_dimensions(width, height, is_retina(path), resize)
+    }
+}
+
+fn decode_stored(
+    path: &Path,
+    limits: &Limits,
+    resize: &Resize,
+    sideways: bool,
+    page: Option<usize>,
+    frame: video::Frame,
+) -> std::result::Result<(DynamicImage, (u32, u32)), OptimizeError> {
+    let unreadable = |e: &dyn std::fmt::Display| OptimizeError::Unreadable(e.to_string());
+    let undecodable = |e: ImageError| match e {
+        ImageError::Unsupported(e) => OptimizeError::Unsupported(e.to_string()),
+        ImageError::Limits(e) => OptimizeError::TooLarge(e.to_string()),
+        e => unreadable(&e),
+    };
+
+    // Only the one frame is decoded, so a long recording's size doesn't matter
+    if video::is_video(path) {
+        return decode_video(path, limits, frame);
+    }
+
+    let bytes = std::fs::metadata(path).map_err(|e| unreadable(&e))?.len();
+    if bytes > limits.max_input_bytes {
+        return Err(OptimizeError::TooLarge(format!(
+            "file is {}, the limit is {}",
+            format_bytes(bytes),
+            format_bytes(limits.max_input_bytes)
+        )));
+    }
+
+    if raw::is_raw(path) {
+        return decode_raw(path, limits, resize, sideways);
+    }
+    if psd::is_psd(path) {
+        return decode_psd(path, limits);
+    }
+    if pages::is_pdf(path) {
+        return decode_pdf(path, limits, page.unwrap_or(1));
+    }
+    if let Some(page) = page.filter(|&page| page != 1) {
+        return decode_page(path, limits, page);
+    }
+
+    let open = || ImageReader::open(path).and_then(|r| r.with_guessed_format()).map_err(|e| unreadable(&e));
+
+    let (width, height) = open()?.into_dimensions().map_err(undecodable)?;
+    let pixels = width as u64 * height as u64;
+
+    let factor = reduction_factor(width, height, upright_target(path, (width, height), resize, sideways));
+    if factor >= 2 && pixels <= limits.max_scaled_pixels {
+        if let Some(decoded) = decode_reduced(path, width, height, factor) {
+            return decoded.map(|img| (img, (width, height))).map_err(|e| unreadable(&e));
+        }
+    }
+
+    if pixels > limits.max_pixels {
+        return Err(OptimizeError::TooLarge(format!(
+            "{}x{} is {:.1} megapixels, the limit is {:.1}",
+            width,
+            height,
+            pixels as f64 / 1e6,
+            limits.max_pixels as f64 / 1e6
+        )));
+    }
+
+    let mut decoder_limits = image::Limits::default();
+    // Room for a 16-bit RGBA buffer of the largest allowed image
+    decoder_limits.max_alloc = Some(limits.max_pixels.saturating_mul(8));
+
+    let mut reader = open()?;
+    reader.limits(decoder_limits);
+    let img = reader.decode().map_err(undecodable)?;
+    Ok((img, (width, height)))
+}
+
+// The JPEG preview inside a camera RAW file, reduced while decoding when the
+// output is much smaller
+fn decode_raw(
+    path: &Path,
+    limits: &Limits,
+    resize: &Resize,
+    sideways: bool,
+) -> std::result::Result<(DynamicImage, (u32, u32)), OptimizeError> {
+    let preview = raw::preview(path).map_err(OptimizeError::Unreadable)?;
+    let (width, height) = (preview.width, preview.height);
+    let pixels = width as u64 * height as u64;
+    if pixels > limits.max_scaled_pixels {
+        return Err(OptimizeError::TooLarge(format!("{}x{} preview is {:.1} megapixels", width, height, pixels as f64 / 1e6)));
+    }
+    let factor = reduction_factor(width, height, upright_target(path, (width, height), resize, sideways));
+    let img = decode_jpeg(preview.bytes.as_slice(), width, height, factor).map_err(OptimizeError::Unreadable)?;
+    Ok((img, (width, height)))
+}
+
+// The flattened copy inside a Photoshop document
+fn decode_psd(path: &Path, limits: &Limits) -> std::result::Result<(DynamicImage, (u32, u32)), OptimizeError> {
+    let (width, height) = psd::dimensions(path).map_err(OptimizeError::Unreadable)?;
+    let pixels = width as u64 * height as u64;
+    if pixels > limits.max_pixels {
+        return Err(OptimizeError::TooLarge(format!("{}x{} is {:.1} megapixels", width, height, pixels as f64 / 1e6)));
+    }
+    let img = psd::decode(path).map_err(OptimizeError::Unreadable)?;
+    Ok((img, (width, height)))
+}
+
+// A later page of a TIFF. Other formats only have the one.
+fn decode_page(path: &Path, limits: &Limits, page: usize) -> std::result::Result<(DynamicImage, (u32, u32)), OptimizeError> {
+    let sizes = pages::read_sizes(path).map_err(OptimizeError::Unreadable)?;
+    let (width, height) = pages::size_of(&sizes, page).map_err(OptimizeError::Unreadable)?;
+    let pixels = width as u64 * height as u64;
+    if pixels > limits.max_pixels {
+        return Err(OptimizeError::TooLarge(format!("{}x{} is {:.1} megapixels", width, height, pixels as f64 / 1e6)));
+    }
+    let data = pages::tiff_page(path, page).map_err(OptimizeError::Unreadable)?;
+    let img = image::load_from_memory_with_format(&data, ImageFormat::Tiff).map_err(|e| OptimizeError::Unreadable(e.to_string()))?;
+    Ok((img, (width, height)))
+}
+
+// One page of a PDF, rendered at pages::PDF_DPI
+fn decode_pdf(path: &Path, limits: &Limits, page: usize) -> std::result::Result<(DynamicImage, (u32, u32)), OptimizeError> {
+    let sizes = pages::read_sizes(path).map_err(OptimizeError::Unreadable)?;
+    let (width, height) = pages::size_of(&sizes, page).map_err(OptimizeError::Unreadable)?;
+    let pixels = width as u64 * height as u64;
+    if pixels > limits.max_pixels {
+        return Err(OptimizeError::TooLarge(format!("{}x{} page is {:.1} megapixels", width, height, pixels as f64 / 1e6)));
+    }
+    let img = pages::render_pdf(path, page).map_err(OptimizeError::Unreadable)?;
+    let rendered = (img.width(), img.height());
+    Ok((img, rendered))
+}
+
+// One frame of a screen recording, through ffmpeg
+fn decode_video(path: &Path, limits: &Limits, frame: video::Frame) -> std::result::Result<(DynamicImage, (u32, u32)), OptimizeError> {
+    let ((width, height), _) = video::probe(path).map_err(OptimizeError::Unreadable)?;
+    let pixels = width as u64 * height as u64;
+    if pixels > limits.max_pixels {
+        return Err(OptimizeError::TooLarge(format!("{}x{} video is {:.1} megapixels", width, height, pixels as f64 / 1e6)));
+    }
+    let img = video::extract(path, frame).map_err(OptimizeError::Unreadable)?;
+    let extracted = (img.width(), img.height());
+    Ok((img, extracted))
+}
+
+// Lanczos3 through fast_image_resize, which uses SIMD where the CPU has it.
+// Falls back to `image`'s own resizer for pixel types it can't handle.
+pub fn resample(img: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+    let mut resized = DynamicImage::new(width, height, img.color());
+    let options = ResizeOptions::new().resize_alg(ResizeAlg::Convolution(fir::FilterType::Lanczos3));
+    match Resizer::new().resize(img, &mut resized, &options) {
+        Ok(()) => resized,
+        Err(e) => {
+            log(&format!("Fast resize unavailable ({}), using fallback", e));
+            img.resize_exact(width, height, FilterType::Lanczos3)
+        }
+    }
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1048575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0)),
+    }
+}
+
+// Retina captures are halved to logical pixels first when `retina` is on
+pub fn output_dimensions(width: u32, height: u32, retina: bool, resize: &Resize) -> (u32, u32) {
+    if resize.retina && retina {
+        return target_dimensions((width / 2).max(1), (height / 2).max(1), resize);
+    }
+    target_dimensions(width, height, resize)
+}
+
+// Scales the image so it fits the resize settings, keeping the aspect ratio
+pub fn target_dimensions(width: u32, height: u32, resize: &Resize) -> (u32, u32) {
+    let (w, h) = (width.max(1) as f64, height.max(1) as f64);
+    let longest = w.max(h);
+    let mut scale: f64 = 1.0;
+
+    // Fitted inside the canvas, whatever the other limits say; letterboxing fills the rest
+    if let Some((canvas_width, canvas_height)) = resize.canvas {
+        scale = (canvas_width as f64 / w).min(canvas_height as f64 / h);
+        if resize.never_enlarge {
+            scale = scale.min(1.0);
+        }
+        let side = |length: f64, limit: u32| ((length * scale).round() as u32).clamp(1, limit);
+        return (side(w, canvas_width), side(h, canvas_height));
+    }
+
+    // Enlarge until the longest (or, per-axis, the shortest) side reaches the minimum
+    let reference = if resize.longest_side_only { longest } else { w.min(h) };
+    if reference < resize.min_dimension as f64 {
+        scale = resize.min_dimension as f64 / reference;
+    }
+
+    // Every cap wins over enlarging
+    scale = scale.min(resize.max_dimension as f64 / longest);
+    if let Some(max_width) = resize.max_width {
+        scale = scale.min(max_width as f64 / w);
+    }
+    if let Some(max_height) = resize.max_height {
+        scale = scale.min(max_height as f64 / h);
+    }
+    if resize.never_enlarge {
+        scale = scale.min(1.0);
+    }
+
+    if scale == 1.0 {
+        return (width, height);
+    }
+    let side = |length: f64| ((length * scale).round() as u32).max(1);
+    (side(w), side(h))
+}
+
+// Anything at least twice the size we need can be reduced while decoding
+pub fn reduction_factor(width: u32, height: u32, (target_width, target_height): (u32, u32)) -> u32 {
+    (width.max(height) / (2 * target_width.max(target_height))).max(1)
+}
+
+// Keep the result around as a file when the clipboard is unavailable
+pub fn fallback_path(source: &Path, suffix: &str, extension: &str) -> PathBuf {
+    let pasted = pasted::stand_in(source);
+    let source = pasted.as_deref().unwrap_or(source);
+    let stem = source.file_stem().and_then(|s| s.to_str()).unwrap_or("image");
+    let name = format!("{}_{}.{}", stem, suffix, extension);
+    match folder(source) {
+        Some(dir) if is_writable(dir) => dir.join(name),
+        _ => std::env::temp_dir().join(name),
+    }
+}
+
+// A folder for several outputs, named like fallback_path's files
+pub fn output_dir(source: &Path, suffix: &str) -> PathBuf {
+    let pasted = pasted::stand_in(source);
+    let source = pasted.as_deref().unwrap_or(source);
+    let stem = source.file_stem().and_then(|s| s.to_str()).unwrap_or("image");
+    let name = format!("{}_{}", stem, suffix);
+    match folder(source) {
+        Some(dir) if is_writable(dir) => dir.join(name),
+        _ => std::env::temp_dir().join(name),
+    }
+}
+
+// The folder a file is in; a bare name like `a.png` is in the current one
+fn folder(path: &Path) -> Option<&Path> {
+    path.parent().map(|dir| if dir.as_os_str().is_empty() { Path::new(".") } else { dir })
+}
+
+fn is_writable(dir: &Path) -> bool {
+    std::fs::metadata(dir).map(|m| !m.permissions().readonly()).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resize(change: impl FnOnce(&mut Resize)) -> Resize {
+        let mut resize = Resize::default();
+        change(&mut resize);
+        resize
+    }
+
+    #[test]
+    fn images_are_scaled_to_fit_the_limits() {
+        let cases = [
+            ("already in range", (600, 400), Resize::default(), (600, 400)),
+            ("too large", (1000, 500), Resize::default(), (720, 360)),
+            ("too small", (200, 100), Resize::default(), (480, 240)),
+            ("empty", (0, 0), Resize::default(), (480, 480)),
+            ("shortest side enlarged, then capped", (600, 200), resize(|r| r.longest_side_only = false), (720, 240)),
+            ("max width", (1000, 500), resize(|r| r.max_width = Some(500)), (500, 250)),
+            ("max height", (600, 600), resize(|r| r.max_height = Some(300)), (300, 300)),
+            ("max height beats min dimension", (200, 400), resize(|r| r.max_height = Some(300)), (150, 300)),
+            ("never enlarged", (200, 100), resize(|r| r.never_enlarge = true), (200, 100)),
+            ("never enlarged, still reduced", (1000, 500), resize(|r| r.never_enlarge = true), (720, 360)),
+            ("canvas, enlarged", (400, 400), resize(|r| r.canvas = Some((800, 600))), (600, 600)),
+            ("canvas, reduced", (1600, 900), resize(|r| r.canvas = Some((800, 600))), (800, 450)),
+            ("canvas ignores the other limits", (100, 100), resize(|r| {
+                r.canvas = Some((1000, 1000));
+                r.max_width = Some(50);
+            }), (1000, 1000)),
+            ("canvas, never enlarged", (400, 400), resize(|r| {
+                r.canvas = Some((800, 600));
+                r.never_enlarge = true;
+            }), (400, 400)),
+        ];
+        for (name, (width, height), resize, expected) in cases {
+            assert_eq!(target_dimensions(width, height, &resize), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn retina_captures_are_halved_first() {
+        let retina = resize(|r| r.retina = true);
+        let cases = [
+            ("retina capture", (1200, 800), true, &retina, (600, 400)),
+            ("plain capture", (1200, 800), false, &retina, (720, 480)),
+            ("retina off", (1200, 800), true, &Resize::default(), (720, 480)),
+            ("a single pixel", (1, 1), true, &retina, (480, 480)),
+        ];
+        for (name, (width, height), is_retina, resize, expected) in cases {
+            assert_eq!(output_dimensions(width, height, is_retina, resize), expected, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use exif::{In, Tag};
+use image::metadata::Orientation;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use crate::optimize::{fallback_path, OptimizeError};
+
+// Cameras and phones store photos the way the sensor saw them and record how
+// to turn them upright in the EXIF orientation. Outputs are turned upright
+// when decoding; the original is never touched.
+pub fn read(path: &Path) -> Orientation {
+    let Ok(file) = File::open(path) else {
+        return Orientation::NoTransforms;
+    };
+    exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()
+        .and_then(|exif| exif.get_field(Tag::Orientation, In::PRIMARY).and_then(|field| field.value.get_uint(0)))
+        .and_then(|value| u8::try_from(value).ok())
+        .and_then(Orientation::from_exif)
+        .unwrap_or(Orientation::NoTransforms)
+}
+
+// Lying on its side: width and height trade places
+pub fn swaps_sides(orientation: Orientation) -> bool {
+    matches!(
+        orientation,
+        Orientation::Rotate90 | Orientation::Rotate270 | Orientation::Rotate90FlipH | Orientation::Rotate270FlipH
+    )
+}
+
+// As quarter turns clockwise after an optional horizontal flip, which makes
+// two orientations easy to combine
+fn turns(orientation: Orientation) -> (u8, bool) {
+    match orientation {
+        Orientation::NoTransforms => (0, false),
+        Orientation::FlipHorizontal => (0, true),
+        Orientation::Rotate180 => (2, false),
+        Orientation::FlipVertical => (2, true),
+        Orientation::Rotate90FlipH => (3, true),
+        Orientation::Rotate90 => (1, false),
+        Orientation::Rotate270FlipH => (1, true),
+        Orientation::Rotate270 => (3, false),
+    }
+}
+
+fn from_turns(rotations: u8, flip: bool) -> Orientation {
+    match (rotations % 4, flip) {
+        (0, false) => Orientation::NoTransforms,
+        (0, true) => Orientation::FlipHorizontal,
+        (1, false) => Orientation::Rotate90,
+        (1, true) => Orientation::Rotate270FlipH,
+        (2, false) => Orientation::Rotate180,
+        (2, true) => Orientation::FlipVertical,
+        (3, false) => Orientation::Rotate270,
+        _ => Orientation::Rotate90FlipH,
+    }
+}
+
+// `then` applied to what `first` shows. A flip mirrors the turns made
+// before it, so they count the other way.
+pub fn combine(first: Orientation, then: Orientation) -> Orientation {
+    let (first_turns, first_flip) = turns(first);
+    let (then_turns, then_flip) = turns(then);
+    let carried = if then_flip { 4 - first_turns } else { first_turns };
+    from_turns(then_turns + carried, first_flip != then_flip)
+}
+
+// "90", "180" or "270" degrees clockwise
+pub fn rotation(degrees: &str) -> Option<Orientation> {
+    match degrees {
+        "90" => Some(Orientation::Rotate90),
+        "180" => Some(Orientation::Rotate180),
+        "270" => Some(Orientation::Rotate270),
+        _ => None,
+    }
+}
+
+pub fn flip(direction: &str) -> Option<Orientation> {
+    match direction {
+        "horizontal" | "h" => Some(Orientation::FlipHorizontal),
+        "vertical" | "v" => Some(Orientation::FlipVertical),
+        _ => None,
+    }
+}
+
+// `imgopt orient`: a copy of a JPEG whose pixels are byte for byte the same
+// but whose orientation tag adds `change` to what it said before, for
+// viewers that honor it. Written as <name>_oriented.jpg.
+pub fn write_copy(path: &Path, change: Orientation) -> Result<PathBuf, OptimizeError> {
+    let mut data = std::fs::read(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => OptimizeError::NotFound,
+        _ => OptimizeError::Unreadable(e.to_string()),
+    })?;
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return Err(OptimizeError::Unsupported("only JPEGs carry an orientation that can be rewritten".into()));
+    }
+    let orientation = combine(read(path), change);
+    set_orientation(&mut data, orientation.to_exif()).map_err(OptimizeError::Unreadable)?;
+
+    let target = fallback_path(path, "oriented", "jpg");
+    std::fs::write(&target, &data).map_err(|e| OptimizeError::Output(format!("{}: {}", target.display(), e)))?;
+    crate::log(&format!("Wrote {} with orientation {}", target.display(), orientation.to_exif()));
+    Ok(target)
+}
+
+const ORIENTATION_TAG: u16 = 0x0112;
+
+// Rewrites the tag in place in the APP1 Exif segment, or adds a segment
+// holding only the tag when there is none
+fn set_orientation(data: &mut Vec<u8>, value: u8) -> Result<(), String> {
+    let mut pos = 2;
+    // Where a new segment goes: after the JFIF header, if any
+    let mut insert_at = 2;
+    loop {
+        let header = data.get(pos..pos + 4).ok_or("the JPEG is truncated")?;
+        if header[0] != 0xFF {
+            return Err("the JPEG is corrupt".into());
+        }
+        let marker = header[1];
+        // Start of scan: the metadata is over
+        if marker == 0xDA {
+            break;
+        }
+        let length = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let segment = data.get(pos + 4..pos + 2 + length).ok_or("the JPEG is truncated")?;
+        if marker == 0xE1 && segment.starts_with(b"Exif\0\0") {
+            let offset = pos + 4 + 6;
+            return patch_tiff(&mut data[offset..pos + 2 + length], value);
+        }
+        if marker == 0xE0 {
+            insert_at = pos + 2 + length;
+        }
+        pos += 2 + length;
+    }
+
+    // Big-endian TIFF with one IFD of one entry: SHORT, count 1
+    let mut segment = vec![0xFF, 0xE1, 0x00, 0x22];
+    segment.extend_from_slice(b"Exif\0\0MM\0\x2a\0\0\0\x08\0\x01");
+    segment.extend_from_slice(&ORIENTATION_TAG.to_be_bytes());
+    segment.extend_from_slice(&[0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, value, 0x00, 0x00]);
+    segment.extend_from_slice(&[0x00; 4]);
+    data.splice(insert_at..insert_at, segment);
+    Ok(())
+}
+
+fn patch_tiff(tiff: &mut [u8], value: u8) -> Result<(), String> {
+    let big_endian = match tiff.get(..2) {
+        Some(b"MM") => true,
+        Some(b"II") => false,
+        _ => return Err("the EXIF block is corrupt".into()),
+    };
+    let number = |tiff: &[u8], at: usize, len: usize| {
+        let bytes = tiff.get(at..at + len)?;
+        let ordered = |(i, byte): (usize, &u8)| (*byte as usize) << (8 * if big_endian { len - 1 - i } else { i });
+        Some(bytes.iter().enumerate().map(ordered).sum::<usize>())
+    };
+    let u16_at = |tiff: &[u8], at: usize| number(tiff, at, 2).map(|n| n as u16);
+    let ifd = number(tiff, 4, 4).ok_or("the EXIF block is truncated")?;
+    let count = u16_at(tiff, ifd).ok_or("the EXIF block is truncated")? as usize;
+    for entry in (0..count).map(|i| ifd + 2 + i * 12) {
+        if u16_at(tiff, entry) == Some(ORIENTATION_TAG) {
+            let at = entry + 8;
+            let bytes = if big_endian { [0, value] } else { [value, 0] };
+            tiff.get_mut(at..at + 2).ok_or("the EXIF block is truncated")?.copy_from_slice(&bytes);
+            return Ok(());
+        }
+    }
+    // Adding an entry would move everything after it
+    Err("the EXIF block has no orientation tag to rewrite".into())
+}
@@ -0,0 +1,178 @@
+use image::DynamicImage;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tiff::decoder::Decoder;
+use tiff::tags::Tag;
+
+use crate::log;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+// PDFs are rendered at this density, which puts a letter page at 1275x1650:
+// enough for any output size imgopt picks by default
+pub const PDF_DPI: u32 = 150;
+
+// Multi-page files: TIFFs with several images (fax and scanner output) and
+// PDFs. Pages are counted from 1, as the TUI picker and --page show them.
+pub fn is_pdf(path: &Path) -> bool {
+    extension_is(path, &["pdf"])
+}
+
+fn is_tiff(path: &Path) -> bool {
+    extension_is(path, &["tif", "tiff"])
+}
+
+fn extension_is(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.contains(&ext.to_lowercase().as_str()))
+}
+
+// The size of each page in pixels, in order. Empty for files that aren't
+// paged, and for those that can't be read, since decoding says why later.
+pub fn sizes(path: &Path) -> Vec<(u32, u32)> {
+    read_sizes(path).unwrap_or_else(|e| {
+        log(&format!("Could not count pages of {}: {}", path.display(), e));
+        Vec::new()
+    })
+}
+
+pub fn read_sizes(path: &Path) -> Result<Vec<(u32, u32)>, String> {
+    if is_pdf(path) {
+        pdf_sizes(path)
+    } else if is_tiff(path) {
+        Ok(tiff_pages(path)?.into_iter().map(|page| page.size).collect())
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+// Files that aren't paged have the one page, of unknown size here
+pub fn size_of(sizes: &[(u32, u32)], page: usize) -> Result<(u32, u32), String> {
+    page.checked_sub(1).and_then(|i| sizes.get(i)).copied().ok_or_else(|| match sizes.len() {
+        0 | 1 => format!("there is no page {}, the file has only one", page),
+        count => format!("there is no page {}, the file has {}", page, count),
+    })
+}
+
+struct TiffPage {
+    // Where the page's directory starts in the file
+    ifd: u64,
+    size: (u32, u32),
+}
+
+// Reduced-resolution copies that scanners store after a page are not pages
+// of their own
+fn tiff_pages(path: &Path) -> Result<Vec<TiffPage>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut decoder = Decoder::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+    let mut pages = Vec::new();
+    loop {
+        let reduced = decoder.find_tag_unsigned::<u32>(Tag::NewSubfileType).ok().flatten().is_some_and(|kind| kind & 1 != 0);
+        if !reduced {
+            let ifd = decoder.ifd_pointer().ok_or("no image directory")?.0;
+            pages.push(TiffPage { ifd, size: decoder.dimensions().map_err(|e| e.to_string())? });
+        }
+        if !decoder.more_images() {
+            return Ok(pages);
+        }
+        decoder.next_image().map_err(|e| e.to_string())?;
+    }
+}
+
+// The TIFF with its header pointing at `page` instead of the first image, so
+// the image crate's decoder reads that page with all of its color handling
+pub fn tiff_page(path: &Path, page: usize) -> Result<Vec<u8>, String> {
+    let pages = tiff_pages(path)?;
+    let sizes: Vec<(u32, u32)> = pages.iter().map(|page| page.size).collect();
+    size_of(&sizes, page)?;
+    let ifd = pages[page - 1].ifd;
+    let mut data = std::fs::read(path).map_err(|e| e.to_string())?;
+    let little = data.starts_with(b"II");
+    let magic = data.get(2..4).map(|magic| if little { magic[0] } else { magic[1] });
+    match magic {
+        Some(42) => {
+            let ifd = u32::try_from(ifd).map_err(|_| "image directory out of range")?;
+            data[4..8].copy_from_slice(&if little { ifd.to_le_bytes() } else { ifd.to_be_bytes() });
+        }
+        // BigTIFF
+        Some(43) if data.len() >= 16 => data[8..16].copy_from_slice(&if little { ifd.to_le_bytes() } else { ifd.to_be_bytes() }),
+        _ => return Err("not a TIFF file".to_string()),
+    }
+    Ok(data)
+}
+
+// From poppler's pdfinfo: "Page    2 size: 612 x 792 pts (letter)" and
+// "Page    2 rot:  90" for each page
+fn pdf_sizes(path: &Path) -> Result<Vec<(u32, u32)>, String> {
+    let output = Command::new("pdfinfo")
+        .args(["-f", "1", "-l", &i32::MAX.to_string()])
+        .arg(path)
+        .output()
+        .map_err(|e| poppler_error("pdfinfo", e))?;
+    if !output.status.success() {
+        return Err(failed("pdfinfo", &output));
+    }
+    let to_pixels = |points: &str| points.parse::<f64>().ok().map(|points| (points * PDF_DPI as f64 / 72.0).round() as u32);
+    let mut sizes: Vec<(u32, u32)> = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some(rest) = line.strip_prefix("Page").map(str::trim_start) else {
+            continue;
+        };
+        let Some((_, field)) = rest.split_once(' ') else {
+            continue;
+        };
+        let field = field.trim_start();
+        if let Some(size) = field.strip_prefix("size:") {
+            let mut words = size.split_whitespace();
+            if let (Some(width), Some("x"), Some(height)) = (words.next(), words.next(), words.next()) {
+                sizes.push((to_pixels(width).ok_or("bad page size")?, to_pixels(height).ok_or("bad page size")?));
+            }
+        } else if let Some(rotation) = field.strip_prefix("rot:") {
+            if let (Some(last), Ok(90 | 270)) = (sizes.last_mut(), rotation.trim().parse::<u32>()) {
+                *last = (last.1, last.0);
+            }
+        }
+    }
+    if sizes.is_empty() {
+        return Err("pdfinfo listed no pages".to_string());
+    }
+    Ok(sizes)
+}
+
+// One page through poppler's pdftoppm, via a temp PNG
+pub fn render_pdf(path: &Path, page: usize) -> Result<DynamicImage, String> {
+    let root = std::env::temp_dir().join(format!("imgopt-page-{}-{}", std::process::id(), NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+    let output = Command::new("pdftoppm")
+        .args(["-png", "-singlefile", "-r", &PDF_DPI.to_string(), "-f", &page.to_string(), "-l", &page.to_string()])
+        .arg(path)
+        .arg(&root)
+        .output()
+        .map_err(|e| poppler_error("pdftoppm", e))?;
+    let rendered = root.with_extension("png");
+    let image = if output.status.success() {
+        image::open(&rendered).map_err(|e| format!("pdftoppm wrote an unreadable page: {}", e))
+    } else {
+        Err(failed("pdftoppm", &output))
+    };
+    std::fs::remove_file(&rendered).ok();
+    image
+}
+
+fn poppler_error(program: &str, e: std::io::Error) -> String {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => format!("{} not found, is poppler installed?", program),
+        _ => format!("could not run {}: {}", program, e),
+    }
+}
+
+fn failed(program: &str, output: &std::process::Output) -> String {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match stderr.trim().lines().last() {
+        Some(line) => format!("{}: {}", program, line),
+        None => format!("{} exited with {}", program, output.status),
+    }
+}
@@ -0,0 +1,61 @@
+use image::DynamicImage;
+use std::collections::HashMap;
+
+// Colors closer than this (RGB distance) count as the same swatch
+const MIN_DISTANCE: u32 = 48;
+
+// The most common colors, most common first. Pixels are bucketed at 4 bits
+// per channel on a thumbnail, and each bucket reports its average color.
+pub fn dominant_colors(img: &DynamicImage, count: usize) -> Vec<[u8; 3]> {
+    if count == 0 {
+        return Vec::new();
+    }
+
+    let mut buckets: HashMap<(u8, u8, u8), (u32, [u32; 3])> = HashMap::new();
+    for pixel in img.thumbnail(96, 96).to_rgba8().pixels() {
+        let [r, g, b, a] = pixel.0;
+        // Transparent areas aren't part of the picture
+        if a < 128 {
+            continue;
+        }
+        let (n, sums) = buckets.entry((r >> 4, g >> 4, b >> 4)).or_default();
+        *n += 1;
+        sums[0] += r as u32;
+        sums[1] += g as u32;
+        sums[2] += b as u32;
+    }
+
+    let mut ranked: Vec<(u32, [u8; 3])> = buckets
+        .into_values()
+        .map(|(n, sums)| (n, sums.map(|sum| (sum / n) as u8)))
+        .collect();
+    ranked.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+    let mut colors: Vec<[u8; 3]> = Vec::new();
+    for (_, color) in ranked {
+        if colors.iter().all(|chosen| distance(*chosen, color) >= MIN_DISTANCE * MIN_DISTANCE) {
+            colors.push(color);
+            if colors.len() == count {
+                break;
+            }
+        }
+    }
+    colors
+}
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter().zip(b).map(|(x, y)| (*x as i32 - y as i32).pow(2) as u32).sum()
+}
+
+pub fn hex(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+pub fn css_variables(colors: &[[u8; 3]]) -> String {
+    let mut css = String::from(":root {\n");
+    for (i, color) in colors.iter().enumerate() {
+        css.push_str(&format!("  --palette-{}: {};\n", i + 1, hex(*color)));
+    }
+    css.push_str("}\n");
+    css
+}
@@ -0,0 +1,301 @@
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::log;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// An image found in pasted HTML or RTF instead of a path: copied from a web
+// page, its source, or a document
+#[derive(Debug, PartialEq)]
+pub enum Embedded {
+    // <img src="https://...">, or a word processor's INCLUDEPICTURE field
+    Url(String),
+    // A data: URL, or an RTF picture's hex dump
+    Data(Vec<u8>),
+}
+
+impl Embedded {
+    // Where it comes from, for the toast while it's fetched
+    pub fn describe(&self) -> Option<String> {
+        match self {
+            Embedded::Url(url) => Some(url.split("://").nth(1)?.split(['/', '?', '#']).next()?.to_string()),
+            Embedded::Data(_) => None,
+        }
+    }
+
+    // Downloads or decodes the image into a temp file named after it, so the
+    // rest goes as if that file had been dropped. Nothing over `max_bytes` is
+    // read, the same limit dropped files have.
+    pub fn save(self, max_bytes: u64) -> Result<Pasted, String> {
+        let (bytes, name) = match self {
+            Embedded::Url(url) => {
+                log(&format!("Fetching pasted image: {}", url));
+                (download(&url, max_bytes)?, file_name(&url))
+            }
+            Embedded::Data(bytes) => (bytes, None),
+        };
+        let format = image::guess_format(&bytes).map_err(|_| "the pasted image is in a format imgopt can't read".to_string())?;
+        let extension = format.extensions_str()[0];
+        let stem = name.as_deref().and_then(|name| name.rsplit_once('.').map_or(Some(name), |(stem, _)| Some(stem)));
+        let stem = stem.filter(|stem| !stem.is_empty()).unwrap_or("pasted");
+        // A folder of its own per paste, so two pastes of logo.png don't collide
+        let dir = std::env::temp_dir().join(format!("{}{}", folder_prefix(), NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+        let pasted = Pasted { path: dir.join(format!("{}.{}", stem, extension)) };
+        std::fs::create_dir_all(&dir)
+            .and_then(|_| std::fs::write(&pasted.path, &bytes))
+            .map_err(|e| format!("could not save the pasted image: {}", e))?;
+        Ok(pasted)
+    }
+}
+
+// A saved paste; its folder is removed once this is dropped
+pub struct Pasted {
+    pub path: PathBuf,
+}
+
+impl Drop for Pasted {
+    fn drop(&mut self) {
+        if let Some(dir) = self.path.parent() {
+            std::fs::remove_dir_all(dir).ok();
+        }
+    }
+}
+
+fn folder_prefix() -> String {
+    format!("imgopt-pasted-{}-", std::process::id())
+}
+
+// Results of a pasted image are saved where imgopt was started, named after
+// it, instead of next to its copy in the temp folder
+pub fn stand_in(source: &Path) -> Option<PathBuf> {
+    let dir = source.parent()?;
+    let ours = dir.file_name()?.to_str()?.starts_with(&folder_prefix()) && dir.parent() == Some(&std::env::temp_dir());
+    if !ours {
+        return None;
+    }
+    Some(std::env::current_dir().ok()?.join(source.file_name()?))
+}
+
+// Whether a paste is HTML or RTF rather than a path
+pub fn is_markup(text: &str) -> bool {
+    let text = text.trim_start();
+    text.starts_with("{\\rtf") || (text.starts_with('<') && text.to_ascii_lowercase().contains("<img"))
+}
+
+// The first image in pasted HTML or RTF
+pub fn find(text: &str) -> Option<Embedded> {
+    if !is_markup(text) {
+        return None;
+    }
+    if text.trim_start().starts_with("{\\rtf") {
+        rtf_picture(text).or_else(|| include_picture(text))
+    } else {
+        img_src(text).and_then(|src| source(&src))
+    }
+}
+
+// The src attribute of the first <img>, with entities like &amp; undone
+fn img_src(html: &str) -> Option<String> {
+    // ASCII only, so byte offsets stay the same in both
+    let lower = html.to_ascii_lowercase();
+    let mut at = 0;
+    while let Some(found) = lower[at..].find("<img") {
+        let start = at + found + "<img".len();
+        let end = lower[start..].find('>').map_or(lower.len(), |end| start + end);
+        if let Some(src) = attribute(&html[start..end], &lower[start..end], "src") {
+            return Some(unescape(&src));
+        }
+        at = end;
+    }
+    None
+}
+
+// A quoted or bare attribute value in the inside of a tag
+fn attribute(tag: &str, lower: &str, name: &str) -> Option<String> {
+    let mut at = 0;
+    while let Some(found) = lower[at..].find(name) {
+        let start = at + found;
+        at = start + name.len();
+        // "src" inside "data-src" or a value doesn't count
+        if lower[..start].chars().next_back().is_some_and(|c| !c.is_whitespace()) {
+            continue;
+        }
+        let rest = tag[at..].trim_start();
+        let Some(value) = rest.strip_prefix('=').map(str::trim_start) else {
+            continue;
+        };
+        return match value.chars().next()? {
+            quote @ ('"' | '\'') => value[1..].split(quote).next().map(str::to_string),
+            _ => value.split(|c: char| c.is_whitespace() || c == '>').next().map(str::to_string),
+        };
+    }
+    None
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&quot;", "\"").replace("&#39;", "'").replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+}
+
+// What an img src points to: a web address or the image itself. Relative
+// addresses say nothing about the page they were on.
+fn source(src: &str) -> Option<Embedded> {
+    let src = src.trim();
+    if let Some(data) = src.strip_prefix("data:") {
+        let (header, payload) = data.split_once(',')?;
+        let payload: String = payload.chars().filter(|c| !c.is_whitespace()).collect();
+        return if header.ends_with(";base64") { BASE64.decode(payload).ok().map(Embedded::Data) } else { None };
+    }
+    let src = if src.starts_with("//") { format!("https:{}", src) } else { src.to_string() };
+    (src.starts_with("https://") || src.starts_with("http://")).then_some(Embedded::Url(src))
+}
+
+// The hex dump of the first PNG or JPEG \pict group. Word writes a metafile
+// copy of each picture as well, which imgopt can't read.
+fn rtf_picture(rtf: &str) -> Option<Embedded> {
+    rtf.match_indices("\\pict").find_map(|(at, _)| {
+        // Control words (and groups such as {\*\blipuid ...}) come first, then
+        // the picture as hex digits up to the closing brace
+        let (mut depth, mut chars, mut hex, mut readable) = (0, rtf[at..].chars().peekable(), String::new(), false);
+        while let Some(c) = chars.next() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => break,
+                '}' => depth -= 1,
+                '\\' => {
+                    let mut word = String::new();
+                    while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '-') {
+                        word.push(c);
+                    }
+                    chars.next_if(|c| *c == ' ');
+                    readable |= depth == 0 && (word == "pngblip" || word == "jpegblip");
+                }
+                c if depth == 0 && c.is_ascii_hexdigit() => hex.push(c),
+                _ => {}
+            }
+        }
+        let bytes: Option<Vec<u8>> =
+            hex.as_bytes().chunks_exact(2).map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()).collect();
+        bytes.filter(|bytes| readable && !bytes.is_empty()).map(Embedded::Data)
+    })
+}
+
+// Word and Pages link web images with an INCLUDEPICTURE "url" field
+fn include_picture(rtf: &str) -> Option<Embedded> {
+    let field = &rtf[rtf.find("INCLUDEPICTURE")?..];
+    let url = field.split('"').nth(1)?;
+    source(url)
+}
+
+fn download(url: &str, max_bytes: u64) -> Result<Vec<u8>, String> {
+    let mut response = ureq::get(url)
+        .config()
+        .http_status_as_error(false)
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .call()
+        .map_err(|e| format!("could not fetch {}: {}", url, e))?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("{} answered {}", url, status));
+    }
+    response
+        .body_mut()
+        .with_config()
+        .limit(max_bytes)
+        .read_to_vec()
+        .map_err(|e| format!("could not fetch {}: {}", url, e))
+}
+
+// The last path segment, when it looks like a file name
+fn file_name(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next()?;
+    let name = path.rsplit('/').next()?;
+    let name: String = name.chars().filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_')).collect();
+    (!name.is_empty() && name.len() <= 100).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn img_src_takes_quoted_and_bare_values() {
+        assert_eq!(img_src(r#"<p><IMG alt="x" SRC="https://a.example/x.png?w=1&amp;h=2"></p>"#).as_deref(), Some("https://a.example/x.png?w=1&h=2"));
+        assert_eq!(img_src("<img class='a' src='//cdn.example/a.jpg'/>").as_deref(), Some("//cdn.example/a.jpg"));
+        assert_eq!(img_src("<img src=https://a.example/b.png width=10>").as_deref(), Some("https://a.example/b.png"));
+        assert_eq!(img_src("<img src = \"a.png\">").as_deref(), Some("a.png"));
+        // The first <img> with a src, past lazy-loading placeholders
+        assert_eq!(img_src(r#"<img alt="none"><img data-src="lazy.png" src="real.png">"#).as_deref(), Some("real.png"));
+        assert_eq!(img_src(r#"<img data-src="lazy.png">"#), None);
+        assert_eq!(img_src("<p>no images</p>"), None);
+    }
+
+    #[test]
+    fn attribute_skips_names_inside_others() {
+        let tag = r#" alt="src=x" data-src="a" srcset="b 2x" src="c""#;
+        assert_eq!(attribute(tag, tag, "src").as_deref(), Some("c"));
+        assert_eq!(attribute(" src", " src", "src"), None);
+    }
+
+    #[test]
+    fn sources_are_urls_or_data() {
+        assert_eq!(source(" data:image/png;base64,iVBO\nRw0K "), Some(Embedded::Data(b"\x89PNG\r\n".to_vec())));
+        assert_eq!(source("data:image/svg+xml,<svg/>"), None);
+        assert_eq!(source("data:image/png;base64,!!!"), None);
+        assert_eq!(source("//cdn.example/a.png"), Some(Embedded::Url("https://cdn.example/a.png".to_string())));
+        assert_eq!(source("http://a.example/a.png"), Some(Embedded::Url("http://a.example/a.png".to_string())));
+        assert_eq!(source("/images/a.png"), None);
+        assert_eq!(
+            find(r#"<html><img src="data:image/gif;base64,R0lGODlh"></html>"#),
+            Some(Embedded::Data(b"GIF89a".to_vec()))
+        );
+    }
+
+    #[test]
+    fn rtf_takes_png_and_jpeg_pictures_only() {
+        // Word's order: the metafile copy first, then the PNG in a \shppict group
+        let rtf = concat!(
+            r"{\rtf1{\pict\wmetafile8\picw10\pich10 0100090000}",
+            r"{\*\shppict{\pict{\*\picprop}\picscalex100\pngblip{\*\blipuid 0123abcd}",
+            "\n8950 4e47\n0d0a}}}"
+        );
+        assert_eq!(find(rtf), Some(Embedded::Data(vec![0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a])));
+        assert_eq!(rtf_picture(r"{\rtf1{\pict\jpegblip ffd8ffe0}}"), Some(Embedded::Data(vec![0xff, 0xd8, 0xff, 0xe0])));
+        assert_eq!(rtf_picture(r"{\rtf1{\pict\emfblip 01000000}}"), None);
+        assert_eq!(rtf_picture(r"{\rtf1{\pict\pngblip }}"), None);
+    }
+
+    #[test]
+    fn rtf_links_through_includepicture() {
+        let rtf = r#"{\rtf1{\field{\*\fldinst { INCLUDEPICTURE "https://a.example/logo.png" \\* MERGEFORMATINET }}}}"#;
+        assert_eq!(find(rtf), Some(Embedded::Url("https://a.example/logo.png".to_string())));
+        assert_eq!(include_picture(r#"{\rtf1 INCLUDEPICTURE "images/logo.png"}"#), None);
+    }
+
+    #[test]
+    fn pastes_are_named_after_the_image() {
+        assert_eq!(file_name("https://a.example/img/Logo%20v2.png?w=100#top").as_deref(), Some("Logo20v2.png"));
+        assert_eq!(file_name("https://a.example/"), None);
+        assert_eq!(Embedded::Url("https://a.example:8080/a.png".to_string()).describe().as_deref(), Some("a.example:8080"));
+    }
+
+    #[test]
+    fn saved_pastes_are_removed_with_their_folder() {
+        let png = BASE64.decode("iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR4nGNgYGD4DwABBAEAwS2OUAAAAABJRU5ErkJggg==").unwrap();
+        let pasted = Embedded::Data(png).save(1 << 20).unwrap();
+        let dir = pasted.path.parent().unwrap().to_path_buf();
+        assert_eq!(pasted.path.file_name().unwrap(), "pasted.png");
+        assert_eq!(stand_in(&pasted.path), Some(std::env::current_dir().unwrap().join("pasted.png")));
+        assert_eq!(stand_in(&std::env::temp_dir().join("pasted.png")), None);
+        drop(pasted);
+        assert!(!dir.exists());
+
+        assert!(Embedded::Data(b"not an image".to_vec()).save(1 << 20).is_err());
+    }
+}
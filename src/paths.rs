@@ -0,0 +1,302 @@
+use std::path::{Path, PathBuf};
+
+use crate::raw::is_raw;
+
+// TIFF and Photoshop files are read through their flattened composite
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "tif", "tiff", "psd", "psb", "pdf"];
+
+// Directories that are never worth descending into when searching for images
+const SKIP_DIRS: &[&str] = &["node_modules", "target"];
+
+pub fn is_image_path(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        || is_raw(Path::new(path))
+}
+
+// Shell-style wildcard match on a file name, ignoring case: `*` stands for any
+// run of characters and `?` for one
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Where the last `*` was and how much of the name it has swallowed so far
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                // Let the star take one more character and try again
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+pub fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME").filter(|h| !h.is_empty()).map(PathBuf::from)
+}
+
+// Where the shell would find `name`, if anywhere on PATH
+pub fn find_program(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    let file = format!("{}{}", name, std::env::consts::EXE_SUFFIX);
+    std::env::split_paths(&path).map(|dir| dir.join(&file)).find(|candidate| candidate.is_file())
+}
+
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+// Links followed before giving up, as the kernel does for symlinks
+const MAX_LINKS: usize = 40;
+
+// Follows symlinks and Finder aliases to the file they lead to, so a drop from
+// a folder of shortcuts is checked and processed as the file itself. A path
+// that doesn't exist comes back as it is, for the caller to report.
+pub fn resolve(path: &Path) -> Result<PathBuf, String> {
+    let mut current = path.to_path_buf();
+    let mut seen = Vec::new();
+    for _ in 0..MAX_LINKS {
+        let Ok(metadata) = std::fs::symlink_metadata(&current) else {
+            return Ok(current);
+        };
+        let next = if metadata.is_symlink() {
+            let target = std::fs::read_link(&current).map_err(|e| format!("could not follow {}: {}", current.display(), e))?;
+            // Relative targets are relative to the link's own folder
+            current.parent().map_or(target.clone(), |dir| dir.join(target))
+        } else if metadata.is_file() && is_alias(&current) {
+            alias_target(&current)?
+        } else if seen.is_empty() {
+            return Ok(current);
+        } else {
+            // Tidies up the `..` that relative links leave behind
+            return Ok(std::fs::canonicalize(&current).unwrap_or(current));
+        };
+        if seen.contains(&next) {
+            return Err(format!("{} leads around in a loop", path.display()));
+        }
+        seen.push(std::mem::replace(&mut current, next));
+    }
+    Err(format!("{} goes through more than {} links", path.display(), MAX_LINKS))
+}
+
+// Finder aliases are regular files holding bookmark data
+fn is_alias(path: &Path) -> bool {
+    let mut magic = [0u8; 16];
+    std::fs::File::open(path).and_then(|mut file| std::io::Read::read_exact(&mut file, &mut magic)).is_ok()
+        && magic == *b"book\0\0\0\0mark\0\0\0\0"
+}
+
+// Finder knows where an alias points, even after the original moved
+#[cfg(target_os = "macos")]
+fn alias_target(path: &Path) -> Result<PathBuf, String> {
+    let output = std::process::Command::new("osascript")
+        .args(["-e", "on run argv"])
+        .args(["-e", "tell application \"Finder\" to set original to original item of (POSIX file (item 1 of argv) as alias)"])
+        .args(["-e", "return POSIX path of (original as alias)"])
+        .args(["-e", "end run"])
+        .arg(path)
+        .output()
+        .map_err(|e| format!("could not run osascript to resolve {}: {}", path.display(), e))?;
+    if !output.status.success() {
+        return Err(format!("{} is an alias whose original can't be found", path.display()));
+    }
+    Ok(PathBuf::from(String::from_utf8_lossy(&output.stdout).trim_end_matches('\n')))
+}
+
+#[cfg(not(target_os = "macos"))]
+fn alias_target(path: &Path) -> Result<PathBuf, String> {
+    Err(format!("{} is a Finder alias, which only resolves on macOS", path.display()))
+}
+
+// Turns whatever a terminal produced for a dropped file into a path. Handles
+// `file://` URLs, single/double quoting, backslash-escaped characters
+// (`My\ Screenshot.png`) and a leading `~`.
+pub fn parse_dropped_path(input: &str) -> PathBuf {
+    let input = input.trim();
+    file_url(input).unwrap_or_else(|| expand_home(Path::new(&unescape_shell_word(input))))
+}
+
+// A path given on the command line, which the shell has already unquoted:
+// only `file://` URLs and a leading `~` (quoted, so the shell left it) are
+// taken care of, and the rest is the file's name as it is
+pub fn parse_argument(arg: &str) -> PathBuf {
+    file_url(arg).unwrap_or_else(|| expand_home(Path::new(arg)))
+}
+
+fn file_url(input: &str) -> Option<PathBuf> {
+    let rest = input.strip_prefix("file://")?;
+    // `file://localhost/path` and `file:///path` are both valid
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    Some(PathBuf::from(percent_decode(rest)))
+}
+
+fn unescape_shell_word(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut chars = input.chars();
+    let mut quote: Option<char> = None;
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '\\') => out.extend(chars.next()),
+            // Inside double quotes a backslash only escapes a few characters
+            (Some('"'), '\\') => match chars.next() {
+                Some(next @ ('"' | '\\' | '$' | '`')) => out.push(next),
+                Some(next) => {
+                    out.push('\\');
+                    out.push(next);
+                }
+                None => out.push('\\'),
+            },
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                out.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+// Recursively collects image files below `root`, skipping hidden entries
+pub fn collect_images(root: &Path, max_depth: usize, limit: usize) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut pending = vec![(root.to_path_buf(), 0)];
+
+    while let Some((dir, depth)) = pending.pop() {
+        let Ok(read) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in read.filter_map(|e| e.ok()) {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            if path.is_dir() {
+                if depth < max_depth && !SKIP_DIRS.contains(&name.as_str()) {
+                    pending.push((path, depth + 1));
+                }
+            } else if is_image_path(&name) {
+                found.push(path);
+                if found.len() >= limit {
+                    return found;
+                }
+            }
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dropped_paths_are_unquoted() {
+        let parse = |input: &str| parse_dropped_path(input).to_string_lossy().into_owned();
+        assert_eq!(parse("  /tmp/My\\ Screenshot\\ (1).png\n"), "/tmp/My Screenshot (1).png");
+        assert_eq!(parse("'/tmp/it\\s here.png'"), "/tmp/it\\s here.png");
+        assert_eq!(parse(r#""/tmp/a \"b\" \$c \d.png""#), r#"/tmp/a "b" $c \d.png"#);
+        assert_eq!(parse("/tmp/'a b'\" c\".png"), "/tmp/a b c.png");
+        assert_eq!(parse("file:///tmp/a%20b%C3%A9.png"), "/tmp/a bé.png");
+        assert_eq!(parse("file://localhost/tmp/100%.png"), "/tmp/100%.png");
+        if let Some(home) = home_dir() {
+            assert_eq!(parse_dropped_path("~/Desktop/shot.png"), home.join("Desktop/shot.png"));
+        }
+    }
+
+    #[test]
+    fn arguments_are_taken_as_they_are() {
+        for name in ["it's.png", "a \\\"b\\\".png", "/tmp/My\\ Shot.png", " padded.png "] {
+            assert_eq!(parse_argument(name), PathBuf::from(name));
+        }
+        assert_eq!(parse_argument("file:///tmp/a%20b.png"), PathBuf::from("/tmp/a b.png"));
+        if let Some(home) = home_dir() {
+            assert_eq!(parse_argument("~/shot.png"), home.join("shot.png"));
+        }
+    }
+
+    #[test]
+    fn patterns_match_names_ignoring_case() {
+        let matching = [("*.png", "Shot.PNG"), ("screen?hot*", "Screenshot 2024.png"), ("*", ""), ("a*b*c", "aXbYbZc"), ("**x", "x")];
+        for (pattern, name) in matching {
+            assert!(matches_pattern(pattern, name), "{} should match {}", pattern, name);
+        }
+        for (pattern, name) in [("*.png", "shot.png.bak"), ("?", ""), ("a*b", "aXbY"), ("shot", "shots")] {
+            assert!(!matches_pattern(pattern, name), "{} shouldn't match {}", pattern, name);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_are_followed_until_they_loop() {
+        use std::os::unix::fs::symlink;
+        let dir = std::env::temp_dir().join(format!("imgopt-paths-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(dir.join("links")).unwrap();
+        let dir = std::fs::canonicalize(&dir).unwrap();
+        std::fs::write(dir.join("shot.png"), b"").unwrap();
+
+        // A relative link to a link, from a folder of shortcuts
+        symlink("../shot.png", dir.join("links/first")).unwrap();
+        symlink("first", dir.join("links/second")).unwrap();
+        assert_eq!(resolve(&dir.join("links/second")), Ok(dir.join("shot.png")));
+        assert_eq!(resolve(&dir.join("shot.png")), Ok(dir.join("shot.png")));
+        assert_eq!(resolve(&dir.join("missing.png")), Ok(dir.join("missing.png")));
+
+        symlink("b", dir.join("a")).unwrap();
+        symlink("a", dir.join("b")).unwrap();
+        let error = resolve(&dir.join("a")).unwrap_err();
+        assert!(error.ends_with("leads around in a loop"), "{}", error);
+
+        for n in 0..MAX_LINKS {
+            symlink(format!("chain{}", n + 1), dir.join(format!("chain{}", n))).unwrap();
+        }
+        std::fs::write(dir.join(format!("chain{}", MAX_LINKS)), b"").unwrap();
+        assert!(resolve(&dir.join("chain1")).is_ok());
+        assert!(resolve(&dir.join("chain0")).unwrap_err().contains("more than 40 links"));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,84 @@
+use crossterm::{
+    event::{KeyCode, KeyEvent},
+    terminal,
+};
+use std::io;
+use std::path::PathBuf;
+
+use crate::i18n::{tr, trf};
+use crate::theme::Theme;
+use crate::ui::{draw_footer, draw_list, print_centered, truncate, Action, ListItem};
+use crate::Result;
+
+// Which page of a multi-page TIFF or PDF to optimize, shown when one is
+// dropped. Typing a number jumps to that page.
+pub struct PagePicker {
+    path: PathBuf,
+    sizes: Vec<(u32, u32)>,
+    selected: usize,
+    typed: String,
+}
+
+impl PagePicker {
+    pub fn new(path: PathBuf, sizes: Vec<(u32, u32)>) -> PagePicker {
+        PagePicker { path, sizes, selected: 0, typed: String::new() }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let last = self.sizes.len().saturating_sub(1);
+        if !matches!(key.code, KeyCode::Char('0'..='9')) {
+            self.typed.clear();
+        }
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => return Action::Back,
+            KeyCode::Enter => return Action::OpenPage(self.path.clone(), self.selected + 1),
+            KeyCode::Char(c @ '0'..='9') => {
+                let count = self.sizes.len();
+                let valid = |typed: &str| typed.parse::<usize>().ok().filter(|page| (1..=count).contains(page));
+                self.typed.push(c);
+                // A number past the last page starts over with this digit
+                if valid(&self.typed).is_none() {
+                    self.typed = c.to_string();
+                }
+                if let Some(page) = valid(&self.typed) {
+                    self.selected = page - 1;
+                }
+            }
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(10),
+            KeyCode::PageDown => self.selected = (self.selected + 10).min(last),
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = last,
+            _ => {}
+        }
+        Action::None
+    }
+
+    pub fn render(&self, stdout: &mut io::Stdout, theme: &Theme) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let max_len = width.saturating_sub(4) as usize;
+        let name = self.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+
+        // Two header lines, a gap and the footer around the list
+        let rows = (height.saturating_sub(6) as usize).min(self.sizes.len());
+        let top = height.saturating_sub(rows as u16 + 4) / 2;
+        print_centered(stdout, top, theme.title, &format!("{}{}", theme.pick("📄 ", ""), tr("pages.title")))?;
+        let count = trf("pages.count", &[("name", &name), ("count", &self.sizes.len())]);
+        print_centered(stdout, top + 1, theme.muted, &truncate(&count, max_len))?;
+
+        let items: Vec<ListItem> = self
+            .sizes
+            .iter()
+            .enumerate()
+            .map(|(i, (width, height))| ListItem {
+                text: trf("pages.item", &[("page", &(i + 1)), ("width", width), ("height", height)]),
+                color: theme.text,
+            })
+            .collect();
+
+        let left = width.saturating_sub(max_len.min(40) as u16) / 2;
+        draw_list(stdout, theme, left, top + 3, rows, &items, self.selected)?;
+        draw_footer(stdout, theme, theme.pick(tr("pages.footer"), tr("pages.footer_ascii")))
+    }
+}
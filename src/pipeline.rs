@@ -0,0 +1,313 @@
+use image::DynamicImage;
+use std::path::{Path, PathBuf};
+
+use crate::adjust;
+use crate::cache::{self, Cache};
+use crate::config::{Adjust, Config, Limits, Resize};
+use crate::content::{self, Content};
+use crate::denoise;
+use crate::dpi::is_retina;
+use crate::frame::{wrap, Frame};
+use crate::letterbox::letterbox;
+use crate::log;
+use crate::optimize::{checkpoint, decode, output_dimensions, resample, OptimizeError, Rendered, Stage};
+use crate::palette::{dominant_colors, hex};
+use crate::stamp::Stamp;
+use crate::transform::{self, Transform};
+use crate::video;
+
+// One image on its way through a pipeline, with what is known about it so far
+pub struct Work {
+    pub image: DynamicImage,
+    pub path: PathBuf,
+    // Dimensions of the file before anything was done to it
+    pub original: (u32, u32),
+    // Dominant colors, once the palette step has run
+    pub palette: Vec<[u8; 3]>,
+}
+
+// One step of a pipeline. Steps take the image and hand back a new one; a
+// step that fails stops the pipeline.
+pub trait Step: Send + Sync {
+    // For the log
+    fn name(&self) -> String;
+
+    // Progress reported just before the step runs, for slow steps
+    fn stage(&self) -> Option<Stage> {
+        None
+    }
+
+    fn apply(&self, work: Work) -> Result<Work, OptimizeError>;
+}
+
+// Decode followed by the steps the config asks for: denoise, resize, letterbox, the
+// color adjustments, the transforms, the palette, the stamp and the frame. Everything up
+// to and including the letterbox is cached, keyed by the file, the page or video frame
+// and the settings of those steps.
+pub struct Pipeline {
+    limits: Limits,
+    resize: Resize,
+    denoise: u32,
+    page: Option<usize>,
+    video_frame: video::Frame,
+    // Behind transparent images letterboxed onto a blurred copy of
+    // themselves, which the cache has to tell apart
+    matte: Option<[u8; 3]>,
+    // With the auto preset, the config to tune once the image has been looked at
+    auto: Option<Box<Config>>,
+    steps: Vec<Box<dyn Step>>,
+    // How many leading steps the cache covers
+    cached_steps: usize,
+}
+
+impl Pipeline {
+    pub fn new(config: &Config) -> Pipeline {
+        // A frame goes around the resized image, so the image itself gets less room
+        let resize = config.resize().shrunk(config.frame.extra());
+
+        let mut steps: Vec<Box<dyn Step>> = Vec::new();
+        if config.denoise > 0 {
+            steps.push(Box::new(DenoiseStep(config.denoise)));
+        }
+        steps.push(Box::new(ResizeStep(resize)));
+        let matte = resize.canvas.filter(|_| resize.canvas_background.is_none()).map(|_| config.appearance.matte());
+        if let Some(canvas) = resize.canvas {
+            let matte = matte.unwrap_or_default();
+            steps.push(Box::new(LetterboxStep { canvas, background: resize.canvas_background, matte }));
+        }
+        let cached_steps = steps.len();
+        let adjust = config.adjust();
+        if !adjust.is_neutral() {
+            steps.push(Box::new(AdjustStep(adjust)));
+        }
+        // Checked when the config was loaded
+        match transform::pipeline(config.transforms()) {
+            Ok(transforms) => {
+                let specs = config.transforms().iter();
+                steps.extend(specs.zip(transforms).map(|(spec, transform)| {
+                    Box::new(TransformStep { spec: spec.clone(), transform }) as Box<dyn Step>
+                }));
+            }
+            Err(e) => log(&format!("Skipping transforms: {}", e)),
+        }
+        steps.push(Box::new(PaletteStep(config.palette_size)));
+        let stamp = config.stamp();
+        if !stamp.is_off() {
+            steps.push(Box::new(StampStep(stamp)));
+        }
+        if config.frame != Frame::None {
+            steps.push(Box::new(FrameStep { frame: config.frame, dark: config.appearance.is_dark() }));
+        }
+
+        // The auto preset may yet give text more room, which decoding has to leave
+        let decoded = if config.is_auto() { Content::Text.tune(config).resize().shrunk(config.frame.extra()) } else { resize };
+        Pipeline {
+            limits: config.limits,
+            resize: decoded,
+            denoise: config.denoise,
+            page: config.page,
+            video_frame: config.video_frame,
+            matte,
+            auto: config.is_auto().then(|| Box::new(config.clone())),
+            steps,
+            cached_steps,
+        }
+    }
+
+    pub fn run(&self, path: &Path, cache: &Cache, progress: &dyn Fn(Stage) -> bool) -> Result<Rendered, OptimizeError> {
+        if !path.exists() {
+            return Err(OptimizeError::NotFound);
+        }
+
+        // Load image, unless the same file was optimized recently
+        checkpoint(progress, Stage::Decoding)?;
+        let key = cache::key(path, self.limits.max_input_bytes, &(self.resize, self.denoise, self.page, self.video_frame, self.matte, self.auto.is_some()));
+        let (mut work, cached, content, tuned) = match key.and_then(|key| cache.get(key)) {
+            Some((original, image, content)) => {
+                log(&format!("Cache hit: {}", path.display()));
+                (Work { image, path: path.to_path_buf(), original, palette: Vec::new() }, true, content, self.tuned(content))
+            }
+            None => {
+                let (image, original) = decode(path, &self.limits, &self.resize, self.page, self.video_frame)?;
+                // Looked at in full, before resizing blurs the edges that give text away
+                let content = self.auto.as_ref().map(|_| content::classify(&image));
+                let tuned = self.tuned(content);
+                let pipeline = tuned.as_ref().unwrap_or(self);
+                let mut work = Work { image, path: path.to_path_buf(), original, palette: Vec::new() };
+                work = pipeline.apply(&pipeline.steps[..pipeline.cached_steps], work, progress)?;
+                if let Some(key) = key {
+                    cache.insert(key, work.original, work.image.clone(), content);
+                }
+                (work, false, content, tuned)
+            }
+        };
+        if let Some(content) = content {
+            log(&format!("Auto preset: {} looks like {}", path.display(), content.name()));
+        }
+        let pipeline = tuned.as_ref().unwrap_or(self);
+        work = pipeline.apply(&pipeline.steps[pipeline.cached_steps..], work, progress)?;
+
+        Ok(Rendered {
+            image: work.image,
+            original: work.original,
+            cached,
+            palette: work.palette,
+            content,
+            warnings: Vec::new(),
+        })
+    }
+
+    // The pipeline for what the auto preset found
+    fn tuned(&self, content: Option<Content>) -> Option<Pipeline> {
+        let (config, content) = self.auto.as_ref().zip(content)?;
+        Some(Pipeline::new(&content.tune(config)))
+    }
+
+    fn apply(&self, steps: &[Box<dyn Step>], work: Work, progress: &dyn Fn(Stage) -> bool) -> Result<Work, OptimizeError> {
+        steps.iter().try_fold(work, |work, step| {
+            if let Some(stage) = step.stage() {
+                checkpoint(progress, stage)?;
+            }
+            log(&format!("Step: {}", step.name()));
+            step.apply(work)
+        })
+    }
+}
+
+// Smooths sensor noise at full size, before resizing mixes it into the result
+pub struct DenoiseStep(pub u32);
+
+impl Step for DenoiseStep {
+    fn name(&self) -> String {
+        format!("denoise {}", self.0)
+    }
+
+    fn stage(&self) -> Option<Stage> {
+        Some(Stage::Denoising)
+    }
+
+    fn apply(&self, mut work: Work) -> Result<Work, OptimizeError> {
+        work.image = denoise::bilateral(&work.image, self.0);
+        Ok(work)
+    }
+}
+
+// Lanczos3 down (or up) to the resize settings
+pub struct ResizeStep(pub Resize);
+
+impl Step for ResizeStep {
+    fn name(&self) -> String {
+        format!("resize {}", self.0.describe())
+    }
+
+    fn stage(&self) -> Option<Stage> {
+        Some(Stage::Resizing)
+    }
+
+    fn apply(&self, mut work: Work) -> Result<Work, OptimizeError> {
+        // Output size follows the original dimensions even when decoding already reduced the image
+        let (width, height) = output_dimensions(work.original.0, work.original.1, is_retina(&work.path), &self.0);
+        work.image = resample(&work.image, width, height);
+        Ok(work)
+    }
+}
+
+// Onto a canvas of the exact size asked for
+pub struct LetterboxStep {
+    canvas: (u32, u32),
+    background: Option<[u8; 3]>,
+    matte: [u8; 3],
+}
+
+impl Step for LetterboxStep {
+    fn name(&self) -> String {
+        let background = match self.background {
+            Some(color) => hex(color),
+            None => "blurred".to_string(),
+        };
+        format!("letterbox {}x{} {}", self.canvas.0, self.canvas.1, background)
+    }
+
+    fn apply(&self, mut work: Work) -> Result<Work, OptimizeError> {
+        work.image = letterbox(&work.image, self.canvas, self.background, self.matte);
+        Ok(work)
+    }
+}
+
+// Grayscale, brightness, contrast and saturation from [adjust]
+pub struct AdjustStep(pub Adjust);
+
+impl Step for AdjustStep {
+    fn name(&self) -> String {
+        format!("adjust {}", self.0.describe())
+    }
+
+    fn apply(&self, mut work: Work) -> Result<Work, OptimizeError> {
+        work.image = adjust::apply(work.image, &self.0);
+        Ok(work)
+    }
+}
+
+// A transform from the config, such as "sharpen:1.5"
+pub struct TransformStep {
+    spec: String,
+    transform: Box<dyn Transform>,
+}
+
+impl Step for TransformStep {
+    fn name(&self) -> String {
+        self.spec.clone()
+    }
+
+    fn apply(&self, mut work: Work) -> Result<Work, OptimizeError> {
+        work.image = self.transform.apply(work.image).map_err(OptimizeError::Transform)?;
+        Ok(work)
+    }
+}
+
+// Picks the dominant colors for the result screen; the image is left alone.
+// Runs before the frame so the window chrome doesn't count.
+pub struct PaletteStep(pub usize);
+
+impl Step for PaletteStep {
+    fn name(&self) -> String {
+        format!("palette of {}", self.0)
+    }
+
+    fn apply(&self, mut work: Work) -> Result<Work, OptimizeError> {
+        work.palette = dominant_colors(&work.image, self.0);
+        Ok(work)
+    }
+}
+
+// The capture date and original size in a corner, inside any frame
+pub struct StampStep(pub Stamp);
+
+impl Step for StampStep {
+    fn name(&self) -> String {
+        format!("stamp {}", self.0.describe())
+    }
+
+    fn apply(&self, mut work: Work) -> Result<Work, OptimizeError> {
+        work.image = self.0.apply(work.image, &work.path, work.original);
+        Ok(work)
+    }
+}
+
+pub struct FrameStep {
+    pub frame: Frame,
+    // Dark window chrome, for a dark `appearance`
+    pub dark: bool,
+}
+
+impl Step for FrameStep {
+    fn name(&self) -> String {
+        let shade = if self.dark { "dark " } else { "" };
+        format!("{}{:?} frame", shade, self.frame).to_lowercase()
+    }
+
+    fn apply(&self, mut work: Work) -> Result<Work, OptimizeError> {
+        work.image = DynamicImage::ImageRgba8(wrap(&work.image.to_rgba8(), self.frame, self.dark));
+        Ok(work)
+    }
+}
@@ -0,0 +1,173 @@
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use crate::cache::Cache;
+use crate::config::Config;
+use crate::qr::{self, UploadQr};
+use crate::optimize::{format_bytes, optimize, optimize_diff, optimize_grid, Delivery, OptimizeError, Outcome};
+use crate::palette::hex;
+use crate::paths::parse_dropped_path;
+use crate::session::Session;
+use crate::transfer;
+use crate::{log, Result};
+
+// Line-by-line mode for screen readers and dumb terminals: one path per line
+// on stdin, plain status text on stdout, no colors or cursor movement
+pub fn run_plain(config: &Config) -> Result<()> {
+    log("Plain mode starting");
+
+    println!("imgopt: image optimizer ({}).", config.resize().describe());
+    println!("Type or drop an image path and press Enter. Type diff to compare two images, grid to combine 2-4, report to save a session report, q or Ctrl+D to quit.");
+
+    let cache = Cache::default();
+    let mut session = Session::new();
+
+    while let Some(line) = prompt("path> ")? {
+        match line.as_str() {
+            "" => continue,
+            "q" | "quit" => break,
+            "report" => {
+                let dir = config.report_dir().unwrap_or_else(|| std::env::current_dir().unwrap_or_else(|_| ".".into()));
+                save_report(&session, &dir, config);
+            }
+            "diff" => {
+                let Some(before) = prompt("before> ")? else { break };
+                let Some(after) = prompt("after> ")? else { break };
+                let (before, after) = (parse_dropped_path(&before), parse_dropped_path(&after));
+                println!("Comparing {} with {}", before.display(), after.display());
+                let outcome = optimize_diff(&before, &after, config, &|_| true);
+                session.record(vec![before, after], &outcome, config);
+                report(outcome, config);
+                config.notify.done(config.notify_sound().as_deref());
+            }
+            "grid" => {
+                // Up to four paths; an empty line ends the list early
+                let mut paths = Vec::new();
+                while paths.len() < 4 {
+                    match prompt(&format!("image {}> ", paths.len() + 1))? {
+                        Some(line) if !line.is_empty() => paths.push(parse_dropped_path(&line)),
+                        _ => break,
+                    }
+                }
+                if paths.len() < 2 {
+                    println!("A grid needs at least two images.");
+                    continue;
+                }
+                println!("Combining {} images", paths.len());
+                let outcome = optimize_grid(&paths, config, &|_| true);
+                session.record(paths, &outcome, config);
+                report(outcome, config);
+                config.notify.done(config.notify_sound().as_deref());
+            }
+            line => {
+                let path = parse_dropped_path(line);
+                println!("Processing {}", path.display());
+                let outcome = optimize(&path, config, &cache, &|_| true);
+                session.record(vec![path], &outcome, config);
+                report(outcome, config);
+                config.notify.done(config.notify_sound().as_deref());
+            }
+        }
+    }
+
+    if let Some(dir) = config.report_dir().filter(|_| !session.is_empty()) {
+        save_report(&session, &dir, config);
+    }
+    println!("Goodbye.");
+    log("Plain mode exited");
+    Ok(())
+}
+
+// Reads one trimmed line; None at end of input
+fn prompt(label: &str) -> io::Result<Option<String>> {
+    print!("{}", label);
+    io::stdout().flush()?;
+
+    let mut line = String::new();
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        println!();
+        return Ok(None);
+    }
+    Ok(Some(line.trim().to_string()))
+}
+
+fn save_report(session: &Session, dir: &Path, config: &Config) {
+    if session.is_empty() {
+        println!("Nothing to report yet.");
+        return;
+    }
+    match session.write(dir, config.report_format) {
+        Ok(path) => println!("Session report saved to {}", path.display()),
+        Err(e) => println!("Error: could not save the session report: {}", e),
+    }
+}
+
+fn report(outcome: std::result::Result<Outcome, OptimizeError>, config: &Config) {
+    let outcome = match outcome {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            println!("Error: {}.", e);
+            return;
+        }
+    };
+
+    if let Some(diff) = &outcome.diff {
+        println!("Diff: {:.1}% similar, {} changed regions.", diff.similarity, diff.regions);
+    } else if let Some(count) = outcome.grid {
+        println!("Grid of {} images.", count);
+    } else if outcome.cached {
+        println!("Already optimized (cache hit).");
+    } else if outcome.passed_through {
+        println!("Already within bounds, copied unchanged.");
+    } else if let Some(content) = outcome.content {
+        println!("Auto preset: looks like {}.", content.name());
+    }
+    let size = |bytes: u64| format!(", {}", format_bytes(bytes));
+    let (original_bytes, optimized_bytes) = outcome.bytes.map(|(o, n)| (size(o), size(n))).unwrap_or_default();
+    println!("Original: {}x{} pixels{}.", outcome.original.0, outcome.original.1, original_bytes);
+    println!("Optimized: {}x{} pixels{}.", outcome.optimized.0, outcome.optimized.1, optimized_bytes);
+    if let Some(bytes) = outcome.bytes {
+        println!("{}.", transfer::describe(bytes, config.upload_mbps, "->"));
+    }
+    for warning in &outcome.warnings {
+        println!("Warning: {}", warning);
+    }
+    if !outcome.palette.is_empty() {
+        let colors: Vec<String> = outcome.palette.iter().map(|c| hex(*c)).collect();
+        println!("Palette: {}.", colors.join(", "));
+    }
+
+    match outcome.delivery {
+        Delivery::Clipboard => println!("Copied to clipboard. Ready to paste."),
+        Delivery::Saved(saved) => println!("Saved to {}", saved.display()),
+        Delivery::Stdout => println!("Written to standard output."),
+        Delivery::Written { dir, files, copied } => {
+            println!("Wrote {} to {}", files.join(", "), dir.display());
+            match copied {
+                Some(Ok(())) => println!("HTML snippet copied to clipboard. Ready to paste."),
+                Some(Err(e)) => println!("Error: could not copy the HTML snippet: {}", e),
+                None => {}
+            }
+        }
+        Delivery::Uploaded { url, copied } => {
+            println!("Uploaded to {}", url);
+            // Dumb terminals may not have block characters
+            let code = qr::lines(&url, true).filter(|_| config.upload_qr != UploadQr::Off);
+            let copied_what = if code.is_some() { config.upload_qr.copied() } else { "URL" };
+            match copied {
+                Ok(()) => println!("{} copied to clipboard. Ready to paste.", copied_what),
+                Err(e) => println!("Error: could not copy the {}: {}", copied_what, e),
+            }
+            for line in code.unwrap_or_default() {
+                println!("{}", line.trim_end());
+            }
+        }
+        Delivery::Fallback { reason, saved } => {
+            println!("Error: clipboard copy failed: {}", reason);
+            match saved {
+                Ok(fallback) => println!("Saved optimized image to {}", fallback.display()),
+                Err(e) => println!("Error: could not save a fallback file either: {}", e),
+            }
+        }
+    }
+}
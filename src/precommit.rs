@@ -0,0 +1,166 @@
+use image::{ImageFormat, ImageReader};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::cache::Cache;
+use crate::config::Config;
+use crate::exit::Exit;
+use crate::optimize::{archive, format_bytes};
+use crate::serve::process;
+use crate::{log, say, Result};
+
+const HOOK: &str = "#!/bin/sh\n# Installed by `imgopt precommit --install`\nexec imgopt precommit\n";
+
+// `imgopt precommit`: shrinks staged PNGs and JPEGs that exceed the [budget]
+// in place and stages them again. Fails, blocking the commit, when an image
+// can't be brought within budget or has unstaged changes we would sweep in.
+pub fn run_precommit(config: &Config, install: bool) -> Result<Exit> {
+    let root = PathBuf::from(git(&["rev-parse", "--show-toplevel"])?.trim());
+    if install {
+        install_hook()?;
+        return Ok(Exit::Success);
+    }
+
+    let staged = git(&["diff", "--cached", "--name-only", "--diff-filter=ACMR", "-z"])?;
+    let unstaged = git(&["diff", "--name-only", "-z"])?;
+    let unstaged: HashSet<&str> = unstaged.split('\0').collect();
+
+    let budget = config.budget;
+    let config = budget_config(config);
+    let cache = Cache::default();
+    let mut restaged = 0;
+    let mut over = Vec::new();
+    for name in staged.split('\0').filter(|name| !name.is_empty()) {
+        let path = root.join(name);
+        let Some((format, (width, height), bytes)) = inspect(&path) else {
+            continue;
+        };
+        if budget.exceeded((width, height), bytes).is_empty() {
+            continue;
+        }
+        if unstaged.contains(name) {
+            eprintln!("imgopt: {}: over budget, but it has unstaged changes; stage or stash them first", name);
+            over.push(name);
+            continue;
+        }
+
+        let params = HashMap::from([("format".to_string(), format.extensions_str()[0].to_string())]);
+        let processed = match process(&path, &params, &config, &cache) {
+            Ok(processed) => processed,
+            Err((_, e)) => {
+                eprintln!("imgopt: {}: {}", name, e);
+                over.push(name);
+                continue;
+            }
+        };
+        let optimized = &processed.metadata["optimized"];
+        let size = (optimized["width"].as_u64().unwrap_or(0) as u32, optimized["height"].as_u64().unwrap_or(0) as u32);
+        let new_bytes = processed.bytes.len() as u64;
+
+        let better = budget.improved(((width, height), bytes), (size, new_bytes));
+        let (final_size, final_bytes) = if better { (size, new_bytes) } else { ((width, height), bytes) };
+        if better {
+            if let Err(e) = archive(&[&path], &config) {
+                eprintln!("imgopt: {}: {}", name, e);
+                over.push(name);
+                continue;
+            }
+            std::fs::write(&path, &processed.bytes)?;
+            git(&["add", "--", &path.to_string_lossy()])?;
+            restaged += 1;
+            say(&format!(
+                "{}: {}x{} -> {}x{}, {} -> {}",
+                name,
+                width,
+                height,
+                size.0,
+                size.1,
+                format_bytes(bytes),
+                format_bytes(new_bytes)
+            ));
+            log(&format!("Pre-commit optimized {}", path.display()));
+        }
+        let still = budget.exceeded(final_size, final_bytes);
+        if !still.is_empty() {
+            eprintln!(
+                "imgopt: {}: still over budget on {}: {}x{}, {}; the budget is {}px, {}",
+                name,
+                still.join(" and "),
+                final_size.0,
+                final_size.1,
+                format_bytes(final_bytes),
+                budget.max_dimension,
+                format_bytes(budget.max_bytes)
+            );
+            over.push(name);
+        }
+    }
+
+    if restaged > 0 {
+        say(&format!("imgopt: optimized and re-staged {} image(s)", restaged));
+    }
+    if !over.is_empty() {
+        eprintln!("imgopt: {} staged image(s) over budget: {}", over.len(), over.join(", "));
+        return Ok(Exit::OverBudget);
+    }
+    Ok(Exit::Success)
+}
+
+// Format, dimensions and file size of a PNG or JPEG; None for anything else
+pub fn inspect(path: &Path) -> Option<(ImageFormat, (u32, u32), u64)> {
+    let reader = ImageReader::open(path).ok()?.with_guessed_format().ok()?;
+    let format = reader.format().filter(|f| matches!(f, ImageFormat::Png | ImageFormat::Jpeg))?;
+    let dimensions = reader.into_dimensions().ok()?;
+    Some((format, dimensions, std::fs::metadata(path).ok()?.len()))
+}
+
+// Repository images keep their content: only the budget's size limit
+// applies. Everything else starts from the defaults, so what is set up for
+// screenshots (presets, frames, transforms, adjustments, stamps, color
+// profiles, pixel density, legibility passes) never reaches them; only the
+// JPEG quality, input limits and archive folder are carried over.
+pub fn budget_config(config: &Config) -> Config {
+    Config {
+        resize: config.budget.resize(),
+        budget: config.budget,
+        jpeg_quality: config.jpeg_quality,
+        limits: config.limits,
+        archive_dir: config.archive_dir.clone(),
+        ..Config::default()
+    }
+}
+
+fn install_hook() -> Result<()> {
+    // Relative to the current directory, and honors core.hooksPath
+    let hooks = PathBuf::from(git(&["rev-parse", "--git-path", "hooks"])?.trim());
+    let hook = std::env::current_dir()?.join(hooks).join("pre-commit");
+    if hook.exists() {
+        return Err(format!("{} already exists; add `imgopt precommit` to it yourself", hook.display()).into());
+    }
+    std::fs::write(&hook, HOOK)?;
+    make_executable(&hook)?;
+    println!("Installed {}", hook.display());
+    Ok(())
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    Ok(std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?)
+}
+
+// Git for Windows runs hooks through its own sh, executable bit or not
+#[cfg(not(unix))]
+fn make_executable(_: &Path) -> Result<()> {
+    Ok(())
+}
+
+fn git(args: &[&str]) -> Result<String> {
+    let output = Command::new("git").args(args).output().map_err(|e| format!("could not run git: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git {}: {}", args.join(" "), stderr.trim()).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
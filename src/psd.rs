@@ -0,0 +1,206 @@
+use image::{DynamicImage, RgbImage, RgbaImage};
+use std::path::Path;
+
+// Photoshop documents (PSD, and PSB for large ones). Saved with "Maximize
+// compatibility", the default, they carry a flattened copy of the whole
+// document after the layers, and that is what imgopt reads: the layers
+// themselves are never composited here.
+pub fn is_psd(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| matches!(ext.to_lowercase().as_str(), "psd" | "psb"))
+}
+
+struct Header {
+    // PSB: some lengths are 64 bits, and RLE row sizes 32
+    big: bool,
+    channels: usize,
+    width: u32,
+    height: u32,
+    depth: u16,
+    mode: u16,
+}
+
+// Reads big-endian fields front to back, failing on truncated files
+struct Fields<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.data.len()).ok_or("file is truncated")?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn length(&mut self, big: bool) -> Result<usize, String> {
+        if big {
+            Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()) as usize)
+        } else {
+            Ok(self.u32()? as usize)
+        }
+    }
+}
+
+fn header(fields: &mut Fields) -> Result<Header, String> {
+    if fields.take(4)? != b"8BPS" {
+        return Err("not a Photoshop document".into());
+    }
+    let big = match fields.u16()? {
+        1 => false,
+        2 => true,
+        version => return Err(format!("unknown Photoshop version {}", version)),
+    };
+    fields.take(6)?;
+    let channels = fields.u16()? as usize;
+    let height = fields.u32()?;
+    let width = fields.u32()?;
+    let depth = fields.u16()?;
+    let mode = fields.u16()?;
+    if !matches!(depth, 1 | 8 | 16 | 32) {
+        return Err(format!("unsupported bit depth {}", depth));
+    }
+    Ok(Header { big, channels, width, height, depth, mode })
+}
+
+// Without decoding anything
+pub fn dimensions(path: &Path) -> Result<(u32, u32), String> {
+    let mut start = [0u8; 26];
+    std::io::Read::read_exact(&mut std::fs::File::open(path).map_err(|e| e.to_string())?, &mut start)
+        .map_err(|_| "file is truncated".to_string())?;
+    let header = header(&mut Fields { data: &start, pos: 0 })?;
+    Ok((header.width, header.height))
+}
+
+pub fn decode(path: &Path) -> Result<DynamicImage, String> {
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+    let mut fields = Fields { data: &data, pos: 0 };
+    let header = header(&mut fields)?;
+    let (width, height) = (header.width as usize, header.height as usize);
+
+    let color_len = fields.u32()? as usize;
+    let color_data = fields.take(color_len)?;
+    let resources_len = fields.u32()? as usize;
+    fields.take(resources_len)?;
+    let layers_len = fields.length(header.big)?;
+    let layers = fields.take(layers_len)?;
+
+    // A negative layer count means the first extra channel is the
+    // transparency of the flattened image rather than a saved selection
+    let transparent = {
+        let mut layers = Fields { data: layers, pos: 0 };
+        layers.length(header.big).and_then(|_| layers.u16()).is_ok_and(|count| (count as i16) < 0)
+    };
+
+    let row_bytes = match header.depth {
+        1 => width.div_ceil(8),
+        depth => width * depth as usize / 8,
+    };
+    let rows = header.channels * height;
+    let raw = match fields.u16()? {
+        0 => fields.take(rows * row_bytes)?.to_vec(),
+        1 => {
+            let mut counts = Vec::with_capacity(rows);
+            for _ in 0..rows {
+                counts.push(if header.big { fields.u32()? as usize } else { fields.u16()? as usize });
+            }
+            let mut raw = Vec::with_capacity(rows * row_bytes);
+            for count in counts {
+                unpack_bits(fields.take(count)?, row_bytes, &mut raw)?;
+            }
+            raw
+        }
+        _ => return Err("the flattened image is ZIP-compressed, which isn't supported".into()),
+    };
+
+    let planes: Vec<Vec<u8>> = raw.chunks(height * row_bytes).map(|plane| to_8bit(plane, header.depth, width)).collect();
+    let plane = |i: usize| planes.get(i).ok_or_else(|| format!("missing channel {}", i + 1));
+    let (w, h) = (header.width, header.height);
+
+    let (colors, extra) = match header.mode {
+        // Bitmap, grayscale and duotone (shown as its grayscale base)
+        0 | 1 | 8 => {
+            let gray = plane(0)?;
+            (gray.iter().map(|v| [*v, *v, *v]).collect::<Vec<_>>(), 1)
+        }
+        2 => {
+            if color_data.len() < 768 {
+                return Err("indexed image without a color table".into());
+            }
+            let index = plane(0)?;
+            (index.iter().map(|i| [0, 256, 512].map(|table| color_data[table + *i as usize])).collect(), 1)
+        }
+        3 => {
+            let (r, g, b) = (plane(0)?, plane(1)?, plane(2)?);
+            ((0..width * height).map(|i| [r[i], g[i], b[i]]).collect(), 3)
+        }
+        // Stored inverted: 255 is no ink
+        4 => {
+            let (c, m, y, k) = (plane(0)?, plane(1)?, plane(2)?, plane(3)?);
+            let rgb = |v: u8, k: u8| (v as u32 * k as u32 / 255) as u8;
+            ((0..width * height).map(|i| [rgb(c[i], k[i]), rgb(m[i], k[i]), rgb(y[i], k[i])]).collect(), 4)
+        }
+        mode => return Err(format!("unsupported Photoshop color mode {}", mode)),
+    };
+
+    let alpha = planes.get(extra).filter(|_| transparent);
+    let image = match alpha {
+        Some(alpha) => {
+            let pixels = colors.iter().zip(alpha).flat_map(|([r, g, b], a)| [*r, *g, *b, *a]).collect();
+            RgbaImage::from_raw(w, h, pixels).map(DynamicImage::ImageRgba8)
+        }
+        None => RgbImage::from_raw(w, h, colors.concat()).map(DynamicImage::ImageRgb8),
+    };
+    image.ok_or_else(|| "image data is shorter than the header says".to_string())
+}
+
+// PackBits, one row at a time; short rows are padded so planes stay aligned
+fn unpack_bits(input: &[u8], row_bytes: usize, out: &mut Vec<u8>) -> Result<(), String> {
+    let start = out.len();
+    let mut i = 0;
+    while i < input.len() && out.len() - start < row_bytes {
+        let n = input[i] as i8;
+        i += 1;
+        if n >= 0 {
+            let literal = input.get(i..i + n as usize + 1).ok_or("corrupt RLE data")?;
+            out.extend_from_slice(literal);
+            i += literal.len();
+        } else if n != -128 {
+            let value = *input.get(i).ok_or("corrupt RLE data")?;
+            out.extend(std::iter::repeat_n(value, 1 + (-(n as i32)) as usize));
+            i += 1;
+        }
+    }
+    out.resize(start + row_bytes, 0);
+    Ok(())
+}
+
+// One channel as 8-bit samples
+fn to_8bit(plane: &[u8], depth: u16, width: usize) -> Vec<u8> {
+    match depth {
+        // 1 is black
+        1 => plane
+            .chunks(width.div_ceil(8))
+            .flat_map(|row| (0..width).map(move |x| if row[x / 8] & (0x80 >> (x % 8)) != 0 { 0 } else { 255 }))
+            .collect(),
+        8 => plane.to_vec(),
+        16 => plane.chunks_exact(2).map(|sample| sample[0]).collect(),
+        _ => plane
+            .chunks_exact(4)
+            .map(|sample| {
+                let value = f32::from_be_bytes(sample.try_into().unwrap());
+                (value.clamp(0.0, 1.0) * 255.0).round() as u8
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,76 @@
+use image::{DynamicImage, GrayImage, Luma};
+use qrcodegen::{QrCode, QrCodeEcc};
+use serde::Deserialize;
+
+use crate::i18n::tr;
+
+// Light modules around the code; scanners need some quiet space to find it
+const TERMINAL_QUIET: i32 = 1;
+const IMAGE_QUIET: i32 = 4;
+const IMAGE_MODULE: u32 = 8;
+
+// Uploaded URLs as a QR code, to open the image on a phone: drawn in the
+// terminal, and with "clipboard" also copied as an image instead of the URL
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UploadQr {
+    #[default]
+    Off,
+    Terminal,
+    Clipboard,
+}
+
+impl UploadQr {
+    // What ends up on the clipboard after an upload
+    pub fn copied(self) -> &'static str {
+        match self {
+            UploadQr::Clipboard => tr("result.copied_qr"),
+            _ => tr("result.copied_url"),
+        }
+    }
+}
+
+// None when the text is too long for any QR code
+fn encode(text: &str) -> Option<QrCode> {
+    QrCode::encode_text(text, QrCodeEcc::Medium).ok()
+}
+
+// Light modules are drawn and dark ones left blank, which scans on the usual
+// dark terminal background. Each line holds two rows of modules in half
+// blocks, or in ASCII one row at two characters per module.
+pub fn lines(text: &str, ascii: bool) -> Option<Vec<String>> {
+    let code = encode(text)?;
+    let range = -TERMINAL_QUIET..code.size() + TERMINAL_QUIET;
+    let light = |x: i32, y: i32| !code.get_module(x, y);
+    let lines = if ascii {
+        range.clone().map(|y| range.clone().map(|x| if light(x, y) { "##" } else { "  " }).collect()).collect()
+    } else {
+        range
+            .clone()
+            .step_by(2)
+            .map(|y| {
+                range
+                    .clone()
+                    .map(|x| match (light(x, y), light(x, y + 1) && y + 1 < range.end) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    })
+                    .collect()
+            })
+            .collect()
+    };
+    Some(lines)
+}
+
+// Black on white, for the clipboard
+pub fn image(text: &str) -> Option<DynamicImage> {
+    let code = encode(text)?;
+    let side = (code.size() + 2 * IMAGE_QUIET) as u32 * IMAGE_MODULE;
+    let img = GrayImage::from_fn(side, side, |x, y| {
+        let (x, y) = ((x / IMAGE_MODULE) as i32 - IMAGE_QUIET, (y / IMAGE_MODULE) as i32 - IMAGE_QUIET);
+        Luma([if code.get_module(x, y) { 0 } else { 255 }])
+    });
+    Some(DynamicImage::ImageLuma8(img))
+}
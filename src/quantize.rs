@@ -0,0 +1,149 @@
+use image::RgbaImage;
+use std::collections::HashMap;
+
+// Pixels looked at when choosing the palette; larger images are sampled evenly
+const MAX_SAMPLES: usize = 256 * 1024;
+
+// 4x4 Bayer matrix for ordered dithering
+const BAYER: [[f32; 4]; 4] = [[0.0, 8.0, 2.0, 10.0], [12.0, 4.0, 14.0, 6.0], [3.0, 11.0, 1.0, 9.0], [15.0, 7.0, 13.0, 5.0]];
+
+// How the error of each reduced pixel is hidden. Flat UI stays crisp with
+// none; gradients band without one of the others.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dither {
+    None,
+    // A fixed crosshatch: regular, with no stray specks
+    Ordered,
+    // Error diffusion: smoothest gradients, but noisy and larger files
+    FloydSteinberg,
+}
+
+impl Dither {
+    pub fn parse(name: &str) -> Option<Dither> {
+        match name {
+            "none" => Some(Dither::None),
+            "ordered" => Some(Dither::Ordered),
+            "floyd-steinberg" => Some(Dither::FloydSteinberg),
+            _ => None,
+        }
+    }
+}
+
+// Reduces the image to at most `colors` colors (alpha included), so it can be
+// written as a palette PNG. The palette comes from median cut, and every pixel
+// takes the nearest palette color.
+pub fn quantize(img: &RgbaImage, colors: usize, dither: Dither) -> RgbaImage {
+    let palette = median_cut(img, colors);
+    let mut nearest: HashMap<[u8; 4], [u8; 4]> = HashMap::new();
+    // Roughly the distance between neighboring palette colors
+    let spread = 255.0 / (colors as f32).cbrt();
+    reduce(img, dither, spread, |color| *nearest.entry(color).or_insert_with(|| closest(&palette, color)))
+}
+
+// Keeps `bits` bits of each color channel; alpha is left alone
+pub fn posterize(img: &RgbaImage, bits: u8, dither: Dither) -> RgbaImage {
+    let levels = ((1u32 << bits) - 1) as f32;
+    let level = |value: u8| ((value as f32 * levels / 255.0).round() * 255.0 / levels).round() as u8;
+    reduce(img, dither, 255.0 / levels, |[r, g, b, a]| [level(r), level(g), level(b), a])
+}
+
+// Maps every pixel through `nearest`, dithering the color channels first
+fn reduce(img: &RgbaImage, dither: Dither, spread: f32, mut nearest: impl FnMut([u8; 4]) -> [u8; 4]) -> RgbaImage {
+    let mut out = img.clone();
+    match dither {
+        Dither::None => {
+            for pixel in out.pixels_mut() {
+                pixel.0 = nearest(pixel.0);
+            }
+        }
+        Dither::Ordered => {
+            for (x, y, pixel) in out.enumerate_pixels_mut() {
+                let offset = ((BAYER[y as usize % 4][x as usize % 4] + 0.5) / 16.0 - 0.5) * spread;
+                let [r, g, b, a] = pixel.0;
+                let shift = |value: u8| (value as f32 + offset).round().clamp(0.0, 255.0) as u8;
+                pixel.0 = nearest([shift(r), shift(g), shift(b), a]);
+            }
+        }
+        Dither::FloydSteinberg => {
+            let (width, height) = (img.width() as usize, img.height() as usize);
+            let mut values: Vec<[f32; 4]> = img.pixels().map(|p| p.0.map(|v| v as f32)).collect();
+            for y in 0..height {
+                for x in 0..width {
+                    let wanted = values[y * width + x].map(|v| v.round().clamp(0.0, 255.0) as u8);
+                    let got = nearest(wanted);
+                    out.put_pixel(x as u32, y as u32, image::Rgba(got));
+                    let error: [f32; 4] = std::array::from_fn(|c| values[y * width + x][c] - got[c] as f32);
+                    let mut spread_to = |dx: isize, dy: usize, share: f32| {
+                        let nx = x as isize + dx;
+                        if nx >= 0 && (nx as usize) < width && y + dy < height {
+                            let target = &mut values[(y + dy) * width + nx as usize];
+                            for c in 0..4 {
+                                target[c] += error[c] * share;
+                            }
+                        }
+                    };
+                    spread_to(1, 0, 7.0 / 16.0);
+                    spread_to(-1, 1, 3.0 / 16.0);
+                    spread_to(0, 1, 5.0 / 16.0);
+                    spread_to(1, 1, 1.0 / 16.0);
+                }
+            }
+        }
+    }
+    out
+}
+
+// A group of similar colors and the channel they differ most in
+struct ColorBox {
+    pixels: Vec<[u8; 4]>,
+    channel: usize,
+    range: u8,
+}
+
+impl ColorBox {
+    fn new(pixels: Vec<[u8; 4]>) -> ColorBox {
+        let (channel, range) = (0..4)
+            .map(|channel| {
+                let values = pixels.iter().map(|p| p[channel]);
+                (channel, values.clone().max().unwrap_or(0) - values.min().unwrap_or(0))
+            })
+            .max_by_key(|(_, range)| *range)
+            .unwrap_or((0, 0));
+        ColorBox { pixels, channel, range }
+    }
+
+    fn average(&self) -> [u8; 4] {
+        let mut sums = [0u64; 4];
+        for pixel in &self.pixels {
+            for (sum, value) in sums.iter_mut().zip(pixel) {
+                *sum += *value as u64;
+            }
+        }
+        sums.map(|sum| (sum / self.pixels.len().max(1) as u64) as u8)
+    }
+}
+
+// Splits the box with the widest channel at its median until there are
+// `colors` boxes or nothing left to split
+fn median_cut(img: &RgbaImage, colors: usize) -> Vec<[u8; 4]> {
+    let step = (img.pixels().len() / MAX_SAMPLES).max(1);
+    let mut boxes = vec![ColorBox::new(img.pixels().step_by(step).map(|p| p.0).collect())];
+    while boxes.len() < colors {
+        let Some(widest) = (0..boxes.len()).filter(|i| boxes[*i].range > 0).max_by_key(|i| boxes[*i].range) else {
+            break;
+        };
+        let ColorBox { mut pixels, channel, .. } = boxes.swap_remove(widest);
+        pixels.sort_unstable_by_key(|p| p[channel]);
+        let upper = pixels.split_off(pixels.len() / 2);
+        boxes.push(ColorBox::new(pixels));
+        boxes.push(ColorBox::new(upper));
+    }
+    boxes.iter().map(ColorBox::average).collect()
+}
+
+fn closest(palette: &[[u8; 4]], color: [u8; 4]) -> [u8; 4] {
+    let distance = |other: &[u8; 4]| -> u32 {
+        color.iter().zip(other).map(|(a, b)| (*a as i32 - *b as i32).pow(2) as u32).sum()
+    };
+    palette.iter().copied().min_by_key(distance).unwrap_or(color)
+}
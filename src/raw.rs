@@ -0,0 +1,47 @@
+use std::path::Path;
+
+// Camera RAW files. Each of these formats embeds a JPEG the camera rendered
+// itself, usually at or near full resolution, and that is what imgopt works
+// from: the sensor data isn't developed.
+pub const RAW_EXTENSIONS: &[&str] = &["cr2", "cr3", "nef", "nrw", "arw", "dng", "orf", "raf", "rw2", "pef"];
+
+pub fn is_raw(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| RAW_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+// The embedded JPEG, from its first byte to the end of the file
+pub struct Preview {
+    pub bytes: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Picks the largest embedded JPEG. Looking for JPEG start markers instead of
+// walking each maker's container covers the TIFF-based formats, CR3 and RAF
+// alike; chance matches in the sensor data fail to parse and are skipped.
+pub fn preview(path: &Path) -> Result<Preview, String> {
+    let mut data = std::fs::read(path).map_err(|e| e.to_string())?;
+    let mut best: Option<(usize, u32, u32)> = None;
+
+    for start in data.windows(3).enumerate().filter(|(_, w)| *w == [0xFF, 0xD8, 0xFF]).map(|(i, _)| i) {
+        let mut decoder = jpeg_decoder::Decoder::new(&data[start..]);
+        let Some(info) = decoder.read_info().ok().and(decoder.info()) else {
+            continue;
+        };
+        // Lossless JPEG holds undeveloped sensor data, not a picture
+        if info.coding_process == jpeg_decoder::CodingProcess::Lossless {
+            continue;
+        }
+        let (width, height) = (info.width as u32, info.height as u32);
+        if best.is_none_or(|(_, w, h)| width as u64 * height as u64 > w as u64 * h as u64) {
+            best = Some((start, width, height));
+        }
+    }
+
+    let (start, width, height) = best.ok_or("no embedded JPEG preview found")?;
+    crate::log(&format!("RAW preview: {}x{} at byte {}", width, height, start));
+    data.drain(..start);
+    Ok(Preview { bytes: data, width, height })
+}
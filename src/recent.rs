@@ -0,0 +1,131 @@
+use crossterm::{
+    event::{KeyCode, KeyEvent},
+    terminal,
+};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::i18n::{tr, trf};
+use crate::paths::is_image_path;
+use crate::theme::Theme;
+use crate::ui::{draw_footer, draw_list, print_centered, truncate, Action, ListItem};
+use crate::Result;
+
+// Number keys 1-9 select an entry, so more than nine can't be addressed
+const MAX_RECENT: usize = 9;
+
+struct Recent {
+    path: PathBuf,
+    modified: SystemTime,
+}
+
+fn recent_images(dir: &Path, count: usize) -> Vec<Recent> {
+    let Ok(read) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut recent: Vec<Recent> = read
+        .filter_map(|e| e.ok())
+        .filter(|e| is_image_path(&e.file_name().to_string_lossy()))
+        .filter_map(|e| {
+            let modified = e.metadata().ok()?.modified().ok()?;
+            Some(Recent { path: e.path(), modified })
+        })
+        .collect();
+
+    recent.sort_by_key(|r| std::cmp::Reverse(r.modified));
+    recent.truncate(count.min(MAX_RECENT));
+    recent
+}
+
+fn age(modified: SystemTime) -> String {
+    let secs = SystemTime::now().duration_since(modified).map(|d| d.as_secs()).unwrap_or(0);
+    match secs {
+        0..=59 => tr("age.now").to_string(),
+        60..=3599 => trf("age.minutes", &[("count", &(secs / 60))]),
+        3600..=86399 => trf("age.hours", &[("count", &(secs / 3600))]),
+        _ => trf("age.days", &[("count", &(secs / 86400))]),
+    }
+}
+
+// The newest images in the screenshots folder, addressable by number
+pub struct RecentList {
+    dir: PathBuf,
+    recent: Vec<Recent>,
+    selected: usize,
+}
+
+impl RecentList {
+    pub fn new(dir: &Path, count: usize) -> RecentList {
+        RecentList {
+            dir: dir.to_path_buf(),
+            recent: recent_images(dir, count),
+            selected: 0,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') => Action::Back,
+            KeyCode::Char(c @ '1'..='9') => {
+                let index = c as usize - '1' as usize;
+                match self.recent.get(index) {
+                    Some(entry) => Action::Open(entry.path.clone()),
+                    None => Action::None,
+                }
+            }
+            KeyCode::Up => {
+                self.selected = self.selected.saturating_sub(1);
+                Action::None
+            }
+            KeyCode::Down => {
+                self.selected = (self.selected + 1).min(self.recent.len().saturating_sub(1));
+                Action::None
+            }
+            KeyCode::Enter => match self.recent.get(self.selected) {
+                Some(entry) => Action::Open(entry.path.clone()),
+                None => Action::None,
+            },
+            KeyCode::Char('i') => match self.recent.get(self.selected) {
+                Some(entry) => Action::Inspect(entry.path.clone()),
+                None => Action::None,
+            },
+            _ => Action::None,
+        }
+    }
+
+    pub fn render(&self, stdout: &mut io::Stdout, theme: &Theme) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let max_len = width.saturating_sub(4) as usize;
+
+        let top = height.saturating_sub(self.recent.len() as u16 + 4) / 2;
+        print_centered(stdout, top, theme.title, &format!("{}{}", theme.pick("🕘 ", ""), tr("recent.title")))?;
+        print_centered(stdout, top + 1, theme.muted, &truncate(&self.dir.display().to_string(), max_len))?;
+
+        if self.recent.is_empty() {
+            print_centered(stdout, top + 3, theme.muted, tr("recent.empty"))?;
+        }
+
+        let items: Vec<ListItem> = self
+            .recent
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let name = entry.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                ListItem {
+                    text: format!("{}  {}  ({})", i + 1, name, age(entry.modified)),
+                    color: theme.text,
+                }
+            })
+            .collect();
+
+        let left = width.saturating_sub(max_len.min(60) as u16) / 2;
+        draw_list(stdout, theme, left, top + 3, items.len(), &items, self.selected)?;
+        draw_footer(
+            stdout,
+            theme,
+            theme.pick(tr("recent.footer"), tr("recent.footer_ascii")),
+        )
+    }
+}
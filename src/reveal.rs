@@ -0,0 +1,44 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::i18n::tr;
+use crate::{log, Result};
+
+// What `o` on the result screen opens
+pub fn file_manager() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "Finder"
+    } else if cfg!(windows) {
+        "Explorer"
+    } else {
+        tr("reveal.file_manager")
+    }
+}
+
+// Shows a file in the file manager: selected in Finder and Explorer, while
+// xdg-open can only open the folder it is in
+pub fn reveal(path: &Path) -> Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg("-R").arg(path);
+        command
+    } else if cfg!(windows) {
+        let mut command = Command::new("explorer");
+        command.arg(format!("/select,{}", path.display()));
+        command
+    } else {
+        let mut command = Command::new("xdg-open");
+        command.arg(if path.is_dir() { path } else { path.parent().unwrap_or(path) });
+        command
+    };
+    // Explorer exits with 1 even when it worked, so only starting it counts
+    command.stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn().map_err(|e| {
+        let program = command.get_program().to_string_lossy().into_owned();
+        match e.kind() {
+            std::io::ErrorKind::NotFound => format!("{} not found", program),
+            _ => format!("could not run {}: {}", program, e),
+        }
+    })?;
+    log(&format!("Revealed {}", path.display()));
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use image::{DynamicImage, RgbaImage};
+use std::path::Path;
+use wasmi::{Config, Engine, Linker, Memory, Module, Store, TypedFunc};
+
+use crate::config::Config as AppConfig;
+use crate::transform::Transform;
+
+// Enough for a few hundred instructions per pixel of a large output; stops
+// runaway loops instead of hanging the worker
+const FUEL: u64 = 5_000_000_000;
+const PAGE: usize = 64 * 1024;
+
+// A user-supplied WebAssembly module that edits the image in place. It must
+// export `memory` and `transform(ptr, width, height)`, and gets the pixels as
+// RGBA8 rows at `ptr`. If it exports `alloc(len) -> ptr` that decides where
+// they go; otherwise they are written at offset 0, growing memory as needed.
+// Modules have no imports, so they can't touch files or the network.
+pub struct Script {
+    engine: Engine,
+    module: Module,
+}
+
+// `name` is a .wasm or .wat file, relative to the config directory unless absolute
+pub fn load(name: Option<&str>) -> Result<Box<dyn Transform>, String> {
+    let name = name.filter(|n| !n.is_empty()).ok_or("needs a module file, e.g. \"wasm:redact.wasm\"")?;
+    let path = match AppConfig::path().and_then(|p| p.parent().map(Path::to_path_buf)) {
+        Some(dir) => dir.join(name),
+        None => Path::new(name).to_path_buf(),
+    };
+    let bytes = std::fs::read(&path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+
+    let mut config = Config::default();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config);
+    let module = Module::new(&engine, bytes).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(Box::new(Script { engine, module }))
+}
+
+impl Transform for Script {
+    fn apply(&self, img: DynamicImage) -> Result<DynamicImage, String> {
+        let (width, height) = (img.width(), img.height());
+        let mut pixels = img.to_rgba8().into_raw();
+        let len = pixels.len();
+
+        let mut store = Store::new(&self.engine, ());
+        store.set_fuel(FUEL).map_err(|e| e.to_string())?;
+        let instance = Linker::new(&self.engine).instantiate_and_start(&mut store, &self.module).map_err(|e| e.to_string())?;
+        let memory: Memory = instance.get_memory(&store, "memory").ok_or("module exports no memory")?;
+        let transform: TypedFunc<(i32, i32, i32), ()> =
+            instance.get_typed_func(&store, "transform").map_err(|e| format!("transform export: {}", e))?;
+
+        let ptr = match instance.get_typed_func::<i32, i32>(&store, "alloc") {
+            Ok(alloc) => alloc.call(&mut store, len as i32).map_err(|e| format!("alloc: {}", e))? as usize,
+            Err(_) => 0,
+        };
+        let needed = (ptr + len).saturating_sub(memory.data_size(&store));
+        if needed > 0 {
+            memory.grow(&mut store, needed.div_ceil(PAGE) as u64).map_err(|e| format!("growing memory: {}", e))?;
+        }
+
+        memory.write(&mut store, ptr, &pixels).map_err(|e| e.to_string())?;
+        transform.call(&mut store, (ptr as i32, width as i32, height as i32)).map_err(|e| e.to_string())?;
+        memory.read(&store, ptr, &mut pixels).map_err(|e| e.to_string())?;
+
+        Ok(DynamicImage::ImageRgba8(RgbaImage::from_raw(width, height, pixels).ok_or("buffer size changed")?))
+    }
+}
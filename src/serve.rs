@@ -0,0 +1,537 @@
+use image::{DynamicImage, ImageFormat};
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::cache::Cache;
+use crate::clipboard::flatten;
+use crate::autoformat;
+use crate::content::Content;
+use crate::config::{Config, OutputFormat, Resize};
+use crate::dpi;
+use crate::frame::Frame;
+use crate::optimize::{format_bytes, render, OptimizeError};
+use crate::ssim;
+use crate::{log, say, Result};
+
+pub const DEFAULT_PORT: u16 = 8080;
+
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_HEADERS: usize = 100;
+// Longest request line or header, and the whole head, in bytes
+const MAX_LINE: u64 = 8 * 1024;
+const MAX_HEAD: u64 = 64 * 1024;
+
+// Temp file names, unique across concurrent requests
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+struct Request {
+    method: String,
+    path: String,
+    query: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn error(status: u16, message: impl Into<String>) -> Response {
+        Response {
+            status,
+            content_type: "application/json",
+            headers: Vec::new(),
+            body: json!({ "error": message.into() }).to_string().into_bytes(),
+        }
+    }
+}
+
+// `imgopt serve`: a small HTTP server on localhost so editors and scripts can
+// run images through the same pipeline. POST the image as the request body to
+// /optimize; the optimized image comes back as the response body, with its
+// metadata as JSON in the X-Imgopt-Metadata header. Nothing is copied,
+// post-processed or uploaded.
+pub fn run_server(port: u16, config: &Config) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| format!("could not listen on port {}: {}", port, e))?;
+    say(&format!("imgopt serve: listening on http://127.0.0.1:{}", port));
+    say("POST an image to /optimize, Ctrl-C to stop");
+    log(&format!("Serving on port {}", port));
+
+    // As many connections at a time as background jobs; the rest wait in the
+    // listen backlog
+    let slots = config.background.slots();
+    let cache = Cache::default();
+    std::thread::scope(|scope| {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let slot = slots.take();
+            let cache = &cache;
+            scope.spawn(move || {
+                handle(stream, config, cache);
+                drop(slot);
+            });
+        }
+    });
+    Ok(())
+}
+
+fn handle(mut stream: TcpStream, config: &Config, cache: &Cache) {
+    stream.set_read_timeout(Some(READ_TIMEOUT)).ok();
+    stream.set_write_timeout(Some(WRITE_TIMEOUT)).ok();
+    let (line, response) = match read_request(&mut stream, config.limits.max_input_bytes) {
+        Ok(request) => (format!("{} {}", request.method, request.path), route(&request, config, cache)),
+        Err(response) => ("(unreadable request)".to_string(), response),
+    };
+    log(&format!("Served {} -> {}", line, response.status));
+    if let Err(e) = write_response(&mut stream, &response) {
+        log(&format!("Could not send response: {}", e));
+    }
+}
+
+fn route(request: &Request, config: &Config, cache: &Cache) -> Response {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/optimize") => optimize_request(request, config, cache),
+        (_, "/optimize") => Response::error(405, "use POST"),
+        _ => Response::error(404, format!("no such endpoint: {}", request.path)),
+    }
+}
+
+fn optimize_request(request: &Request, config: &Config, cache: &Cache) -> Response {
+    if request.body.is_empty() {
+        return Response::error(400, "send the image as the request body");
+    }
+    let input = match temp_input(&request.body) {
+        Ok(input) => input,
+        Err(e) => return Response::error(500, format!("could not store the upload: {}", e)),
+    };
+    let processed = process(&input, &request.query, config, cache);
+    std::fs::remove_file(&input).ok();
+
+    match processed {
+        Ok(processed) => Response {
+            status: 200,
+            content_type: processed.format.to_mime_type(),
+            headers: vec![("X-Imgopt-Metadata", processed.metadata.to_string())],
+            body: processed.bytes,
+        },
+        Err((status, message)) => Response::error(status, message),
+    }
+}
+
+// An encoded result, ready to send back
+pub struct Processed {
+    pub bytes: Vec<u8>,
+    pub format: ImageFormat,
+    pub metadata: serde_json::Value,
+}
+
+// Optimizes the image at `input` with `params` (the /optimize query
+// parameters) applied to the config. Errors carry the HTTP status to answer with.
+pub fn process(
+    input: &Path,
+    params: &HashMap<String, String>,
+    config: &Config,
+    cache: &Cache,
+) -> std::result::Result<Processed, (u16, String)> {
+    let config = with_params(config, params).map_err(|e| (400, e))?.with_source_profile(input);
+    let requested = match params.get("format") {
+        None => None,
+        Some(name) => Some(OutputFormat::parse(name).ok_or_else(|| (400, format!("unknown format \"{}\" (png, jpeg or auto)", name)))?),
+    };
+
+    let rendered = render(input, &config, cache, &|_| true).map_err(|e| {
+        let status = match e {
+            OptimizeError::NotFound => 404,
+            OptimizeError::Unsupported(_) => 415,
+            OptimizeError::Unreadable(_) => 422,
+            OptimizeError::TooLarge(_) => 413,
+            _ => 500,
+        };
+        (status, e.to_string())
+    })?;
+    // The auto preset's pick stands in for `format`, not for the parameter
+    let format = requested.or_else(|| rendered.content.and_then(Content::format)).unwrap_or(config.format);
+    let (candidates, reason) = match format.image_format() {
+        Some(format) => (vec![format], None),
+        None => {
+            let choice = autoformat::choose(&rendered.image);
+            (choice.candidates, Some(choice.reason))
+        }
+    };
+    // The smallest encoding wins when auto leaves more than one
+    let mut best: Option<(ImageFormat, Vec<u8>)> = None;
+    for format in candidates {
+        let output = input.with_extension(format!("out.{}", format.extensions_str()[0]));
+        let bytes = encode(&rendered.image, format, &config, &output).map_err(|e| (500, format!("could not encode: {}", e)))?;
+        if best.as_ref().is_none_or(|(_, smallest)| bytes.len() < smallest.len()) {
+            best = Some((format, bytes));
+        }
+    }
+    let (format, bytes) = best.ok_or((500, "nothing to encode".to_string()))?;
+    if let Some(reason) = reason {
+        log(&format!("Auto format: {} ({})", format.extensions_str()[0], reason));
+    }
+
+    // Only JPEG loses anything; PNG and WebP are lossless
+    let ssim = (format == ImageFormat::Jpeg)
+        .then(|| image::load_from_memory_with_format(&bytes, format).ok())
+        .flatten()
+        .map(|encoded| ssim::ssim(&flatten(&rendered.image, config.appearance.matte()), &encoded));
+    let mut warnings = rendered.warnings;
+    if let Some(score) = ssim.filter(|score| *score < config.min_ssim) {
+        warnings.push(format!(
+            "JPEG quality {} scores SSIM {:.3}, under min_ssim {}; raise jpeg_quality for a closer result",
+            config.jpeg_quality, score, config.min_ssim
+        ));
+    }
+
+    let input_bytes = std::fs::metadata(input).map(|m| m.len()).unwrap_or(0);
+    log(&format!("Optimized {}: {} -> {}", input.display(), format_bytes(input_bytes), format_bytes(bytes.len() as u64)));
+    let mut metadata = json!({
+        "original": { "width": rendered.original.0, "height": rendered.original.1 },
+        "optimized": { "width": rendered.image.width(), "height": rendered.image.height() },
+        "format": format.extensions_str()[0],
+        "bytes": bytes.len(),
+        "input_bytes": input_bytes,
+        "cached": rendered.cached,
+        "palette": rendered.palette.iter().map(|[r, g, b]| format!("#{:02x}{:02x}{:02x}", r, g, b)).collect::<Vec<_>>(),
+    });
+    if let Some(reason) = reason {
+        metadata["format_reason"] = json!(reason);
+    }
+    if let Some(content) = rendered.content {
+        metadata["content"] = json!(content.name());
+    }
+    if let Some(score) = ssim {
+        metadata["ssim"] = json!((score * 10000.0).round() / 10000.0);
+    }
+    if !warnings.is_empty() {
+        metadata["warnings"] = json!(warnings);
+    }
+    Ok(Processed { bytes, format, metadata })
+}
+
+// Parameters given as a JSON object (MCP arguments, daemon jobs) in the same
+// string form as query parameters, minus the `skip` keys
+pub fn params_from_json(object: &serde_json::Value, skip: &[&str]) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    for (name, value) in object.as_object().into_iter().flatten() {
+        if skip.contains(&name.as_str()) {
+            continue;
+        }
+        let value = match value {
+            serde_json::Value::String(text) => text.clone(),
+            other => other.to_string(),
+        };
+        params.insert(name.clone(), value);
+    }
+    params
+}
+
+// The pipeline works on files, so uploaded bytes get one for the duration; the
+// caller removes it. The extension matters: large JPEGs and PNGs are picked for
+// reduced decoding by it.
+pub fn temp_input(bytes: &[u8]) -> std::io::Result<PathBuf> {
+    let name = format!("imgopt-serve-{}-{}", std::process::id(), NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    let mut input = std::env::temp_dir().join(name);
+    if let Ok(guessed) = image::guess_format(bytes) {
+        input.set_extension(guessed.extensions_str()[0]);
+    }
+    std::fs::write(&input, bytes)?;
+    Ok(input)
+}
+
+// The config with query parameters applied on top. Resize parameters adjust
+// the selected preset, or [resize] when there is none.
+fn with_params(config: &Config, query: &HashMap<String, String>) -> std::result::Result<Config, String> {
+    let mut config = config.clone();
+    if let Some(name) = query.get("preset") {
+        if !config.has_preset(name) {
+            return Err(format!("no preset named \"{}\"", name));
+        }
+        config.preset = Some(name.clone());
+    }
+    if let Some(frame) = query.get("frame") {
+        config.frame = match frame.as_str() {
+            "none" => Frame::None,
+            "window" => Frame::Window,
+            "browser" => Frame::Browser,
+            other => return Err(format!("unknown frame \"{}\" (none, window or browser)", other)),
+        };
+    }
+
+    let selected = config.preset.clone();
+    let resize = match selected.and_then(|name| config.presets.get_mut(&name)) {
+        Some(preset) => &mut preset.resize,
+        None => &mut config.resize,
+    };
+    for (name, value) in query {
+        let number = || value.parse::<u32>().ok().filter(|n| *n > 0).ok_or(format!("{} must be a positive number", name));
+        let flag = || match value.as_str() {
+            "true" | "1" => Ok(true),
+            "false" | "0" => Ok(false),
+            _ => Err(format!("{} must be true or false", name)),
+        };
+        match name.as_str() {
+            "min_dimension" => resize.min_dimension = number()?,
+            "max_dimension" => resize.max_dimension = number()?,
+            "max_width" => resize.max_width = Some(number()?),
+            "max_height" => resize.max_height = Some(number()?),
+            "never_enlarge" => resize.never_enlarge = flag()?,
+            "retina" => resize.retina = flag()?,
+            "canvas" => resize.canvas = Some(Resize::parse_canvas(value).ok_or("canvas must be WIDTHxHEIGHT, like 1200x630")?),
+            "preset" | "frame" | "format" => {}
+            other => return Err(format!("unknown parameter \"{}\"", other)),
+        }
+    }
+    Ok(config)
+}
+
+// Encodes through a temp file so DPI tagging works the same as for clipboard output
+fn encode(img: &DynamicImage, format: ImageFormat, config: &Config, path: &Path) -> Result<Vec<u8>> {
+    let (dpi, quality, icc) = (config.output_dpi, config.jpeg_quality, config.icc_profile());
+    let saved = match format {
+        ImageFormat::Jpeg => dpi::save(&flatten(img, config.appearance.matte()), path, format, dpi, quality, icc),
+        _ => dpi::save(img, path, format, dpi, quality, icc),
+    };
+    let bytes = saved.and_then(|_| Ok(std::fs::read(path)?));
+    std::fs::remove_file(path).ok();
+    bytes
+}
+
+// Just enough HTTP/1.1 for local clients: one request per connection, with
+// the body sized by Content-Length
+fn read_request(stream: &mut (impl Read + Write), max_body: u64) -> std::result::Result<Request, Response> {
+    let bad = |message: &str| Response::error(400, message);
+    let mut reader = BufReader::new(stream);
+
+    let mut left = MAX_HEAD;
+    let line = read_line(&mut reader, &mut left, "request line")?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(bad("malformed request line"));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(name), percent_decode(value))
+        })
+        .collect();
+
+    let mut length = None;
+    let mut expects_continue = false;
+    for _ in 0..MAX_HEADERS {
+        let header = read_line(&mut reader, &mut left, "header")?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Err(bad("malformed header"));
+        };
+        match name.trim().to_ascii_lowercase().as_str() {
+            "content-length" => length = Some(value.trim().parse::<u64>().map_err(|_| bad("malformed Content-Length"))?),
+            "expect" => expects_continue = value.trim().eq_ignore_ascii_case("100-continue"),
+            "transfer-encoding" => return Err(Response::error(411, "chunked uploads are not supported, send Content-Length")),
+            _ => {}
+        }
+    }
+
+    let length = length.unwrap_or(0);
+    if length > max_body {
+        return Err(Response::error(
+            413,
+            format!("image is {}, the limit is {}", format_bytes(length), format_bytes(max_body)),
+        ));
+    }
+    // curl waits for this before sending large bodies
+    if expects_continue {
+        reader.get_mut().write_all(b"HTTP/1.1 100 Continue\r\n\r\n").ok();
+    }
+    let mut body = Vec::with_capacity(length as usize);
+    reader.take(length).read_to_end(&mut body).map_err(|_| bad("could not read the body"))?;
+    if (body.len() as u64) < length {
+        return Err(bad("body shorter than Content-Length"));
+    }
+
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query,
+        body,
+    })
+}
+
+// One line of the request head. A line that runs past MAX_LINE, or past
+// what is left of MAX_HEAD, is refused instead of read on.
+fn read_line(reader: &mut impl BufRead, left: &mut u64, what: &str) -> std::result::Result<String, Response> {
+    let limit = MAX_LINE.min(*left);
+    let mut line = String::new();
+    (&mut *reader)
+        .take(limit)
+        .read_line(&mut line)
+        .map_err(|_| Response::error(400, format!("could not read the {}", what)))?;
+    *left -= line.len() as u64;
+    if !line.ends_with('\n') && line.len() as u64 >= limit {
+        return Err(Response::error(431, format!("{} too long", what)));
+    }
+    Ok(line)
+}
+
+fn write_response(stream: &mut TcpStream, response: &Response) -> std::io::Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        422 => "Unprocessable Content",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+// Query strings: %XX escapes and + for space
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => match text.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            b => decoded.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Preset;
+    use std::io::Cursor;
+
+    // The request, or the status it was refused with
+    fn read(head: &str, max_body: u64) -> std::result::Result<Request, u16> {
+        read_request(&mut Cursor::new(head.as_bytes().to_vec()), max_body).map_err(|response| response.status)
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn requests_are_parsed() {
+        let head = "POST /optimize?max_width=800&preset=a%20b+c&flag HTTP/1.1\r\nContent-Length: 3\r\n\r\nabcdef";
+        let Ok(request) = read(head, 10) else {
+            panic!("refused");
+        };
+        assert_eq!((request.method.as_str(), request.path.as_str(), request.body.as_slice()), ("POST", "/optimize", &b"abc"[..]));
+        assert_eq!(request.query, params(&[("max_width", "800"), ("preset", "a b c"), ("flag", "")]));
+    }
+
+    #[test]
+    fn oversized_heads_are_refused() {
+        let long = "x".repeat(MAX_LINE as usize);
+        assert_eq!(read(&format!("GET /{} HTTP/1.1\r\n\r\n", long), 0).err(), Some(431));
+        assert_eq!(read(&format!("GET / HTTP/1.1\r\nX-Long: {}\r\n\r\n", long), 0).err(), Some(431));
+        // Lines within MAX_LINE that add up past MAX_HEAD
+        let header = format!("X-Pad: {}\r\n", "y".repeat(MAX_LINE as usize / 2));
+        assert_eq!(read(&format!("GET / HTTP/1.1\r\n{}\r\n", header.repeat(20)), 0).err(), Some(431));
+        assert!(read(&format!("GET / HTTP/1.1\r\n{}\r\n", header.repeat(6)), 0).is_ok());
+    }
+
+    #[test]
+    fn malformed_requests_are_refused() {
+        assert_eq!(read("GET\r\n\r\n", 0).err(), Some(400));
+        assert_eq!(read("GET / HTTP/1.1\r\nno colon\r\n\r\n", 0).err(), Some(400));
+        assert_eq!(read("POST / HTTP/1.1\r\nContent-Length: ten\r\n\r\n", 100).err(), Some(400));
+        assert_eq!(read("POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort", 100).err(), Some(400));
+        assert_eq!(read("POST / HTTP/1.1\r\nContent-Length: 101\r\n\r\n", 100).err(), Some(413));
+        assert_eq!(read("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n", 100).err(), Some(411));
+    }
+
+    #[test]
+    fn escapes_are_decoded_and_broken_ones_kept() {
+        assert_eq!(percent_decode("a%20b+c%2B"), "a b c+");
+        assert_eq!(percent_decode("%e2%82%AC"), "\u{20ac}");
+        for kept in ["100%", "%4", "%zz", "%%41", "%\u{e9}"] {
+            assert_eq!(percent_decode(kept), kept.replace("%41", "A"), "{}", kept);
+        }
+        assert_eq!(percent_decode("%ff"), "\u{fffd}");
+    }
+
+    #[test]
+    fn parameters_change_the_config() {
+        let mut config = Config::default();
+        config.presets.insert("docs".to_string(), Preset::default());
+
+        let query = params(&[("max_width", "800"), ("retina", "1"), ("canvas", "1200x630"), ("frame", "window")]);
+        let changed = with_params(&config, &query).unwrap();
+        let resize = changed.resize();
+        assert_eq!((resize.max_width, resize.retina, resize.canvas), (Some(800), true, Some((1200, 630))));
+        assert!(matches!(changed.frame, Frame::Window));
+
+        // With a preset, its sizes are the ones adjusted
+        let changed = with_params(&config, &params(&[("preset", "docs"), ("max_dimension", "300")])).unwrap();
+        assert_eq!((changed.resize().max_dimension, changed.resize.max_dimension), (300, config.resize.max_dimension));
+    }
+
+    #[test]
+    fn invalid_parameters_are_refused() {
+        let config = Config::default();
+        for (name, value, error) in [
+            ("preset", "missing", "no preset named \"missing\""),
+            ("frame", "polaroid", "unknown frame \"polaroid\" (none, window or browser)"),
+            ("max_width", "0", "max_width must be a positive number"),
+            ("min_dimension", "-5", "min_dimension must be a positive number"),
+            ("max_height", "", "max_height must be a positive number"),
+            ("never_enlarge", "yes", "never_enlarge must be true or false"),
+            ("canvas", "1200", "canvas must be WIDTHxHEIGHT, like 1200x630"),
+            ("quality", "80", "unknown parameter \"quality\""),
+        ] {
+            assert_eq!(with_params(&config, &params(&[(name, value)])).err().as_deref(), Some(error), "{}={}", name, value);
+        }
+    }
+}
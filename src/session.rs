@@ -0,0 +1,182 @@
+use serde::Deserialize;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::audit::csv_field;
+use crate::config::Config;
+use crate::optimize::{Delivery, OptimizeError, Outcome};
+use crate::stamp::civil_date;
+use crate::{log, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl ReportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Csv => "csv",
+            ReportFormat::Json => "json",
+        }
+    }
+}
+
+// One job, with the settings it ran with
+struct Entry {
+    time: String,
+    inputs: Vec<PathBuf>,
+    original: Option<(u32, u32)>,
+    optimized: Option<(u32, u32)>,
+    bytes: Option<(u64, u64)>,
+    // "clipboard", "upload", "file"... and where to, if anywhere
+    delivery: &'static str,
+    output: String,
+    error: Option<String>,
+    preset: String,
+    resize: String,
+    transforms: String,
+}
+
+impl Entry {
+    fn savings(&self) -> u64 {
+        self.bytes.map(|(before, after)| before.saturating_sub(after)).unwrap_or(0)
+    }
+}
+
+// Everything the TUI or plain mode handled since it started, for tracking
+// image budgets over time. Written to `report_dir` on exit, or whenever `e`
+// is pressed; each session has one file, rewritten as it grows.
+pub struct Session {
+    started: SystemTime,
+    entries: Vec<Entry>,
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session { started: SystemTime::now(), entries: Vec::new() }
+    }
+
+    pub fn record(&mut self, inputs: Vec<PathBuf>, outcome: &std::result::Result<Outcome, OptimizeError>, config: &Config) {
+        // Nothing happened to the image
+        if matches!(outcome, Err(OptimizeError::Cancelled)) {
+            return;
+        }
+        let (delivery, output) = match outcome.as_ref().map(|outcome| &outcome.delivery) {
+            Ok(Delivery::Clipboard) => ("clipboard", String::new()),
+            Ok(Delivery::Uploaded { url, .. }) => ("upload", url.clone()),
+            Ok(Delivery::Saved(path)) => ("file", path.display().to_string()),
+            Ok(Delivery::Stdout) => ("stdout", String::new()),
+            Ok(Delivery::Written { dir, .. }) => ("files", dir.display().to_string()),
+            Ok(Delivery::Fallback { saved, .. }) => ("fallback", saved.as_ref().map(|p| p.display().to_string()).unwrap_or_default()),
+            Err(_) => ("", String::new()),
+        };
+        let ok = outcome.as_ref().ok();
+        self.entries.push(Entry {
+            time: timestamp(SystemTime::now()),
+            inputs,
+            original: ok.map(|outcome| outcome.original),
+            optimized: ok.map(|outcome| outcome.optimized),
+            bytes: ok.and_then(|outcome| outcome.bytes),
+            delivery,
+            output,
+            error: outcome.as_ref().err().map(|e| e.to_string()),
+            preset: config.preset.clone().unwrap_or_default(),
+            resize: config.resize().describe(),
+            transforms: config.transforms().join(" "),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Writes imgopt-session-<start time>.csv (or .json) into `dir`
+    pub fn write(&self, dir: &Path, format: ReportFormat) -> Result<PathBuf> {
+        std::fs::create_dir_all(dir)?;
+        let started = timestamp(self.started).replace(' ', "-").replace(':', "");
+        let name = format!("imgopt-session-{}.{}", started, format.extension());
+        let path = dir.join(name);
+        let contents = match format {
+            ReportFormat::Csv => self.csv(),
+            ReportFormat::Json => self.json()?,
+        };
+        std::fs::write(&path, contents).map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+        log(&format!("Session report: {} jobs to {}", self.entries.len(), path.display()));
+        Ok(path)
+    }
+
+    fn csv(&self) -> String {
+        let mut text = String::from(
+            "time,inputs,width,height,optimized_width,optimized_height,bytes,optimized_bytes,savings,delivery,output,preset,resize,transforms,error\n",
+        );
+        for entry in &self.entries {
+            let inputs: Vec<String> = entry.inputs.iter().map(|path| path.display().to_string()).collect();
+            let size = |size: Option<(u32, u32)>| size.map(|(w, h)| (w.to_string(), h.to_string())).unwrap_or_default();
+            let ((width, height), (optimized_width, optimized_height)) = (size(entry.original), size(entry.optimized));
+            let (bytes, optimized_bytes) = entry.bytes.map(|(b, o)| (b.to_string(), o.to_string())).unwrap_or_default();
+            let fields = [
+                entry.time.clone(),
+                csv_field(&inputs.join(";")),
+                width,
+                height,
+                optimized_width,
+                optimized_height,
+                bytes,
+                optimized_bytes,
+                entry.savings().to_string(),
+                entry.delivery.to_string(),
+                csv_field(&entry.output),
+                csv_field(&entry.preset),
+                csv_field(&entry.resize),
+                csv_field(&entry.transforms),
+                csv_field(entry.error.as_deref().unwrap_or_default()),
+            ];
+            text.push_str(&fields.join(","));
+            text.push('\n');
+        }
+        text
+    }
+
+    fn json(&self) -> Result<String> {
+        let jobs: Vec<_> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                json!({
+                    "time": entry.time,
+                    "inputs": entry.inputs,
+                    "original": entry.original.map(|(width, height)| json!({ "width": width, "height": height })),
+                    "optimized": entry.optimized.map(|(width, height)| json!({ "width": width, "height": height })),
+                    "bytes": entry.bytes.map(|(bytes, _)| bytes),
+                    "optimized_bytes": entry.bytes.map(|(_, bytes)| bytes),
+                    "savings": entry.savings(),
+                    "delivery": entry.delivery,
+                    "output": entry.output,
+                    "settings": { "preset": entry.preset, "resize": entry.resize, "transforms": entry.transforms },
+                    "error": entry.error,
+                })
+            })
+            .collect();
+        let report = json!({
+            "started": timestamp(self.started),
+            "failed": self.entries.iter().filter(|entry| entry.error.is_some()).count(),
+            "bytes": self.entries.iter().filter_map(|entry| entry.bytes).map(|(bytes, _)| bytes).sum::<u64>(),
+            "savings": self.entries.iter().map(Entry::savings).sum::<u64>(),
+            "jobs": jobs,
+        });
+        Ok(serde_json::to_string_pretty(&report)?)
+    }
+}
+
+// UTC, e.g. "2026-10-16 13:20:05"
+pub fn timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (year, month, day) = civil_date(secs / 86400);
+    let seconds = secs % 86400;
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, seconds / 3600, seconds % 3600 / 60, seconds % 60)
+}
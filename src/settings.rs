@@ -0,0 +1,280 @@
+use crossterm::{
+    event::{KeyCode, KeyEvent},
+    style::Color,
+    terminal,
+};
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use crate::cache::Cache;
+use crate::config::{Adjust, Config};
+use crate::i18n::{tr, trf};
+use crate::optimize::format_bytes;
+use crate::serve::process;
+use crate::theme::Theme;
+use crate::ui::{draw_footer, draw_list, pad, pad_start, print_centered, truncate, Action, ListItem};
+use crate::Result;
+
+// Left/Right moves an amount this far, the JPEG quality QUALITY_STEP and the
+// longest side SIZE_STEP pixels
+const STEP: i32 = 10;
+const QUALITY_STEP: i32 = 5;
+const SIZE_STEP: u32 = 128;
+const ROWS: usize = 7;
+// The preview is rendered again once the keys have been left alone this long
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+// The color adjustments, JPEG quality and longest side, tweaked for this
+// session. The app copies them into its config after every key, so the next
+// drop uses them; the config file is left alone and a reload puts its values
+// back.
+pub struct Settings {
+    pub adjust: Adjust,
+    pub jpeg_quality: u8,
+    pub max_dimension: u32,
+    // What Reset all goes back to
+    initial: (u8, u32),
+    // Lossy previews scoring under this are shown as a warning
+    min_ssim: f64,
+    // The preset whose adjustments are being changed, if any
+    preset: Option<String>,
+    selected: usize,
+    preview: Option<Preview>,
+}
+
+// The last image of the session optimized again in memory with the values on
+// screen, so their effect on the output shows while they change
+struct Preview {
+    input: PathBuf,
+    changed_at: Option<Instant>,
+    running: Option<Receiver<std::result::Result<Projection, String>>>,
+    // The newest render
+    shown: Option<std::result::Result<Projection, String>>,
+}
+
+struct Projection {
+    size: (u32, u32),
+    bytes: u64,
+    input_bytes: u64,
+    // Set for lossy output
+    ssim: Option<f64>,
+}
+
+impl Settings {
+    pub fn new(config: &Config, last_input: Option<PathBuf>) -> Settings {
+        let initial = (config.jpeg_quality, config.resize().max_dimension);
+        Settings {
+            adjust: config.adjust(),
+            jpeg_quality: initial.0,
+            max_dimension: initial.1,
+            initial,
+            min_ssim: config.min_ssim,
+            preset: config.preset.clone(),
+            selected: 0,
+            // Rendered shortly after the screen opens
+            preview: last_input.map(|input| Preview { input, changed_at: Some(Instant::now()), running: None, shown: None }),
+        }
+    }
+
+    // Into the config in use, with the adjustments and longest side going to
+    // the preset if one is selected
+    pub fn apply(&self, config: &mut Config) {
+        config.set_adjust(self.adjust);
+        config.jpeg_quality = self.jpeg_quality;
+        config.resize_mut().max_dimension = self.max_dimension;
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let before = (self.adjust, self.jpeg_quality, self.max_dimension);
+        let action = self.key(key);
+        if (self.adjust, self.jpeg_quality, self.max_dimension) != before {
+            if let Some(preview) = &mut self.preview {
+                // A render of older values is no longer worth waiting for
+                preview.running = None;
+                preview.changed_at = Some(Instant::now());
+            }
+        }
+        action
+    }
+
+    fn key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('s') => return Action::Back,
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(ROWS - 1),
+            KeyCode::Left | KeyCode::Char('-') => self.change(-STEP),
+            KeyCode::Right | KeyCode::Char('+') | KeyCode::Char('=') => self.change(STEP),
+            KeyCode::Char(' ') | KeyCode::Enter if self.selected == 0 => self.adjust.grayscale = !self.adjust.grayscale,
+            KeyCode::Enter if self.selected == ROWS - 1 => {
+                self.adjust = Adjust::default();
+                (self.jpeg_quality, self.max_dimension) = self.initial;
+            }
+            KeyCode::Char('0') | KeyCode::Backspace => self.change(0),
+            _ => {}
+        }
+        Action::None
+    }
+
+    // A step of 0 resets the selected row
+    fn change(&mut self, step: i32) {
+        let amount = match self.selected {
+            0 => {
+                self.adjust.grayscale = step > 0;
+                return;
+            }
+            1 => &mut self.adjust.brightness,
+            2 => &mut self.adjust.contrast,
+            3 => &mut self.adjust.saturation,
+            4 => {
+                let quality = (self.jpeg_quality as i32 + step.signum() * QUALITY_STEP).clamp(1, 100) as u8;
+                self.jpeg_quality = if step == 0 { self.initial.0 } else { quality };
+                return;
+            }
+            5 => {
+                self.max_dimension = match step.signum() {
+                    0 => self.initial.1,
+                    1 => self.max_dimension.saturating_add(SIZE_STEP),
+                    _ => self.max_dimension.saturating_sub(SIZE_STEP).max(SIZE_STEP),
+                };
+                return;
+            }
+            _ => return,
+        };
+        *amount = if step == 0 { 0 } else { (*amount + step).clamp(-100, 100) };
+    }
+
+    // Starts a render once the keys have settled and picks up a finished one;
+    // true when there is something new to draw. `config` already holds the
+    // values on screen.
+    pub fn tick(&mut self, config: &Config) -> bool {
+        let Some(preview) = &mut self.preview else {
+            return false;
+        };
+        if let Some(running) = &preview.running {
+            return match running.try_recv() {
+                Ok(result) => {
+                    preview.shown = Some(result);
+                    preview.running = None;
+                    true
+                }
+                Err(mpsc::TryRecvError::Empty) => false,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    preview.running = None;
+                    true
+                }
+            };
+        }
+        if preview.changed_at.is_none_or(|at| at.elapsed() < DEBOUNCE) {
+            return false;
+        }
+        preview.changed_at = None;
+        let (tx, rx) = mpsc::channel();
+        let (input, config) = (preview.input.clone(), config.clone());
+        std::thread::spawn(move || {
+            // A cache of its own, so previews don't push real results out of the app's
+            let processed = process(&input, &HashMap::new(), &config, &Cache::default()).map_err(|(_, e)| e);
+            let _ = tx.send(processed.map(|processed| {
+                let optimized = &processed.metadata["optimized"];
+                Projection {
+                    size: (optimized["width"].as_u64().unwrap_or(0) as u32, optimized["height"].as_u64().unwrap_or(0) as u32),
+                    bytes: processed.bytes.len() as u64,
+                    input_bytes: processed.metadata["input_bytes"].as_u64().unwrap_or(0),
+                    ssim: processed.metadata["ssim"].as_f64(),
+                }
+            }));
+        });
+        preview.running = Some(rx);
+        true
+    }
+
+    pub fn render(&self, stdout: &mut io::Stdout, theme: &Theme) -> Result<()> {
+        let (width, height) = terminal::size()?;
+        let top = height.saturating_sub(ROWS as u16 + 4) / 2;
+        print_centered(stdout, top, theme.title, &format!("{}{}", theme.pick("🎨 ", ""), tr("settings.title")))?;
+        let scope = match &self.preset {
+            Some(name) => trf("settings.scope_preset", &[("name", name)]),
+            None => tr("settings.scope").to_string(),
+        };
+        print_centered(stdout, top + 1, theme.muted, &scope)?;
+
+        let amount = |name: &str, value: i32| ListItem {
+            text: format!("{}{:>+5}  {}", pad(name, 12), value, bar(theme, value)),
+            color: if value == 0 { theme.muted } else { theme.text },
+        };
+        let items = [
+            ListItem {
+                text: format!(
+                    "{}{}",
+                    pad(tr("settings.grayscale"), 12),
+                    pad_start(tr(if self.adjust.grayscale { "settings.on" } else { "settings.off" }), 5)
+                ),
+                color: if self.adjust.grayscale { theme.text } else { theme.muted },
+            },
+            amount(tr("settings.brightness"), self.adjust.brightness),
+            amount(tr("settings.contrast"), self.adjust.contrast),
+            amount(tr("settings.saturation"), self.adjust.saturation),
+            ListItem {
+                text: format!("{}{:>5}", pad(tr("settings.quality"), 12), self.jpeg_quality),
+                color: if self.jpeg_quality == self.initial.0 { theme.muted } else { theme.text },
+            },
+            ListItem {
+                text: format!("{}{:>5}", pad(tr("settings.max_dimension"), 12), format!("{}px", self.max_dimension)),
+                color: if self.max_dimension == self.initial.1 { theme.muted } else { theme.text },
+            },
+            ListItem { text: tr("settings.reset").to_string(), color: theme.text },
+        ];
+        let left = width.saturating_sub(44) / 2;
+        draw_list(stdout, theme, left, top + 3, items.len(), &items, self.selected)?;
+        let (preview, color) = self.preview_line(theme);
+        print_centered(stdout, top + 4 + ROWS as u16, color, &truncate(&preview, width.saturating_sub(4) as usize))?;
+        draw_footer(
+            stdout,
+            theme,
+            theme.pick(tr("settings.footer"), tr("settings.footer_ascii")),
+        )
+    }
+
+    fn preview_line(&self, theme: &Theme) -> (String, Color) {
+        let Some(preview) = &self.preview else {
+            return (tr("settings.preview_none").to_string(), theme.muted);
+        };
+        let name = preview.input.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        let busy = preview.running.is_some() || preview.changed_at.is_some();
+        match &preview.shown {
+            None => (trf("settings.preview_rendering", &[("name", &name)]), theme.muted),
+            Some(Err(e)) => (trf("settings.preview_failed", &[("name", &name), ("error", e)]), theme.error),
+            Some(Ok(projection)) => {
+                let args: [(&str, &dyn std::fmt::Display); 5] = [
+                    ("name", &name),
+                    ("width", &projection.size.0),
+                    ("height", &projection.size.1),
+                    ("size", &format_bytes(projection.bytes)),
+                    ("original", &format_bytes(projection.input_bytes)),
+                ];
+                let mut text = trf("settings.preview", &args);
+                if let Some(ssim) = projection.ssim {
+                    text.push_str(&trf("settings.preview_ssim", &[("ssim", &format!("{:.3}", ssim))]));
+                }
+                // The last numbers stay up, dimmed, while newer ones are on the way
+                let color = match projection.ssim {
+                    _ if busy => theme.muted,
+                    Some(ssim) if ssim < self.min_ssim => theme.warning,
+                    _ => theme.accent,
+                };
+                (text, color)
+            }
+        }
+    }
+}
+
+// -100..100 as a 21-cell gauge with the middle marked
+fn bar(theme: &Theme, value: i32) -> String {
+    let (mark, line, middle) = if theme.ascii() { ("#", "-", "|") } else { ("●", "─", "┼") };
+    let position = ((value + 100) / STEP) as usize;
+    (0..=20)
+        .map(|i| if i == position { mark } else if i == 10 { middle } else { line })
+        .collect()
+}
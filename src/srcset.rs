@@ -0,0 +1,80 @@
+use image::{DynamicImage, ImageFormat};
+use std::path::Path;
+
+use crate::config::Srcset;
+use crate::dpi;
+use crate::optimize::resample;
+use crate::Result;
+
+// The configured widths the image can fill without enlarging, smallest first.
+// An image narrower than all of them goes out at its own width.
+pub fn widths(width: u32, settings: &Srcset) -> Vec<u32> {
+    let mut widths: Vec<u32> = settings.widths.iter().copied().filter(|w| *w <= width).collect();
+    widths.sort_unstable();
+    widths.dedup();
+    if widths.is_empty() {
+        widths.push(width);
+    }
+    widths
+}
+
+// Writes a JPEG (PNG when the image has transparency) and a WebP at each width
+// into `dir`. Returns the file names and a <picture> element referring to them
+// by name. The image crate only writes lossless WebP, which beats JPEG on
+// screenshots and flat graphics but not on photos: when the WebPs come out
+// larger in total they are removed and the snippet leaves them out.
+pub fn write_set(img: &DynamicImage, dir: &Path, stem: &str, settings: &Srcset, quality: u8, icc: Option<&[u8]>) -> Result<(Vec<String>, String)> {
+    std::fs::create_dir_all(dir)?;
+    let (fallback, extension) = if img.color().has_alpha() { (ImageFormat::Png, "png") } else { (ImageFormat::Jpeg, "jpg") };
+
+    let mut files = Vec::new();
+    let mut webp = Vec::new();
+    let mut webp_files = Vec::new();
+    let mut other = Vec::new();
+    let (mut webp_bytes, mut other_bytes) = (0, 0);
+    let mut largest = (0, 0);
+    for width in widths(img.width(), settings) {
+        let height = ((img.height() as f64 * width as f64 / img.width() as f64).round() as u32).max(1);
+        let scaled = resample(img, width, height);
+        let scaled = match fallback {
+            ImageFormat::Png => DynamicImage::ImageRgba8(scaled.to_rgba8()),
+            _ => DynamicImage::ImageRgb8(scaled.to_rgb8()),
+        };
+
+        let name = format!("{}-{}.webp", stem, width);
+        dpi::save(&scaled, &dir.join(&name), ImageFormat::WebP, None, quality, icc)?;
+        webp_bytes += std::fs::metadata(dir.join(&name))?.len();
+        webp.push(format!("{} {}w", name, width));
+        webp_files.push(name);
+
+        let name = format!("{}-{}.{}", stem, width, extension);
+        dpi::save(&scaled, &dir.join(&name), fallback, None, quality, icc)?;
+        other_bytes += std::fs::metadata(dir.join(&name))?.len();
+        other.push(format!("{} {}w", name, width));
+        files.push(name);
+        largest = (width, height);
+    }
+
+    let sizes = &settings.sizes;
+    let source = if webp_bytes < other_bytes {
+        files.extend(webp_files);
+        format!("  <source type=\"image/webp\" srcset=\"{}\" sizes=\"{}\">\n", webp.join(", "), sizes)
+    } else {
+        for name in webp_files {
+            std::fs::remove_file(dir.join(name)).ok();
+        }
+        String::new()
+    };
+    let snippet = format!(
+        "<picture>\n{}  <img src=\"{}-{}.{}\" srcset=\"{}\" sizes=\"{}\" width=\"{}\" height=\"{}\" alt=\"\">\n</picture>\n",
+        source,
+        stem,
+        largest.0,
+        extension,
+        other.join(", "),
+        sizes,
+        largest.0,
+        largest.1
+    );
+    Ok((files, snippet))
+}
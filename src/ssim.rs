@@ -0,0 +1,88 @@
+use image::{DynamicImage, GrayImage};
+
+// Windows are WINDOW pixels square and STRIDE apart, so they overlap by half
+const WINDOW: u32 = 8;
+const STRIDE: u32 = 4;
+// The usual stabilizers for 8-bit values, (0.01 * 255)² and (0.03 * 255)²
+const C1: f64 = 6.5025;
+const C2: f64 = 58.5225;
+
+// Structural similarity of two images of the same size, from 0 to 1 (the
+// same image): how well `encoded` keeps the local brightness, contrast and
+// structure of `reference`. Compared on luma only, which is where lossy
+// encoders lose the detail people notice.
+pub fn ssim(reference: &DynamicImage, encoded: &DynamicImage) -> f64 {
+    let (a, b) = (reference.to_luma8(), encoded.to_luma8());
+    if a.dimensions() != b.dimensions() {
+        return 0.0;
+    }
+    let (width, height) = a.dimensions();
+    let (window_width, window_height) = (WINDOW.min(width), WINDOW.min(height));
+    let (mut total, mut count) = (0.0, 0);
+    for y in (0..=height.saturating_sub(window_height)).step_by(STRIDE as usize) {
+        for x in (0..=width.saturating_sub(window_width)).step_by(STRIDE as usize) {
+            total += window(&a, &b, x, y, window_width, window_height);
+            count += 1;
+        }
+    }
+    total / count as f64
+}
+
+fn window(a: &GrayImage, b: &GrayImage, left: u32, top: u32, width: u32, height: u32) -> f64 {
+    let n = (width * height) as f64;
+    let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    for y in top..top + height {
+        for x in left..left + width {
+            let (va, vb) = (a.get_pixel(x, y).0[0] as f64, b.get_pixel(x, y).0[0] as f64);
+            sum_a += va;
+            sum_b += vb;
+            sum_aa += va * va;
+            sum_bb += vb * vb;
+            sum_ab += va * vb;
+        }
+    }
+    let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+    let variance_a = sum_aa / n - mean_a * mean_a;
+    let variance_b = sum_bb / n - mean_b * mean_b;
+    let covariance = sum_ab / n - mean_a * mean_b;
+    ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+        / ((mean_a * mean_a + mean_b * mean_b + C1) * (variance_a + variance_b + C2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    fn pattern(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| Luma([((x * 37 + y * 91) % 256) as u8])))
+    }
+
+    #[test]
+    fn the_same_image_scores_one() {
+        let img = pattern(40, 30);
+        assert!((ssim(&img, &img) - 1.0).abs() < 1e-9);
+        // Smaller than a window: one window of the whole image
+        let tiny = pattern(3, 5);
+        assert!((ssim(&tiny, &tiny) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn different_sizes_score_zero() {
+        assert_eq!(ssim(&pattern(40, 30), &pattern(30, 40)), 0.0);
+    }
+
+    #[test]
+    fn lost_detail_scores_lower_than_changed_brightness() {
+        let img = pattern(64, 64);
+        let mut brighter = img.to_luma8();
+        brighter.pixels_mut().for_each(|pixel| pixel.0[0] = pixel.0[0].saturating_add(8));
+        let brighter = DynamicImage::ImageLuma8(brighter);
+        let blurred = img.blur(2.0);
+        let flat = DynamicImage::ImageLuma8(GrayImage::from_pixel(64, 64, Luma([128])));
+        let (brighter, blurred, flat) = (ssim(&img, &brighter), ssim(&img, &blurred), ssim(&img, &flat));
+        assert!(brighter > 0.9, "{}", brighter);
+        assert!(blurred < brighter, "{} {}", blurred, brighter);
+        assert!(flat < 0.05, "{}", flat);
+    }
+}
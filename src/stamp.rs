@@ -0,0 +1,126 @@
+use exif::{In, Tag};
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+use serde::Deserialize;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::font::{self, GLYPH_HEIGHT};
+
+const SCALE: u32 = 2;
+const PADDING: u32 = 4;
+const MARGIN: u32 = 8;
+
+// A caption burned into a corner of the output saying when the image was
+// captured and how large the original was, so archived screenshots still
+// carry both after resizing. Off unless one of them is turned on.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Stamp {
+    pub date: bool,
+    pub resolution: bool,
+    pub corner: Corner,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    // Out of the way of the watermark transform
+    #[default]
+    BottomLeft,
+    BottomRight,
+}
+
+impl Stamp {
+    pub fn is_off(&self) -> bool {
+        !self.date && !self.resolution
+    }
+
+    pub fn describe(&self) -> String {
+        let parts: Vec<&str> = [(self.date, "date"), (self.resolution, "resolution")]
+            .into_iter()
+            .filter_map(|(on, name)| on.then_some(name))
+            .collect();
+        let corner = match self.corner {
+            Corner::TopLeft => "top left",
+            Corner::TopRight => "top right",
+            Corner::BottomLeft => "bottom left",
+            Corner::BottomRight => "bottom right",
+        };
+        format!("{} at the {}", parts.join(" and "), corner)
+    }
+
+    // `original` is the size of the file before resizing
+    pub fn apply(&self, img: DynamicImage, path: &Path, original: (u32, u32)) -> DynamicImage {
+        let mut parts = Vec::new();
+        if self.date {
+            parts.extend(captured(path));
+        }
+        if self.resolution {
+            parts.push(format!("{}x{}", original.0, original.1));
+        }
+        if parts.is_empty() {
+            return img;
+        }
+        let text = parts.join("  ");
+
+        // White on a translucent dark box, legible on any background
+        let (width, height) = (font::text_width(&text, SCALE) + 2 * PADDING, GLYPH_HEIGHT * SCALE + 2 * PADDING);
+        let mut label = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 150]));
+        font::draw_text(&mut label, PADDING, PADDING, &text, SCALE, Rgba([255, 255, 255, 230]));
+
+        let mut img = img.to_rgba8();
+        let right = img.width() as i64 - width as i64 - MARGIN as i64;
+        let bottom = img.height() as i64 - height as i64 - MARGIN as i64;
+        let (x, y) = match self.corner {
+            Corner::TopLeft => (MARGIN as i64, MARGIN as i64),
+            Corner::TopRight => (right, MARGIN as i64),
+            Corner::BottomLeft => (MARGIN as i64, bottom),
+            Corner::BottomRight => (right, bottom),
+        };
+        imageops::overlay(&mut img, &label, x.max(0), y.max(0));
+        DynamicImage::ImageRgba8(img)
+    }
+}
+
+// When the photo was taken by the camera's clock, else when the file was
+// last written (screenshots carry no EXIF), in UTC
+fn captured(path: &Path) -> Option<String> {
+    let taken = File::open(path).ok().and_then(|file| {
+        let exif = exif::Reader::new().read_from_container(&mut BufReader::new(file)).ok()?;
+        let field = exif.get_field(Tag::DateTimeOriginal, In::PRIMARY)?;
+        match &field.value {
+            exif::Value::Ascii(values) => exif::DateTime::from_ascii(values.first()?).ok(),
+            _ => None,
+        }
+    });
+    if let Some(taken) = taken {
+        return Some(format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            taken.year, taken.month, taken.day, taken.hour, taken.minute
+        ));
+    }
+
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    let secs = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let (year, month, day) = civil_date(secs / 86400);
+    let minutes = secs % 86400 / 60;
+    Some(format!("{:04}-{:02}-{:02} {:02}:{:02} UTC", year, month, day, minutes / 60, minutes % 60))
+}
+
+// Days since 1970-01-01 to a Gregorian date (Howard Hinnant's algorithm)
+pub fn civil_date(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
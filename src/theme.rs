@@ -0,0 +1,91 @@
+use crossterm::style::Color;
+use serde::Deserialize;
+
+use crate::multiplexer::Multiplexer;
+
+// Colors for every part of the UI, plus an ASCII-only mode for terminals
+// whose fonts can't render emoji or box-drawing characters. Unless `ascii` is
+// set either way, it's on inside tmux and screen.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub ascii: Option<bool>,
+    pub border: Color,
+    pub title: Color,
+    pub text: Color,
+    pub muted: Color,
+    pub accent: Color,
+    pub highlight: Color,
+    pub success: Color,
+    pub success_dim: Color,
+    pub warning: Color,
+    pub info: Color,
+    pub error: Color,
+    pub error_dim: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            ascii: None,
+            border: Color::Blue,
+            title: Color::Blue,
+            text: Color::White,
+            muted: Color::DarkGrey,
+            accent: Color::Blue,
+            highlight: Color::Green,
+            success: Color::Green,
+            success_dim: Color::DarkGreen,
+            warning: Color::Yellow,
+            info: Color::DarkYellow,
+            error: Color::Red,
+            error_dim: Color::DarkRed,
+        }
+    }
+}
+
+pub struct BoxChars {
+    pub top_left: char,
+    pub top_right: char,
+    pub bottom_left: char,
+    pub bottom_right: char,
+    pub horizontal: char,
+    pub vertical: char,
+}
+
+impl Theme {
+    pub fn ascii(&self) -> bool {
+        self.ascii.unwrap_or_else(|| Multiplexer::detect().is_some())
+    }
+
+    // Chooses between the decorated and the plain spelling of a label
+    pub fn pick<'a>(&self, fancy: &'a str, plain: &'a str) -> &'a str {
+        if self.ascii() {
+            plain
+        } else {
+            fancy
+        }
+    }
+
+    pub fn box_chars(&self) -> BoxChars {
+        if self.ascii() {
+            BoxChars {
+                top_left: '+',
+                top_right: '+',
+                bottom_left: '+',
+                bottom_right: '+',
+                horizontal: '-',
+                vertical: '|',
+            }
+        } else {
+            BoxChars {
+                top_left: '╔',
+                top_right: '╗',
+                bottom_left: '╚',
+                bottom_right: '╝',
+                horizontal: '═',
+                vertical: '║',
+            }
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use crate::i18n::trf;
+
+// How long the original and the optimized file would take to upload, on a
+// few typical connections or on `upload_mbps` when it's set. Shown on the
+// result screen to weigh quality against size.
+const UPLINKS: [(&str, f64); 3] = [("3G", 1.0), ("4G", 10.0), ("fiber", 100.0)];
+
+// e.g. "Upload: 3G 4.9s -> 2.0s, 4G 0.5s -> 0.2s, fiber <0.1s -> <0.1s"
+pub fn describe((original, optimized): (u64, u64), upload_mbps: Option<f64>, arrow: &str) -> String {
+    let times = |mbps: f64| format!("{} {} {}", duration(original, mbps), arrow, duration(optimized, mbps));
+    match upload_mbps {
+        Some(mbps) => trf("transfer.at", &[("mbps", &mbps), ("times", &times(mbps))]),
+        None => {
+            let uplinks: Vec<String> = UPLINKS.iter().map(|(name, mbps)| format!("{} {}", name, times(*mbps))).collect();
+            trf("transfer.typical", &[("times", &uplinks.join(", "))])
+        }
+    }
+}
+
+fn duration(bytes: u64, mbps: f64) -> String {
+    let secs = bytes as f64 * 8.0 / (mbps * 1_000_000.0);
+    if secs < 0.1 {
+        "<0.1s".to_string()
+    } else if secs < 10.0 {
+        format!("{:.1}s", secs)
+    } else if secs < 60.0 {
+        format!("{:.0}s", secs)
+    } else {
+        format!("{}m {:02}s", secs as u64 / 60, secs as u64 % 60)
+    }
+}
@@ -0,0 +1,194 @@
+use image::metadata::Orientation;
+use image::{imageops, DynamicImage, Rgba, RgbaImage};
+
+use crate::font::{self, GLYPH_HEIGHT};
+use crate::orient;
+use crate::quantize::{self, Dither};
+
+// One step applied to the resized image before it is encoded. New filters
+// implement this and get an entry in REGISTRY (behind a `#[cfg(feature)]` if
+// they pull in extra dependencies).
+pub trait Transform: Send + Sync {
+    fn apply(&self, img: DynamicImage) -> Result<DynamicImage, String>;
+}
+
+// Builds a transform from the text after the colon in its spec, if any
+type Constructor = fn(Option<&str>) -> Result<Box<dyn Transform>, String>;
+
+const REGISTRY: &[(&str, Constructor)] = &[
+    ("grayscale", grayscale),
+    ("blur", blur),
+    ("sharpen", sharpen),
+    ("watermark", watermark),
+    ("quantize", quantize),
+    ("posterize", posterize),
+    ("rotate", rotate),
+    ("flip", flip),
+    #[cfg(feature = "wasm")]
+    ("wasm", crate::script::load),
+];
+
+// A spec is a name with an optional argument: "grayscale", "blur:4",
+// "watermark:ACME CONFIDENTIAL"
+pub fn build(spec: &str) -> Result<Box<dyn Transform>, String> {
+    let (name, argument) = match spec.split_once(':') {
+        Some((name, argument)) => (name.trim(), Some(argument.trim())),
+        None => (spec.trim(), None),
+    };
+    let (_, constructor) = REGISTRY.iter().find(|(known, _)| *known == name).ok_or_else(|| {
+        let names: Vec<&str> = REGISTRY.iter().map(|(known, _)| *known).collect();
+        format!("unknown transform \"{}\" (available: {})", name, names.join(", "))
+    })?;
+    constructor(argument).map_err(|e| format!("transform \"{}\": {}", spec, e))
+}
+
+pub fn pipeline(specs: &[String]) -> Result<Vec<Box<dyn Transform>>, String> {
+    specs.iter().map(|spec| build(spec)).collect()
+}
+
+fn number(argument: Option<&str>, default: f32) -> Result<f32, String> {
+    match argument {
+        None => Ok(default),
+        Some(text) => text.parse().map_err(|_| format!("\"{}\" is not a number", text)),
+    }
+}
+
+struct Grayscale;
+
+impl Transform for Grayscale {
+    fn apply(&self, img: DynamicImage) -> Result<DynamicImage, String> {
+        Ok(img.grayscale())
+    }
+}
+
+fn grayscale(_: Option<&str>) -> Result<Box<dyn Transform>, String> {
+    Ok(Box::new(Grayscale))
+}
+
+// Gaussian blur; the argument is the sigma in pixels
+struct Blur(f32);
+
+impl Transform for Blur {
+    fn apply(&self, img: DynamicImage) -> Result<DynamicImage, String> {
+        Ok(img.blur(self.0))
+    }
+}
+
+fn blur(argument: Option<&str>) -> Result<Box<dyn Transform>, String> {
+    Ok(Box::new(Blur(number(argument, 4.0)?)))
+}
+
+// Unsharp mask, to crisp up text after downscaling
+struct Sharpen(f32);
+
+impl Transform for Sharpen {
+    fn apply(&self, img: DynamicImage) -> Result<DynamicImage, String> {
+        Ok(img.unsharpen(self.0, 2))
+    }
+}
+
+fn sharpen(argument: Option<&str>) -> Result<Box<dyn Transform>, String> {
+    Ok(Box::new(Sharpen(number(argument, 1.0)?)))
+}
+
+// A number, then optionally the dithering: "64", "64:ordered"
+fn with_dither(argument: Option<&str>) -> Result<(Option<&str>, Dither), String> {
+    match argument.and_then(|argument| argument.split_once(':')) {
+        None => Ok((argument, Dither::None)),
+        Some((number, name)) => {
+            let dither = Dither::parse(name.trim())
+                .ok_or_else(|| format!("unknown dithering \"{}\" (none, ordered or floyd-steinberg)", name.trim()))?;
+            Ok((Some(number.trim()).filter(|n| !n.is_empty()), dither))
+        }
+    }
+}
+
+// Fewer colors for smaller PNGs; the argument is how many, up to 256
+struct Quantize(usize, Dither);
+
+impl Transform for Quantize {
+    fn apply(&self, img: DynamicImage) -> Result<DynamicImage, String> {
+        Ok(DynamicImage::ImageRgba8(quantize::quantize(&img.to_rgba8(), self.0, self.1)))
+    }
+}
+
+fn quantize(argument: Option<&str>) -> Result<Box<dyn Transform>, String> {
+    let (argument, dither) = with_dither(argument)?;
+    match number(argument, 256.0)? {
+        colors if (2.0..=256.0).contains(&colors) && colors.fract() == 0.0 => Ok(Box::new(Quantize(colors as usize, dither))),
+        _ => Err("the number of colors must be a whole number from 2 to 256".to_string()),
+    }
+}
+
+// Fewer bits per channel; the argument is how many are kept
+struct Posterize(u8, Dither);
+
+impl Transform for Posterize {
+    fn apply(&self, img: DynamicImage) -> Result<DynamicImage, String> {
+        Ok(DynamicImage::ImageRgba8(quantize::posterize(&img.to_rgba8(), self.0, self.1)))
+    }
+}
+
+fn posterize(argument: Option<&str>) -> Result<Box<dyn Transform>, String> {
+    let (argument, dither) = with_dither(argument)?;
+    match number(argument, 4.0)? {
+        bits if (1.0..=7.0).contains(&bits) && bits.fract() == 0.0 => Ok(Box::new(Posterize(bits as u8, dither))),
+        _ => Err("the number of bits must be a whole number from 1 to 7".to_string()),
+    }
+}
+
+// Turns or mirrors the output only, on top of the EXIF orientation already
+// applied; the original stays as it is
+struct Orient(Orientation);
+
+impl Transform for Orient {
+    fn apply(&self, mut img: DynamicImage) -> Result<DynamicImage, String> {
+        img.apply_orientation(self.0);
+        Ok(img)
+    }
+}
+
+// Degrees clockwise
+fn rotate(argument: Option<&str>) -> Result<Box<dyn Transform>, String> {
+    match orient::rotation(argument.unwrap_or("90")) {
+        Some(orientation) => Ok(Box::new(Orient(orientation))),
+        None => Err("the angle must be 90, 180 or 270".to_string()),
+    }
+}
+
+fn flip(argument: Option<&str>) -> Result<Box<dyn Transform>, String> {
+    match orient::flip(argument.unwrap_or("horizontal")) {
+        Some(orientation) => Ok(Box::new(Orient(orientation))),
+        None => Err("the direction must be horizontal or vertical".to_string()),
+    }
+}
+
+// Semi-transparent text in the bottom-right corner
+struct Watermark(String);
+
+const WATERMARK_SCALE: u32 = 2;
+const WATERMARK_MARGIN: u32 = 10;
+
+impl Transform for Watermark {
+    fn apply(&self, img: DynamicImage) -> Result<DynamicImage, String> {
+        let mut img = img.to_rgba8();
+        let (width, height) = (font::text_width(&self.0, WATERMARK_SCALE), GLYPH_HEIGHT * WATERMARK_SCALE);
+
+        // Drawn on its own layer so the alpha blends with what's underneath
+        let mut layer = RgbaImage::new(width + WATERMARK_SCALE, height + WATERMARK_SCALE);
+        font::draw_text(&mut layer, WATERMARK_SCALE, WATERMARK_SCALE, &self.0, WATERMARK_SCALE, Rgba([0, 0, 0, 110]));
+        font::draw_text(&mut layer, 0, 0, &self.0, WATERMARK_SCALE, Rgba([255, 255, 255, 170]));
+
+        let x = img.width() as i64 - layer.width() as i64 - WATERMARK_MARGIN as i64;
+        let y = img.height() as i64 - layer.height() as i64 - WATERMARK_MARGIN as i64;
+        imageops::overlay(&mut img, &layer, x.max(0), y.max(0));
+        Ok(DynamicImage::ImageRgba8(img))
+    }
+}
+
+fn watermark(argument: Option<&str>) -> Result<Box<dyn Transform>, String> {
+    match argument {
+        Some(text) if !text.is_empty() => Ok(Box::new(Watermark(text.to_string()))),
+        _ => Err("needs the text to draw, e.g. \"watermark:CONFIDENTIAL\"".to_string()),
+    }
+}
@@ -0,0 +1,201 @@
+use crossterm::{
+    cursor,
+    queue,
+    style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal,
+};
+use std::io;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::i18n::trf;
+use crate::theme::Theme;
+use crate::Result;
+
+// Terminal columns, not bytes or chars: emoji and CJK take two, combining
+// marks and variation selectors none. Everything that lines text up goes
+// through these helpers rather than `len()`.
+pub fn display_width(text: &str) -> usize {
+    text.width()
+}
+
+pub fn char_width(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+// Left-aligns text in a column `width` wide, like `{:<width$}` but by columns
+pub fn pad(text: &str, width: usize) -> String {
+    format!("{}{}", text, " ".repeat(width.saturating_sub(display_width(text))))
+}
+
+// The same, right-aligned
+pub fn pad_start(text: &str, width: usize) -> String {
+    format!("{}{}", " ".repeat(width.saturating_sub(display_width(text))), text)
+}
+
+// "❌ File not found", or "Error: File not found" without emoji
+pub fn error_title(theme: &Theme, text: &str) -> String {
+    if theme.ascii() {
+        trf("error", &[("message", &text)])
+    } else {
+        format!("❌ {}", text)
+    }
+}
+
+pub fn print_centered(stdout: &mut io::Stdout, y: u16, color: Color, text: &str) -> Result<()> {
+    let (term_width, _) = terminal::size()?;
+    let x = term_width.saturating_sub(display_width(text) as u16) / 2;
+    queue!(
+        stdout,
+        cursor::MoveTo(x, y),
+        SetForegroundColor(color),
+        Print(text),
+        ResetColor,
+    )?;
+    Ok(())
+}
+
+// Fits text into `max_len` columns, keeping the end
+pub fn truncate(text: &str, max_len: usize) -> String {
+    if display_width(text) <= max_len {
+        return text.to_string();
+    }
+    let mut budget = max_len.saturating_sub(3);
+    let mut tail: Vec<char> = text
+        .chars()
+        .rev()
+        .take_while(|c| {
+            let fits = char_width(*c) <= budget;
+            budget = budget.saturating_sub(char_width(*c));
+            fits
+        })
+        .collect();
+    tail.reverse();
+    format!("...{}", tail.into_iter().collect::<String>())
+}
+
+// Breaks text that should be read whole, like an output path, over up to
+// `lines` lines; only what still doesn't fit is shortened, keeping the end
+pub fn wrap(text: &str, max_len: usize, lines: usize) -> Vec<String> {
+    let max_len = max_len.max(4);
+    let mut wrapped: Vec<String> = Vec::new();
+    let mut used = 0;
+    for c in text.chars() {
+        match wrapped.last_mut() {
+            Some(line) if used + char_width(c) <= max_len => line.push(c),
+            _ => {
+                wrapped.push(c.to_string());
+                used = 0;
+            }
+        }
+        used += char_width(c);
+    }
+    if wrapped.len() > lines.max(1) {
+        let rest = wrapped.split_off(lines.max(1) - 1).concat();
+        wrapped.push(truncate(&rest, max_len));
+    }
+    wrapped
+}
+
+// Draws a bordered box centered on screen; each line is padded to `inner_width`
+pub fn draw_box(
+    stdout: &mut io::Stdout,
+    theme: &Theme,
+    inner_width: usize,
+    lines: &[(&str, Color)],
+) -> Result<()> {
+    let (width, height) = terminal::size()?;
+    let chars = theme.box_chars();
+    let start_x = width.saturating_sub(inner_width as u16 + 2) / 2;
+    let start_y = height.saturating_sub(lines.len() as u16 + 2) / 2;
+    let horizontal: String = std::iter::repeat_n(chars.horizontal, inner_width).collect();
+
+    queue!(
+        stdout,
+        cursor::MoveTo(start_x, start_y),
+        SetForegroundColor(theme.border),
+        Print(format!("{}{}{}", chars.top_left, horizontal, chars.top_right)),
+    )?;
+
+    for (i, (line, color)) in lines.iter().enumerate() {
+        let padding = inner_width.saturating_sub(display_width(line));
+        queue!(
+            stdout,
+            cursor::MoveTo(start_x, start_y + 1 + i as u16),
+            SetForegroundColor(theme.border),
+            Print(chars.vertical),
+            SetForegroundColor(*color),
+            Print(line),
+            Print(" ".repeat(padding)),
+            SetForegroundColor(theme.border),
+            Print(chars.vertical),
+        )?;
+    }
+
+    queue!(
+        stdout,
+        cursor::MoveTo(start_x, start_y + 1 + lines.len() as u16),
+        Print(format!("{}{}{}", chars.bottom_left, horizontal, chars.bottom_right)),
+        ResetColor,
+    )?;
+    Ok(())
+}
+
+// What a screen wants the app to do after handling a key
+pub enum Action {
+    None,
+    Back,
+    Open(std::path::PathBuf),
+    // A page of a multi-page file, counted from 1
+    OpenPage(std::path::PathBuf, usize),
+    Inspect(std::path::PathBuf),
+}
+
+pub struct ListItem {
+    pub text: String,
+    pub color: Color,
+}
+
+// Scrolling list that keeps `selected` in view between rows `top` and `top + rows`
+pub fn draw_list(
+    stdout: &mut io::Stdout,
+    theme: &Theme,
+    x: u16,
+    top: u16,
+    rows: usize,
+    items: &[ListItem],
+    selected: usize,
+) -> Result<()> {
+    let (width, _) = terminal::size()?;
+    let max_len = width.saturating_sub(x + 3) as usize;
+    let rows = rows.max(1);
+    let first = selected.saturating_sub(rows - 1);
+
+    for (row, (i, item)) in items.iter().enumerate().skip(first).take(rows).enumerate() {
+        let (marker, color) = if i == selected {
+            (theme.pick("▶ ", "> "), theme.highlight)
+        } else {
+            ("  ", item.color)
+        };
+        queue!(
+            stdout,
+            cursor::MoveTo(x, top + row as u16),
+            SetForegroundColor(color),
+            Print(truncate(&format!("{}{}", marker, item.text), max_len)),
+            ResetColor,
+        )?;
+    }
+    Ok(())
+}
+
+// One line of key hints along the bottom edge
+pub fn draw_footer(stdout: &mut io::Stdout, theme: &Theme, hint: &str) -> Result<()> {
+    let (width, height) = terminal::size()?;
+    queue!(
+        stdout,
+        cursor::MoveTo(1, height.saturating_sub(1)),
+        SetForegroundColor(theme.muted),
+        Print(truncate(hint, width.saturating_sub(2) as usize)),
+        ResetColor,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,270 @@
+use hmac::{Hmac, KeyInit, Mac};
+use image::ImageFormat;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use ureq::typestate::WithBody;
+use ureq::RequestBuilder;
+
+use crate::stamp::civil_date;
+use crate::{log, Result};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// Where to upload results; the clipboard then gets the public URL instead of
+// the image. Picked with `kind` in the [upload] table.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Upload {
+    Imgur {
+        client_id: String,
+    },
+    // Any S3-compatible store (AWS, R2, MinIO, ...), addressed path-style
+    S3 {
+        endpoint: String,
+        bucket: String,
+        region: String,
+        access_key: String,
+        secret_key: String,
+        #[serde(default)]
+        prefix: String,
+        // Base URL the object is served from; defaults to endpoint/bucket
+        public_url: Option<String>,
+    },
+    // POSTs the image to `url`, as a multipart form field when `field` is set and
+    // as the raw body otherwise. The URL comes back in the JSON field at
+    // `url_field` (e.g. "data.link"), or as the whole response body.
+    Http {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+        field: Option<String>,
+        url_field: Option<String>,
+    },
+}
+
+// `bytes` is the image in `format`: PNG, or an input passed through as it is
+pub fn upload(upload: &Upload, bytes: &[u8], format: ImageFormat) -> Result<String> {
+    let (mime, extension) = (format.to_mime_type(), format.extensions_str()[0]);
+    let url = match upload {
+        Upload::Imgur { client_id } => {
+            let (content_type, body) = multipart("image", bytes, format);
+            let request = ureq::post("https://api.imgur.com/3/image")
+                .header("Authorization", format!("Client-ID {}", client_id))
+                .header("Content-Type", content_type);
+            json_field(&send(request, &body)?, "data.link")?
+        }
+        Upload::S3 { endpoint, bucket, region, access_key, secret_key, prefix, public_url } => {
+            let key = format!("{}{}.{}", prefix, content_hash(bytes), extension);
+            let endpoint = endpoint.trim_end_matches('/');
+            let path = format!("/{}/{}", bucket, uri_encode(&key));
+            let host = endpoint.split("://").last().unwrap_or(endpoint).split('/').next().unwrap_or_default();
+
+            let signed = sign_s3(host, &path, region, access_key, secret_key, bytes, utc_now());
+            let mut request = ureq::put(format!("{}{}", endpoint, path)).header("Content-Type", mime);
+            for (name, value) in signed {
+                request = request.header(name, value);
+            }
+            send(request, bytes)?;
+
+            match public_url {
+                Some(base) => format!("{}/{}", base.trim_end_matches('/'), uri_encode(&key)),
+                None => format!("{}{}", endpoint, path),
+            }
+        }
+        Upload::Http { url, headers, field, url_field } => {
+            let mut request = ureq::post(url);
+            for (name, value) in headers {
+                request = request.header(name, value);
+            }
+            let response = match field {
+                Some(field) => {
+                    let (content_type, body) = multipart(field, bytes, format);
+                    send(request.header("Content-Type", content_type), &body)?
+                }
+                None => send(request.header("Content-Type", mime), bytes)?,
+            };
+            match url_field {
+                Some(path) => json_field(&response, path)?,
+                None => response.trim().to_string(),
+            }
+        }
+    };
+
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(format!("the server did not return a URL: {}", short(&url)).into());
+    }
+    log(&format!("Uploaded to {}", url));
+    Ok(url)
+}
+
+fn send(request: RequestBuilder<WithBody>, body: &[u8]) -> Result<String> {
+    let mut response = request
+        .config()
+        .http_status_as_error(false)
+        .timeout_global(Some(TIMEOUT))
+        .build()
+        .send(body)
+        .map_err(|e| e.to_string())?;
+    let status = response.status();
+    let text = response.body_mut().read_to_string().unwrap_or_default();
+    if !status.is_success() {
+        return Err(format!("server answered {}: {}", status, short(&text)).into());
+    }
+    Ok(text)
+}
+
+// A dotted path into a JSON response, e.g. "data.link"
+fn json_field(response: &str, path: &str) -> Result<String> {
+    let value: serde_json::Value = serde_json::from_str(response).map_err(|e| format!("response is not JSON ({}): {}", e, short(response)))?;
+    let pointer = format!("/{}", path.replace('.', "/"));
+    match value.pointer(&pointer) {
+        Some(serde_json::Value::String(url)) => Ok(url.clone()),
+        _ => Err(format!("no \"{}\" in the response: {}", path, short(response)).into()),
+    }
+}
+
+fn short(text: &str) -> String {
+    let line = text.trim().lines().next().unwrap_or_default();
+    line.chars().take(200).collect()
+}
+
+fn multipart(field: &str, bytes: &[u8], format: ImageFormat) -> (String, Vec<u8>) {
+    let boundary = format!("imgopt-{}", content_hash(bytes));
+    let mut body = format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"image.{}\"\r\nContent-Type: {}\r\n\r\n",
+        boundary,
+        field,
+        format.extensions_str()[0],
+        format.to_mime_type()
+    )
+    .into_bytes();
+    body.extend_from_slice(bytes);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+    (format!("multipart/form-data; boundary={}", boundary), body)
+}
+
+// Same image, same object name, so re-uploads overwrite instead of piling
+// up, across runs and imgopt versions too
+fn content_hash(bytes: &[u8]) -> String {
+    hex(&Sha256::digest(bytes)[..8])
+}
+
+// AWS Signature Version 4 for a single PUT at `now` (see utc_now); returns
+// the headers to send
+fn sign_s3(
+    host: &str,
+    path: &str,
+    region: &str,
+    access_key: &str,
+    secret_key: &str,
+    body: &[u8],
+    (date, time): (String, String),
+) -> Vec<(&'static str, String)> {
+    let amz_date = format!("{}T{}Z", date, time);
+    let payload_hash = hex(&Sha256::digest(body));
+
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+    let canonical = format!(
+        "PUT\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        path, host, payload_hash, amz_date, signed_headers, payload_hash
+    );
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+    let to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", amz_date, scope, hex(&Sha256::digest(canonical.as_bytes())));
+
+    let mut key = hmac(format!("AWS4{}", secret_key).as_bytes(), date.as_bytes());
+    for part in [region, "s3", "aws4_request"] {
+        key = hmac(&key, part.as_bytes());
+    }
+    let signature = hex(&hmac(&key, to_sign.as_bytes()));
+
+    vec![
+        ("x-amz-date", amz_date),
+        ("x-amz-content-sha256", payload_hash),
+        (
+            "Authorization",
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                access_key, scope, signed_headers, signature
+            ),
+        ),
+    ]
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Percent-encodes everything but unreserved characters and slashes
+fn uri_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn utc_now() -> (String, String) {
+    utc(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0))
+}
+
+// ("YYYYMMDD", "HHMMSS") in UTC
+fn utc(secs: u64) -> (String, String) {
+    let ((year, month, day), rest) = (civil_date(secs / 86400), secs % 86400);
+    (
+        format!("{:04}{:02}{:02}", year, month, day),
+        format!("{:02}{:02}{:02}", rest / 3600, rest % 3600 / 60, rest % 60),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hmac_matches_rfc_4231() {
+        let mac = hmac(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(hex(&mac), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    }
+
+    #[test]
+    fn s3_requests_are_signed_like_the_aws_sdk() {
+        let secret = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+        let path = format!("/shots/{}", uri_encode("2024/a b.png"));
+        let headers = sign_s3("s3.eu-west-1.amazonaws.com", &path, "eu-west-1", "AKIDEXAMPLE", secret, b"imgopt", utc(1709993107));
+        // What botocore sends for the same request
+        assert_eq!(
+            headers,
+            [
+                ("x-amz-date", "20240309T140507Z".to_string()),
+                ("x-amz-content-sha256", "ebafcf180a0ee7a2d8eeccd6074d90db82650ce4fce5cfd817dfe9bd4e08cfe2".to_string()),
+                (
+                    "Authorization",
+                    "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20240309/eu-west-1/s3/aws4_request, \
+                     SignedHeaders=host;x-amz-content-sha256;x-amz-date, \
+                     Signature=27cb24c1baeb9df369f81f2e437ec8d9d6ac51d9f4e7fd91f87317e4525ca2df"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn keys_are_encoded_but_keep_their_slashes() {
+        assert_eq!(uri_encode("shots/2024/a b+c~d_é.png"), "shots/2024/a%20b%2Bc~d_%C3%A9.png");
+    }
+
+    #[test]
+    fn dates_are_utc() {
+        assert_eq!(utc(0), ("19700101".to_string(), "000000".to_string()));
+        assert_eq!(utc(951868799), ("20000229".to_string(), "235959".to_string()));
+    }
+}
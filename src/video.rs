@@ -0,0 +1,164 @@
+use image::DynamicImage;
+use serde::Deserialize;
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::{AnimationFormat, Gif};
+
+// Screen recordings: QuickTime from macOS, MP4 and WebM from most others
+const VIDEO_EXTENSIONS: &[&str] = &["mov", "mp4", "m4v", "webm", "mkv"];
+
+// How far before the end the last frame is taken from; seeking to the very
+// end finds nothing to decode
+const LAST_FRAME_MS: u64 = 100;
+
+// libwebp's quality for animated WebP, 0-100
+const WEBP_QUALITY: &str = "75";
+
+// Which frame of a video is optimized: `video_frame` in the config or
+// `--frame`. Timestamps are kept in milliseconds so the choice can be part of
+// a cache key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Frame {
+    First,
+    #[default]
+    Middle,
+    Last,
+    At(u64),
+}
+
+impl Frame {
+    // "first", "middle", "last", or a time: "12.5", "1:05" or "0:01:05.250"
+    pub fn parse(text: &str) -> Result<Frame, String> {
+        match text.trim().to_lowercase().as_str() {
+            "first" => Ok(Frame::First),
+            "middle" => Ok(Frame::Middle),
+            "last" => Ok(Frame::Last),
+            time => {
+                let mut seconds = 0.0;
+                for part in time.split(':') {
+                    let part: f64 = part.parse().ok().filter(|part: &f64| part.is_finite() && *part >= 0.0).ok_or_else(|| {
+                        format!("\"{}\" is not a frame: first, middle, last or a time like 12.5 or 1:05", text)
+                    })?;
+                    seconds = seconds * 60.0 + part;
+                }
+                Ok(Frame::At((seconds * 1000.0).round() as u64))
+            }
+        }
+    }
+
+    // Where to seek, in milliseconds
+    fn time(self, duration_ms: u64) -> Result<u64, String> {
+        match self {
+            Frame::First => Ok(0),
+            Frame::Middle => Ok(duration_ms / 2),
+            Frame::Last => Ok(duration_ms.saturating_sub(LAST_FRAME_MS)),
+            Frame::At(ms) if ms < duration_ms => Ok(ms),
+            Frame::At(ms) => Err(format!("{}s is past the end of the video ({}s long)", seconds(ms), seconds(duration_ms))),
+        }
+    }
+}
+
+impl TryFrom<String> for Frame {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Frame, String> {
+        Frame::parse(&text)
+    }
+}
+
+pub fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+// The first video stream's size as it plays (turned by its rotation), and
+// the length in milliseconds, from ffprobe
+pub fn probe(path: &Path) -> Result<((u32, u32), u64), String> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0", "-of", "json"])
+        .args(["-show_entries", "stream=width,height:stream_side_data=rotation:stream_tags=rotate:format=duration"])
+        .arg(path)
+        .output()
+        .map_err(|e| ffmpeg_error("ffprobe", e))?;
+    if !output.status.success() {
+        return Err(failed("ffprobe", &output));
+    }
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout).map_err(|e| format!("ffprobe: {}", e))?;
+    let stream = &info["streams"][0];
+    let (Some(width), Some(height)) = (stream["width"].as_u64(), stream["height"].as_u64()) else {
+        return Err("no video stream".to_string());
+    };
+    // Phones record sideways and say so in side data, or in an older tag
+    let rotation = stream["side_data_list"]
+        .as_array()
+        .and_then(|list| list.iter().find_map(|data| data["rotation"].as_i64()))
+        .or_else(|| stream["tags"]["rotate"].as_str().and_then(|rotate| rotate.parse().ok()))
+        .unwrap_or(0);
+    let size = if rotation.rem_euclid(180) == 90 { (height as u32, width as u32) } else { (width as u32, height as u32) };
+    let duration = info["format"]["duration"].as_str().and_then(|duration| duration.parse::<f64>().ok()).unwrap_or(0.0);
+    Ok((size, (duration * 1000.0) as u64))
+}
+
+// One frame through ffmpeg, as a PNG on its stdout
+pub fn extract(path: &Path, frame: Frame) -> Result<DynamicImage, String> {
+    let (_, duration) = probe(path)?;
+    let at = seconds(frame.time(duration)?);
+    let output = Command::new("ffmpeg")
+        .args(["-v", "error", "-nostdin", "-ss", &at, "-i"])
+        .arg(path)
+        .args(["-frames:v", "1", "-f", "image2pipe", "-c:v", "png", "-"])
+        .output()
+        .map_err(|e| ffmpeg_error("ffmpeg", e))?;
+    if !output.status.success() {
+        return Err(failed("ffmpeg", &output));
+    }
+    if output.stdout.is_empty() {
+        return Err(format!("ffmpeg found no frame at {}s", at));
+    }
+    image::load_from_memory(&output.stdout).map_err(|e| format!("ffmpeg wrote an unreadable frame: {}", e))
+}
+
+// The start of a recording, at most `gif.max_seconds` of it, as an animation of
+// `width`x`height` written to `out`. A GIF gets one palette made for the whole
+// clip, from the pixels that change, so text stays crisp.
+pub fn animate(path: &Path, (width, height): (u32, u32), gif: &Gif, out: &Path) -> Result<(), String> {
+    let scale = format!("fps={},scale={}:{}:flags=lanczos", gif.fps, width, height);
+    let length = seconds((gif.max_seconds * 1000.0).round() as u64);
+    let mut command = Command::new("ffmpeg");
+    command.args(["-v", "error", "-nostdin", "-y", "-t", &length, "-i"]).arg(path).args(["-an", "-loop", "0"]);
+    match gif.format {
+        AnimationFormat::Gif => {
+            let palette = "split[a][b];[a]palettegen=stats_mode=diff[p];[b][p]paletteuse=dither=bayer:bayer_scale=5:diff_mode=rectangle";
+            command.args(["-vf", &format!("{},{}", scale, palette), "-f", "gif"])
+        }
+        AnimationFormat::Webp => command.args(["-vf", &scale, "-c:v", "libwebp", "-q:v", WEBP_QUALITY, "-f", "webp"]),
+    };
+    let output = command.arg(out).output().map_err(|e| ffmpeg_error("ffmpeg", e))?;
+    if !output.status.success() {
+        return Err(failed("ffmpeg", &output));
+    }
+    Ok(())
+}
+
+// "12.500", as ffmpeg takes times
+fn seconds(ms: u64) -> String {
+    format!("{}.{:03}", ms / 1000, ms % 1000)
+}
+
+fn ffmpeg_error(program: &str, e: std::io::Error) -> String {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => format!("{} not found, is ffmpeg installed?", program),
+        _ => format!("could not run {}: {}", program, e),
+    }
+}
+
+fn failed(program: &str, output: &std::process::Output) -> String {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match stderr.trim().lines().last() {
+        Some(line) => format!("{}: {}", program, line),
+        None => format!("{} exited with {}", program, output.status),
+    }
+}
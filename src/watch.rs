@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::background::wait_for_power;
+use crate::config::{Config, ConfigWatcher};
+use crate::deliver::Destination;
+use crate::oneshot::run_optimize;
+use crate::paths::is_image_path;
+use crate::{say, Result};
+
+const POLL: Duration = Duration::from_millis(500);
+
+// Our own fallback outputs; optimizing those would loop forever
+const OUTPUT_SUFFIXES: &[&str] = &["_optimized", "_diff", "_grid"];
+
+// `imgopt watch [dir]`: optimizes and copies every image that appears in (or
+// is rewritten in) `dir`, the screenshots folder by default. Polls instead of
+// using file system events, which is plenty for screenshots. Edits to the
+// config file apply from the next image on, except the [background] limits,
+// which are set at startup.
+pub fn run_watch(dir: &Path, config: &Config, to: Option<Destination>) -> Result<()> {
+    if !dir.is_dir() {
+        return Err(format!("not a directory: {}", dir.display()).into());
+    }
+    say(&format!("imgopt watch: optimizing new images in {}, Ctrl-C to stop", dir.display()));
+
+    let with_destination = |mut config: Config| {
+        if let Some(to) = to {
+            config.set_destination(to);
+        }
+        config
+    };
+    let mut config = with_destination(config.clone());
+    config.background.apply();
+    let slots = config.background.slots();
+    let mut watcher = ConfigWatcher::new();
+    let mut seen = snapshot(dir);
+    // Sizes from the previous poll of files that are still being written
+    let mut pending: HashMap<PathBuf, u64> = HashMap::new();
+    std::thread::scope(|scope| loop {
+        std::thread::sleep(POLL);
+        match watcher.poll() {
+            Some(Ok(reloaded)) => {
+                config = with_destination(reloaded);
+                say("Config reloaded");
+            }
+            Some(Err(e)) => eprintln!("imgopt: config not reloaded: {}", e),
+            None => {}
+        }
+        for (path, modified) in snapshot(dir) {
+            if seen.get(&path) == Some(&modified) {
+                continue;
+            }
+            // Wait until the size holds still for one poll, so half-written files are left alone
+            let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            if size == 0 || pending.insert(path.clone(), size) != Some(size) {
+                continue;
+            }
+            pending.remove(&path);
+            seen.insert(path.clone(), modified);
+            wait_for_power(&config.background);
+            let (slot, config) = (slots.take(), config.clone());
+            scope.spawn(move || {
+                run_optimize(&[path], &config, false, None);
+                config.notify.done(config.notify_sound().as_deref());
+                drop(slot);
+            });
+        }
+    })
+}
+
+fn snapshot(dir: &Path) -> HashMap<PathBuf, SystemTime> {
+    let Ok(read) = std::fs::read_dir(dir) else {
+        return HashMap::new();
+    };
+    read.filter_map(|e| e.ok())
+        .filter(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            // macOS writes screenshots under a hidden name first
+            !name.starts_with('.') && is_image_path(&name)
+        })
+        .filter(|e| {
+            let path = e.path();
+            let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+            !OUTPUT_SUFFIXES.iter().any(|suffix| stem.ends_with(suffix))
+        })
+        .filter_map(|e| Some((e.path(), e.metadata().ok()?.modified().ok()?)))
+        .collect()
+}
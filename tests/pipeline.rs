@@ -0,0 +1,355 @@
+// Runs the imgopt binary against generated fixture images, with no terminal,
+// clipboard or user config involved: results go to stdout or next to the
+// fixture, and `imgopt plan` reports the decisions made on the way.
+use image::{ImageFormat, RgbImage};
+use serde_json::Value;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+// A scratch folder per test, with an empty config folder so only the defaults
+// (and whatever the test writes there) apply
+struct Fixture {
+    dir: PathBuf,
+}
+
+impl Fixture {
+    fn new(name: &str) -> Fixture {
+        let dir = std::env::temp_dir().join(format!("imgopt-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("config/imgopt")).unwrap();
+        Fixture { dir }
+    }
+
+    fn config(&self, text: &str) {
+        std::fs::write(self.dir.join("config/imgopt/config.toml"), text).unwrap();
+    }
+
+    fn image(&self, name: &str, width: u32, height: u32) -> PathBuf {
+        let path = self.dir.join(name);
+        let image = RgbImage::from_fn(width, height, |x, y| image::Rgb([(x % 256) as u8, (y % 256) as u8, ((x + y) % 256) as u8]));
+        image.save(&path).unwrap();
+        path
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.run_in(&self.dir, args)
+    }
+
+    fn run_in(&self, dir: &Path, args: &[&str]) -> Output {
+        Command::new(env!("CARGO_BIN_EXE_imgopt"))
+            .args(args)
+            .env_clear()
+            .env("HOME", &self.dir)
+            .env("XDG_CONFIG_HOME", self.dir.join("config"))
+            .env("TMPDIR", &self.dir)
+            .current_dir(dir)
+            .output()
+            .unwrap()
+    }
+
+    fn plan(&self, file: &Path) -> Value {
+        let output = self.run(&["plan", file.to_str().unwrap()]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+        serde_json::from_slice(&output.stdout).unwrap()
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn size(value: &Value) -> (u64, u64) {
+    (value[0].as_u64().unwrap(), value[1].as_u64().unwrap())
+}
+
+#[test]
+fn large_image_is_shrunk_to_the_planned_size() {
+    let fixture = Fixture::new("shrink");
+    let input = fixture.image("wide.png", 2000, 1000);
+
+    let plan = fixture.plan(&input);
+    assert_eq!(size(&plan["original"]), (2000, 1000));
+    assert_eq!(size(&plan["target"]), (720, 360));
+    assert_eq!(plan["pass_through"], false);
+
+    let output = fixture.run(&["optimize", "--to", "stdout", input.to_str().unwrap()]);
+    assert!(output.status.success());
+    let optimized = image::load_from_memory_with_format(&output.stdout, ImageFormat::Png).unwrap();
+    assert_eq!((optimized.width(), optimized.height()), (720, 360));
+}
+
+#[test]
+fn small_image_is_enlarged_to_the_minimum() {
+    let fixture = Fixture::new("enlarge");
+    let input = fixture.image("icon.png", 200, 100);
+
+    assert_eq!(size(&fixture.plan(&input)["target"]), (480, 240));
+    let output = fixture.run(&["optimize", "--to", "stdout", input.to_str().unwrap()]);
+    let optimized = image::load_from_memory(&output.stdout).unwrap();
+    assert_eq!((optimized.width(), optimized.height()), (480, 240));
+}
+
+#[test]
+fn image_within_bounds_is_saved_unchanged() {
+    let fixture = Fixture::new("passthrough");
+    let input = fixture.image("fits.png", 600, 400);
+
+    assert_eq!(fixture.plan(&input)["pass_through"], true);
+    let output = fixture.run(&["optimize", "--to", "file", input.to_str().unwrap()]);
+    assert!(output.status.success());
+    let saved = std::fs::read(fixture.dir.join("fits_optimized.png")).unwrap();
+    assert_eq!(saved, std::fs::read(&input).unwrap());
+}
+
+#[test]
+fn bare_file_name_is_saved_next_to_it() {
+    let fixture = Fixture::new("relative");
+    std::fs::create_dir(fixture.dir.join("shots")).unwrap();
+    fixture.image("shots/wide.png", 1600, 900);
+
+    // Run from the image's own folder; TMPDIR is the folder above
+    let output = fixture.run_in(&fixture.dir.join("shots"), &["optimize", "--to", "file", "wide.png"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let saved = image::open(fixture.dir.join("shots/wide_optimized.png")).unwrap();
+    assert_eq!((saved.width(), saved.height()), (720, 405));
+    assert!(!fixture.dir.join("wide_optimized.png").exists());
+}
+
+#[test]
+fn config_changes_the_plan() {
+    let fixture = Fixture::new("config");
+    fixture.config("[resize]\nmax_dimension = 500\nretina = true\n");
+    let input = fixture.image("shot@2x.png", 1600, 1200);
+
+    // Halved to 800x600 logical pixels, then capped at 500
+    assert_eq!(size(&fixture.plan(&input)["target"]), (500, 375));
+    assert_eq!(fixture.plan(&input)["retina"], true);
+}
+
+#[test]
+fn dropped_file_url_is_decoded() {
+    let fixture = Fixture::new("url");
+    let input = fixture.image("my shot.png", 1000, 1000);
+    let url = format!("file://{}", input.to_str().unwrap().replace(' ', "%20"));
+
+    let output = fixture.run(&["optimize", "--to", "stdout", &url]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let optimized = image::load_from_memory(&output.stdout).unwrap();
+    assert_eq!((optimized.width(), optimized.height()), (720, 720));
+}
+
+#[test]
+fn quotes_and_backslashes_in_arguments_are_part_of_the_name() {
+    let fixture = Fixture::new("argv");
+    for name in ["it's.png", "say \"cheese\".png", "back\\slash.png"] {
+        let input = fixture.image(name, 1000, 1000);
+        let output = fixture.run(&["optimize", "--to", "stdout", input.to_str().unwrap()]);
+        assert!(output.status.success(), "{}: {}", name, String::from_utf8_lossy(&output.stderr));
+        let optimized = image::load_from_memory(&output.stdout).unwrap();
+        assert_eq!((optimized.width(), optimized.height()), (720, 720));
+    }
+    // Relative, as typed after `cd`
+    fixture.image("don't.png", 800, 800);
+    assert!(fixture.run(&["optimize", "--to", "file", "don't.png"]).status.success());
+    assert!(fixture.dir.join("don't_optimized.png").exists());
+}
+
+#[test]
+fn failures_have_their_exit_status() {
+    let fixture = Fixture::new("failures");
+    let missing = fixture.dir.join("missing.png");
+    let broken = fixture.dir.join("broken.png");
+    std::fs::write(&broken, b"not a png").unwrap();
+
+    assert_eq!(fixture.run(&["plan", missing.to_str().unwrap()]).status.code(), Some(5));
+    assert_eq!(fixture.run(&["optimize", "--to", "stdout", missing.to_str().unwrap()]).status.code(), Some(5));
+    assert_eq!(fixture.run(&["plan", broken.to_str().unwrap()]).status.code(), Some(3));
+    assert_eq!(fixture.run(&["optimize", "--to", "stdout", broken.to_str().unwrap()]).status.code(), Some(3));
+}
+
+#[test]
+fn page_of_a_multi_page_tiff_is_picked() {
+    let fixture = Fixture::new("pages");
+    let input = fixture.dir.join("scan.tiff");
+    let mut encoder = tiff::encoder::TiffEncoder::new(std::fs::File::create(&input).unwrap()).unwrap();
+    for (width, height) in [(200, 100), (1000, 500)] {
+        let pixels = vec![128u8; width as usize * height as usize * 3];
+        encoder.write_image::<tiff::encoder::colortype::RGB8>(width, height, &pixels).unwrap();
+    }
+    drop(encoder);
+    let file = input.to_str().unwrap();
+
+    let plan = fixture.plan(&input);
+    assert_eq!(plan["pages"], 2);
+    assert_eq!(size(&plan["original"]), (200, 100));
+
+    let output = fixture.run(&["optimize", "--to", "stdout", "--page", "2", file]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let optimized = image::load_from_memory(&output.stdout).unwrap();
+    assert_eq!((optimized.width(), optimized.height()), (720, 360));
+
+    // The first page, with a warning that there were more
+    let output = fixture.run(&["optimize", "--to", "stdout", file]);
+    let optimized = image::load_from_memory(&output.stdout).unwrap();
+    assert_eq!((optimized.width(), optimized.height()), (480, 240));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("2 pages"));
+
+    assert_eq!(fixture.run(&["optimize", "--to", "stdout", "--page", "3", file]).status.code(), Some(3));
+}
+
+#[test]
+fn auto_preset_keeps_text_larger_than_photos() {
+    let fixture = Fixture::new("auto");
+    fixture.config("preset = \"auto\"\n");
+    // Dark glyph-sized strokes on white, and noise
+    let text = fixture.dir.join("text.png");
+    let strokes = |x: u32, y: u32| x % 8 < 2 && y % 12 < 8;
+    RgbImage::from_fn(800, 400, |x, y| if strokes(x, y) { image::Rgb([20, 20, 20]) } else { image::Rgb([255, 255, 255]) })
+        .save(&text)
+        .unwrap();
+    let photo = fixture.dir.join("photo.png");
+    let noise = |x: u32, y: u32| (x.wrapping_mul(2654435761) ^ y.wrapping_mul(40503)).wrapping_mul(2246822519).to_le_bytes();
+    RgbImage::from_fn(800, 400, |x, y| {
+        let [r, g, b, _] = noise(x, y);
+        image::Rgb([r, g, b])
+    })
+    .save(&photo)
+    .unwrap();
+
+    let optimized = |path: &Path| {
+        let output = fixture.run(&["optimize", "--to", "stdout", path.to_str().unwrap()]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let image = image::load_from_memory(&output.stdout).unwrap();
+        (image.width(), image.height())
+    };
+    assert_eq!(optimized(&text), (960, 480));
+    assert_eq!(optimized(&photo), (720, 360));
+}
+
+#[test]
+fn canvas_letterboxes_to_the_exact_size() {
+    let fixture = Fixture::new("canvas");
+    fixture.config("preset = \"og\"\n[presets.og]\ncanvas = [1200, 630]\ncanvas_background = [10, 20, 30]\n");
+    let input = fixture.image("tall.png", 400, 600);
+
+    let plan = fixture.plan(&input);
+    assert_eq!(size(&plan["target"]), (1200, 630));
+
+    let output = fixture.run(&["optimize", "--to", "stdout", input.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let optimized = image::load_from_memory(&output.stdout).unwrap().to_rgb8();
+    assert_eq!(optimized.dimensions(), (1200, 630));
+    // Bars on both sides of the 420x630 image
+    assert_eq!(optimized.get_pixel(0, 315).0, [10, 20, 30]);
+    assert_eq!(optimized.get_pixel(1199, 315).0, [10, 20, 30]);
+    assert_ne!(optimized.get_pixel(600, 315).0, [10, 20, 30]);
+}
+
+#[test]
+fn dark_appearance_darkens_the_window_frame() {
+    let fixture = Fixture::new("appearance");
+    let input = fixture.image("shot.png", 600, 400);
+    let bar = |appearance: &str| {
+        fixture.config(&format!("frame = \"window\"\nappearance = \"{}\"\n", appearance));
+        let output = fixture.run(&["optimize", "--to", "stdout", input.to_str().unwrap()]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        // Inside the title bar, right of the traffic lights, past the 28px shadow margin
+        image::load_from_memory(&output.stdout).unwrap().to_rgba8().get_pixel(28 + 100, 28 + 10).0
+    };
+    assert_eq!(bar("light"), [236, 236, 236, 255]);
+    assert_eq!(bar("dark"), [54, 54, 56, 255]);
+}
+
+#[test]
+fn resumed_batch_skips_finished_files() {
+    let fixture = Fixture::new("resume");
+    let first = fixture.image("first.png", 100, 100);
+    let broken = fixture.dir.join("second.png");
+    std::fs::write(&broken, b"not a png").unwrap();
+    let files = [first.to_str().unwrap(), broken.to_str().unwrap()];
+    let optimize = |extra: &[&str]| fixture.run(&[&["optimize", "--to", "file"], &files[..], extra].concat());
+    let output = |name: &str| fixture.dir.join(name);
+
+    assert_eq!(optimize(&[]).status.code(), Some(3));
+    assert!(output("first_optimized.png").exists());
+
+    // Fixed since; only the file that failed is optimized again
+    std::fs::remove_file(output("first_optimized.png")).unwrap();
+    fixture.image("second.png", 100, 100);
+    let resumed = optimize(&["--resume"]);
+    assert!(resumed.status.success(), "{}", String::from_utf8_lossy(&resumed.stderr));
+    assert!(String::from_utf8_lossy(&resumed.stdout).contains("1 of 2 files already done"));
+    assert!(!output("first_optimized.png").exists());
+    assert!(output("second_optimized.png").exists());
+
+    // A run that went through leaves nothing to resume
+    assert!(String::from_utf8_lossy(&optimize(&["--resume"]).stdout).contains("Nothing to resume"));
+}
+
+#[test]
+fn check_fails_when_images_exceed_the_budget() {
+    let fixture = Fixture::new("check");
+    std::fs::create_dir(fixture.dir.join("assets")).unwrap();
+    fixture.image("assets/wide.png", 3000, 100);
+    fixture.image("assets/icon.png", 32, 32);
+    let assets = fixture.dir.join("assets");
+    let check = |extra: &[&str]| fixture.run(&[&["check", assets.to_str().unwrap()], extra].concat());
+
+    let over = check(&["--budget", "300KB"]);
+    assert_eq!(over.status.code(), Some(8));
+    let listing = String::from_utf8_lossy(&over.stdout);
+    assert!(listing.contains("wide.png: 3000x100") && listing.contains("[dimensions]"), "{}", listing);
+    assert!(!listing.contains("icon.png"));
+    assert!(listing.contains("1 of 2 images over budget"));
+
+    let within = check(&["--budget", "300KB", "--max-dimension", "4000"]);
+    assert!(within.status.success(), "{}", String::from_utf8_lossy(&within.stdout));
+    assert_eq!(check(&["--budget", "1B", "--max-dimension", "4000"]).status.code(), Some(8));
+}
+
+#[test]
+fn color_profile_is_embedded_per_preset() {
+    let fixture = Fixture::new("color-profile");
+    let input = fixture.image("shot.png", 1000, 500);
+    let profile = |preset: &str| {
+        fixture.config(&format!("color_profile = \"srgb\"\npreset = \"{}\"\n[presets.web]\n[presets.plain]\ncolor_profile = \"none\"\n", preset));
+        let output = fixture.run(&["optimize", "--to", "stdout", input.to_str().unwrap()]);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let mut decoder = image::codecs::png::PngDecoder::new(std::io::Cursor::new(output.stdout)).unwrap();
+        image::ImageDecoder::icc_profile(&mut decoder).unwrap()
+    };
+
+    let srgb = profile("web").expect("an sRGB profile");
+    assert_eq!(&srgb[36..40], b"acsp");
+    assert_eq!(&srgb[16..20], b"RGB ");
+    assert_eq!(profile("plain"), None);
+}
+
+#[test]
+fn bundle_packs_saved_files_with_a_manifest() {
+    let fixture = Fixture::new("bundle");
+    std::fs::create_dir(fixture.dir.join("other")).unwrap();
+    let first = fixture.image("shot.png", 1600, 900);
+    let second = fixture.image("other/shot.png", 300, 200);
+    let archive = fixture.dir.join("assets.zip");
+    let output = fixture.run(&["optimize", "--bundle", archive.to_str().unwrap(), first.to_str().unwrap(), second.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    // The images as they were saved, then the manifest
+    let mut zip = zip::ZipArchive::new(std::fs::File::open(&archive).unwrap()).unwrap();
+    let names: Vec<_> = (0..zip.len()).map(|index| zip.name_for_index(index).unwrap().to_string()).collect();
+    assert_eq!(names, ["shot_optimized.png", "shot_optimized-2.png", "manifest.json"]);
+    for (name, saved) in [(&names[0], "shot_optimized.png"), (&names[1], "other/shot_optimized.png")] {
+        let mut bytes = Vec::new();
+        zip.by_name(name).unwrap().read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes, std::fs::read(fixture.dir.join(saved)).unwrap(), "{}", name);
+    }
+    let manifest: Value = serde_json::from_reader(zip.by_name("manifest.json").unwrap()).unwrap();
+    let files = manifest["files"].as_array().unwrap();
+    assert_eq!(files.len(), 2);
+    assert_eq!((files[0]["file"].as_str(), files[1]["file"].as_str()), (Some("shot_optimized.png"), Some("shot_optimized-2.png")));
+    assert_eq!(size(&files[0]["optimized"]), (720, 405));
+}